@@ -0,0 +1,54 @@
+//! benches/common/market_fixtures.rs
+//! Market set-ups shared by `market_step` and the CSV suite in
+//! `order_book_enhanced` (pulled in with `#[path]`).
+
+use market_simulator::{AgentType, Market, Marketable, Stock, StockMarket};
+
+// ────────────────────────────────────────────────────────────────────────────
+//  Parameter grids
+// ────────────────────────────────────────────────────────────────────────────
+/// Number of `DumbLimitAgent`s; every population also gets market makers
+/// and a few market-order agents.
+pub const POPULATIONS: &[usize] = &[10, 100, 300];
+pub const UNIVERSES: &[usize] = &[2, 10, 50];
+pub const WARMUP_TICKS: usize = 30;
+
+/// Default universe topped up with synthetic names to `n` stocks.
+pub fn universe(n: usize) -> StockMarket {
+    let mut stocks = StockMarket::new();
+    let mut id = stocks.get_all_ids().into_iter().max().unwrap_or(0);
+    while stocks.stocks.len() < n {
+        id += 1;
+        stocks.add_stock(Stock::new(
+            format!("SYN{id}"),
+            id,
+            format!("Synthetic {id}"),
+            1_000_000_000,
+            50.0 + id as f64,
+        ));
+    }
+    stocks
+}
+
+pub fn population(limit_agents: usize) -> Vec<AgentType> {
+    let mut agents = vec![AgentType::MarketMaker; 2];
+    agents.extend(std::iter::repeat_n(AgentType::DumbLimit, limit_agents));
+    agents.extend(std::iter::repeat_n(
+        AgentType::DumbMarket,
+        limit_agents / 10 + 1,
+    ));
+    agents
+}
+
+/// Seeded market with populated books.
+pub fn warmed_market(limit_agents: usize, stocks: usize) -> Market {
+    let mut m = Market::with_seed(&population(limit_agents), universe(stocks), 42);
+    for _ in 0..WARMUP_TICKS {
+        m.step();
+    }
+    m
+}
+
+pub fn param(agents: usize, stocks: usize) -> String {
+    format!("agents_{agents}_stocks_{stocks}")
+}
@@ -0,0 +1,88 @@
+//! benches/market_step.rs
+//! Run with:  cargo bench --bench market_step
+//! HTML:      target/criterion/report/index.html
+//!
+//! Whole-engine latency: one `Market::step` with realistic populations, plus
+//! its two halves measured on their own (agent decisions, then matching).
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use market_simulator::Marketable;
+use std::hint::black_box;
+
+#[path = "common/market_fixtures.rs"]
+mod market_fixtures;
+use market_fixtures::{POPULATIONS, UNIVERSES, param, warmed_market};
+
+// ────────────────────────────────────────────────────────────────────────────
+//  Benches
+// ────────────────────────────────────────────────────────────────────────────
+pub fn bench_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("market_step");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(1)); // ticks
+
+    for &agents in POPULATIONS {
+        for &stocks in UNIVERSES {
+            let base = warmed_market(agents, stocks);
+            let id = param(agents, stocks);
+
+            group.bench_function(BenchmarkId::new("full", &id), |b| {
+                b.iter_batched(
+                    || base.fork(),
+                    |mut m| {
+                        black_box(m.step());
+                        m // dropped outside the timing
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+
+            group.bench_function(BenchmarkId::new("full_parallel", &id), |b| {
+                let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+                b.iter_batched(
+                    || {
+                        let mut m = base.fork();
+                        m.set_threads(threads);
+                        m
+                    },
+                    |mut m| {
+                        black_box(m.step());
+                        m
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+
+            group.bench_function(BenchmarkId::new("decide", &id), |b| {
+                b.iter_batched(
+                    || base.fork(),
+                    |mut m| {
+                        let requests = m.decide();
+                        (m, black_box(requests))
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+
+            group.bench_function(BenchmarkId::new("match", &id), |b| {
+                b.iter_batched(
+                    || {
+                        let mut m = base.fork();
+                        let requests = m.decide();
+                        (m, requests)
+                    },
+                    |(mut m, requests)| {
+                        black_box(m.execute(requests));
+                        m
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_step);
+criterion_main!(benches);
@@ -4,7 +4,7 @@
 
 use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
 use market_simulator::{
-    simulators::order_book::OrderBook,
+    simulators::{levels::BookKind, order_book::OrderBook},
     types::{Order, Side},
 };
 use rand::rngs::StdRng;
@@ -16,12 +16,33 @@ use std::hint::black_box;
 // ────────────────────────────────────────────────────────────────────────────
 const BOOK_SIZES: &[usize] = &[50_000, 100_000, 500_000, 1_000_000];
 const SWEEP_VOLUMES: &[u64] = &[25_000, 100_000, 250_000];
+const QUEUE_DEPTHS: &[usize] = &[1_000, 10_000, 100_000, 1_000_000];
+
+/// The band `MarketMakerAgent` clamps to: $1 – $3,000 in cents.
+const LADDER_WIDE: BookKind = BookKind::Ladder {
+    min_price: 100,
+    max_price: 300_000,
+};
+/// A per-stock band around where the bench book actually trades.
+const LADDER_NARROW: BookKind = BookKind::Ladder {
+    min_price: 50,
+    max_price: 2_000,
+};
+const KINDS: &[(&str, BookKind)] = &[
+    ("tree", BookKind::Tree),
+    ("ladder_wide", LADDER_WIDE),
+    ("ladder_narrow", LADDER_NARROW),
+];
 
 /// Build a fresh OrderBook with `n_orders` *sell* orders.
 /// Prices cycle 100–109; volumes random 1–256.
 fn setup_book(n_orders: usize) -> OrderBook {
+    setup_book_of(BookKind::Tree, n_orders)
+}
+
+fn setup_book_of(kind: BookKind, n_orders: usize) -> OrderBook {
     let mut rng = StdRng::seed_from_u64(42);
-    let mut book = OrderBook::new();
+    let mut book = OrderBook::with_kind(kind, n_orders);
 
     for i in 0..n_orders as u64 {
         let price = 100 + (i % 10); // ten levels: 100..109
@@ -74,5 +95,131 @@ pub fn bench_scaling(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_scaling);
+/// One price level holding `depth` orders (ids 0..depth), arena preallocated.
+fn setup_deep_level(depth: usize) -> OrderBook {
+    let mut book = OrderBook::with_capacity(depth + 1);
+    for i in 0..depth as u64 {
+        let mut order = Order {
+            id: i,
+            agent_id: 1,
+            stock_id: 0,
+            side: Side::Sell,
+            price: 100,
+            volume: 10,
+            filled: 0,
+        };
+        let _ = book.process_limit_order(&mut order);
+    }
+    book
+}
+
+/// Cancel (and re-queue) the order at the *back* of a single deep level.
+/// A per-level scan grows with the queue; the flat line across depths is the
+/// O(1) handle lookup at work.
+pub fn bench_cancel_depth(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel_vs_queue_depth");
+
+    for &depth in QUEUE_DEPTHS {
+        group.throughput(Throughput::Elements(1));
+        let mut book = setup_deep_level(depth);
+        let back = depth as u64 - 1;
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.iter(|| {
+                let ok = book.cancel_order(black_box(back), 1);
+                let mut order = Order {
+                    id: back,
+                    agent_id: 1,
+                    stock_id: 0,
+                    side: Side::Sell,
+                    price: 100,
+                    volume: 10,
+                    filled: 0,
+                };
+                black_box((ok, book.process_limit_order(&mut order)));
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Reprice the back order between two non-crossing prices.
+pub fn bench_modify_depth(c: &mut Criterion) {
+    let mut group = c.benchmark_group("modify_vs_queue_depth");
+
+    for &depth in QUEUE_DEPTHS {
+        group.throughput(Throughput::Elements(1));
+        let mut book = setup_deep_level(depth);
+        let mut id = depth as u64 - 1;
+        let mut price = 100;
+        group.bench_function(BenchmarkId::from_parameter(depth), |b| {
+            b.iter(|| {
+                price = if price == 100 { 101 } else { 100 };
+                let trades = book.modify_order(black_box(id), 1, price, 10, id + 1);
+                id += 1;
+                black_box(trades);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Same sweep as `bench_scaling`, tree vs ladder levels.
+pub fn bench_sweep_by_kind(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sweep_tree_vs_ladder");
+    let n = 100_000;
+
+    for &(name, kind) in KINDS {
+        for &sweep in SWEEP_VOLUMES {
+            let id = BenchmarkId::new(name, format!("book_{}_sweep_{}", n, sweep));
+            group.bench_function(id, |b| {
+                b.iter_batched(
+                    || setup_book_of(kind, n),
+                    |mut book| {
+                        let trades = book.process_market_order(999, Side::Buy, sweep);
+                        (book, black_box(trades)) // drop the book outside the timing
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// 1,000 cancels spread over the levels, tree vs ladder levels.
+pub fn bench_cancel_by_kind(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel_tree_vs_ladder");
+    group.throughput(Throughput::Elements(1000));
+
+    for &(name, kind) in KINDS {
+        for &n in &[10_000, 100_000] {
+            group.bench_function(BenchmarkId::new(name, n), |b| {
+                b.iter_batched(
+                    || setup_book_of(kind, n),
+                    |mut book| {
+                        for i in 0..1000u64 {
+                            black_box(book.cancel_order(i * 7, (i * 7 % 10) as usize));
+                        }
+                        book
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_scaling,
+    bench_cancel_depth,
+    bench_modify_depth,
+    bench_sweep_by_kind,
+    bench_cancel_by_kind
+);
 criterion_main!(benches);
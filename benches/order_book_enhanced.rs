@@ -5,6 +5,7 @@
 
 use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group};
 use market_simulator::{
+    Marketable,
     simulators::order_book::OrderBook,
     types::{Order, Side},
 };
@@ -17,6 +18,10 @@ use std::path::Path;
 use std::sync::Mutex;
 use std::time::Instant;
 
+#[path = "common/market_fixtures.rs"]
+mod market_fixtures;
+use market_fixtures::{POPULATIONS, UNIVERSES, param, population, warmed_market};
+
 // ──────────────────────────────────────────────────────────────────────────────
 //  CSV Export Utilities
 // ──────────────────────────────────────────────────────────────────────────────
@@ -30,6 +35,8 @@ struct BenchmarkResult {
     sweep_volume: Option<u64>,
     price_levels: Option<u64>,
     side: Option<String>,
+    agent_count: Option<usize>,
+    stock_count: Option<usize>,
     mean_time_ns: f64,
     std_dev_ns: f64,
     throughput_elements_per_sec: Option<f64>,
@@ -39,7 +46,7 @@ struct BenchmarkResult {
 impl BenchmarkResult {
     fn to_csv_row(&self) -> String {
         format!(
-            "{},{},{},{},{},{},{},{:.2},{:.2},{},{}\n",
+            "{},{},{},{},{},{},{},{},{},{:.2},{:.2},{},{}\n",
             self.benchmark_group,
             self.test_name,
             self.parameter,
@@ -47,6 +54,8 @@ impl BenchmarkResult {
             self.sweep_volume.map_or("".to_string(), |v| v.to_string()),
             self.price_levels.map_or("".to_string(), |v| v.to_string()),
             self.side.as_ref().map_or("", |s| s),
+            self.agent_count.map_or("".to_string(), |v| v.to_string()),
+            self.stock_count.map_or("".to_string(), |v| v.to_string()),
             self.mean_time_ns,
             self.std_dev_ns,
             self.throughput_elements_per_sec
@@ -80,7 +89,7 @@ impl CsvExporter {
         // Write header
         writeln!(
             writer,
-            "benchmark_group,test_name,parameter,book_size,sweep_volume,price_levels,side,mean_time_ns,std_dev_ns,throughput_elements_per_sec,sample_count"
+            "benchmark_group,test_name,parameter,book_size,sweep_volume,price_levels,side,agent_count,stock_count,mean_time_ns,std_dev_ns,throughput_elements_per_sec,sample_count"
         )?;
 
         // Write data
@@ -124,6 +133,7 @@ fn export_all_results() {
             "order_cancellation",
             "short_covering",
             "negative_inventory_simulation",
+            "market_step",
         ];
 
         for group in &groups {
@@ -160,6 +170,7 @@ fn export_all_results() {
 }
 
 // Custom measurement function to capture timing data
+#[allow(clippy::too_many_arguments)]
 fn measure_and_record<F>(
     benchmark_group: &str,
     test_name: &str,
@@ -197,6 +208,8 @@ fn measure_and_record<F>(
         sweep_volume,
         price_levels,
         side: side.map(|s| s.to_string()),
+        agent_count: None,
+        stock_count: None,
         mean_time_ns: mean_time,
         std_dev_ns: std_dev,
         throughput_elements_per_sec: throughput,
@@ -206,6 +219,45 @@ fn measure_and_record<F>(
     add_result_to_exporter(result);
 }
 
+/// Like `measure_and_record` for whole-engine runs: throughput is ticks/sec
+/// and the population/universe land in their own columns.
+fn measure_and_record_step<F>(
+    test_name: &str,
+    agent_count: usize,
+    stock_count: usize,
+    setup_fn: F,
+    iterations: usize,
+) where
+    F: Fn() -> Box<dyn FnMut()>,
+{
+    let mut times = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let mut bench_fn = setup_fn();
+        let start = Instant::now();
+        bench_fn();
+        times.push(start.elapsed().as_nanos() as f64);
+    }
+
+    let mean_time = times.iter().sum::<f64>() / times.len() as f64;
+    let variance = times.iter().map(|&t| (t - mean_time).powi(2)).sum::<f64>() / times.len() as f64;
+
+    add_result_to_exporter(BenchmarkResult {
+        benchmark_group: "market_step".to_string(),
+        test_name: test_name.to_string(),
+        parameter: param(agent_count, stock_count),
+        book_size: None,
+        sweep_volume: None,
+        price_levels: None,
+        side: None,
+        agent_count: Some(agent_count),
+        stock_count: Some(stock_count),
+        mean_time_ns: mean_time,
+        std_dev_ns: variance.sqrt(),
+        throughput_elements_per_sec: Some(1_000_000_000.0 / mean_time),
+        sample_count: times.len(),
+    });
+}
+
 // ──────────────────────────────────────────────────────────────────────────────
 //  Parameter grids
 // ──────────────────────────────────────────────────────────────────────────────
@@ -369,7 +421,7 @@ pub fn bench_limit_order_insertion(c: &mut Criterion) {
                                 agent_id: (i % 10) as usize,
                                 stock_id: 0,
                                 side: Side::Sell,
-                                price: 110 + (i % 20) as u64, // New price levels
+                                price: 110 + (i % 20), // New price levels
                                 volume: rng.gen_range(1..=100),
                                 filled: 0,
                             }
@@ -405,7 +457,7 @@ pub fn bench_limit_order_insertion(c: &mut Criterion) {
                         agent_id: (i % 10) as usize,
                         stock_id: 0,
                         side: Side::Sell,
-                        price: 110 + (i % 20) as u64,
+                        price: 110 + (i % 20),
                         volume: rng.gen_range(1..=100),
                         filled: 0,
                     })
@@ -688,6 +740,55 @@ pub fn bench_negative_inventory_patterns(c: &mut Criterion) {
     group.finish();
 }
 
+/// `Market::step` and its two phases; the Criterion versions live in
+/// `benches/market_step.rs`, this one feeds the CSV export.
+pub fn bench_market_step(_c: &mut Criterion) {
+    for &agents in POPULATIONS {
+        for &stocks in UNIVERSES {
+            let base = warmed_market(agents, stocks);
+            let agent_count = population(agents).len();
+
+            measure_and_record_step(
+                "full",
+                agent_count,
+                stocks,
+                || {
+                    let mut m = base.fork();
+                    Box::new(move || {
+                        black_box(m.step());
+                    })
+                },
+                30,
+            );
+            measure_and_record_step(
+                "decide",
+                agent_count,
+                stocks,
+                || {
+                    let mut m = base.fork();
+                    Box::new(move || {
+                        black_box(m.decide());
+                    })
+                },
+                30,
+            );
+            measure_and_record_step(
+                "match",
+                agent_count,
+                stocks,
+                || {
+                    let mut m = base.fork();
+                    let mut requests = Some(m.decide());
+                    Box::new(move || {
+                        black_box(m.execute(requests.take().unwrap_or_default()));
+                    })
+                },
+                30,
+            );
+        }
+    }
+}
+
 // ──────────────────────────────────────────────────────────────────────────────
 //  Main benchmark entry point
 // ──────────────────────────────────────────────────────────────────────────────
@@ -704,6 +805,7 @@ fn run_benchmarks() {
     bench_order_cancellation(&mut criterion);
     bench_short_covering_scenarios(&mut criterion);
     bench_negative_inventory_patterns(&mut criterion);
+    bench_market_step(&mut criterion);
 
     // Export all CSV results at the end
     export_all_results();
@@ -717,7 +819,8 @@ criterion_group!(
     bench_price_level_impact,
     bench_order_cancellation,
     bench_short_covering_scenarios,
-    bench_negative_inventory_patterns
+    bench_negative_inventory_patterns,
+    bench_market_step
 );
 
 //criterion_main!(benches);
@@ -732,6 +835,7 @@ fn main() {
     bench_order_cancellation(&mut c);
     bench_short_covering_scenarios(&mut c);
     bench_negative_inventory_patterns(&mut c);
+    bench_market_step(&mut c);
     // finally export
     export_all_results();
 }
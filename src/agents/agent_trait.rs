@@ -1,21 +1,53 @@
 // src/agents/agent_trait.rs
 
-use crate::simulators::order_book::OrderBook;
-use crate::stocks::definitions::StockMarket;
-use crate::types::order::{Order, OrderRequest, Trade}; // replaces Symbol import
-//use std::collections::HashMap;
+use std::collections::HashMap;
+
+use super::portfolio::Portfolio;
+use crate::corporate::CorporateAction;
+use crate::fundamental::Fundamentals;
+use crate::sentiment::Sentiment;
+use crate::simulators::order_book::{MarketImpact, OrderBook};
+use crate::simulators::tape::{StockStats, TradeTape};
+use crate::stocks::basket::UnitSettlement;
+use crate::stocks::definitions::{Stock, StockMarket};
+use crate::types::order::{Order, OrderRequest, RejectReason, Side, Trade}; // replaces Symbol import
+
 /// A read-only snapshot of the market given to an agent for decision-making.
+/// Everything is borrowed from the `Market`; parts a caller cannot supply
+/// (a bare set of books in a test, a live agent's rebuilt feed) stay `None`.
+#[derive(Clone, Copy)]
 pub struct MarketView<'a> {
-    /// One book per stock id.
     /// Live order books keyed by stock-id.
-    pub order_books: &'a std::collections::HashMap<u64, OrderBook>,
+    pub order_books: &'a HashMap<u64, OrderBook>,
     /// Static instrument metadata if an agent wants names, floats, etc.
     pub stocks: &'a StockMarket,
+    /// Simulation clock: the tick being decided.
+    pub tick: u64,
+    /// Recent prints across all stocks.
+    pub tape: Option<&'a TradeTape>,
+    /// Session statistics per stock id.
+    pub stats: Option<&'a HashMap<u64, StockStats>>,
+    pub sentiment: Option<&'a Sentiment>,
+    /// Latent values, when the market keeps them; see
+    /// [`MarketView::fundamental`].
+    pub fundamentals: Option<&'a Fundamentals>,
+    /// The exchange's ledger of every participant's starting inventory and
+    /// fills, keyed by agent id.
+    pub accounts: Option<&'a HashMap<usize, Portfolio>>,
+    /// The agent this view was handed to, see [`MarketView::for_agent`].
+    pub agent_id: Option<usize>,
+    /// Corporate actions that took effect as this tick started, in the
+    /// order they were applied.
+    pub corporate_actions: &'a [CorporateAction],
+    /// Indicative NAV in dollars per basket share, keyed by the basket's
+    /// stock id, as of the end of the last batch.
+    pub nav: Option<&'a HashMap<u64, f64>>,
 }
 
-/// The core trait that all our participant types will implement.
+/// The core trait that all our participant types will implement. `Send` so a
+/// `Market` can run decisions on worker threads.
 /// TODO: Later add a SYMBOL ticker argument when we are managing lots of stocks to handle
-pub trait Agent {
+pub trait Agent: Send {
     // === Core Decision-Making ===
     fn decide_actions(&mut self, market_view: &MarketView) -> Vec<OrderRequest>;
 
@@ -37,29 +69,134 @@ pub trait Agent {
     fn update_portfolio(&mut self, trade_volume: i64, trade: &Trade);
     /// A way for the agent to look at the net worth of their portfolio
     fn evaluate_port(&mut self, market_view: &MarketView) -> f64;
+    /// Cash and per-stock positions, for agents that keep a `Portfolio`.
+    fn portfolio(&self) -> Option<&Portfolio> {
+        None
+    }
     /// Get a list of all currently open orders for this agent.
     fn get_pending_orders(&self) -> Vec<Order>;
 
+    /// The Market confirms a `CancelOrder` took the order off the book.
+    fn cancel_acknowledged(&mut self, _order_id: u64) {}
+    /// The Market refused `request`; failed cancels land here too.
+    fn order_rejected(&mut self, _request: &OrderRequest, _reason: RejectReason) {}
+    /// `stock` joined the universe, at start-up or mid-session. Agents that
+    /// start out with inventory take it here, at the stock's initial price.
+    fn stock_listed(&mut self, _stock: &Stock) {}
+    /// `stock_id` stopped trading. Resting orders were already cancelled
+    /// (each through `cancel_acknowledged`) and any position was settled at
+    /// `final_price` cents on the exchange's ledger.
+    fn stock_delisted(&mut self, _stock_id: u64, _final_price: u64) {}
+    /// `action` took effect as this tick started, before `decide_actions`.
+    /// Resting orders were already restated in the book (those it left
+    /// nothing of went through `cancel_acknowledged`) and the exchange's
+    /// ledger adjusted; the agent brings its own records in line.
+    fn corporate_action(&mut self, _action: &CorporateAction) {}
+    /// A creation or redemption this agent asked for went through and the
+    /// exchange's ledger already carries it.
+    fn units_settled(&mut self, _settlement: &UnitSettlement) {}
+
     /// Creates a request to cancel an open order.
     fn cancel_open_order(&mut self, order_id: u64) -> Vec<OrderRequest>;
-    /// To have the agents run perpetually
-    // TODO: decide_actions return type should be void since we will be adding a mpsc channel to send actions to the market.
-    // Right now this will be a stub
+    /// To have the agents run perpetually. Live mode (`Market::run_live`)
+    /// drives `decide_actions` on the agent's own thread instead, so this
+    /// stays a stub.
     fn run(&mut self);
     // === Getters & Housekeeping ===
     fn get_id(&self) -> usize;
     fn get_inventory(&self) -> i64;
+    /// Deep copy of the agent, including inventory, open orders and RNG state.
     fn clone_agent(&self) -> Box<dyn Agent>;
+    /// Restart the agent's random stream from `seed`.
+    /// Agents without randomness can keep the default no-op.
+    fn reseed(&mut self, _seed: u64) {}
 }
 /// The whale needs this
 impl<'a> MarketView<'a> {
+    /// Books and reference data only.
+    pub fn new(order_books: &'a HashMap<u64, OrderBook>, stocks: &'a StockMarket) -> Self {
+        Self {
+            order_books,
+            stocks,
+            tick: 0,
+            tape: None,
+            stats: None,
+            sentiment: None,
+            fundamentals: None,
+            accounts: None,
+            agent_id: None,
+            corporate_actions: &[],
+            nav: None,
+        }
+    }
+
+    /// The same view as seen by `agent_id` (what `account` and
+    /// `open_orders` report on).
+    pub fn for_agent(self, agent_id: usize) -> Self {
+        Self {
+            agent_id: Some(agent_id),
+            ..self
+        }
+    }
+
+    pub fn stats(&self, stock_id: u64) -> Option<&'a StockStats> {
+        self.stats?.get(&stock_id)
+    }
+
+    /// Last traded price in cents.
+    pub fn last_price(&self, stock_id: u64) -> Option<u64> {
+        self.stats(stock_id)?.last_price
+    }
+
+    /// Indicative NAV of the basket `stock_id`, dollars per share.
+    pub fn nav(&self, stock_id: u64) -> Option<f64> {
+        self.nav?.get(&stock_id).copied()
+    }
+
+    /// Current sentiment in [-1, 1]; 0 when the market runs without one.
+    pub fn sentiment(&self, stock_id: u64) -> f64 {
+        self.sentiment.map_or(0.0, |s| s.get(stock_id))
+    }
+
+    /// Latent value of `stock_id` as it stood `delay` ticks ago, dollars.
+    /// What an informed agent learns; any noise is its own to add.
+    pub fn fundamental(&self, stock_id: u64, delay: usize) -> Option<f64> {
+        self.fundamentals?.lagged(stock_id, delay)
+    }
+
+    /// The viewing agent's starting inventory and fills as the exchange
+    /// booked them. Cash is the net flow since the session started.
+    pub fn account(&self) -> Option<&'a Portfolio> {
+        self.accounts?.get(&self.agent_id?)
+    }
+
+    /// The viewing agent's resting orders as the books hold them, by stock
+    /// id then order id.
+    pub fn open_orders(&self) -> Vec<Order> {
+        let Some(me) = self.agent_id else {
+            return vec![];
+        };
+        let mut stock_ids: Vec<&u64> = self.order_books.keys().collect();
+        stock_ids.sort_unstable();
+        stock_ids
+            .into_iter()
+            .flat_map(|s| self.order_books[s].orders_of(me))
+            .collect()
+    }
+
     pub fn book(&self, stock_id: u64) -> Option<&OrderBook> {
         self.order_books.get(&stock_id)
     }
     pub fn get_mid_price(&self, stock_id: u64) -> Option<u64> {
-        let book = self.book(stock_id)?;
-        let best_bid = book.bids.keys().next_back()?;
-        let best_ask = book.asks.keys().next()?;
-        Some((best_bid + best_ask) / 2)
+        self.book(stock_id)?.mid_price()
+    }
+    /// What a market order for `qty` shares would cost right now; `None` for an unknown stock.
+    pub fn simulate_market_order(
+        &self,
+        stock_id: u64,
+        side: Side,
+        qty: u64,
+    ) -> Option<MarketImpact> {
+        Some(self.book(stock_id)?.simulate_market_order(side, qty))
     }
 }
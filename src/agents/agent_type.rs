@@ -1,10 +1,77 @@
 // src/agents/agent_type.rs
 
-#[derive(Debug, Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum AgentType {
     DumbMarket,
     DumbLimit,
     MarketMaker,
     IPO,
     WhaleAgent, // We can add more here later, like MarketMaker, Institutional, etc.
+    /// Authorised participant arbing baskets against their constituents.
+    EtfArb,
+    /// Trades on a noisy, delayed read of each stock's fundamental value
+    /// (`Market::enable_fundamentals`); idle without one.
+    Informed,
+}
+
+/// Overrides of one built-in agent's defaults from `agents::config`;
+/// `None` keeps the default. Each kind takes only the fields that mean
+/// something to it, see [`AgentType::unsupported`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct AgentParams {
+    /// Chance of acting, per trader and tick for the ensembles.
+    pub action_prob: Option<f64>,
+    /// Inclusive `[min, max]` shares per order.
+    pub volume: Option<[u64; 2]>,
+    /// Starting cash in dollars.
+    pub cash: Option<f64>,
+    /// Starting shares of every listed stock.
+    pub inventory: Option<i64>,
+    /// Ticks an informed agent's signal lags the fundamental value.
+    pub signal_delay: Option<usize>,
+    /// Log-normal noise on an informed agent's signal.
+    pub signal_noise: Option<f64>,
+    /// Premium or discount to NAV, in basis points, an ETF arbitrageur
+    /// waits for.
+    pub threshold_bps: Option<f64>,
+    /// Creation units per ETF arbitrage.
+    pub units: Option<u64>,
+}
+
+impl AgentType {
+    /// Names of the fields set in `params` that this kind has no use for.
+    pub fn unsupported(self, params: &AgentParams) -> Vec<&'static str> {
+        let takes: &[&str] = match self {
+            AgentType::DumbMarket => &["action_prob", "volume", "cash"],
+            AgentType::DumbLimit => &["action_prob", "volume", "cash", "inventory"],
+            AgentType::MarketMaker => &["volume", "cash", "inventory"],
+            AgentType::IPO => &[],
+            AgentType::WhaleAgent => &["action_prob", "cash", "inventory"],
+            AgentType::EtfArb => &["cash", "threshold_bps", "units"],
+            AgentType::Informed => &[
+                "action_prob",
+                "volume",
+                "cash",
+                "signal_delay",
+                "signal_noise",
+            ],
+        };
+        let set = [
+            ("action_prob", params.action_prob.is_some()),
+            ("volume", params.volume.is_some()),
+            ("cash", params.cash.is_some()),
+            ("inventory", params.inventory.is_some()),
+            ("signal_delay", params.signal_delay.is_some()),
+            ("signal_noise", params.signal_noise.is_some()),
+            ("threshold_bps", params.threshold_bps.is_some()),
+            ("units", params.units.is_some()),
+        ];
+        set.into_iter()
+            .filter(|&(name, is_set)| is_set && !takes.contains(&name))
+            .map(|(name, _)| name)
+            .collect()
+    }
 }
@@ -44,6 +44,7 @@ pub const LIMIT_AGENT_VOL_MAX: u64 = 5_000;
 // The speculative offset remains large, representing diverse opinions on price.
 pub const LIMIT_AGENT_MAX_OFFSET: u64 = 200; // $5.00 in cents
 pub const LIMIT_AGENT_NUM_TRADERS: u32 = 200;
+pub const LIMIT_AGENT_INITIAL_INVENTORY: i64 = 200_000_000; // per listed stock
 // The whales are here
 pub const WHALE_INITIAL_INVENTORY: i64 = 50_000_000;
 pub const WHALE_ACTION_PROB: f64 = 0.01; // Acts very infrequently (5% chance per tick)
@@ -51,3 +52,17 @@ pub const WHALE_ORDER_VOLUME: u64 = 1_000_000; // Places massive orders
 pub const WHALE_PRICE_OFFSET_MAX: u64 = 1000;
 pub const WHALE_PRICE_OFFSET_MIN: u64 = 500;
 pub const CRAZY_WHALE: f64 = 0.01;
+// --- EtfArbAgent (authorised participant) ---
+// Arbs a basket against its constituents once the premium or discount to NAV
+// clears the threshold and a full round trip still pays after the fee.
+pub const ETF_ARB_THRESHOLD_BPS: f64 = 25.0;
+pub const ETF_ARB_UNITS: u64 = 1; // creation units per trade
+pub const ETF_ARB_INITIAL_CASH: f64 = 10_000_000.0;
+// --- InformedAgent (trades on a noisy, late read of fundamental value) ---
+pub const INFORMED_ACTION_PROB: f64 = 0.2;
+pub const INFORMED_SIGNAL_DELAY: usize = 5; // ticks behind the true value
+pub const INFORMED_SIGNAL_NOISE: f64 = 0.005; // log-normal, 0.5 %
+pub const INFORMED_EDGE_BPS: f64 = 20.0; // beyond the touch before trading
+pub const INFORMED_VOL_MIN: u64 = 100;
+pub const INFORMED_VOL_MAX: u64 = 1_000;
+pub const INFORMED_INITIAL_CASH: f64 = 10_000_000.0;
@@ -1,5 +1,5 @@
 // src/agents/dumb_agent.rs
-use rand::{Rng, seq::SliceRandom};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use std::collections::HashMap;
 
 use super::{
@@ -9,37 +9,60 @@ use super::{
         DUMB_AGENT_LARGE_VOL_MIN, DUMB_AGENT_NUM_TRADERS, DUMB_AGENT_TYPICAL_VOL_MAX,
         DUMB_AGENT_TYPICAL_VOL_MIN,
     },
+    portfolio::Portfolio,
 };
 use crate::{
     agents::latency::DUMB_AGENT_TICKS_UNTIL_ACTIVE,
+    corporate::CorporateAction,
     types::order::{Order, OrderRequest, Side, Trade},
 };
 //allow cloning
 #[derive(Debug, Clone)]
 pub struct DumbAgent {
     id: usize,
-    // update inventory as a hashmap linking the stock id to the number of shares held .(Signed so I can short)
-    inventory: HashMap<u64, i64>,
+    // per-stock positions and cash (signed so I can short)
+    portfolio: Portfolio,
     ticks_until_active: u32,
     open_orders: HashMap<u64, Order>,
-    cash: f64,
     margin: f64,
-    port_value: f64,
+    action_prob: f64,
+    /// Range of a typical (not "burn") order.
+    volume: (u64, u64),
+    rng: StdRng,
 }
 
 impl DumbAgent {
     pub fn new(id: usize) -> Self {
         Self {
             id,
-            // empty inventory hashmap
-            inventory: HashMap::new(),
+            portfolio: Portfolio::new(1_000_000_000.0),
             ticks_until_active: DUMB_AGENT_TICKS_UNTIL_ACTIVE,
             open_orders: HashMap::new(),
-            cash: 1_000_000_000.0,
             margin: 4_000_000_000.0,
-            port_value: 0.0,
+            action_prob: DUMB_AGENT_ACTION_PROB,
+            volume: (DUMB_AGENT_TYPICAL_VOL_MIN, DUMB_AGENT_TYPICAL_VOL_MAX),
+            rng: StdRng::from_entropy(),
         }
     }
+
+    /// Chance, per trader and tick, of placing an order.
+    pub fn with_action_prob(mut self, p: f64) -> Self {
+        self.action_prob = p.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Shares per typical order; the rare "burn" orders keep their size, drawn from `min..=max`.
+    pub fn with_volume(mut self, min: u64, max: u64) -> Self {
+        let min = min.max(1);
+        self.volume = (min, max.max(min));
+        self
+    }
+
+    /// Starting cash in dollars.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.portfolio = Portfolio::new(cash);
+        self
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -52,7 +75,6 @@ impl Agent for DumbAgent {
             return vec![];
         }
 
-        let mut rng = rand::thread_rng();
         let mut out = Vec::new();
 
         /* --- choose a random instrument for this tick --- */
@@ -60,10 +82,14 @@ impl Agent for DumbAgent {
         if universe.is_empty() {
             return out;
         }
-        let stock_id = *universe.choose(&mut rng).unwrap();
+        let stock_id = *universe.choose(&mut self.rng).unwrap();
+        let rng = &mut self.rng;
+        let (action_prob, (vol_min, vol_max)) = (self.action_prob, self.volume);
+        let mut orders = Vec::new();
+        let mut buying = 0; // shares already committed to buys this tick
 
         for _ in 0..DUMB_AGENT_NUM_TRADERS {
-            if rng.gen_bool(DUMB_AGENT_ACTION_PROB) {
+            if rng.gen_bool(action_prob) {
                 let side = if rng.gen_bool(0.5) {
                     Side::Buy
                 } else {
@@ -73,27 +99,33 @@ impl Agent for DumbAgent {
                 let volume = if rng.gen_bool(DUMB_AGENT_LARGE_VOL_CHANCE) {
                     rng.gen_range(DUMB_AGENT_LARGE_VOL_MIN..=DUMB_AGENT_LARGE_VOL_MAX)
                 } else {
-                    rng.gen_range(DUMB_AGENT_TYPICAL_VOL_MIN..=DUMB_AGENT_TYPICAL_VOL_MAX)
+                    rng.gen_range(vol_min..=vol_max)
                 };
 
-                /* --- buying-power check --- */
+                /* --- buying-power check: sweep the book for this tick's buys --- */
                 if side == Side::Buy {
-                    if let Some(px) = view.get_mid_price(stock_id) {
-                        let cost = volume as f64 * (px as f64 / 100.0);
-                        if cost > self.cash + self.margin {
+                    if let Some(impact) =
+                        view.simulate_market_order(stock_id, Side::Buy, buying + volume)
+                    {
+                        let cost = impact.notional as f64 / 100.0;
+                        if cost > self.portfolio.cash() + self.margin {
                             continue; // skip action
                         }
                     }
+                    buying += volume;
                 }
 
-                let reqs = if side == Side::Buy {
-                    self.buy_stock(stock_id, volume)
-                } else {
-                    self.sell_stock(stock_id, volume)
-                };
-                out.extend(reqs);
+                orders.push((side, volume));
             }
         }
+        for (side, volume) in orders {
+            let reqs = if side == Side::Buy {
+                self.buy_stock(stock_id, volume)
+            } else {
+                self.sell_stock(stock_id, volume)
+            };
+            out.extend(reqs);
+        }
         out
     }
     fn run(&mut self) {}
@@ -116,24 +148,19 @@ impl Agent for DumbAgent {
     }
 
     fn margin_call(&mut self) -> Vec<OrderRequest> {
-        if self.cash < -self.margin {
-            // CREATE AN empty vector to hold the liquidation orders
-            let mut liquidation_orders = Vec::new();
-            // sweep the inventory hashmap and burn all shares into the lqiuidation orders
-            for (&stock_id, &vol) in &self.inventory {
-                if vol != 0 {
-                    liquidation_orders.push(OrderRequest::MarketOrder {
-                        agent_id: self.id,
-                        stock_id,
-                        side: Side::Sell,
-                        volume: vol.unsigned_abs() as u64, // convert to unsigned for market order
-                    });
-                }
-            }
-            // clear the inventory
-            self.inventory.clear();
-            // return the liquidation orders
-            return liquidation_orders;
+        if self.portfolio.cash() < -self.margin {
+            // flatten every position; the fills bring the portfolio back to zero
+            return self
+                .portfolio
+                .positions()
+                .filter(|(_, p)| p.qty != 0)
+                .map(|(stock_id, p)| OrderRequest::MarketOrder {
+                    agent_id: self.id,
+                    stock_id,
+                    side: if p.qty > 0 { Side::Sell } else { Side::Buy },
+                    volume: p.qty.unsigned_abs(),
+                })
+                .collect();
         }
 
         vec![]
@@ -146,23 +173,29 @@ impl Agent for DumbAgent {
     }
 
     fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
-        // Update the inventory for the specific stock_id
-        let stock_id = tr.stock_id;
-        // update the hashmap inventory
-        let current_inventory = self.inventory.entry(stock_id).or_insert(0);
-        *current_inventory += vol;
-        self.cash -= vol as f64 * (tr.price as f64 / 100.0);
-
-        if tr.maker_agent_id == self.id {
-            if let Some(o) = self.open_orders.get_mut(&tr.maker_order_id) {
-                o.filled += tr.volume;
-                if o.filled >= o.volume {
-                    self.open_orders.remove(&tr.maker_order_id);
-                }
+        self.portfolio.on_trade(vol, tr);
+
+        if tr.maker_agent_id == self.id
+            && let Some(o) = self.open_orders.get_mut(&tr.maker_order_id)
+        {
+            o.filled += tr.volume;
+            if o.filled >= o.volume {
+                self.open_orders.remove(&tr.maker_order_id);
             }
         }
     }
 
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+        self.open_orders.retain(|_, o| o.stock_id != stock_id);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+        self.open_orders
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+
     fn get_pending_orders(&self) -> Vec<Order> {
         self.open_orders.values().cloned().collect()
     }
@@ -177,25 +210,20 @@ impl Agent for DumbAgent {
         self.id
     }
     fn get_inventory(&self) -> i64 {
-        // count the total inventory across all stocks
-        self.inventory.values().sum()
-        //self.inventory
+        self.portfolio.shares()
     }
     fn clone_agent(&self) -> Box<dyn Agent> {
         Box::new(self.clone()) // clone the agent while preserving the inventory and stuff.
     }
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
 
     fn evaluate_port(&mut self, view: &MarketView) -> f64 {
-        // iterate over all stocks in the inventory and calculate the total value
-        // take out all the stock and use their mid price
-        self.port_value = self.inventory.iter().fold(0.0, |acc, (stock_id, &vol)| {
-            if let Some(px) = view.get_mid_price(*stock_id) {
-                acc + vol as f64 * (px as f64 / 100.0)
-            } else {
-                acc
-            }
-        });
-        self.port_value
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
     }
 }
 
@@ -225,35 +253,35 @@ mod tests {
     #[test]
     fn cash_updates_on_buy() {
         let mut a = DumbAgent::new(0);
-        let cash0 = a.cash;
+        let cash0 = a.portfolio.cash();
         let tr = mock_trade(15_000, 10); // $150 × 10
         a.update_portfolio(10, &tr); // buy
         let cost = 10.0 * 150.0;
-        assert!((a.cash - (cash0 - cost)).abs() < 1e-9);
+        assert!((a.portfolio.cash() - (cash0 - cost)).abs() < 1e-9);
         // inventory should increase by 10 shares
-        assert_eq!(a.inventory.get(&STOCK_ID).unwrap_or(&0), &10);
-        //assert_eq!(a.inventory, 300_000_000 + 10);
+        assert_eq!(a.portfolio.qty(STOCK_ID), 10);
     }
 
     #[test]
     fn cash_updates_on_sell() {
         let mut a = DumbAgent::new(0);
-        let cash0 = a.cash;
+        let cash0 = a.portfolio.cash();
         let tr = mock_trade(15_000, 10);
         a.update_portfolio(-10, &tr); // sell
         let proceeds = 10.0 * 150.0;
-        assert!((a.cash - (cash0 + proceeds)).abs() < 1e-9);
+        assert!((a.portfolio.cash() - (cash0 + proceeds)).abs() < 1e-9);
         // inventory should decrease by 10 shares
-        assert_eq!(a.inventory.get(&STOCK_ID).unwrap_or(&0), &-10);
-        //assert_eq!(a.inventory, 300_000_000 - 10);
+        assert_eq!(a.portfolio.qty(STOCK_ID), -10);
     }
 
     #[test]
     fn margin_call_triggers() {
         let mut a = DumbAgent::new(0);
-        a.cash = -4_000_000_000.1; // breach
-        a.inventory.insert(0, 500);
-        a.inventory.insert(1, 100);
+        a.portfolio = Portfolio::new(-4_000_000_000.1 + 600.0); // breach once filled
+        a.portfolio.apply_fill(0, 500, 100);
+        a.portfolio.apply_fill(1, 100, 100);
+        a.portfolio.apply_fill(2, -50, 100);
+        a.portfolio.apply_fill(2, 50, 100); // flat: nothing to sell
 
         let reqs = a.margin_call();
         assert_eq!(reqs.len(), 2, "should liquidate all inventory");
@@ -279,22 +307,69 @@ mod tests {
         // Verify we got the right liquidations
         assert_eq!(liquidations.get(&0), Some(&500));
         assert_eq!(liquidations.get(&1), Some(&100));
-        assert!(a.inventory.is_empty(), "inventory should be cleared");
+
+        // the liquidation fills flatten the book
+        for (&stock_id, &volume) in &liquidations {
+            a.portfolio.apply_fill(stock_id, -(volume as i64), 100);
+        }
+        assert_eq!(a.get_inventory(), 0, "inventory should be cleared");
     }
 
     #[test]
     fn margin_call_not_triggered_when_safe() {
         let mut good = DumbAgent::new(0);
-        good.cash = 1_000.0;
+        good.portfolio = Portfolio::new(1_000.0);
 
         let mut within = DumbAgent::new(1);
-        within.cash = -3_999_999_999.9;
+        within.portfolio = Portfolio::new(-3_999_999_999.9);
 
         let mut at_limit = DumbAgent::new(2);
-        at_limit.cash = -4_000_000_000.0;
+        at_limit.portfolio = Portfolio::new(-4_000_000_000.0);
 
         assert!(good.margin_call().is_empty());
         assert!(within.margin_call().is_empty());
         assert!(at_limit.margin_call().is_empty());
     }
+
+    #[test]
+    fn buys_stay_within_buying_power() {
+        use crate::{OrderBook, stocks::definitions::StockMarket};
+        let stocks = StockMarket::new();
+        let mut books = HashMap::new();
+        for id in stocks.get_all_ids() {
+            let mut book = OrderBook::new();
+            book.process_limit_order(&mut Order {
+                id,
+                agent_id: 99,
+                stock_id: id,
+                side: Side::Sell,
+                price: 100, // $1.00
+                volume: 10_000_000,
+                filled: 0,
+            });
+            books.insert(id, book);
+        }
+        let view = MarketView::new(&books, &stocks);
+
+        let mut a = DumbAgent::new(0);
+        a.reseed(1);
+        a.ticks_until_active = 0;
+        a.portfolio = Portfolio::new(0.0);
+        a.margin = 1_000.0; // enough for 1 000 shares per tick
+        for _ in 0..50 {
+            let bought: u64 = a
+                .decide_actions(&view)
+                .iter()
+                .map(|r| match r {
+                    OrderRequest::MarketOrder {
+                        side: Side::Buy,
+                        volume,
+                        ..
+                    } => *volume,
+                    _ => 0,
+                })
+                .sum();
+            assert!(bought <= 1_000, "bought {bought} shares on $1 000");
+        }
+    }
 }
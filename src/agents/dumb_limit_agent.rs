@@ -1,43 +1,77 @@
 // src/agents/dumb_limit_agent.rs
-use rand::{Rng, seq::SliceRandom};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use std::collections::HashMap;
 
 use super::{
     agent_trait::{Agent, MarketView},
     config::{
-        LIMIT_AGENT_ACTION_PROB, LIMIT_AGENT_MAX_OFFSET, LIMIT_AGENT_NUM_TRADERS,
-        LIMIT_AGENT_VOL_MAX, LIMIT_AGENT_VOL_MIN, MARGIN_CALL_THRESHOLD,
+        LIMIT_AGENT_ACTION_PROB, LIMIT_AGENT_INITIAL_INVENTORY, LIMIT_AGENT_MAX_OFFSET,
+        LIMIT_AGENT_NUM_TRADERS, LIMIT_AGENT_VOL_MAX, LIMIT_AGENT_VOL_MIN, MARGIN_CALL_THRESHOLD,
     },
+    portfolio::Portfolio,
 };
 use crate::{
     agents::latency::LIMIT_AGENT_TICKS_UNTIL_ACTIVE,
+    corporate::CorporateAction,
+    stocks::definitions::Stock,
     types::order::{Order, OrderRequest, Side, Trade},
 };
 
+#[derive(Debug, Clone)]
 pub struct DumbLimitAgent {
     id: usize,
-    inventory: i64,
+    portfolio: Portfolio,
     ticks_until_active: u32,
     open_orders: HashMap<u64, Order>,
-    cash: f64,
     // allow dead code
     #[allow(dead_code)]
     margin: f64,
-    port_value: f64,
+    action_prob: f64,
+    volume: (u64, u64),
+    /// Starting shares of each listed stock.
+    inventory: i64,
+    rng: StdRng,
 }
 
 impl DumbLimitAgent {
     pub fn new(id: usize) -> Self {
         Self {
             id,
-            inventory: 200_000_000,
+            portfolio: Portfolio::new(100_000_000.0),
             ticks_until_active: LIMIT_AGENT_TICKS_UNTIL_ACTIVE,
             open_orders: HashMap::new(),
-            cash: 100_000_000.0,
             margin: 10_000_000_000.0,
-            port_value: 0.0,
+            action_prob: LIMIT_AGENT_ACTION_PROB,
+            volume: (LIMIT_AGENT_VOL_MIN, LIMIT_AGENT_VOL_MAX),
+            inventory: LIMIT_AGENT_INITIAL_INVENTORY,
+            rng: StdRng::from_entropy(),
         }
     }
+
+    /// Chance, per trader and tick, of placing an order.
+    pub fn with_action_prob(mut self, p: f64) -> Self {
+        self.action_prob = p.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Shares per order, drawn from `min..=max`.
+    pub fn with_volume(mut self, min: u64, max: u64) -> Self {
+        let min = min.max(1);
+        self.volume = (min, max.max(min));
+        self
+    }
+
+    /// Starting cash in dollars.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.portfolio = Portfolio::new(cash);
+        self
+    }
+
+    /// Shares of every stock it is endowed with when the stock lists.
+    pub fn with_inventory(mut self, qty: i64) -> Self {
+        self.inventory = qty;
+        self
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -50,7 +84,8 @@ impl Agent for DumbLimitAgent {
             return vec![];
         }
 
-        let mut rng = rand::thread_rng();
+        let rng = &mut self.rng;
+        let (action_prob, (vol_min, vol_max)) = (self.action_prob, self.volume);
         let mut out = Vec::new();
 
         /* choose a random instrument for this tick */
@@ -58,17 +93,16 @@ impl Agent for DumbLimitAgent {
         if ids.is_empty() {
             return out;
         }
-        let stock_id = *ids.choose(&mut rng).unwrap();
+        let stock_id = *ids.choose(rng).unwrap();
         let book = match view.book(stock_id) {
             Some(b) => b,
             None => return out,
         };
 
-        let best_bid = book.bids.keys().next_back().copied();
-        let best_ask = book.asks.keys().next().copied();
+        let (best_bid, best_ask) = book.best_bid_ask();
 
         for _ in 0..LIMIT_AGENT_NUM_TRADERS {
-            if !rng.gen_bool(LIMIT_AGENT_ACTION_PROB) {
+            if !rng.gen_bool(action_prob) {
                 continue;
             }
 
@@ -87,7 +121,7 @@ impl Agent for DumbLimitAgent {
                     Side::Buy => bid.saturating_add(offset),
                     Side::Sell => ask.saturating_sub(offset),
                 };
-                let volume = rng.gen_range(LIMIT_AGENT_VOL_MIN..=LIMIT_AGENT_VOL_MAX);
+                let volume = rng.gen_range(vol_min..=vol_max);
 
                 out.push(OrderRequest::LimitOrder {
                     agent_id: self.id,
@@ -121,18 +155,18 @@ impl Agent for DumbLimitAgent {
 
     /* margin call: liquidate when inventory too low ------------------------ */
     fn margin_call(&mut self) -> Vec<OrderRequest> {
-        if self.inventory <= MARGIN_CALL_THRESHOLD {
-            let deficit = self.inventory.unsigned_abs();
-            /* choose any stock_id we’re active in (else 0) */
-            let sid = self
-                .open_orders
-                .values()
-                .next()
-                .map(|o| o.stock_id)
-                .unwrap_or(0);
-            return self.buy_stock(sid, deficit);
-        }
-        vec![]
+        /* buy back every stock we are too short in */
+        let mut short: Vec<(u64, i64)> = self
+            .portfolio
+            .positions()
+            .filter(|(_, p)| p.qty <= MARGIN_CALL_THRESHOLD)
+            .map(|(id, p)| (id, p.qty))
+            .collect();
+        short.sort_unstable();
+        short
+            .into_iter()
+            .flat_map(|(sid, qty)| self.buy_stock(sid, qty.unsigned_abs()))
+            .collect()
     }
 
     /* bookkeeping ----------------------------------------------------------- */
@@ -141,19 +175,34 @@ impl Agent for DumbLimitAgent {
     }
 
     fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
-        self.inventory += vol;
-        self.cash -= vol as f64 * (tr.price as f64 / 100.0);
-
-        if tr.maker_agent_id == self.id {
-            if let Some(o) = self.open_orders.get_mut(&tr.maker_order_id) {
-                o.filled += tr.volume;
-                if o.filled >= o.volume {
-                    self.open_orders.remove(&tr.maker_order_id);
-                }
+        self.portfolio.on_trade(vol, tr);
+
+        if tr.maker_agent_id == self.id
+            && let Some(o) = self.open_orders.get_mut(&tr.maker_order_id)
+        {
+            o.filled += tr.volume;
+            if o.filled >= o.volume {
+                self.open_orders.remove(&tr.maker_order_id);
             }
         }
     }
 
+    fn stock_listed(&mut self, stock: &Stock) {
+        self.portfolio
+            .endow(stock.id, self.inventory, stock.initial_price);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+        self.open_orders.retain(|_, o| o.stock_id != stock_id);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+        self.open_orders
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+
     fn get_pending_orders(&self) -> Vec<Order> {
         self.open_orders.values().cloned().collect()
     }
@@ -167,17 +216,19 @@ impl Agent for DumbLimitAgent {
         self.id
     }
     fn get_inventory(&self) -> i64 {
-        self.inventory
+        self.portfolio.shares()
     }
     fn clone_agent(&self) -> Box<dyn Agent> {
-        Box::new(DumbLimitAgent::new(self.id))
+        Box::new(self.clone())
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
     fn evaluate_port(&mut self, view: &MarketView) -> f64 {
-        let sid = *view.stocks.get_all_ids().first().unwrap_or(&0);
-        if let Some(px) = view.get_mid_price(sid) {
-            self.port_value = self.inventory as f64 * (px as f64 / 100.0);
-        }
-        self.port_value
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
     }
 }
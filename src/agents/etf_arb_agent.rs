@@ -0,0 +1,316 @@
+// src/agents/etf_arb_agent.rs
+//
+// Authorised participant that keeps baskets in line with their constituents.
+// When a basket trades rich to its NAV it buys the constituents, creates
+// units and sells the new basket shares; when it trades cheap it does the
+// reverse. Everything goes in as market orders in one batch, priced first
+// against the books so the round trip pays after the creation fee.
+use super::{
+    agent_trait::{Agent, MarketView},
+    config::{ETF_ARB_INITIAL_CASH, ETF_ARB_THRESHOLD_BPS, ETF_ARB_UNITS},
+    latency::ETF_ARB_TICKS_UNTIL_ACTIVE,
+    portfolio::Portfolio,
+};
+use crate::{
+    corporate::CorporateAction,
+    stocks::basket::{Basket, UnitSettlement},
+    types::order::{Order, OrderRequest, Side, Trade},
+};
+
+#[derive(Debug, Clone)]
+pub struct EtfArbAgent {
+    id: usize,
+    portfolio: Portfolio,
+    ticks_until_active: u32,
+    threshold_bps: f64,
+    units: u64,
+}
+
+impl EtfArbAgent {
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            portfolio: Portfolio::new(ETF_ARB_INITIAL_CASH),
+            ticks_until_active: ETF_ARB_TICKS_UNTIL_ACTIVE,
+            threshold_bps: ETF_ARB_THRESHOLD_BPS,
+            units: ETF_ARB_UNITS,
+        }
+    }
+
+    /// Trade only once the basket is this far from NAV, in basis points.
+    pub fn with_threshold_bps(mut self, bps: f64) -> Self {
+        self.threshold_bps = bps;
+        self
+    }
+
+    /// Starting cash in dollars.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.portfolio = Portfolio::new(cash);
+        self
+    }
+
+    /// Creation units per trade.
+    pub fn with_units(mut self, units: u64) -> Self {
+        self.units = units.max(1);
+        self
+    }
+
+    /// The orders and creation/redemption for one basket, or nothing if it
+    /// is inside the threshold or the books are too thin to pay for it.
+    fn arb(&self, basket: &Basket, view: &MarketView) -> Vec<OrderRequest> {
+        let mid = |id| view.get_mid_price(id).map(|c| c as f64 / 100.0);
+        let Some(nav) = view
+            .nav(basket.stock_id)
+            .or_else(|| basket.nav(mid))
+            .filter(|&n| n > 0.0)
+        else {
+            return vec![];
+        };
+        let Some(price) = mid(basket.stock_id) else {
+            return vec![];
+        };
+        let premium_bps = (price - nav) / nav * 10_000.0;
+        if premium_bps.abs() < self.threshold_bps {
+            return vec![];
+        }
+
+        // rich: buy the constituents, create, sell the basket; cheap: reverse
+        let (leg_side, basket_side) = if premium_bps > 0.0 {
+            (Side::Buy, Side::Sell)
+        } else {
+            (Side::Sell, Side::Buy)
+        };
+        let mut legs = vec![(
+            basket.stock_id,
+            basket_side,
+            basket.creation_unit * self.units,
+        )];
+        legs.extend(
+            basket
+                .constituents
+                .iter()
+                .map(|c| (c.stock_id, leg_side, c.shares * self.units)),
+        );
+
+        // cents received minus cents paid over every leg
+        let mut edge = 0i128;
+        for &(stock_id, side, volume) in &legs {
+            let Some(impact) = view.simulate_market_order(stock_id, side, volume) else {
+                return vec![];
+            };
+            if impact.unfilled > 0 {
+                return vec![];
+            }
+            edge += match side {
+                Side::Sell => impact.notional as i128,
+                Side::Buy => -(impact.notional as i128),
+            };
+        }
+        if edge as f64 / 100.0 <= basket.fee * self.units as f64 {
+            return vec![];
+        }
+
+        let order = |&(stock_id, side, volume): &(u64, Side, u64)| OrderRequest::MarketOrder {
+            agent_id: self.id,
+            stock_id,
+            side,
+            volume,
+        };
+        let units = if premium_bps > 0.0 {
+            OrderRequest::CreateUnits {
+                agent_id: self.id,
+                stock_id: basket.stock_id,
+                units: self.units,
+            }
+        } else {
+            OrderRequest::RedeemUnits {
+                agent_id: self.id,
+                stock_id: basket.stock_id,
+                units: self.units,
+            }
+        };
+        // shares to deliver are bought first, shares received sold last
+        if premium_bps > 0.0 {
+            legs[1..]
+                .iter()
+                .map(order)
+                .chain([units, order(&legs[0])])
+                .collect()
+        } else {
+            [order(&legs[0]), units]
+                .into_iter()
+                .chain(legs[1..].iter().map(order))
+                .collect()
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Agent impl
+// -----------------------------------------------------------------------------
+impl Agent for EtfArbAgent {
+    fn decide_actions(&mut self, view: &MarketView) -> Vec<OrderRequest> {
+        if self.ticks_until_active > 0 {
+            self.ticks_until_active -= 1;
+            return vec![];
+        }
+        view.stocks
+            .baskets()
+            .flat_map(|b| self.arb(b, view))
+            .collect()
+    }
+    fn run(&mut self) {
+        // Driven entirely by decide_actions.
+    }
+    fn buy_stock(&mut self, stock_id: u64, volume: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.id,
+            stock_id,
+            side: Side::Buy,
+            volume,
+        }]
+    }
+    fn sell_stock(&mut self, stock_id: u64, volume: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.id,
+            stock_id,
+            side: Side::Sell,
+            volume,
+        }]
+    }
+
+    fn margin_call(&mut self) -> Vec<OrderRequest> {
+        vec![] // every position is hedged by the basket it came from
+    }
+
+    /* bookkeeping ---------------------------------------------------------- */
+    fn acknowledge_order(&mut self, _o: Order) {
+        // only market orders, which never rest
+    }
+
+    fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
+        self.portfolio.on_trade(vol, tr);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+    }
+
+    fn units_settled(&mut self, settlement: &UnitSettlement) {
+        self.portfolio.apply_settlement(settlement);
+    }
+
+    fn get_pending_orders(&self) -> Vec<Order> {
+        vec![]
+    }
+
+    fn cancel_open_order(&mut self, _id: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+
+    /* misc getters --------------------------------------------------------- */
+    fn get_id(&self) -> usize {
+        self.id
+    }
+    fn get_inventory(&self) -> i64 {
+        self.portfolio.shares()
+    }
+    fn clone_agent(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+
+    fn evaluate_port(&mut self, view: &MarketView) -> f64 {
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
+    }
+}
+// -----------------------------------------------------------------------------
+//  Unit Tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        simulators::order_book::OrderBook,
+        stocks::definitions::{Stock, StockMarket},
+    };
+
+    fn book(stock_id: u64, bid: u64, ask: u64) -> OrderBook {
+        let mut book = OrderBook::new();
+        for (id, side, price) in [(1, Side::Buy, bid), (2, Side::Sell, ask)] {
+            book.process_limit_order(&mut Order {
+                id,
+                agent_id: 9,
+                stock_id,
+                side,
+                price,
+                volume: 100,
+                filled: 0,
+            });
+        }
+        book
+    }
+
+    #[test]
+    fn cheap_basket_is_bought_redeemed_and_sold_off() {
+        let mut stocks = StockMarket::new();
+        stocks.add_stock(Stock::new("PAIR", 50, "Pair ETF", 1_000_000, 80.0));
+        stocks
+            .add_basket(
+                Basket::new(50, 10)
+                    .with_constituent(1, 2)
+                    .with_constituent(2, 1),
+            )
+            .unwrap();
+        let mut books = HashMap::from([(1, book(1, 19_900, 20_100)), (2, book(2, 39_900, 40_100))]);
+        let mut arb = EtfArbAgent::new(3);
+        arb.ticks_until_active = 0;
+
+        // inside the threshold: $80.05 against $80.00
+        books.insert(50, book(50, 7_990, 8_020));
+        assert!(
+            arb.decide_actions(&MarketView::new(&books, &stocks))
+                .is_empty()
+        );
+
+        // $72 mid: paying $74 × 10 for stocks that sell for 2 × $199 + $399
+        books.insert(50, book(50, 7_000, 7_400));
+        let reqs = arb.decide_actions(&MarketView::new(&books, &stocks));
+        let market = |stock_id, side, volume| OrderRequest::MarketOrder {
+            agent_id: 3,
+            stock_id,
+            side,
+            volume,
+        };
+        assert_eq!(
+            reqs,
+            vec![
+                market(50, Side::Buy, 10),
+                OrderRequest::RedeemUnits {
+                    agent_id: 3,
+                    stock_id: 50,
+                    units: 1,
+                },
+                market(1, Side::Sell, 2),
+                market(2, Side::Sell, 1),
+            ]
+        );
+
+        // a fee above the edge ($797 - $740) keeps it out
+        let mut priced = stocks.clone();
+        let costly = priced.basket(50).unwrap().clone().with_fee(60.0);
+        priced.add_basket(costly).unwrap();
+        assert!(
+            arb.decide_actions(&MarketView::new(&books, &priced))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,578 @@
+// src/agents/event_agent.rs
+//
+// Event-driven alternative to the polling `Agent` trait. A strategy reacts to
+// callbacks (market data, acks, fills with role, cancel acks, rejects,
+// timers) and may answer any of them with new requests. `EventDriven` adapts
+// such a strategy to `Agent` so a `Market` runs it next to the built-ins, and
+// keeps the open-order bookkeeping every built-in agent repeats by hand.
+// Conversely every `Agent` is an `EventAgent` through the blanket impl below.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::agent_trait::{Agent, MarketView};
+use crate::corporate::CorporateAction;
+use crate::stocks::basket::UnitSettlement;
+use crate::stocks::definitions::Stock;
+use crate::types::order::{Order, OrderRequest, RejectReason, Side, Trade};
+
+/// Which side of the match the agent was on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    /// The agent's resting order was hit.
+    Maker,
+    /// The agent's incoming order crossed the book.
+    Taker,
+}
+
+/// One execution seen from the agent's side of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fill {
+    pub trade: Trade,
+    pub role: Role,
+    /// The agent's side, which for a self-trade differs between its two fills.
+    pub side: Side,
+}
+
+impl Fill {
+    /// Position change: positive when buying.
+    pub fn signed_volume(&self) -> i64 {
+        match self.side {
+            Side::Buy => self.trade.volume as i64,
+            Side::Sell => -(self.trade.volume as i64),
+        }
+    }
+
+    /// The resting order that was hit (ours only when `role` is `Maker`).
+    pub fn maker_order_id(&self) -> u64 {
+        self.trade.maker_order_id
+    }
+}
+
+/// An agent's resting orders as last seen on the exchange.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOrders {
+    orders: HashMap<u64, Order>,
+}
+
+impl OpenOrders {
+    pub fn get(&self, order_id: u64) -> Option<&Order> {
+        self.orders.get(&order_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Order> {
+        self.orders.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn insert(&mut self, order: Order) {
+        self.orders.insert(order.id, order);
+    }
+
+    fn remove(&mut self, order_id: u64) {
+        self.orders.remove(&order_id);
+    }
+
+    fn remove_stock(&mut self, stock_id: u64) {
+        self.orders.retain(|_, o| o.stock_id != stock_id);
+    }
+
+    fn restate(&mut self, action: &CorporateAction) {
+        self.orders
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+
+    fn fill(&mut self, order_id: u64, volume: u64) {
+        if let Some(o) = self.orders.get_mut(&order_id) {
+            o.filled += volume;
+            if o.filled >= o.volume {
+                self.orders.remove(&order_id);
+            }
+        }
+    }
+
+    /// Drop orders that are no longer resting and refresh the rest from the
+    /// books, which also catches market-order acks and immediate taker fills.
+    fn sync(&mut self, view: &MarketView) {
+        self.orders.retain(
+            |&id, o| match view.book(o.stock_id).and_then(|b| b.order(id)) {
+                Some(resting) => {
+                    *o = resting;
+                    true
+                }
+                None => false,
+            },
+        );
+    }
+}
+
+/// Callback-style participant. Every callback may return requests; the
+/// default for all but `on_market_data` is to ignore the event.
+pub trait EventAgent: Send {
+    fn id(&self) -> usize;
+    fn inventory(&self) -> i64;
+
+    /// A fresh view of the books, once per tick. `open` is what the exchange
+    /// still has resting for this agent.
+    fn on_market_data(&mut self, view: &MarketView, open: &OpenOrders) -> Vec<OrderRequest>;
+
+    /// A new order was accepted under `order.id`.
+    fn on_ack(&mut self, _order: &Order) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn on_fill(&mut self, _fill: &Fill) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// A resting order was cancelled at the agent's request.
+    fn on_cancel(&mut self, _order_id: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn on_reject(&mut self, _request: &OrderRequest, _reason: RejectReason) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// `stock` joined the universe; see `Agent::stock_listed`.
+    fn on_listed(&mut self, _stock: &Stock) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// `stock_id` was delisted; positions settle at `final_price` cents.
+    fn on_delisted(&mut self, _stock_id: u64, _final_price: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// `action` took effect as this tick started, before market data.
+    fn on_corporate_action(&mut self, _action: &CorporateAction) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// A creation or redemption the agent asked for was settled.
+    fn on_units_settled(&mut self, _settlement: &UnitSettlement) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// Fires every `EventDriven::with_timer` ticks, before market data.
+    fn on_timer(&mut self, _tick: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// The market's risk check, once per tick after matching. Requests go
+    /// straight to the books as market orders.
+    fn on_margin_call(&mut self) -> Vec<OrderRequest> {
+        vec![]
+    }
+    /// Net worth at the view's prices.
+    fn evaluate(&mut self, _view: &MarketView) -> f64 {
+        0.0
+    }
+    /// Restart the agent's random stream from `seed`.
+    fn reseed(&mut self, _seed: u64) {}
+}
+
+// -----------------------------------------------------------------------------
+//  Every polling agent is an event agent
+// -----------------------------------------------------------------------------
+impl<A: Agent + ?Sized> EventAgent for A {
+    fn id(&self) -> usize {
+        self.get_id()
+    }
+    fn inventory(&self) -> i64 {
+        self.get_inventory()
+    }
+    fn on_market_data(&mut self, view: &MarketView, _open: &OpenOrders) -> Vec<OrderRequest> {
+        self.decide_actions(view)
+    }
+    fn on_margin_call(&mut self) -> Vec<OrderRequest> {
+        self.margin_call()
+    }
+    fn on_ack(&mut self, order: &Order) -> Vec<OrderRequest> {
+        self.acknowledge_order(*order);
+        vec![]
+    }
+    fn on_fill(&mut self, fill: &Fill) -> Vec<OrderRequest> {
+        self.update_portfolio(fill.signed_volume(), &fill.trade);
+        vec![]
+    }
+    fn on_cancel(&mut self, order_id: u64) -> Vec<OrderRequest> {
+        self.cancel_acknowledged(order_id);
+        vec![]
+    }
+    fn on_reject(&mut self, request: &OrderRequest, reason: RejectReason) -> Vec<OrderRequest> {
+        self.order_rejected(request, reason);
+        vec![]
+    }
+    fn on_listed(&mut self, stock: &Stock) -> Vec<OrderRequest> {
+        self.stock_listed(stock);
+        vec![]
+    }
+    fn on_delisted(&mut self, stock_id: u64, final_price: u64) -> Vec<OrderRequest> {
+        self.stock_delisted(stock_id, final_price);
+        vec![]
+    }
+    fn on_corporate_action(&mut self, action: &CorporateAction) -> Vec<OrderRequest> {
+        self.corporate_action(action);
+        vec![]
+    }
+    fn on_units_settled(&mut self, settlement: &UnitSettlement) -> Vec<OrderRequest> {
+        self.units_settled(settlement);
+        vec![]
+    }
+    fn evaluate(&mut self, view: &MarketView) -> f64 {
+        self.evaluate_port(view)
+    }
+    fn reseed(&mut self, seed: u64) {
+        Agent::reseed(self, seed)
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Event agents inside a Market
+// -----------------------------------------------------------------------------
+/// Runs an `EventAgent` as an `Agent`. Requests returned from exchange
+/// callbacks are queued and submitted with the next tick's decisions.
+#[derive(Debug, Clone)]
+pub struct EventDriven<E> {
+    inner: E,
+    open: OpenOrders,
+    queued: Vec<OrderRequest>,
+    timer_every: Option<u64>,
+    ticks: u64,
+}
+
+impl<E: EventAgent> EventDriven<E> {
+    pub fn new(inner: E) -> Self {
+        Self {
+            inner,
+            open: OpenOrders::default(),
+            queued: Vec::new(),
+            timer_every: None,
+            ticks: 0,
+        }
+    }
+
+    /// Call `on_timer` every `every` ticks (0 turns the timer off).
+    pub fn with_timer(mut self, every: u64) -> Self {
+        self.timer_every = (every > 0).then_some(every);
+        self
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn open_orders(&self) -> &OpenOrders {
+        &self.open
+    }
+}
+
+impl<E: EventAgent + Clone + 'static> Agent for EventDriven<E> {
+    fn decide_actions(&mut self, view: &MarketView) -> Vec<OrderRequest> {
+        self.ticks += 1;
+        self.open.sync(view);
+        let mut out = std::mem::take(&mut self.queued);
+        if let Some(every) = self.timer_every
+            && self.ticks.is_multiple_of(every)
+        {
+            out.extend(self.inner.on_timer(self.ticks));
+        }
+        out.extend(self.inner.on_market_data(view, &self.open));
+        out
+    }
+
+    fn buy_stock(&mut self, stock_id: u64, volume: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.inner.id(),
+            stock_id,
+            side: Side::Buy,
+            volume,
+        }]
+    }
+
+    fn sell_stock(&mut self, stock_id: u64, volume: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.inner.id(),
+            stock_id,
+            side: Side::Sell,
+            volume,
+        }]
+    }
+
+    fn acknowledge_order(&mut self, order: Order) {
+        self.open.insert(order);
+        let more = self.inner.on_ack(&order);
+        self.queued.extend(more);
+    }
+
+    fn margin_call(&mut self) -> Vec<OrderRequest> {
+        self.inner.on_margin_call()
+    }
+
+    fn update_portfolio(&mut self, delta: i64, trade: &Trade) {
+        let side = if delta >= 0 { Side::Buy } else { Side::Sell };
+        // for a self-trade the taker leg is the one on the taker's side
+        let role = if trade.taker_agent_id == self.inner.id() && side == trade.taker_side {
+            Role::Taker
+        } else {
+            Role::Maker
+        };
+        if role == Role::Maker {
+            self.open.fill(trade.maker_order_id, trade.volume);
+        }
+        let fill = Fill {
+            trade: *trade,
+            role,
+            side,
+        };
+        let more = self.inner.on_fill(&fill);
+        self.queued.extend(more);
+    }
+
+    fn cancel_acknowledged(&mut self, order_id: u64) {
+        self.open.remove(order_id);
+        let more = self.inner.on_cancel(order_id);
+        self.queued.extend(more);
+    }
+
+    fn order_rejected(&mut self, request: &OrderRequest, reason: RejectReason) {
+        let more = self.inner.on_reject(request, reason);
+        self.queued.extend(more);
+    }
+
+    fn stock_listed(&mut self, stock: &Stock) {
+        let more = self.inner.on_listed(stock);
+        self.queued.extend(more);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.open.remove_stock(stock_id);
+        let more = self.inner.on_delisted(stock_id, final_price);
+        self.queued.extend(more);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.open.restate(action);
+        let more = self.inner.on_corporate_action(action);
+        self.queued.extend(more);
+    }
+
+    fn units_settled(&mut self, settlement: &UnitSettlement) {
+        let more = self.inner.on_units_settled(settlement);
+        self.queued.extend(more);
+    }
+
+    fn evaluate_port(&mut self, view: &MarketView) -> f64 {
+        self.inner.evaluate(view)
+    }
+
+    fn get_pending_orders(&self) -> Vec<Order> {
+        self.open.iter().copied().collect()
+    }
+
+    fn cancel_open_order(&mut self, order_id: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::CancelOrder {
+            agent_id: self.inner.id(),
+            order_id,
+        }]
+    }
+
+    fn run(&mut self) {}
+
+    fn get_id(&self) -> usize {
+        self.inner.id()
+    }
+
+    fn get_inventory(&self) -> i64 {
+        self.inner.inventory()
+    }
+
+    fn clone_agent(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.inner.reseed(seed);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentType, Market, Marketable, StockMarket};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct Log {
+        inventory: i64,
+        fills: Vec<Fill>,
+        cancels: Vec<u64>,
+        rejects: Vec<RejectReason>,
+        timers: u64,
+    }
+
+    /// Quotes one share either side of the mid on stock 1, pulls last tick's
+    /// quotes, and records every callback where the test can see it.
+    #[derive(Debug, Clone)]
+    struct Recorder {
+        id: usize,
+        ticks: u64,
+        log: Arc<Mutex<Log>>,
+    }
+
+    impl EventAgent for Recorder {
+        fn id(&self) -> usize {
+            self.id
+        }
+        fn inventory(&self) -> i64 {
+            self.log.lock().unwrap().inventory
+        }
+        fn on_market_data(&mut self, view: &MarketView, open: &OpenOrders) -> Vec<OrderRequest> {
+            self.ticks += 1;
+            let mut out: Vec<_> = open
+                .iter()
+                .map(|o| OrderRequest::CancelOrder {
+                    agent_id: self.id,
+                    order_id: o.id,
+                })
+                .collect();
+            if self.ticks == 1 {
+                out.push(OrderRequest::MarketOrder {
+                    agent_id: self.id,
+                    stock_id: 999_999, // no such stock
+                    side: Side::Buy,
+                    volume: 1,
+                });
+            }
+            if let Some(mid) = view.get_mid_price(1) {
+                for (side, price) in [(Side::Buy, mid - 1), (Side::Sell, mid + 1)] {
+                    out.push(OrderRequest::LimitOrder {
+                        agent_id: self.id,
+                        stock_id: 1,
+                        side,
+                        price,
+                        volume: 1,
+                    });
+                }
+            }
+            out
+        }
+        fn on_fill(&mut self, fill: &Fill) -> Vec<OrderRequest> {
+            let mut log = self.log.lock().unwrap();
+            log.inventory += fill.signed_volume();
+            log.fills.push(*fill);
+            vec![]
+        }
+        fn on_cancel(&mut self, order_id: u64) -> Vec<OrderRequest> {
+            self.log.lock().unwrap().cancels.push(order_id);
+            vec![]
+        }
+        fn on_reject(&mut self, _req: &OrderRequest, reason: RejectReason) -> Vec<OrderRequest> {
+            self.log.lock().unwrap().rejects.push(reason);
+            vec![]
+        }
+        fn on_timer(&mut self, _tick: u64) -> Vec<OrderRequest> {
+            self.log.lock().unwrap().timers += 1;
+            vec![]
+        }
+    }
+
+    #[test]
+    fn event_agent_trades_inside_a_market() {
+        let mut m = Market::with_seed(
+            &[AgentType::MarketMaker, AgentType::DumbMarket],
+            StockMarket::new(),
+            3,
+        );
+        let base = m.total_inventory();
+        let log = Arc::new(Mutex::new(Log::default()));
+        let me = m.add_agent(|id| {
+            Box::new(
+                EventDriven::new(Recorder {
+                    id,
+                    ticks: 0,
+                    log: log.clone(),
+                })
+                .with_timer(5),
+            )
+        });
+        for _ in 0..200 {
+            m.step();
+        }
+
+        let total = m.total_inventory(); // reads the log, so before locking it
+        let log = log.lock().unwrap();
+        assert_eq!(log.rejects.first(), Some(&RejectReason::UnknownStock));
+        let unknown_stock = log
+            .rejects
+            .iter()
+            .filter(|r| **r == RejectReason::UnknownStock);
+        assert_eq!(unknown_stock.count(), 1);
+        assert_eq!(log.timers, 40);
+        assert!(!log.cancels.is_empty(), "stale quotes get pulled");
+        for f in &log.fills {
+            let ours = match f.role {
+                Role::Maker => f.trade.maker_agent_id,
+                Role::Taker => f.trade.taker_agent_id,
+            };
+            assert_eq!(ours, me);
+        }
+        assert_eq!(total, base + log.inventory);
+    }
+
+    #[test]
+    fn polling_agents_answer_events() {
+        let mut a: Box<dyn Agent> = Box::new(crate::DumbLimitAgent::new(4));
+        let trade = Trade {
+            price: 10_000,
+            stock_id: 1,
+            volume: 7,
+            taker_agent_id: 4,
+            maker_agent_id: 9,
+            taker_side: Side::Sell,
+            maker_order_id: 1,
+        };
+        let before = EventAgent::inventory(a.as_ref());
+        EventAgent::on_fill(
+            a.as_mut(),
+            &Fill {
+                trade,
+                role: Role::Taker,
+                side: Side::Sell,
+            },
+        );
+        assert_eq!(EventAgent::inventory(a.as_ref()), before - 7);
+    }
+
+    #[test]
+    fn wrapped_polling_agents_meet_margin_calls_after_matching() {
+        let mut a = EventDriven::new(crate::DumbAgent::new(4));
+        // one share at $10bn blows through the dumb agent's margin
+        let trade = Trade {
+            price: 1_000_000_000_000,
+            stock_id: 1,
+            volume: 1,
+            taker_agent_id: 4,
+            maker_agent_id: 9,
+            taker_side: Side::Buy,
+            maker_order_id: 1,
+        };
+        a.update_portfolio(1, &trade);
+
+        let (books, stocks) = (HashMap::new(), StockMarket::new());
+        let view = MarketView::new(&books, &stocks);
+        assert!(a.decide_actions(&view).is_empty(), "not while deciding");
+        assert_eq!(
+            a.margin_call(),
+            vec![OrderRequest::MarketOrder {
+                agent_id: 4,
+                stock_id: 1,
+                side: Side::Sell,
+                volume: 1,
+            }]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// src/agents/informed_agent.rs
+//
+// A trader with an edge: it reads each stock's fundamental value a few ticks
+// late, blurs it with its own noise and takes liquidity whenever the book is
+// clearly on the wrong side of that estimate. This is what pulls prices back
+// towards value when the market keeps one.
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use rand_distr::{Distribution, StandardNormal};
+
+use super::{
+    agent_trait::{Agent, MarketView},
+    config::{
+        INFORMED_ACTION_PROB, INFORMED_EDGE_BPS, INFORMED_INITIAL_CASH, INFORMED_SIGNAL_DELAY,
+        INFORMED_SIGNAL_NOISE, INFORMED_VOL_MAX, INFORMED_VOL_MIN,
+    },
+    latency::INFORMED_TICKS_UNTIL_ACTIVE,
+    portfolio::Portfolio,
+};
+use crate::{
+    corporate::CorporateAction,
+    types::order::{Order, OrderRequest, Side, Trade},
+};
+
+#[derive(Debug, Clone)]
+pub struct InformedAgent {
+    id: usize,
+    portfolio: Portfolio,
+    ticks_until_active: u32,
+    /// Ticks the signal lags the true value.
+    delay: usize,
+    /// Standard deviation of the log-normal error on the signal.
+    noise: f64,
+    action_prob: f64,
+    volume: (u64, u64),
+    rng: StdRng,
+}
+
+impl InformedAgent {
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            portfolio: Portfolio::new(INFORMED_INITIAL_CASH),
+            ticks_until_active: INFORMED_TICKS_UNTIL_ACTIVE,
+            delay: INFORMED_SIGNAL_DELAY,
+            noise: INFORMED_SIGNAL_NOISE,
+            action_prob: INFORMED_ACTION_PROB,
+            volume: (INFORMED_VOL_MIN, INFORMED_VOL_MAX),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Chance per tick of looking for a trade.
+    pub fn with_action_prob(mut self, p: f64) -> Self {
+        self.action_prob = p.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Shares per order, drawn from `min..=max`.
+    pub fn with_volume(mut self, min: u64, max: u64) -> Self {
+        let min = min.max(1);
+        self.volume = (min, max.max(min));
+        self
+    }
+
+    /// Starting cash in dollars.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.portfolio = Portfolio::new(cash);
+        self
+    }
+
+    /// How stale and how blurred the signal is.
+    pub fn with_signal(mut self, delay: usize, noise: f64) -> Self {
+        self.delay = delay;
+        self.noise = noise.max(0.0);
+        self
+    }
+
+    /// This agent's estimate of `stock_id`'s value, dollars.
+    fn signal(&mut self, view: &MarketView, stock_id: u64) -> Option<f64> {
+        let value = view.fundamental(stock_id, self.delay)?;
+        let z: f64 = StandardNormal.sample(&mut self.rng);
+        Some(value * (self.noise * z).exp())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Agent impl
+// -----------------------------------------------------------------------------
+impl Agent for InformedAgent {
+    fn decide_actions(&mut self, view: &MarketView) -> Vec<OrderRequest> {
+        if self.ticks_until_active > 0 {
+            self.ticks_until_active -= 1;
+            return vec![];
+        }
+        if view.fundamentals.is_none() || !self.rng.gen_bool(self.action_prob) {
+            return vec![];
+        }
+        let mut ids = view.stocks.get_all_ids();
+        ids.sort_unstable();
+        let Some(&stock_id) = ids.choose(&mut self.rng) else {
+            return vec![];
+        };
+        let (Some(book), Some(estimate)) = (view.book(stock_id), self.signal(view, stock_id))
+        else {
+            return vec![];
+        };
+
+        let edge = 1.0 + INFORMED_EDGE_BPS / 10_000.0;
+        let cents = estimate * 100.0;
+        let side = match book.best_bid_ask() {
+            (_, Some(ask)) if cents > ask as f64 * edge => Side::Buy,
+            (Some(bid), _) if cents < bid as f64 / edge => Side::Sell,
+            _ => return vec![],
+        };
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.id,
+            stock_id,
+            side,
+            volume: self.rng.gen_range(self.volume.0..=self.volume.1),
+        }]
+    }
+    fn run(&mut self) {
+        // Driven entirely by decide_actions.
+    }
+    fn buy_stock(&mut self, stock_id: u64, volume: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.id,
+            stock_id,
+            side: Side::Buy,
+            volume,
+        }]
+    }
+    fn sell_stock(&mut self, stock_id: u64, volume: u64) -> Vec<OrderRequest> {
+        vec![OrderRequest::MarketOrder {
+            agent_id: self.id,
+            stock_id,
+            side: Side::Sell,
+            volume,
+        }]
+    }
+
+    fn margin_call(&mut self) -> Vec<OrderRequest> {
+        vec![]
+    }
+
+    /* bookkeeping ---------------------------------------------------------- */
+    fn acknowledge_order(&mut self, _o: Order) {
+        // only market orders, which never rest
+    }
+
+    fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
+        self.portfolio.on_trade(vol, tr);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+    }
+
+    fn get_pending_orders(&self) -> Vec<Order> {
+        vec![]
+    }
+
+    fn cancel_open_order(&mut self, _id: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+
+    /* misc getters --------------------------------------------------------- */
+    fn get_id(&self) -> usize {
+        self.id
+    }
+    fn get_inventory(&self) -> i64 {
+        self.portfolio.shares()
+    }
+    fn clone_agent(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn evaluate_port(&mut self, view: &MarketView) -> f64 {
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
+    }
+}
+// -----------------------------------------------------------------------------
+//  Unit Tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        fundamental::{FundamentalConfig, Fundamentals},
+        simulators::order_book::OrderBook,
+        stocks::definitions::{Stock, StockMarket},
+    };
+
+    fn book(bid: u64, ask: u64) -> OrderBook {
+        let mut book = OrderBook::new();
+        for (id, side, price) in [(1, Side::Buy, bid), (2, Side::Sell, ask)] {
+            book.process_limit_order(&mut Order {
+                id,
+                agent_id: 9,
+                stock_id: 1,
+                side,
+                price,
+                volume: 10_000,
+                filled: 0,
+            });
+        }
+        book
+    }
+
+    /// Side of the first order over enough ticks to get past the action
+    /// draw, or `None` if it never trades.
+    fn trades(value: f64) -> Option<Side> {
+        let stocks = StockMarket::from_stocks(vec![Stock::new(
+            "INFO",
+            1,
+            "Informed Inc",
+            1_000_000,
+            100.0,
+        )])
+        .unwrap();
+        let books = HashMap::from([(1, book(9_990, 10_010))]);
+        let fundamentals = Fundamentals::new([(1, value)], &FundamentalConfig::default(), 0);
+        let mut agent = InformedAgent::new(4).with_signal(0, 0.0);
+        agent.ticks_until_active = 0;
+        agent.reseed(7);
+
+        let mut view = MarketView::new(&books, &stocks);
+        assert!(
+            agent.decide_actions(&view).is_empty(),
+            "blind without values"
+        );
+        view.fundamentals = Some(&fundamentals);
+        (0..200)
+            .flat_map(|_| agent.decide_actions(&view))
+            .map(|req| match req {
+                OrderRequest::MarketOrder {
+                    agent_id: 4,
+                    stock_id: 1,
+                    side,
+                    volume,
+                } => {
+                    assert!((INFORMED_VOL_MIN..=INFORMED_VOL_MAX).contains(&volume));
+                    side
+                }
+                other => panic!("unexpected {other:?}"),
+            })
+            .next()
+    }
+
+    #[test]
+    fn trades_only_when_the_book_is_clearly_mispriced() {
+        assert_eq!(trades(102.0), Some(Side::Buy));
+        assert_eq!(trades(98.0), Some(Side::Sell));
+        // inside the touch plus edge
+        assert_eq!(trades(100.2), None);
+    }
+}
@@ -1,13 +1,24 @@
 // src/agents/ipo_agent.rs
 use std::collections::HashMap;
 
-use super::agent_trait::{Agent, MarketView};
-use crate::types::order::{Order, OrderRequest, Side, Trade};
+use super::{
+    agent_trait::{Agent, MarketView},
+    portfolio::Portfolio,
+};
+use crate::{
+    corporate::CorporateAction,
+    stocks::definitions::Stock,
+    types::order::{Order, OrderRequest, Side, Trade},
+};
+
+/// Shares of every listing the IPO agent holds to distribute.
+const IPO_FLOAT: i64 = 1_000_000;
 
 /// IPO agent: posts one ladder of sell limits at boot and is done.
+#[derive(Debug, Clone)]
 pub struct IpoAgent {
     id: usize,
-    inventory: i64,
+    portfolio: Portfolio,
     has_acted: bool,
     open_orders: HashMap<u64, Order>,
 }
@@ -16,7 +27,7 @@ impl IpoAgent {
     pub fn new(id: usize) -> Self {
         Self {
             id,
-            inventory: 1_000_000, // float to distribute
+            portfolio: Portfolio::new(0.0),
             has_acted: false,
             open_orders: HashMap::new(),
         }
@@ -40,7 +51,7 @@ impl Agent for IpoAgent {
         };
 
         let num_levels = 20;
-        let vol_per = (self.inventory / num_levels) as u64;
+        let vol_per = (self.portfolio.qty(stock_id) / num_levels) as u64;
         let start_px: u64 = 15_000; // $150.00
         let tick: u64 = 5; // $0.05
 
@@ -75,17 +86,33 @@ impl Agent for IpoAgent {
     }
 
     fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
-        self.inventory += vol;
-        if tr.maker_agent_id == self.id {
-            if let Some(o) = self.open_orders.get_mut(&tr.maker_order_id) {
-                o.filled += tr.volume;
-                if o.filled >= o.volume {
-                    self.open_orders.remove(&tr.maker_order_id);
-                }
+        self.portfolio.on_trade(vol, tr);
+        if tr.maker_agent_id == self.id
+            && let Some(o) = self.open_orders.get_mut(&tr.maker_order_id)
+        {
+            o.filled += tr.volume;
+            if o.filled >= o.volume {
+                self.open_orders.remove(&tr.maker_order_id);
             }
         }
     }
 
+    fn stock_listed(&mut self, stock: &Stock) {
+        self.portfolio
+            .endow(stock.id, IPO_FLOAT, stock.initial_price);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+        self.open_orders.retain(|_, o| o.stock_id != stock_id);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+        self.open_orders
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+
     fn get_pending_orders(&self) -> Vec<Order> {
         self.open_orders.values().cloned().collect()
     }
@@ -99,17 +126,16 @@ impl Agent for IpoAgent {
         self.id
     }
     fn get_inventory(&self) -> i64 {
-        self.inventory
+        self.portfolio.shares()
     }
     fn clone_agent(&self) -> Box<dyn Agent> {
-        Box::new(IpoAgent::new(self.id))
+        Box::new(self.clone())
     }
 
     fn evaluate_port(&mut self, view: &MarketView) -> f64 {
-        let sid = *view.stocks.get_all_ids().first().unwrap_or(&0);
-        match view.get_mid_price(sid) {
-            Some(px) => self.inventory as f64 * (px as f64 / 100.0),
-            None => 0.0,
-        }
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
     }
 }
@@ -13,3 +13,9 @@ pub const LIMIT_AGENT_TICKS_UNTIL_ACTIVE: u32 = 10;
 pub const DUMB_AGENT_TICKS_UNTIL_ACTIVE: u32 = 15;
 ///  Whaaaaaleeee
 pub const WHALE_TICKS_UNTIL_ACTIVE: u32 = 20;
+
+/// Arbitrageurs wait for the books to fill in before pricing baskets.
+pub const ETF_ARB_TICKS_UNTIL_ACTIVE: u32 = 5;
+
+/// Informed traders need a few ticks of history before their signal means much.
+pub const INFORMED_TICKS_UNTIL_ACTIVE: u32 = 12;
@@ -1,5 +1,5 @@
 // src/agents/market_maker_agent.rs
-use rand::{Rng, seq::SliceRandom};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use std::collections::HashMap;
 
 use super::{
@@ -9,49 +9,75 @@ use super::{
         MM_QUOTE_VOL_MIN, MM_SEED_DECAY, MM_SEED_DEPTH_PCT, MM_SEED_LEVELS, MM_SEED_TICK_SPACING,
         MM_SKEW_FACTOR, MM_UNSTICK_VOL_MAX, MM_UNSTICK_VOL_MIN,
     },
+    portfolio::Portfolio,
 };
 use crate::{
     agents::latency::MM_TICKS_UNTIL_ACTIVE,
+    corporate::CorporateAction,
+    stocks::definitions::Stock,
     types::order::{Order, OrderRequest, Side, Trade},
 };
 
 /* guard-rails */
 const MIN_PRICE: u64 = 1_00; // $1.00
-const MAX_PRICE: u64 = 3_000_00; // $3 000.00
+const MAX_PRICE: u64 = 300_000; // $3 000.00
 #[inline]
 fn clamp(p: i128) -> u64 {
     p.max(MIN_PRICE as i128).min(MAX_PRICE as i128) as u64
 }
 
+#[derive(Debug, Clone)]
 pub struct MarketMakerAgent {
     id: usize,
-    inventory: i64,
+    portfolio: Portfolio,
     ticks_until_active: u32,
     bootstrapped: HashMap<u64, bool>, // per-stock seeding status
     open_orders: HashMap<u64, Order>,
-    cash: f64,
     margin: f64,
-    port_value: f64,
+    quote_volume: (u64, u64),
+    /// Starting shares of each listed stock.
+    inventory: i64,
+    rng: StdRng,
 }
 
 impl MarketMakerAgent {
     pub fn new(id: usize) -> Self {
         Self {
             id,
-            inventory: MM_INITIAL_INVENTORY,
+            portfolio: Portfolio::new(100_000_000_000.0),
             ticks_until_active: MM_TICKS_UNTIL_ACTIVE,
             bootstrapped: HashMap::new(),
             open_orders: HashMap::new(),
-            cash: 100_000_000_000.0,
             margin: 400_000_000_000.0,
-            port_value: 0.0,
+            quote_volume: (MM_QUOTE_VOL_MIN, MM_QUOTE_VOL_MAX),
+            inventory: MM_INITIAL_INVENTORY,
+            rng: StdRng::from_entropy(),
         }
     }
 
+    /// Shares per two-sided quote, drawn from `min..=max`.
+    pub fn with_volume(mut self, min: u64, max: u64) -> Self {
+        let min = min.max(1);
+        self.quote_volume = (min, max.max(min));
+        self
+    }
+
+    /// Starting cash in dollars.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.portfolio = Portfolio::new(cash);
+        self
+    }
+
+    /// Shares of every stock it is endowed with when the stock lists.
+    pub fn with_inventory(mut self, qty: i64) -> Self {
+        self.inventory = qty;
+        self
+    }
+
     /* seed one instrument’s book with geometric depth */
     // Adding one more argument that is the opening stock price.
     fn seed_liquidity(&self, stock_id: u64, starting_price: u64) -> Vec<OrderRequest> {
-        let side_budget = (self.inventory.abs() as f64 * MM_SEED_DEPTH_PCT) as u64;
+        let side_budget = (self.portfolio.qty(stock_id).abs() as f64 * MM_SEED_DEPTH_PCT) as u64;
         let mut vol_at_lvl = (side_budget as f64 * (1.0 - MM_SEED_DECAY)
             / (1.0 - MM_SEED_DECAY.powi(MM_SEED_LEVELS as i32)))
             as u64;
@@ -104,7 +130,7 @@ impl Agent for MarketMakerAgent {
         if ids.is_empty() {
             return vec![];
         }
-        let stock_id = *ids.choose(&mut rand::thread_rng()).unwrap();
+        let stock_id = *ids.choose(&mut self.rng).unwrap();
         // extract stock initial price by first fetching the stock by id and extracting rpcie from there
         let initial_price = view
             .stocks
@@ -122,13 +148,12 @@ impl Agent for MarketMakerAgent {
             return self.seed_liquidity(stock_id, initial_price);
         }
 
-        let best_bid = book.bids.keys().next_back().copied();
-        let best_ask = book.asks.keys().next().copied();
+        let (best_bid, best_ask) = book.best_bid_ask();
 
         /* --- emergency unstick --- */
         if let (Some(bid), None) = (best_bid, best_ask) {
             let ask_px = clamp(bid as i128 + 1);
-            let vol = rand::thread_rng().gen_range(MM_UNSTICK_VOL_MIN..=MM_UNSTICK_VOL_MAX);
+            let vol = self.rng.gen_range(MM_UNSTICK_VOL_MIN..=MM_UNSTICK_VOL_MAX);
             return vec![OrderRequest::LimitOrder {
                 agent_id: self.id,
                 stock_id,
@@ -139,7 +164,7 @@ impl Agent for MarketMakerAgent {
         }
         if let (None, Some(ask)) = (best_bid, best_ask) {
             let bid_px = clamp(ask as i128 - 1);
-            let vol = rand::thread_rng().gen_range(MM_UNSTICK_VOL_MIN..=MM_UNSTICK_VOL_MAX);
+            let vol = self.rng.gen_range(MM_UNSTICK_VOL_MIN..=MM_UNSTICK_VOL_MAX);
             return vec![OrderRequest::LimitOrder {
                 agent_id: self.id,
                 stock_id,
@@ -156,7 +181,7 @@ impl Agent for MarketMakerAgent {
             _ => return vec![],
         };
 
-        let inventory_skew = (self.inventory as f64 * MM_SKEW_FACTOR) as i64;
+        let inventory_skew = (self.portfolio.qty(stock_id) as f64 * MM_SKEW_FACTOR) as i64;
         let our_center = clamp(center as i128 - inventory_skew as i128);
 
         let bid_px = clamp(our_center as i128 - (MM_DESIRED_SPREAD / 2) as i128);
@@ -165,11 +190,12 @@ impl Agent for MarketMakerAgent {
         if ask_px <= bid_px {
             return vec![];
         }
-        if best_ask.map_or(false, |a| bid_px >= a) || best_bid.map_or(false, |b| ask_px <= b) {
+        if best_ask.is_some_and(|a| bid_px >= a) || best_bid.is_some_and(|b| ask_px <= b) {
             return vec![];
         }
 
-        let vol = rand::thread_rng().gen_range(MM_QUOTE_VOL_MIN..=MM_QUOTE_VOL_MAX);
+        let (vol_min, vol_max) = self.quote_volume;
+        let vol = self.rng.gen_range(vol_min..=vol_max);
         vec![
             OrderRequest::LimitOrder {
                 agent_id: self.id,
@@ -207,15 +233,25 @@ impl Agent for MarketMakerAgent {
     }
 
     fn margin_call(&mut self) -> Vec<OrderRequest> {
-        if self.cash <= -self.margin {
-            /* sell entire inventory on the first id we hold */
-            let sid = self
-                .open_orders
-                .values()
-                .next()
-                .map(|o| o.stock_id)
-                .unwrap_or(0);
-            return self.sell_stock(sid, self.inventory.unsigned_abs());
+        if self.portfolio.cash() <= -self.margin {
+            /* flatten every position on its own book */
+            let mut held: Vec<(u64, i64)> = self
+                .portfolio
+                .positions()
+                .filter(|(_, p)| p.qty != 0)
+                .map(|(id, p)| (id, p.qty))
+                .collect();
+            held.sort_unstable();
+            return held
+                .into_iter()
+                .flat_map(|(sid, qty)| {
+                    if qty > 0 {
+                        self.sell_stock(sid, qty.unsigned_abs())
+                    } else {
+                        self.buy_stock(sid, qty.unsigned_abs())
+                    }
+                })
+                .collect();
         }
         vec![]
     }
@@ -226,19 +262,35 @@ impl Agent for MarketMakerAgent {
     }
 
     fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
-        self.inventory += vol;
-        self.cash -= vol as f64 * (tr.price as f64 / 100.0);
-
-        if tr.maker_agent_id == self.id {
-            if let Some(o) = self.open_orders.get_mut(&tr.maker_order_id) {
-                o.filled += tr.volume;
-                if o.filled >= o.volume {
-                    self.open_orders.remove(&tr.maker_order_id);
-                }
+        self.portfolio.on_trade(vol, tr);
+
+        if tr.maker_agent_id == self.id
+            && let Some(o) = self.open_orders.get_mut(&tr.maker_order_id)
+        {
+            o.filled += tr.volume;
+            if o.filled >= o.volume {
+                self.open_orders.remove(&tr.maker_order_id);
             }
         }
     }
 
+    fn stock_listed(&mut self, stock: &Stock) {
+        self.portfolio
+            .endow(stock.id, self.inventory, stock.initial_price);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+        self.open_orders.retain(|_, o| o.stock_id != stock_id);
+        self.bootstrapped.remove(&stock_id);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+        self.open_orders
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+
     fn get_pending_orders(&self) -> Vec<Order> {
         self.open_orders.values().cloned().collect()
     }
@@ -252,18 +304,20 @@ impl Agent for MarketMakerAgent {
         self.id
     }
     fn get_inventory(&self) -> i64 {
-        self.inventory
+        self.portfolio.shares()
     }
     fn clone_agent(&self) -> Box<dyn Agent> {
-        Box::new(MarketMakerAgent::new(self.id))
+        Box::new(self.clone())
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
     fn evaluate_port(&mut self, view: &MarketView) -> f64 {
-        let sid = *view.stocks.get_all_ids().first().unwrap_or(&0);
-        if let Some(px) = view.get_mid_price(sid) {
-            self.port_value = self.inventory as f64 * (px as f64 / 100.0);
-        }
-        self.port_value
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
     }
 }
 // -----------------------------------------------------------------------------
@@ -312,6 +366,7 @@ mod tests {
     #[test]
     fn maker_partial_fill_updates_open_order() {
         let mut mm = MarketMakerAgent::new(1);
+        mm.stock_listed(&Stock::new("MM", STOCK_ID, "Quoted", 1, 150.0));
         mm.acknowledge_order(new_order(101, 1, Side::Sell, 15_000, 100));
 
         let tr = new_trade(2, 1, 101, Side::Buy, 15_000, 40);
@@ -319,30 +374,60 @@ mod tests {
 
         let ord = mm.open_orders.get(&101).expect("order still open");
         assert_eq!(ord.filled, 40);
-        assert_eq!(mm.inventory, MM_INITIAL_INVENTORY - 40);
+        assert_eq!(mm.get_inventory(), MM_INITIAL_INVENTORY - 40);
     }
 
     #[test]
     fn maker_full_fill_removes_order() {
         let mut mm = MarketMakerAgent::new(1);
+        mm.stock_listed(&Stock::new("MM", STOCK_ID, "Quoted", 1, 150.0));
         mm.acknowledge_order(new_order(101, 1, Side::Sell, 15_000, 100));
 
         let tr = new_trade(2, 1, 101, Side::Buy, 15_000, 100);
         mm.update_portfolio(-100, &tr);
 
-        assert!(mm.open_orders.get(&101).is_none(), "order closed");
-        assert_eq!(mm.inventory, MM_INITIAL_INVENTORY - 100);
+        assert!(!mm.open_orders.contains_key(&101), "order closed");
+        assert_eq!(mm.get_inventory(), MM_INITIAL_INVENTORY - 100);
         assert!(mm.get_pending_orders().is_empty());
     }
 
     #[test]
     fn taker_trade_leaves_open_orders_untouched() {
         let mut mm = MarketMakerAgent::new(1);
+        mm.stock_listed(&Stock::new("MM", STOCK_ID, "Quoted", 1, 150.0));
 
         let tr = new_trade(1, 2, 202, Side::Buy, 15_000, 75); // mm is taker
         mm.update_portfolio(75, &tr);
 
-        assert_eq!(mm.inventory, MM_INITIAL_INVENTORY + 75);
+        assert_eq!(mm.get_inventory(), MM_INITIAL_INVENTORY + 75);
         assert!(mm.open_orders.is_empty());
     }
+
+    #[test]
+    fn split_restates_starting_inventory_for_margin_calls() {
+        let mut mm = MarketMakerAgent::new(1);
+        mm.stock_listed(&Stock::new("MM", STOCK_ID, "Quoted", 1, 150.0));
+        mm.corporate_action(&CorporateAction::Split {
+            stock_id: STOCK_ID,
+            new_shares: 2,
+            old_shares: 1,
+        });
+        assert_eq!(mm.get_inventory(), 2 * MM_INITIAL_INVENTORY);
+        assert_eq!(mm.portfolio.position(STOCK_ID).unwrap().avg_price, 75.0);
+
+        // one share of something at $600bn puts the maker past its margin
+        let mut tr = new_trade(1, 2, 7, Side::Buy, 60_000_000_000_000, 1);
+        tr.stock_id = 2;
+        mm.update_portfolio(1, &tr);
+        let sell = |stock_id, volume| OrderRequest::MarketOrder {
+            agent_id: 1,
+            stock_id,
+            side: Side::Sell,
+            volume,
+        };
+        assert_eq!(
+            mm.margin_call(),
+            vec![sell(STOCK_ID, 2 * MM_INITIAL_INVENTORY as u64), sell(2, 1)]
+        );
+    }
 }
@@ -5,7 +5,11 @@ pub mod agent_type;
 pub mod config;
 pub mod dumb_agent;
 pub mod dumb_limit_agent;
+pub mod etf_arb_agent;
+pub mod event_agent;
+pub mod informed_agent;
 pub mod ipo_agent;
 pub mod latency;
 pub mod market_maker_agent;
+pub mod portfolio;
 pub mod whale_agent;
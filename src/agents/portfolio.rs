@@ -0,0 +1,314 @@
+// src/agents/portfolio.rs
+//
+// Multi-asset account shared by the agents: cash, one position per stock with
+// average entry price, realised PnL, fees, and mark-to-market against a
+// `MarketView`. Prices in and out of fills are cents (as on the books); money
+// is dollars (as agents' cash always was).
+
+use std::collections::HashMap;
+
+use super::agent_trait::MarketView;
+use crate::{corporate::CorporateAction, stocks::basket::UnitSettlement, types::order::Trade};
+
+/// Holding in one stock.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    /// Signed share count; negative is short.
+    pub qty: i64,
+    /// Average entry price of the open quantity, dollars. 0 when flat.
+    pub avg_price: f64,
+    /// PnL locked in by reducing or flipping the position, dollars.
+    pub realized_pnl: f64,
+    /// Price of the latest fill, dollars; the mark when a book has no mid.
+    pub last_price: f64,
+}
+
+impl Position {
+    fn apply(&mut self, delta: i64, price: f64) {
+        let qty = self.qty;
+        if qty == 0 || qty.signum() == delta.signum() {
+            // opening or adding: blend the entry price
+            let open = qty.unsigned_abs() as f64;
+            let added = delta.unsigned_abs() as f64;
+            self.avg_price = (self.avg_price * open + price * added) / (open + added);
+        } else {
+            // reducing, closing or flipping
+            let closed = delta.abs().min(qty.abs());
+            self.realized_pnl += closed as f64 * (price - self.avg_price) * qty.signum() as f64;
+            if delta.abs() > qty.abs() {
+                self.avg_price = price; // the remainder opens a new position
+            } else if qty + delta == 0 {
+                self.avg_price = 0.0;
+            }
+        }
+        self.qty += delta;
+        self.last_price = price;
+    }
+
+    pub fn unrealized_pnl(&self, mark: f64) -> f64 {
+        self.qty as f64 * (mark - self.avg_price)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Portfolio {
+    cash: f64,
+    positions: HashMap<u64, Position>,
+    fee_bps: f64,
+    fees_paid: f64,
+}
+
+impl Portfolio {
+    pub fn new(cash: f64) -> Self {
+        Self {
+            cash,
+            ..Self::default()
+        }
+    }
+
+    /// Start out holding `qty` shares of `stock_id` bought at `price` dollars.
+    pub fn with_position(mut self, stock_id: u64, qty: i64, price: f64) -> Self {
+        self.endow(stock_id, qty, price);
+        self
+    }
+
+    /// Hand over `qty` shares of `stock_id` valued at `price` dollars, as
+    /// starting inventory: no cash changes hands and no fee is charged.
+    pub fn endow(&mut self, stock_id: u64, qty: i64, price: f64) {
+        if qty != 0 {
+            self.positions
+                .entry(stock_id)
+                .or_default()
+                .apply(qty, price);
+        }
+    }
+
+    /// Charge `bps` basis points of notional on every fill.
+    pub fn with_fee_bps(mut self, bps: f64) -> Self {
+        self.fee_bps = bps;
+        self
+    }
+
+    /// Book one fill: `delta` signed shares of `stock_id` at `price` cents.
+    pub fn apply_fill(&mut self, stock_id: u64, delta: i64, price: u64) {
+        if delta == 0 {
+            return;
+        }
+        let px = price as f64 / 100.0;
+        let fee = delta.unsigned_abs() as f64 * px * self.fee_bps / 10_000.0;
+        self.cash -= delta as f64 * px + fee;
+        self.fees_paid += fee;
+        self.positions.entry(stock_id).or_default().apply(delta, px);
+    }
+
+    /// Close out `stock_id` at `price` cents without a fee, as a delisting
+    /// does. Returns the signed shares that were settled.
+    pub fn settle(&mut self, stock_id: u64, price: u64) -> i64 {
+        let Some(pos) = self.positions.get_mut(&stock_id) else {
+            return 0;
+        };
+        let qty = pos.qty;
+        if qty != 0 {
+            let px = price as f64 / 100.0;
+            self.cash += qty as f64 * px;
+            pos.apply(-qty, px);
+        }
+        qty
+    }
+
+    /// Restate a position for a split (a fractional share is paid out at the
+    /// adjusted last price) or pay a dividend on it. Entry prices move with
+    /// a split but not with a dividend, which is income.
+    pub fn apply_corporate_action(&mut self, action: &CorporateAction) {
+        let Some(pos) = self.positions.get_mut(&action.stock_id()) else {
+            return;
+        };
+        match *action {
+            CorporateAction::Split { .. } => {
+                // the exchange refuses a split that would overflow a position
+                let Some((qty, fraction)) = action.shares(pos.qty) else {
+                    return;
+                };
+                pos.qty = qty;
+                pos.last_price = action.price(pos.last_price);
+                pos.avg_price = if qty == 0 {
+                    0.0
+                } else {
+                    action.price(pos.avg_price)
+                };
+                self.cash += fraction * pos.last_price;
+            }
+            CorporateAction::CashDividend { amount, .. } => {
+                self.cash += pos.qty as f64 * amount;
+                pos.last_price = action.price(pos.last_price);
+            }
+            CorporateAction::Rename { .. } => {}
+        }
+    }
+
+    /// Book a creation or redemption. The shares change hands in kind, so
+    /// cash only pays the fee; each leg's price becomes the entry price of
+    /// what it adds, keeping the basket's cost basis equal to that of the
+    /// stocks it was built from.
+    pub fn apply_settlement(&mut self, settlement: &UnitSettlement) {
+        for &(stock_id, delta, price) in &settlement.legs {
+            if delta != 0 {
+                let px = price as f64 / 100.0;
+                self.positions.entry(stock_id).or_default().apply(delta, px);
+            }
+        }
+        self.cash -= settlement.fee;
+        self.fees_paid += settlement.fee;
+    }
+
+    /// `Agent::update_portfolio` in one call.
+    pub fn on_trade(&mut self, delta: i64, trade: &Trade) {
+        self.apply_fill(trade.stock_id, delta, trade.price);
+    }
+
+    // ---------------------------------------------------------------------
+    //  Holdings
+    // ---------------------------------------------------------------------
+    #[inline]
+    pub fn cash(&self) -> f64 {
+        self.cash
+    }
+
+    pub fn position(&self, stock_id: u64) -> Option<&Position> {
+        self.positions.get(&stock_id)
+    }
+
+    /// Signed shares held in `stock_id` (0 if never traded).
+    pub fn qty(&self, stock_id: u64) -> i64 {
+        self.position(stock_id).map_or(0, |p| p.qty)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (u64, &Position)> {
+        self.positions.iter().map(|(&id, p)| (id, p))
+    }
+
+    /// Every share held, summed over all stocks.
+    pub fn shares(&self) -> i64 {
+        self.positions.values().map(|p| p.qty).sum()
+    }
+
+    pub fn fees_paid(&self) -> f64 {
+        self.fees_paid
+    }
+
+    // ---------------------------------------------------------------------
+    //  Valuation
+    // ---------------------------------------------------------------------
+    /// Price a position is marked at: the book's mid, else its last fill.
+    pub fn mark(&self, stock_id: u64, view: &MarketView) -> Option<f64> {
+        view.get_mid_price(stock_id)
+            .map(|c| c as f64 / 100.0)
+            .or_else(|| self.position(stock_id).map(|p| p.last_price))
+    }
+
+    pub fn realized_pnl(&self) -> f64 {
+        self.positions.values().map(|p| p.realized_pnl).sum()
+    }
+
+    pub fn unrealized_pnl(&self, view: &MarketView) -> f64 {
+        self.marked(view).map(|(p, m)| p.unrealized_pnl(m)).sum()
+    }
+
+    /// Σ |qty| × mark.
+    pub fn gross_exposure(&self, view: &MarketView) -> f64 {
+        self.marked(view).map(|(p, m)| p.qty.abs() as f64 * m).sum()
+    }
+
+    /// Σ qty × mark; negative when net short.
+    pub fn net_exposure(&self, view: &MarketView) -> f64 {
+        self.marked(view).map(|(p, m)| p.qty as f64 * m).sum()
+    }
+
+    /// Mark-to-market net worth: cash plus every position at its mark.
+    pub fn evaluate(&self, view: &MarketView) -> f64 {
+        self.cash + self.net_exposure(view)
+    }
+
+    fn marked<'a>(&'a self, view: &'a MarketView) -> impl Iterator<Item = (&'a Position, f64)> {
+        self.positions
+            .iter()
+            .map(move |(&id, p)| (p, self.mark(id, view).unwrap_or(p.last_price)))
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Order, OrderBook, Side, StockMarket};
+
+    fn view_with_mid(books: &mut HashMap<u64, OrderBook>, stock_id: u64, bid: u64, ask: u64) {
+        let mut book = OrderBook::new();
+        for (id, side, price) in [(1, Side::Buy, bid), (2, Side::Sell, ask)] {
+            book.process_limit_order(&mut Order {
+                id,
+                agent_id: 9,
+                stock_id,
+                side,
+                price,
+                volume: 10,
+                filled: 0,
+            });
+        }
+        books.insert(stock_id, book);
+    }
+
+    #[test]
+    fn average_entry_and_realised_pnl() {
+        let mut p = Portfolio::new(10_000.0);
+        p.apply_fill(1, 10, 10_000); // buy 10 @ $100
+        p.apply_fill(1, 10, 11_000); // buy 10 @ $110
+        assert!((p.position(1).unwrap().avg_price - 105.0).abs() < 1e-9);
+
+        p.apply_fill(1, -5, 12_000); // sell 5 @ $120: +$75
+        let pos = *p.position(1).unwrap();
+        assert_eq!(pos.qty, 15);
+        assert!((pos.avg_price - 105.0).abs() < 1e-9);
+        assert!((p.realized_pnl() - 75.0).abs() < 1e-9);
+
+        p.apply_fill(1, -20, 10_000); // flip: close 15 (-$75), short 5 @ $100
+        let pos = *p.position(1).unwrap();
+        assert_eq!(pos.qty, -5);
+        assert!((pos.avg_price - 100.0).abs() < 1e-9);
+        assert!(p.realized_pnl().abs() < 1e-9);
+        // 10k - 1000 - 1100 + 600 + 2000
+        assert!((p.cash() - 10_500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn marks_every_stock_at_its_own_mid() {
+        let stocks = StockMarket::new();
+        let mut books = HashMap::new();
+        view_with_mid(&mut books, 1, 9_900, 10_100); // $100
+        view_with_mid(&mut books, 2, 1_900, 2_100); // $20
+        let view = MarketView::new(&books, &stocks);
+
+        // starting inventory costs no cash but is marked like any position
+        let mut p = Portfolio::new(1_000.0).with_position(4, 100, 5.0);
+        p.apply_fill(1, 2, 9_000); // long 2 @ $90
+        p.apply_fill(2, -10, 2_500); // short 10 @ $25
+        p.apply_fill(3, 1, 500); // no book: marked at last fill
+
+        assert_eq!(p.shares(), 100 - 7);
+        assert!((p.net_exposure(&view) - (200.0 - 200.0 + 5.0 + 500.0)).abs() < 1e-9);
+        assert!((p.gross_exposure(&view) - 905.0).abs() < 1e-9);
+        assert!((p.unrealized_pnl(&view) - (20.0 + 50.0)).abs() < 1e-9);
+        // cash 1000 - 180 + 250 - 5 = 1065, plus 505 net exposure
+        assert!((p.evaluate(&view) - 1_570.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fees_come_out_of_cash() {
+        let mut p = Portfolio::new(0.0).with_fee_bps(10.0);
+        p.apply_fill(1, 100, 5_000); // $5 000 notional, $5 fee
+        assert!((p.fees_paid() - 5.0).abs() < 1e-9);
+        assert!((p.cash() + 5_005.0).abs() < 1e-9);
+    }
+}
@@ -1,5 +1,5 @@
 // src/agents/whale_agent.rs
-use rand::{Rng, seq::SliceRandom};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
 use std::collections::HashMap;
 
 use super::{
@@ -9,35 +9,62 @@ use super::{
         WHALE_PRICE_OFFSET_MAX, WHALE_PRICE_OFFSET_MIN,
     },
     latency::WHALE_TICKS_UNTIL_ACTIVE,
+    portfolio::Portfolio,
+};
+use crate::{
+    corporate::CorporateAction,
+    stocks::definitions::Stock,
+    types::order::{Order, OrderRequest, Side, Trade},
 };
-use crate::types::order::{Order, OrderRequest, Side, Trade};
 
 /// A patient, high-capital agent that places large limit orders far
 /// from mid-price to create support & resistance.
+#[derive(Debug, Clone)]
 pub struct WhaleAgent {
     id: usize,
-    inventory: i64,
+    portfolio: Portfolio,
     ticks_until_active: u32,
     open_orders: HashMap<u64, Order>,
-    cash: f64,
     //alow dead code
     #[allow(dead_code)]
     margin: f64,
-    port_value: f64,
+    action_prob: f64,
+    /// Starting shares of each listed stock.
+    inventory: i64,
+    rng: StdRng,
 }
 
 impl WhaleAgent {
     pub fn new(id: usize) -> Self {
         Self {
             id,
-            inventory: WHALE_INITIAL_INVENTORY,
+            portfolio: Portfolio::new(1_000_000_000_000.0),
             ticks_until_active: WHALE_TICKS_UNTIL_ACTIVE,
             open_orders: HashMap::new(),
-            cash: 1_000_000_000_000.0,
             margin: 10_000_000_000_000.0,
-            port_value: 0.0,
+            action_prob: WHALE_ACTION_PROB,
+            inventory: WHALE_INITIAL_INVENTORY,
+            rng: StdRng::from_entropy(),
         }
     }
+
+    /// Chance, per tick, of placing an order.
+    pub fn with_action_prob(mut self, p: f64) -> Self {
+        self.action_prob = p.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Starting cash in dollars.
+    pub fn with_cash(mut self, cash: f64) -> Self {
+        self.portfolio = Portfolio::new(cash);
+        self
+    }
+
+    /// Shares of every stock it is endowed with when the stock lists.
+    pub fn with_inventory(mut self, qty: i64) -> Self {
+        self.inventory = qty;
+        self
+    }
 }
 
 // -----------------------------------------------------------------------------
@@ -49,8 +76,8 @@ impl Agent for WhaleAgent {
             self.ticks_until_active -= 1;
             return vec![];
         }
-        let mut rng = rand::thread_rng();
-        if !rng.gen_bool(WHALE_ACTION_PROB) {
+        let rng = &mut self.rng;
+        if !rng.gen_bool(self.action_prob) {
             return vec![];
         }
 
@@ -59,7 +86,7 @@ impl Agent for WhaleAgent {
         if ids.is_empty() {
             return vec![];
         }
-        let stock_id = *ids.choose(&mut rng).unwrap();
+        let stock_id = *ids.choose(rng).unwrap();
 
         /* 1) cancel & clear existing orders */
         let cancel_reqs: Vec<OrderRequest> = self
@@ -145,19 +172,34 @@ impl Agent for WhaleAgent {
     }
 
     fn update_portfolio(&mut self, vol: i64, tr: &Trade) {
-        self.inventory += vol;
-        self.cash -= vol as f64 * (tr.price as f64 / 100.0);
-
-        if tr.maker_agent_id == self.id {
-            if let Some(o) = self.open_orders.get_mut(&tr.maker_order_id) {
-                o.filled += tr.volume;
-                if o.filled >= o.volume {
-                    self.open_orders.remove(&tr.maker_order_id);
-                }
+        self.portfolio.on_trade(vol, tr);
+
+        if tr.maker_agent_id == self.id
+            && let Some(o) = self.open_orders.get_mut(&tr.maker_order_id)
+        {
+            o.filled += tr.volume;
+            if o.filled >= o.volume {
+                self.open_orders.remove(&tr.maker_order_id);
             }
         }
     }
 
+    fn stock_listed(&mut self, stock: &Stock) {
+        self.portfolio
+            .endow(stock.id, self.inventory, stock.initial_price);
+    }
+
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+        self.open_orders.retain(|_, o| o.stock_id != stock_id);
+    }
+
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+        self.open_orders
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+
     fn get_pending_orders(&self) -> Vec<Order> {
         self.open_orders.values().cloned().collect()
     }
@@ -178,18 +220,20 @@ impl Agent for WhaleAgent {
         self.id
     }
     fn get_inventory(&self) -> i64 {
-        self.inventory
+        self.portfolio.shares()
     }
     fn clone_agent(&self) -> Box<dyn Agent> {
-        Box::new(WhaleAgent::new(self.id))
+        Box::new(self.clone())
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
     fn evaluate_port(&mut self, view: &MarketView) -> f64 {
-        let sid = *view.stocks.get_all_ids().first().unwrap_or(&0);
-        if let Some(px) = view.get_mid_price(sid) {
-            self.port_value = self.inventory as f64 * (px as f64 / 100.0);
-        }
-        self.port_value
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
     }
 }
 // -----------------------------------------------------------------------------
@@ -199,7 +243,7 @@ impl Agent for WhaleAgent {
 mod tests {
     use super::*;
     use crate::{
-        simulators::order_book::{OrderBook, PriceLevel},
+        simulators::order_book::OrderBook,
         stocks::definitions::StockMarket,
         types::order::{OrderRequest, Side},
     };
@@ -247,15 +291,13 @@ mod tests {
 
         /* build a dummy book + market view */
         let mut book = OrderBook::new();
-        book.bids.insert(14_500, PriceLevel::default());
-        book.asks.insert(15_500, PriceLevel::default());
+        book.process_limit_order(&mut new_order(1, 9, Side::Buy, 14_500, 100));
+        book.process_limit_order(&mut new_order(2, 9, Side::Sell, 15_500, 100));
 
         let mut books = std::collections::HashMap::new();
         books.insert(STOCK_ID, book);
-        let view = MarketView {
-            order_books: &books,
-            stocks: &StockMarket::new(),
-        };
+        let stocks = StockMarket::new();
+        let view = MarketView::new(&books, &stocks);
 
         /* run — note: probabilistic; we accept it may no-op */
         let reqs = whale.decide_actions(&view);
@@ -281,6 +323,8 @@ mod tests {
     #[test]
     fn whale_update_portfolio_as_maker() {
         let mut whale = WhaleAgent::new(1);
+        whale.stock_listed(&Stock::new("WHL", STOCK_ID, "Whale Food", 1, 140.0));
+        whale.stock_listed(&Stock::new("OTH", 2, "Other", 1, 10.0));
         whale.acknowledge_order(new_order(101, 1, Side::Buy, 14_000, 500_000));
 
         let tr = new_trade(2, 1, 101, Side::Sell, 14_000, 10_000); // maker bought
@@ -288,6 +332,10 @@ mod tests {
 
         let ord = whale.open_orders.get(&101).expect("order remains open");
         assert_eq!(ord.filled, 10_000);
-        assert_eq!(whale.inventory, WHALE_INITIAL_INVENTORY + 10_000);
+        assert_eq!(whale.get_inventory(), 2 * WHALE_INITIAL_INVENTORY + 10_000);
+        assert_eq!(
+            whale.portfolio.qty(STOCK_ID),
+            WHALE_INITIAL_INVENTORY + 10_000
+        );
     }
 }
@@ -0,0 +1,51 @@
+// src/bin/fix_gateway.rs
+//! FIX 4.4 order entry in front of a simulated market; see
+//! `market_simulator::fix` for what is supported.
+//!
+//! ```text
+//! fix_gateway [--addr ADDR] [--comp-id ID] [--tick-ms N] [--seed N]
+//! ```
+//! Listens on 127.0.0.1:9878 by default and runs until killed.
+
+use std::{env, process, sync::atomic::AtomicBool, time::Duration};
+
+use market_simulator::{AgentType, FixAcceptor, FixConfig, Market, StockMarket};
+
+fn usage() -> ! {
+    eprintln!("usage: fix_gateway [--addr ADDR] [--comp-id ID] [--tick-ms N] [--seed N]");
+    process::exit(2)
+}
+
+fn main() -> std::io::Result<()> {
+    let mut addr = "127.0.0.1:9878".to_string();
+    let mut cfg = FixConfig::default();
+    let mut seed = 0;
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--addr" => addr = value(),
+            "--comp-id" => cfg.comp_id = value(),
+            "--tick-ms" => {
+                cfg.tick_interval =
+                    Duration::from_millis(value().parse().unwrap_or_else(|_| usage()))
+            }
+            "--seed" => seed = value().parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+
+    let participants = [
+        AgentType::MarketMaker,
+        AgentType::MarketMaker,
+        AgentType::DumbLimit,
+        AgentType::DumbLimit,
+        AgentType::DumbMarket,
+    ];
+    let market = Market::with_seed(&participants, StockMarket::new(), seed);
+    let acceptor = FixAcceptor::bind(&addr, market, cfg)?;
+    println!("FIX 4.4 acceptor on tcp://{}", acceptor.local_addr()?);
+    acceptor.run(&AtomicBool::new(false))?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+// src/bin/market_server.rs
+//! Hosts a market for an external policy; see `market_simulator::server`
+//! for the protocol.
+//!
+//! ```text
+//! market_server [--tcp ADDR | --unix PATH] [--episode-length N] [--warmup N]
+//! ```
+//! Listens on 127.0.0.1:7878 by default.
+
+use std::{env, net::TcpListener, process};
+
+use market_simulator::{EnvConfig, server::Server};
+
+enum Listen {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(String),
+}
+
+fn usage() -> ! {
+    eprintln!("usage: market_server [--tcp ADDR | --unix PATH] [--episode-length N] [--warmup N]");
+    process::exit(2)
+}
+
+fn main() -> std::io::Result<()> {
+    let mut listen = Listen::Tcp("127.0.0.1:7878".into());
+    let mut cfg = EnvConfig::default();
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--tcp" => listen = Listen::Tcp(value()),
+            #[cfg(unix)]
+            "--unix" => listen = Listen::Unix(value()),
+            "--episode-length" => cfg.episode_length = value().parse().unwrap_or_else(|_| usage()),
+            "--warmup" => cfg.warmup_ticks = value().parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+
+    let mut server = Server::new(cfg);
+    match listen {
+        Listen::Tcp(addr) => {
+            let listener = TcpListener::bind(&addr)?;
+            println!("listening on tcp://{}", listener.local_addr()?);
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        eprintln!("accept failed: {e}");
+                        continue;
+                    }
+                };
+                if let Err(e) = stream.set_nodelay(true) {
+                    eprintln!("set_nodelay failed: {e}");
+                }
+                if let Err(e) = server.serve(stream) {
+                    eprintln!("client dropped: {e}");
+                }
+            }
+        }
+        #[cfg(unix)]
+        Listen::Unix(path) => {
+            use std::os::unix::fs::FileTypeExt;
+            // clear a stale socket from a previous run, but nothing else
+            if let Ok(meta) = std::fs::symlink_metadata(&path) {
+                if !meta.file_type().is_socket() {
+                    eprintln!("{path} exists and is not a socket");
+                    process::exit(1);
+                }
+                std::fs::remove_file(&path)?;
+            }
+            let listener = std::os::unix::net::UnixListener::bind(&path)?;
+            println!("listening on unix://{path}");
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        eprintln!("accept failed: {e}");
+                        continue;
+                    }
+                };
+                if let Err(e) = server.serve(stream) {
+                    eprintln!("client dropped: {e}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+// src/bin/simulate.rs
+//! Headless runner: plays a scenario file at full speed and writes its
+//! outputs; see `market_simulator::scenario` for the format.
+//!
+//! ```text
+//! simulate SCENARIO.{toml,json} [--out DIR] [--seed N] [--ticks N]
+//! ```
+//! Flags override the file. Exits non-zero on a bad scenario.
+
+use std::{env, process, time::Instant};
+
+use market_simulator::Scenario;
+
+fn usage() -> ! {
+    eprintln!("usage: simulate SCENARIO.{{toml,json}} [--out DIR] [--seed N] [--ticks N]");
+    process::exit(2)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| usage());
+    let mut scenario = Scenario::from_path(&path).unwrap_or_else(|e| {
+        eprintln!("{path}: {e}");
+        process::exit(1)
+    });
+
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--out" => scenario.outputs.dir = value().into(),
+            "--seed" => scenario.seed = value().parse().unwrap_or_else(|_| usage()),
+            "--ticks" => scenario.ticks = value().parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+
+    let started = Instant::now();
+    match scenario.run() {
+        Ok(summary) => {
+            let secs = started.elapsed().as_secs_f64();
+            println!(
+                "{}: {} ticks, {} trades, {} shares, {} halts in {:.2}s ({:.0} ticks/s) -> {}",
+                summary.name,
+                summary.ticks,
+                summary.trades,
+                summary.volume,
+                summary.halts,
+                secs,
+                summary.ticks as f64 / secs,
+                scenario.outputs.dir.display()
+            );
+            for (ticker, price) in summary.final_prices {
+                println!("  {ticker:<6} {price:>10.2}");
+            }
+        }
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            process::exit(1)
+        }
+    }
+}
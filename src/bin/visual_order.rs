@@ -6,7 +6,7 @@ use egui::{Color32, FontId, RichText, Rounding, Stroke, Vec2};
 use egui_plot::{Legend, Line, Plot, PlotBounds, PlotPoints, Points};
 use market_simulator::{
     AgentType, Market, Marketable,
-    simulators::order_book::{OrderBook, PriceLevel},
+    simulators::order_book::{LevelQuote, OrderBook},
     stocks::definitions::StockMarket,
 };
 //use egui_plot::PlotItem;
@@ -16,24 +16,25 @@ use std::time::{Duration, Instant};
 // Add debug logging
 fn debug_order_book(order_book: &OrderBook, stock_id: u64) {
     println!("=== DEBUG ORDER BOOK FOR STOCK {} ===", stock_id);
-    println!("Bids count: {}", order_book.bids.len());
-    println!("Asks count: {}", order_book.asks.len());
+    let depth = order_book.depth(usize::MAX);
+    println!("Bids count: {}", depth.bids.len());
+    println!("Asks count: {}", depth.asks.len());
 
     println!("First 5 bids:");
-    for (price, level) in order_book.bids.iter().rev().take(5) {
+    for level in depth.bids.iter().take(5) {
         println!(
             "  ${:.2} -> {} shares",
-            *price as f64 / 100.0,
-            level.total_volume
+            level.price as f64 / 100.0,
+            level.volume
         );
     }
 
     println!("First 5 asks:");
-    for (price, level) in order_book.asks.iter().take(5) {
+    for level in depth.asks.iter().take(5) {
         println!(
             "  ${:.2} -> {} shares",
-            *price as f64 / 100.0,
-            level.total_volume
+            level.price as f64 / 100.0,
+            level.volume
         );
     }
     println!("=== END DEBUG ===");
@@ -108,14 +109,11 @@ impl eframe::App for AgentVisualizer {
 
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         // Add debug button
-                        if ui.button("🐛 Debug").clicked() {
-                            if let Some(market) = self.simulator.as_any().downcast_ref::<Market>() {
-                                if let Some(order_book) =
-                                    market.order_books().get(&self.selected_id)
-                                {
-                                    debug_order_book(order_book, self.selected_id);
-                                }
-                            }
+                        if ui.button("🐛 Debug").clicked()
+                            && let Some(market) = self.simulator.as_any().downcast_ref::<Market>()
+                            && let Some(order_book) = market.order_books().get(&self.selected_id)
+                        {
+                            debug_order_book(order_book, self.selected_id);
                         }
                         ui.separator();
 
@@ -209,11 +207,11 @@ impl AgentVisualizer {
                     }
                 }
             }
-            if let Some(hist) = self.price_histories.get(&self.selected_id) {
-                if let Some((&last, tail)) = hist.split_last() {
-                    self.ath = tail.iter().fold(last, |a, &p| a.max(p));
-                    self.atl = tail.iter().fold(last, |a, &p| a.min(p));
-                }
+            if let Some(hist) = self.price_histories.get(&self.selected_id)
+                && let Some((&last, tail)) = hist.split_last()
+            {
+                self.ath = tail.iter().fold(last, |a, &p| a.max(p));
+                self.atl = tail.iter().fold(last, |a, &p| a.min(p));
             }
         }
     }
@@ -276,6 +274,7 @@ impl AgentVisualizer {
                         );
                     });
                     ui.add_space(8.0);
+                    let depth = order_book.depth(10);
                     ui.horizontal_top(|ui| {
                         // Make sure both tables get equal space
                         let available_width = ui.available_width();
@@ -285,7 +284,7 @@ impl AgentVisualizer {
                             egui::Vec2::new(table_width, ui.available_height()),
                             egui::Layout::top_down(egui::Align::LEFT),
                             |ui| {
-                                self.render_side_table(ui, &order_book.bids, true);
+                                self.render_side_table(ui, &depth.bids, true);
                             },
                         );
 
@@ -295,7 +294,7 @@ impl AgentVisualizer {
                             egui::Vec2::new(table_width, ui.available_height()),
                             egui::Layout::top_down(egui::Align::LEFT),
                             |ui| {
-                                self.render_side_table(ui, &order_book.asks, false);
+                                self.render_side_table(ui, &depth.asks, false);
                             },
                         );
                     });
@@ -303,12 +302,7 @@ impl AgentVisualizer {
             });
     }
 
-    fn render_side_table(
-        &self,
-        ui: &mut egui::Ui,
-        book_side: &std::collections::BTreeMap<u64, PriceLevel>,
-        is_bid: bool,
-    ) {
+    fn render_side_table(&self, ui: &mut egui::Ui, book_side: &[LevelQuote], is_bid: bool) {
         let (title, col, rgb) = if is_bid {
             ("📈 Bids", "bids_grid", (40, 167, 69))
         } else {
@@ -348,15 +342,7 @@ impl AgentVisualizer {
                     ui.label(RichText::new("—").color(Color32::GRAY).italics());
                     ui.end_row();
                 } else {
-                    let iter: Box<dyn Iterator<Item = (&u64, &PriceLevel)>> = if is_bid {
-                        Box::new(book_side.iter().rev())
-                    } else {
-                        Box::new(book_side.iter())
-                    };
-
-                    let entries: Vec<_> = iter.take(10).collect();
-
-                    for (price, lvl) in entries {
+                    for lvl in book_side.iter().take(10) {
                         if row % 2 == 0 {
                             let r = ui.available_rect_before_wrap();
                             ui.painter().rect_filled(
@@ -366,13 +352,13 @@ impl AgentVisualizer {
                             );
                         }
                         ui.label(
-                            RichText::new(format!("${:.2}", *price as f64 / 100.0))
+                            RichText::new(format!("${:.2}", lvl.price as f64 / 100.0))
                                 .color(Color32::from_rgb(rgb.0, rgb.1, rgb.2))
                                 .font(FontId::monospace(14.0))
                                 .strong(),
                         );
                         ui.label(
-                            RichText::new(format_number(lvl.total_volume as i32))
+                            RichText::new(format_number(lvl.volume as i32))
                                 .font(FontId::monospace(14.0)),
                         );
                         ui.end_row();
@@ -401,14 +387,15 @@ impl AgentVisualizer {
                         .show_axes([true, true])
                         .show_grid([true, true])
                         .show(ui, |p| {
+                            let depth = order_book.depth(usize::MAX);
                             // ASKS curve + fill
-                            if !order_book.asks.is_empty() {
+                            if !depth.asks.is_empty() {
                                 let mut ask_pts = Vec::new();
                                 let mut cum: u64 = 0;
-                                for (&px, lvl) in order_book.asks.iter() {
-                                    let price = px as f64 / 100.0;
+                                for lvl in &depth.asks {
+                                    let price = lvl.price as f64 / 100.0;
                                     ask_pts.push([price, cum as f64]);
-                                    cum += lvl.total_volume as u64;
+                                    cum += lvl.volume;
                                     ask_pts.push([price, cum as f64]);
                                 }
                                 p.line(
@@ -421,13 +408,13 @@ impl AgentVisualizer {
                             }
 
                             // BIDS curve + fill
-                            if !order_book.bids.is_empty() {
+                            if !depth.bids.is_empty() {
                                 let mut bid_pts = Vec::new();
                                 let mut cum: u64 = 0;
-                                for (&px, lvl) in order_book.bids.iter().rev() {
-                                    let price = px as f64 / 100.0;
+                                for lvl in &depth.bids {
+                                    let price = lvl.price as f64 / 100.0;
                                     bid_pts.push([price, cum as f64]);
-                                    cum += lvl.total_volume as u64;
+                                    cum += lvl.volume;
                                     bid_pts.push([price, cum as f64]);
                                 }
                                 p.line(
@@ -512,8 +499,7 @@ impl AgentVisualizer {
         let Some(ob) = market.order_books().get(&self.selected_id) else {
             return;
         };
-        let best_bid = ob.bids.keys().last().copied();
-        let best_ask = ob.asks.keys().next().copied();
+        let (best_bid, best_ask) = ob.best_bid_ask();
         let total_inv = market.total_inventory();
 
         // Always render the status bar, even if bid/ask are missing
@@ -0,0 +1,286 @@
+// src/corporate.rs
+//! Corporate actions a `Market` applies at the start of a scheduled tick:
+//! splits and reverse splits, cash dividends and ticker changes.
+//!
+//! An action takes effect before agents decide, so the books, the
+//! exchange's ledger and every agent's own books already reflect it when it
+//! shows up in `MarketView::corporate_actions`.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::order::{Order, Side};
+
+/// Largest share count on either side of a split ratio.
+pub const MAX_SPLIT_SHARES: u64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CorporateAction {
+    /// `new_shares` for every `old_shares` held: a 2-for-1 split is
+    /// `{ new_shares: 2, old_shares: 1 }`, a 1-for-10 reverse split
+    /// `{ new_shares: 1, old_shares: 10 }`. Fractional shares are paid out
+    /// in cash at the adjusted price.
+    Split {
+        stock_id: u64,
+        new_shares: u64,
+        old_shares: u64,
+    },
+    /// `amount` dollars per share to whoever holds the stock as the ex-date
+    /// tick starts; shorts pay it. Prices drop by the same amount.
+    CashDividend { stock_id: u64, amount: f64 },
+    /// The stock trades under `ticker` from now on; its id does not change.
+    Rename { stock_id: u64, ticker: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorporateActionError {
+    UnknownStock(u64),
+    /// Scheduled for a tick that has already started.
+    InThePast {
+        tick: u64,
+        now: u64,
+    },
+    /// A zero or oversized share count, a non-positive dividend or an empty
+    /// ticker.
+    Invalid(String),
+    TickerTaken(String),
+}
+
+impl fmt::Display for CorporateActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorporateActionError::UnknownStock(id) => write!(f, "no stock with id {id}"),
+            CorporateActionError::InThePast { tick, now } => {
+                write!(f, "tick {tick} is not after the current tick {now}")
+            }
+            CorporateActionError::Invalid(msg) => write!(f, "{msg}"),
+            CorporateActionError::TickerTaken(t) => write!(f, "ticker {t} is already listed"),
+        }
+    }
+}
+
+impl std::error::Error for CorporateActionError {}
+
+impl CorporateAction {
+    pub fn stock_id(&self) -> u64 {
+        match *self {
+            CorporateAction::Split { stock_id, .. }
+            | CorporateAction::CashDividend { stock_id, .. }
+            | CorporateAction::Rename { stock_id, .. } => stock_id,
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), CorporateActionError> {
+        let bad = |msg: &str| Err(CorporateActionError::Invalid(msg.into()));
+        match self {
+            CorporateAction::Split {
+                new_shares,
+                old_shares,
+                ..
+            } if *new_shares == 0 || *old_shares == 0 => {
+                bad("split ratio needs two positive share counts")
+            }
+            CorporateAction::Split {
+                new_shares,
+                old_shares,
+                ..
+            } if *new_shares > MAX_SPLIT_SHARES || *old_shares > MAX_SPLIT_SHARES => {
+                bad("split ratio share counts are capped at 1 000 000")
+            }
+            CorporateAction::CashDividend { amount, .. }
+                if !(amount.is_finite() && *amount > 0.0) =>
+            {
+                bad("dividend must be a positive amount")
+            }
+            CorporateAction::Rename { ticker, .. } if ticker.trim().is_empty() => {
+                bad("new ticker is empty")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the action touches prices and positions at all.
+    pub fn adjusts_prices(&self) -> bool {
+        !matches!(self, CorporateAction::Rename { .. })
+    }
+
+    /// A dollar price as it reads after the action.
+    pub fn price(&self, dollars: f64) -> f64 {
+        match *self {
+            CorporateAction::Split {
+                new_shares,
+                old_shares,
+                ..
+            } => dollars * old_shares as f64 / new_shares as f64,
+            CorporateAction::CashDividend { amount, .. } => (dollars - amount).max(0.0),
+            CorporateAction::Rename { .. } => dollars,
+        }
+    }
+
+    /// A cents price after the action, rounded to the nearest cent but
+    /// never below one.
+    pub fn price_cents(&self, cents: u64) -> u64 {
+        ((self.price(cents as f64 / 100.0) * 100.0).round() as u64).max(1)
+    }
+
+    /// Rewrite a resting order in place: bids round down and asks up, so a
+    /// book that was uncrossed stays uncrossed, and the unfilled volume is
+    /// rescaled (rounded down) with `filled` reset. `false` when nothing
+    /// sensible is left (no shares, or a price at or below zero) and the
+    /// order should be cancelled instead, or when it would no longer fit.
+    pub fn adjust_order(&self, order: &mut Order) -> bool {
+        let remaining = order.volume.saturating_sub(order.filled);
+        let (price, volume) = match *self {
+            CorporateAction::Split {
+                new_shares,
+                old_shares,
+                ..
+            } => {
+                let scaled = order.price as u128 * old_shares as u128;
+                let price = match order.side {
+                    Side::Buy => scaled / new_shares as u128,
+                    Side::Sell => scaled.div_ceil(new_shares as u128),
+                };
+                let volume = remaining as u128 * new_shares as u128 / old_shares as u128;
+                let (Ok(price), Ok(volume)) = (u64::try_from(price), u64::try_from(volume)) else {
+                    return false;
+                };
+                (price, volume)
+            }
+            CorporateAction::CashDividend { amount, .. } => {
+                let cut = (amount * 100.0).round() as u64;
+                (order.price.saturating_sub(cut), remaining)
+            }
+            CorporateAction::Rename { .. } => return true,
+        };
+        if price == 0 || volume == 0 {
+            return false;
+        }
+        order.price = price;
+        order.volume = volume;
+        order.filled = 0;
+        true
+    }
+
+    /// Signed whole shares a position of `qty` becomes, plus the fraction
+    /// of a share left over (same sign as `qty`). `None` if the whole shares
+    /// overflow.
+    pub fn shares(&self, qty: i64) -> Option<(i64, f64)> {
+        match *self {
+            CorporateAction::Split {
+                new_shares,
+                old_shares,
+                ..
+            } => {
+                let scaled = qty as i128 * new_shares as i128;
+                let whole = i64::try_from(scaled / old_shares as i128).ok()?;
+                let frac = (scaled % old_shares as i128) as f64 / old_shares as f64;
+                Some((whole, frac))
+            }
+            _ => Some((qty, 0.0)),
+        }
+    }
+
+    /// An unsigned share count after the action, fractions dropped; `None`
+    /// if it overflows.
+    pub fn units(&self, count: u64) -> Option<u64> {
+        let (whole, _) = self.shares(i64::try_from(count).ok()?)?;
+        u64::try_from(whole).ok()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(side: Side, price: u64, volume: u64, filled: u64) -> Order {
+        Order {
+            id: 1,
+            agent_id: 0,
+            stock_id: 3,
+            side,
+            price,
+            volume,
+            filled,
+        }
+    }
+
+    #[test]
+    fn splits_keep_the_book_uncrossed_and_scale_shares() {
+        let three_for_one = CorporateAction::Split {
+            stock_id: 3,
+            new_shares: 3,
+            old_shares: 1,
+        };
+        let (mut bid, mut ask) = (
+            order(Side::Buy, 1_001, 100, 40),
+            order(Side::Sell, 1_002, 10, 0),
+        );
+        assert!(three_for_one.adjust_order(&mut bid));
+        assert!(three_for_one.adjust_order(&mut ask));
+        assert_eq!((bid.price, bid.volume, bid.filled), (333, 180, 0));
+        assert_eq!((ask.price, ask.volume), (334, 30));
+        assert_eq!(three_for_one.shares(-7), Some((-21, 0.0)));
+
+        let one_for_ten = CorporateAction::Split {
+            stock_id: 3,
+            new_shares: 1,
+            old_shares: 10,
+        };
+        let mut odd_lot = order(Side::Sell, 250, 9, 0);
+        assert!(!one_for_ten.adjust_order(&mut odd_lot));
+        assert_eq!(one_for_ten.shares(-25), Some((-2, -0.5)));
+        assert_eq!(one_for_ten.price_cents(250), 2_500);
+
+        // the largest ratio still validates, but an overflow is refused
+        let most = CorporateAction::Split {
+            stock_id: 3,
+            new_shares: MAX_SPLIT_SHARES,
+            old_shares: 1,
+        };
+        assert!(most.validate().is_ok());
+        assert_eq!(most.shares(i64::MAX / 2), None);
+        assert_eq!(most.units(u64::MAX), None);
+        assert!(!most.adjust_order(&mut order(Side::Buy, 100, u64::MAX / 2, 0)));
+    }
+
+    #[test]
+    fn dividends_lower_prices_and_validation_catches_nonsense() {
+        let div = CorporateAction::CashDividend {
+            stock_id: 3,
+            amount: 0.5,
+        };
+        let mut bid = order(Side::Buy, 2_000, 100, 0);
+        assert!(div.adjust_order(&mut bid));
+        assert_eq!((bid.price, bid.volume), (1_950, 100));
+        assert!(!div.adjust_order(&mut order(Side::Buy, 50, 100, 0)));
+        assert_eq!(div.shares(12), Some((12, 0.0)));
+
+        assert!(div.validate().is_ok());
+        let bad = [
+            CorporateAction::Split {
+                stock_id: 3,
+                new_shares: 0,
+                old_shares: 1,
+            },
+            CorporateAction::Split {
+                stock_id: 3,
+                new_shares: 1,
+                old_shares: MAX_SPLIT_SHARES + 1,
+            },
+            CorporateAction::CashDividend {
+                stock_id: 3,
+                amount: f64::NAN,
+            },
+            CorporateAction::Rename {
+                stock_id: 3,
+                ticker: " ".into(),
+            },
+        ];
+        assert!(bad.iter().all(|a| a.validate().is_err()));
+    }
+}
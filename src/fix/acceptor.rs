@@ -0,0 +1,1395 @@
+// src/fix/acceptor.rs
+//
+// FIX 4.4 order-entry acceptor in front of a `Market`. Every logged-on
+// session becomes one more agent (a `RemoteAgent` stand-in, as in live
+// mode) whose orders come off the wire instead of out of `decide_actions`.
+//
+// One thread drives everything: accept and read sessions, run the
+// background population for a tick, then execute each queued FIX request
+// on its own so every taker fill can be traced back to the request (and
+// ClOrdID) that caused it. Maker fills are matched by their order id.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, channel},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::message::{FixError, MAX_BODY_LEN, Message, msg_type, tag, utc_timestamp};
+use crate::{
+    Market,
+    live::{ExecReport, RemoteAgent},
+    types::order::{OrderRequest, Side},
+};
+
+#[derive(Debug, Clone)]
+pub struct FixConfig {
+    /// Our SenderCompID.
+    pub comp_id: String,
+    /// Pause between two ticks of the market.
+    pub tick_interval: Duration,
+}
+
+impl Default for FixConfig {
+    fn default() -> Self {
+        Self {
+            comp_id: "SIMEX".into(),
+            tick_interval: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Unparsed bytes buffered per session. Reading pauses above this until
+/// the messages already in the inbox are handled; any valid frame fits.
+const MAX_INBOX_LEN: usize = 4 * MAX_BODY_LEN;
+
+// -----------------------------------------------------------------------------
+//  Session state
+// -----------------------------------------------------------------------------
+/// A live order as the client knows it.
+#[derive(Debug, Clone)]
+struct OrderState {
+    cl_ord_id: String,
+    /// Set by the cancel or replace that last touched the order.
+    orig_cl_ord_id: Option<String>,
+    symbol: String,
+    side: Side,
+    /// OrderQty: total shares, filled ones included.
+    qty: u64,
+    /// Cents; `None` for a market order.
+    price: Option<u64>,
+    cum_qty: u64,
+    /// Cents × shares of everything filled.
+    notional: u128,
+}
+
+impl OrderState {
+    fn leaves(&self) -> u64 {
+        self.qty.saturating_sub(self.cum_qty)
+    }
+
+    fn status(&self) -> char {
+        match self.cum_qty {
+            0 => '0',
+            c if c >= self.qty => '2',
+            _ => '1',
+        }
+    }
+}
+
+/// An application request waiting for the next tick.
+#[derive(Debug, Clone)]
+enum Pending {
+    New {
+        order: OrderState,
+        stock_id: u64,
+    },
+    Cancel {
+        cl_ord_id: String,
+        orig_cl_ord_id: String,
+        order_id: u64,
+    },
+    Replace {
+        cl_ord_id: String,
+        orig_cl_ord_id: String,
+        order_id: u64,
+        qty: u64,
+        price: u64,
+    },
+}
+
+struct Session {
+    stream: TcpStream,
+    inbox: Vec<u8>,
+    comp_id: String,
+    their_comp_id: String,
+    agent_id: Option<usize>,
+    reports: Option<Receiver<ExecReport>>,
+    /// Next MsgSeqNum expected from / sent to the client.
+    in_seq: u64,
+    out_seq: u64,
+    /// Inbound messages received ahead of a sequence gap, by MsgSeqNum;
+    /// `None` for a Logon that was handled on arrival.
+    queued: BTreeMap<u64, Option<Message>>,
+    /// Application messages sent, by MsgSeqNum, with their SendingTime.
+    sent: BTreeMap<u64, (Message, String)>,
+    heartbeat: Duration,
+    last_sent: Instant,
+    /// Live orders by exchange order id.
+    orders: HashMap<u64, OrderState>,
+    seen_cl_ord_ids: HashSet<String>,
+    pending: Vec<Pending>,
+    exec_id: u64,
+    closed: bool,
+}
+
+impl Session {
+    fn new(stream: TcpStream, comp_id: String) -> Self {
+        Self {
+            stream,
+            inbox: Vec::new(),
+            comp_id,
+            their_comp_id: String::new(),
+            agent_id: None,
+            reports: None,
+            in_seq: 1,
+            out_seq: 1,
+            queued: BTreeMap::new(),
+            sent: BTreeMap::new(),
+            heartbeat: Duration::from_secs(30),
+            last_sent: Instant::now(),
+            orders: HashMap::new(),
+            seen_cl_ord_ids: HashSet::new(),
+            pending: Vec::new(),
+            exec_id: 0,
+            closed: false,
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    //  Wire
+    // ---------------------------------------------------------------------
+    /// Send under the next MsgSeqNum; application messages are kept for
+    /// replay.
+    fn send(&mut self, msg: Message) {
+        let seq = self.out_seq;
+        self.out_seq += 1;
+        let keep = matches!(
+            msg.msg_type(),
+            msg_type::EXECUTION_REPORT | msg_type::ORDER_CANCEL_REJECT
+        )
+        .then(|| msg.clone());
+        let sent_at = self.send_as(msg, seq);
+        if let Some(msg) = keep {
+            self.sent.insert(seq, (msg, sent_at));
+        }
+    }
+
+    /// Stamp the header and write; returns the SendingTime used.
+    fn send_as(&mut self, mut msg: Message, seq: u64) -> String {
+        let now = utc_timestamp();
+        msg.insert_header(tag::SENDER_COMP_ID, &self.comp_id);
+        msg.insert_header(tag::TARGET_COMP_ID, &self.their_comp_id);
+        msg.insert_header(tag::MSG_SEQ_NUM, seq);
+        msg.insert_header(tag::SENDING_TIME, &now);
+        if self.stream.write_all(&msg.encode()).is_err() {
+            self.closed = true;
+        }
+        self.last_sent = Instant::now();
+        now
+    }
+
+    /// Drain the socket into the inbox, up to `MAX_INBOX_LEN`; `false`
+    /// once the peer is gone.
+    fn read_available(&mut self) -> bool {
+        let mut buf = [0u8; 4096];
+        while self.inbox.len() < MAX_INBOX_LEN {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => self.inbox.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+
+    fn next_message(&mut self) -> Result<Option<Message>, FixError> {
+        let Some((msg, used)) = Message::parse(&self.inbox)? else {
+            return Ok(None);
+        };
+        self.inbox.drain(..used);
+        Ok(Some(msg))
+    }
+
+    fn logout(&mut self, text: &str) {
+        self.send(Message::new(msg_type::LOGOUT).with(tag::TEXT, text));
+        self.closed = true;
+    }
+
+    fn session_reject(&mut self, ref_seq: u64, ref_tag: Option<u32>, reason: u32, text: &str) {
+        let mut m = Message::new(msg_type::REJECT)
+            .with(tag::REF_SEQ_NUM, ref_seq)
+            .with(tag::SESSION_REJECT_REASON, reason)
+            .with(tag::TEXT, text);
+        if let Some(t) = ref_tag {
+            m.push(tag::REF_TAG_ID, t);
+        }
+        self.send(m);
+    }
+
+    // ---------------------------------------------------------------------
+    //  Inbound
+    // ---------------------------------------------------------------------
+    /// Sequence checks: messages ahead of a gap wait in `queued` until the
+    /// client resends or gap-fills the missing numbers.
+    fn on_message(&mut self, msg: Message, market: &mut Market) {
+        let Some(seq) = msg.get_u64(tag::MSG_SEQ_NUM) else {
+            return self.logout("MsgSeqNum(34) missing");
+        };
+        let logon = msg.msg_type() == msg_type::LOGON;
+        if self.agent_id.is_none() && !logon {
+            return self.logout("first message must be Logon");
+        }
+        if logon && msg.get(tag::RESET_SEQ_NUM_FLAG) == Some("Y") {
+            self.in_seq = 1;
+            self.out_seq = 1;
+            self.queued.clear();
+            self.sent.clear();
+        }
+        let gap_fill = msg.get(tag::GAP_FILL_FLAG) == Some("Y");
+        if msg.msg_type() == msg_type::SEQUENCE_RESET && !gap_fill {
+            // reset mode ignores MsgSeqNum
+            if let Some(next) = msg.get_u64(tag::NEW_SEQ_NO) {
+                self.advance_to(next, market);
+            }
+            return;
+        }
+        if seq < self.in_seq {
+            if msg.get(tag::POSS_DUP_FLAG) == Some("Y") {
+                return; // already processed
+            }
+            return self.logout(&format!("MsgSeqNum too low, expecting {}", self.in_seq));
+        }
+        if seq > self.in_seq {
+            // a Logon is answered first so the ResendRequest follows it
+            let queued = if logon {
+                self.on_logon(&msg, market);
+                if self.closed {
+                    return;
+                }
+                None
+            } else {
+                Some(msg)
+            };
+            // ask for the gap once, and hold this message until it is filled
+            if self.queued.is_empty() {
+                self.send(
+                    Message::new(msg_type::RESEND_REQUEST)
+                        .with(tag::BEGIN_SEQ_NO, self.in_seq)
+                        .with(tag::END_SEQ_NO, 0),
+                );
+            }
+            self.queued.insert(seq, queued);
+            return;
+        }
+        self.in_seq = seq + 1;
+        self.dispatch(msg, seq, market);
+        self.drain_queued(market);
+    }
+
+    /// Skip the inbound sequence ahead to `next` and handle what was
+    /// waiting behind the skipped numbers.
+    fn advance_to(&mut self, next: u64, market: &mut Market) {
+        self.in_seq = self.in_seq.max(next);
+        self.queued = self.queued.split_off(&self.in_seq);
+        self.drain_queued(market);
+    }
+
+    fn drain_queued(&mut self, market: &mut Market) {
+        while !self.closed
+            && let Some(msg) = self.queued.remove(&self.in_seq)
+        {
+            let seq = self.in_seq;
+            self.in_seq += 1;
+            if let Some(msg) = msg {
+                self.dispatch(msg, seq, market);
+            }
+        }
+    }
+
+    /// Admin messages, and app messages into `pending`.
+    fn dispatch(&mut self, msg: Message, seq: u64, market: &mut Market) {
+        match msg.msg_type() {
+            msg_type::LOGON => self.on_logon(&msg, market),
+            msg_type::HEARTBEAT => {}
+            msg_type::TEST_REQUEST => {
+                let mut hb = Message::new(msg_type::HEARTBEAT);
+                if let Some(id) = msg.get(tag::TEST_REQ_ID) {
+                    hb.push(tag::TEST_REQ_ID, id);
+                }
+                self.send(hb);
+            }
+            msg_type::RESEND_REQUEST => {
+                let begin = msg.get_u64(tag::BEGIN_SEQ_NO).unwrap_or(1);
+                let end = msg.get_u64(tag::END_SEQ_NO).unwrap_or(0);
+                self.resend(begin, end);
+            }
+            msg_type::SEQUENCE_RESET => {
+                if let Some(next) = msg.get_u64(tag::NEW_SEQ_NO) {
+                    self.advance_to(next, market);
+                }
+            }
+            msg_type::LOGOUT => self.logout("bye"),
+            msg_type::NEW_ORDER_SINGLE => self.on_new_order(&msg, seq, market),
+            msg_type::ORDER_CANCEL_REQUEST => self.on_cancel(&msg, seq),
+            msg_type::ORDER_CANCEL_REPLACE_REQUEST => self.on_replace(&msg, seq),
+            other => {
+                let text = format!("unsupported MsgType {other}");
+                self.session_reject(seq, Some(tag::MSG_TYPE), 11, &text);
+            }
+        }
+    }
+
+    /// Replay stored application messages in `begin..=end` (0 = up to the
+    /// latest) with PossDupFlag=Y; gap-fill the admin messages between them.
+    fn resend(&mut self, begin: u64, end: u64) {
+        let last = match end {
+            0 => self.out_seq - 1,
+            e => e.min(self.out_seq - 1),
+        };
+        let mut seq = begin.max(1);
+        while seq <= last && !self.closed {
+            if let Some((mut msg, sent_at)) = self.sent.get(&seq).cloned() {
+                msg.insert_header(tag::POSS_DUP_FLAG, "Y");
+                msg.insert_header(tag::ORIG_SENDING_TIME, sent_at);
+                self.send_as(msg, seq);
+                seq += 1;
+            } else {
+                let next = self
+                    .sent
+                    .range(seq..=last)
+                    .next()
+                    .map_or(last + 1, |(&s, _)| s);
+                let fill = Message::new(msg_type::SEQUENCE_RESET)
+                    .with(tag::POSS_DUP_FLAG, "Y")
+                    .with(tag::GAP_FILL_FLAG, "Y")
+                    .with(tag::NEW_SEQ_NO, next);
+                self.send_as(fill, seq);
+                seq = next;
+            }
+        }
+    }
+
+    fn on_logon(&mut self, msg: &Message, market: &mut Market) {
+        if self.agent_id.is_some() {
+            return self.logout("already logged on");
+        }
+        let (Some(them), Some(hb)) = (msg.get(tag::SENDER_COMP_ID), msg.get_u64(tag::HEART_BT_INT))
+        else {
+            return self.logout("Logon needs SenderCompID(49) and HeartBtInt(108)");
+        };
+        self.their_comp_id = them.to_string();
+        self.heartbeat = Duration::from_secs(hb.max(1));
+
+        let (tx, rx) = channel();
+        let id = market.add_agent(|id| Box::new(RemoteAgent { id, reports: tx }));
+        self.agent_id = Some(id);
+        self.reports = Some(rx);
+
+        let mut reply = Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, hb);
+        if msg.get(tag::RESET_SEQ_NUM_FLAG) == Some("Y") {
+            reply.push(tag::RESET_SEQ_NUM_FLAG, "Y");
+        }
+        self.send(reply);
+    }
+
+    /// Value of a required tag, or a session Reject naming it.
+    fn required<'m>(&mut self, msg: &'m Message, t: u32, seq: u64) -> Option<&'m str> {
+        let v = msg.get(t);
+        if v.is_none() {
+            self.session_reject(seq, Some(t), 1, "required tag missing");
+        }
+        v
+    }
+
+    fn quantity(&mut self, msg: &Message, seq: u64) -> Option<u64> {
+        let raw = self.required(msg, tag::ORDER_QTY, seq)?;
+        match raw.parse::<f64>() {
+            Ok(q) if q >= 1.0 && q.fract() == 0.0 => Some(q as u64),
+            _ => {
+                self.session_reject(
+                    seq,
+                    Some(tag::ORDER_QTY),
+                    5,
+                    "OrderQty must be whole shares",
+                );
+                None
+            }
+        }
+    }
+
+    fn limit_price(&mut self, msg: &Message, seq: u64) -> Option<u64> {
+        let raw = self.required(msg, tag::PRICE, seq)?;
+        match raw.parse::<f64>() {
+            Ok(p) if p > 0.0 => Some((p * 100.0).round() as u64),
+            _ => {
+                self.session_reject(seq, Some(tag::PRICE), 5, "bad Price");
+                None
+            }
+        }
+    }
+
+    fn on_new_order(&mut self, msg: &Message, seq: u64, market: &Market) {
+        let Some(cl_ord_id) = self.required(msg, tag::CL_ORD_ID, seq) else {
+            return;
+        };
+        let Some(symbol) = self.required(msg, tag::SYMBOL, seq) else {
+            return;
+        };
+        let side = match self.required(msg, tag::SIDE, seq) {
+            Some("1") => Side::Buy,
+            Some("2") => Side::Sell,
+            Some(_) => return self.session_reject(seq, Some(tag::SIDE), 5, "Side must be 1 or 2"),
+            None => return,
+        };
+        let Some(qty) = self.quantity(msg, seq) else {
+            return;
+        };
+        let price = match self.required(msg, tag::ORD_TYPE, seq) {
+            Some("1") => None,
+            Some("2") => match self.limit_price(msg, seq) {
+                Some(p) => Some(p),
+                None => return,
+            },
+            Some(_) => {
+                return self.session_reject(seq, Some(tag::ORD_TYPE), 5, "OrdType must be 1 or 2");
+            }
+            None => return,
+        };
+        let order = OrderState {
+            cl_ord_id: cl_ord_id.to_string(),
+            orig_cl_ord_id: None,
+            symbol: symbol.to_string(),
+            side,
+            qty,
+            price,
+            cum_qty: 0,
+            notional: 0,
+        };
+
+        if !self.seen_cl_ord_ids.insert(order.cl_ord_id.clone()) {
+            return self.reject_order(&order, 6, "duplicate ClOrdID");
+        }
+        match market.stocks().get_stock_by_ticker(&order.symbol) {
+            Some(stock) => {
+                let stock_id = stock.id;
+                self.pending.push(Pending::New { order, stock_id });
+            }
+            None => self.reject_order(&order, 1, "unknown symbol"),
+        }
+    }
+
+    /// The live order a cancel or replace refers to, by OrigClOrdID.
+    fn find(&self, orig: &str) -> Option<u64> {
+        self.orders
+            .iter()
+            .find(|(_, o)| o.cl_ord_id == orig)
+            .map(|(&id, _)| id)
+    }
+
+    fn on_cancel(&mut self, msg: &Message, seq: u64) {
+        let (Some(cl_ord_id), Some(orig)) = (
+            self.required(msg, tag::CL_ORD_ID, seq),
+            self.required(msg, tag::ORIG_CL_ORD_ID, seq),
+        ) else {
+            return;
+        };
+        match self.find(orig) {
+            Some(order_id) => self.pending.push(Pending::Cancel {
+                cl_ord_id: cl_ord_id.to_string(),
+                orig_cl_ord_id: orig.to_string(),
+                order_id,
+            }),
+            None => self.cancel_reject(cl_ord_id, orig, None, '1'),
+        }
+    }
+
+    fn on_replace(&mut self, msg: &Message, seq: u64) {
+        let (Some(cl_ord_id), Some(orig)) = (
+            self.required(msg, tag::CL_ORD_ID, seq),
+            self.required(msg, tag::ORIG_CL_ORD_ID, seq),
+        ) else {
+            return;
+        };
+        let (Some(qty), Some(price)) = (self.quantity(msg, seq), self.limit_price(msg, seq)) else {
+            return;
+        };
+        let Some(order_id) = self.find(orig) else {
+            return self.cancel_reject(cl_ord_id, orig, None, '2');
+        };
+        if self.orders[&order_id].price.is_none() || qty <= self.orders[&order_id].cum_qty {
+            return self.cancel_reject(cl_ord_id, orig, Some(order_id), '2');
+        }
+        self.pending.push(Pending::Replace {
+            cl_ord_id: cl_ord_id.to_string(),
+            orig_cl_ord_id: orig.to_string(),
+            order_id,
+            qty,
+            price,
+        });
+    }
+
+    // ---------------------------------------------------------------------
+    //  Outbound reports
+    // ---------------------------------------------------------------------
+    fn exec_report(
+        &mut self,
+        order_id: Option<u64>,
+        o: &OrderState,
+        exec_type: char,
+        status: char,
+        last: Option<(u64, u64)>,
+    ) {
+        self.exec_id += 1;
+        let avg = if o.cum_qty == 0 {
+            0.0
+        } else {
+            o.notional as f64 / o.cum_qty as f64 / 100.0
+        };
+        let leaves = if matches!(status, '2' | '4' | '8') {
+            0
+        } else {
+            o.leaves()
+        };
+        let mut m = Message::new(msg_type::EXECUTION_REPORT)
+            .with(
+                tag::ORDER_ID,
+                order_id.map_or("NONE".to_string(), |id| id.to_string()),
+            )
+            .with(tag::CL_ORD_ID, &o.cl_ord_id);
+        if let Some(orig) = &o.orig_cl_ord_id {
+            m.push(tag::ORIG_CL_ORD_ID, orig);
+        }
+        m = m
+            .with(tag::EXEC_ID, self.exec_id)
+            .with(tag::EXEC_TYPE, exec_type)
+            .with(tag::ORD_STATUS, status)
+            .with(tag::SYMBOL, &o.symbol)
+            .with(tag::SIDE, if o.side == Side::Buy { 1 } else { 2 })
+            .with(tag::ORDER_QTY, o.qty)
+            .with(tag::ORD_TYPE, if o.price.is_some() { 2 } else { 1 });
+        if let Some(p) = o.price {
+            m.push(tag::PRICE, dollars(p));
+        }
+        m = m
+            .with(tag::LEAVES_QTY, leaves)
+            .with(tag::CUM_QTY, o.cum_qty)
+            .with(tag::AVG_PX, format!("{avg:.4}"));
+        if let Some((qty, px)) = last {
+            m = m.with(tag::LAST_QTY, qty).with(tag::LAST_PX, dollars(px));
+        }
+        self.send(m.with(tag::TRANSACT_TIME, utc_timestamp()));
+    }
+
+    fn reject_order(&mut self, o: &OrderState, reason: u32, text: &str) {
+        self.exec_id += 1;
+        let m = Message::new(msg_type::EXECUTION_REPORT)
+            .with(tag::ORDER_ID, "NONE")
+            .with(tag::CL_ORD_ID, &o.cl_ord_id)
+            .with(tag::EXEC_ID, self.exec_id)
+            .with(tag::EXEC_TYPE, '8')
+            .with(tag::ORD_STATUS, '8')
+            .with(tag::ORD_REJ_REASON, reason)
+            .with(tag::SYMBOL, &o.symbol)
+            .with(tag::SIDE, if o.side == Side::Buy { 1 } else { 2 })
+            .with(tag::ORDER_QTY, o.qty)
+            .with(tag::LEAVES_QTY, 0)
+            .with(tag::CUM_QTY, 0)
+            .with(tag::AVG_PX, 0)
+            .with(tag::TEXT, text)
+            .with(tag::TRANSACT_TIME, utc_timestamp());
+        self.send(m);
+    }
+
+    /// `response_to`: '1' for a cancel, '2' for a cancel/replace.
+    fn cancel_reject(
+        &mut self,
+        cl_ord_id: &str,
+        orig: &str,
+        order_id: Option<u64>,
+        response_to: char,
+    ) {
+        let status = order_id
+            .and_then(|id| self.orders.get(&id))
+            .map_or('8', |o| o.status());
+        let m = Message::new(msg_type::ORDER_CANCEL_REJECT)
+            .with(
+                tag::ORDER_ID,
+                order_id.map_or("NONE".to_string(), |id| id.to_string()),
+            )
+            .with(tag::CL_ORD_ID, cl_ord_id)
+            .with(tag::ORIG_CL_ORD_ID, orig)
+            .with(tag::ORD_STATUS, status)
+            .with(tag::CXL_REJ_RESPONSE_TO, response_to)
+            // 1 = unknown order, 0 = too late / not allowed
+            .with(tag::CXL_REJ_REASON, u8::from(order_id.is_none()));
+        self.send(m);
+    }
+
+    /// Turn the engine's callbacks into ExecutionReports. `current` is the
+    /// request just executed (`None` for the background batch): its reject,
+    /// ack and taker fills belong to it.
+    fn on_reports(&mut self, current: Option<&Pending>) {
+        let Some(me) = self.agent_id else {
+            return;
+        };
+        let reports: Vec<ExecReport> = match &self.reports {
+            Some(rx) => rx.try_iter().collect(),
+            None => return,
+        };
+        let rejected = reports.iter().find_map(|r| match r {
+            ExecReport::Rejected(_, reason) => Some(*reason),
+            _ => None,
+        });
+        let acked = reports.iter().find_map(|r| match r {
+            ExecReport::Ack(o) => Some(o.id),
+            _ => None,
+        });
+
+        // the request's own outcome first
+        let mut taker_order = None;
+        match current {
+            Some(Pending::New { order, .. }) => match (rejected, acked) {
+                (None, Some(id)) => {
+                    self.exec_report(Some(id), order, '0', '0', None);
+                    self.orders.insert(id, order.clone());
+                    taker_order = Some(id);
+                }
+                _ => self.reject_order(order, 99, "rejected by the exchange"),
+            },
+            Some(Pending::Replace {
+                cl_ord_id,
+                orig_cl_ord_id,
+                order_id,
+                qty,
+                price,
+            }) => {
+                if rejected.is_some() {
+                    self.cancel_reject(cl_ord_id, orig_cl_ord_id, Some(*order_id), '2');
+                } else if let Some(mut o) = self.orders.remove(order_id) {
+                    // a replace that loses priority re-enters under a new id
+                    let id = acked.unwrap_or(*order_id);
+                    o.orig_cl_ord_id = Some(orig_cl_ord_id.clone());
+                    o.cl_ord_id = cl_ord_id.clone();
+                    o.qty = *qty;
+                    o.price = Some(*price);
+                    self.exec_report(Some(id), &o, '5', o.status(), None);
+                    self.orders.insert(id, o);
+                    taker_order = Some(id);
+                }
+            }
+            Some(Pending::Cancel {
+                cl_ord_id,
+                orig_cl_ord_id,
+                order_id,
+            }) if rejected.is_some() => {
+                self.cancel_reject(cl_ord_id, orig_cl_ord_id, Some(*order_id), '1');
+            }
+            _ => {}
+        }
+
+        for report in reports {
+            match report {
+                ExecReport::Fill { delta, trade } => {
+                    let side = if delta > 0 { Side::Buy } else { Side::Sell };
+                    let id = if trade.taker_agent_id == me && side == trade.taker_side {
+                        taker_order
+                    } else if trade.maker_agent_id == me {
+                        Some(trade.maker_order_id)
+                    } else {
+                        None
+                    };
+                    let Some(id) = id else { continue };
+                    let Some(mut o) = self.orders.remove(&id) else {
+                        continue;
+                    };
+                    o.cum_qty += trade.volume;
+                    o.notional += trade.price as u128 * trade.volume as u128;
+                    self.exec_report(
+                        Some(id),
+                        &o,
+                        'F',
+                        o.status(),
+                        Some((trade.volume, trade.price)),
+                    );
+                    if o.leaves() > 0 {
+                        self.orders.insert(id, o);
+                    }
+                }
+                ExecReport::Cancelled(id) => {
+                    if let (
+                        Some(mut o),
+                        Some(Pending::Cancel {
+                            cl_ord_id,
+                            orig_cl_ord_id,
+                            ..
+                        }),
+                    ) = (self.orders.remove(&id), current)
+                    {
+                        o.orig_cl_ord_id = Some(orig_cl_ord_id.clone());
+                        o.cl_ord_id = cl_ord_id.clone();
+                        self.exec_report(Some(id), &o, '4', '4', None);
+                    }
+                }
+                ExecReport::Ack(_)
+                | ExecReport::Rejected(..)
+                | ExecReport::Listed(_)
+                | ExecReport::Delisted { .. }
+                | ExecReport::CorporateAction(_)
+                | ExecReport::UnitsSettled(_) => {}
+            }
+        }
+
+        // a market order never rests: whatever is left is done for the day
+        if let Some(Pending::New { order, .. }) = current
+            && order.price.is_none()
+            && let Some(id) = taker_order
+            && let Some(o) = self.orders.remove(&id)
+        {
+            self.exec_report(Some(id), &o, '4', '4', None);
+        }
+    }
+
+    /// The engine request for `p`, or `None` if it was answered here
+    /// instead.
+    fn request(&mut self, p: &Pending) -> Option<OrderRequest> {
+        let agent_id = self.agent_id.expect("logged on");
+        Some(match p {
+            Pending::New { order, stock_id } => match order.price {
+                Some(price) => OrderRequest::LimitOrder {
+                    agent_id,
+                    stock_id: *stock_id,
+                    side: order.side,
+                    price,
+                    volume: order.qty,
+                },
+                None => OrderRequest::MarketOrder {
+                    agent_id,
+                    stock_id: *stock_id,
+                    side: order.side,
+                    volume: order.qty,
+                },
+            },
+            Pending::Cancel { order_id, .. } => OrderRequest::CancelOrder {
+                agent_id,
+                order_id: *order_id,
+            },
+            Pending::Replace {
+                cl_ord_id,
+                orig_cl_ord_id,
+                order_id,
+                qty,
+                price,
+            } => {
+                // fills since the request was polled may have overtaken it
+                let cum_qty = self.orders.get(order_id).map(|o| o.cum_qty);
+                match cum_qty {
+                    Some(cum_qty) if cum_qty < *qty => OrderRequest::ModifyOrder {
+                        agent_id,
+                        order_id: *order_id,
+                        price: *price,
+                        volume: qty - cum_qty,
+                    },
+                    _ => {
+                        self.cancel_reject(cl_ord_id, orig_cl_ord_id, Some(*order_id), '2');
+                        return None;
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn dollars(cents: u64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+// -----------------------------------------------------------------------------
+//  Acceptor
+// -----------------------------------------------------------------------------
+pub struct FixAcceptor {
+    listener: TcpListener,
+    market: Market,
+    cfg: FixConfig,
+    sessions: Vec<Session>,
+}
+
+impl FixAcceptor {
+    pub fn bind(addr: impl ToSocketAddrs, market: Market, cfg: FixConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            market,
+            cfg,
+            sessions: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    /// Agent ids of the logged-on sessions.
+    pub fn session_agents(&self) -> Vec<usize> {
+        self.sessions.iter().filter_map(|s| s.agent_id).collect()
+    }
+
+    /// Accept new connections and handle everything clients have sent.
+    /// Orders are queued for the next `tick`.
+    pub fn poll(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.sessions
+                        .push(Session::new(stream, self.cfg.comp_id.clone()));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        for s in &mut self.sessions {
+            if !s.read_available() {
+                s.closed = true;
+            }
+            while !s.closed {
+                match s.next_message() {
+                    Ok(Some(msg)) => s.on_message(msg, &mut self.market),
+                    Ok(None) => break,
+                    Err(e) => s.logout(&format!("garbled message: {e}")),
+                }
+            }
+            if s.agent_id.is_some() && !s.closed && s.last_sent.elapsed() >= s.heartbeat {
+                s.send(Message::new(msg_type::HEARTBEAT));
+            }
+        }
+        self.drop_closed();
+        Ok(())
+    }
+
+    /// One market tick: the background population first, then every queued
+    /// FIX request in arrival order, each executed on its own.
+    pub fn tick(&mut self) {
+        let background = self.market.decide();
+        self.market.execute(background);
+        for s in &mut self.sessions {
+            // fills from the background and from earlier sessions' requests
+            // land before this session's requests are checked
+            s.on_reports(None);
+            for p in std::mem::take(&mut s.pending) {
+                let Some(req) = s.request(&p) else {
+                    continue;
+                };
+                self.market.execute(vec![req]);
+                s.on_reports(Some(&p));
+            }
+        }
+        for s in &mut self.sessions {
+            s.on_reports(None);
+        }
+    }
+
+    /// Poll and tick until `shutdown`, then hand the market back.
+    pub fn run(mut self, shutdown: &AtomicBool) -> io::Result<Market> {
+        while !shutdown.load(Ordering::SeqCst) {
+            self.poll()?;
+            self.tick();
+            thread::sleep(self.cfg.tick_interval);
+        }
+        for s in &mut self.sessions {
+            s.logout("exchange shutting down");
+        }
+        self.drop_closed();
+        Ok(self.market)
+    }
+
+    /// Cancel what closed sessions left on the books and forget them.
+    fn drop_closed(&mut self) {
+        let mut cancels = Vec::new();
+        for s in self.sessions.iter().filter(|s| s.closed) {
+            if let Some(agent_id) = s.agent_id {
+                cancels.extend(
+                    s.orders
+                        .keys()
+                        .map(|&order_id| OrderRequest::CancelOrder { agent_id, order_id }),
+                );
+            }
+        }
+        self.sessions.retain(|s| !s.closed);
+        if !cancels.is_empty() {
+            self.market.execute(cancels);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{AgentType, StockMarket};
+
+    /// Minimal initiator for the tests.
+    struct Client {
+        stream: TcpStream,
+        inbox: Vec<u8>,
+        seq: u64,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            Self {
+                stream,
+                inbox: Vec::new(),
+                seq: 1,
+            }
+        }
+
+        fn send(&mut self, mut msg: Message) {
+            msg.insert_header(tag::SENDER_COMP_ID, "CLIENT");
+            msg.insert_header(tag::TARGET_COMP_ID, "SIMEX");
+            msg.insert_header(tag::MSG_SEQ_NUM, self.seq);
+            msg.insert_header(tag::SENDING_TIME, utc_timestamp());
+            self.seq += 1;
+            self.stream.write_all(&msg.encode()).unwrap();
+        }
+
+        fn recv(&mut self) -> Message {
+            loop {
+                if let Some((m, used)) = Message::parse(&self.inbox).unwrap() {
+                    self.inbox.drain(..used);
+                    return m;
+                }
+                let mut buf = [0u8; 4096];
+                let n = self.stream.read(&mut buf).expect("timed out waiting");
+                assert!(n > 0, "acceptor hung up");
+                self.inbox.extend_from_slice(&buf[..n]);
+            }
+        }
+
+        /// Next message of `kind`, skipping heartbeats.
+        fn expect(&mut self, kind: &str) -> Message {
+            loop {
+                let m = self.recv();
+                if m.msg_type() == msg_type::HEARTBEAT && kind != msg_type::HEARTBEAT {
+                    continue;
+                }
+                assert_eq!(m.msg_type(), kind, "{m:?}");
+                return m;
+            }
+        }
+
+        fn logon(&mut self) {
+            self.send(
+                Message::new(msg_type::LOGON)
+                    .with(tag::ENCRYPT_METHOD, 0)
+                    .with(tag::HEART_BT_INT, 30),
+            );
+            let ack = self.expect(msg_type::LOGON);
+            assert_eq!(ack.get(tag::TARGET_COMP_ID), Some("CLIENT"));
+            assert_eq!(ack.get_u64(tag::MSG_SEQ_NUM), Some(1));
+        }
+
+        fn new_order(&mut self, id: &str, symbol: &str, side: u8, qty: u64, price: Option<&str>) {
+            self.send(order(id, symbol, side, qty, price));
+        }
+    }
+
+    fn order(id: &str, symbol: &str, side: u8, qty: u64, price: Option<&str>) -> Message {
+        let mut m = Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tag::CL_ORD_ID, id)
+            .with(tag::SYMBOL, symbol)
+            .with(tag::SIDE, side)
+            .with(tag::ORDER_QTY, qty)
+            .with(tag::ORD_TYPE, if price.is_some() { 2 } else { 1 })
+            .with(tag::TRANSACT_TIME, utc_timestamp());
+        if let Some(p) = price {
+            m.push(tag::PRICE, p);
+        }
+        m
+    }
+
+    fn start() -> (
+        SocketAddr,
+        Arc<AtomicBool>,
+        thread::JoinHandle<Market>,
+        String,
+    ) {
+        let participants = [
+            AgentType::MarketMaker,
+            AgentType::DumbLimit,
+            AgentType::DumbLimit,
+            AgentType::DumbMarket,
+        ];
+        let stocks = StockMarket::new();
+        let ticker = stocks.get_all_tickers()[0].clone();
+        let market = Market::with_seed(&participants, stocks, 7);
+        let cfg = FixConfig {
+            tick_interval: Duration::from_millis(2),
+            ..FixConfig::default()
+        };
+        let acceptor = FixAcceptor::bind("127.0.0.1:0", market, cfg).unwrap();
+        let addr = acceptor.local_addr().unwrap();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let flag = shutdown.clone();
+        let handle = thread::spawn(move || acceptor.run(&flag).unwrap());
+        (addr, shutdown, handle, ticker)
+    }
+
+    #[test]
+    fn session_admin_and_order_lifecycle() {
+        let (addr, shutdown, handle, ticker) = start();
+        let mut c = Client::connect(addr);
+        c.logon();
+
+        c.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, "ping"));
+        let hb = c.expect(msg_type::HEARTBEAT);
+        assert_eq!(hb.get(tag::TEST_REQ_ID), Some("ping"));
+
+        // a bid nobody will hit: New, then Replaced, then Canceled
+        c.new_order("o1", &ticker, 1, 100, Some("0.01"));
+        let new = c.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(new.get(tag::EXEC_TYPE), Some("0"));
+        assert_eq!(new.get(tag::CL_ORD_ID), Some("o1"));
+        assert_eq!(new.get_u64(tag::LEAVES_QTY), Some(100));
+
+        c.send(
+            Message::new(msg_type::ORDER_CANCEL_REPLACE_REQUEST)
+                .with(tag::ORIG_CL_ORD_ID, "o1")
+                .with(tag::CL_ORD_ID, "o2")
+                .with(tag::SYMBOL, &ticker)
+                .with(tag::SIDE, 1)
+                .with(tag::ORDER_QTY, 150)
+                .with(tag::ORD_TYPE, 2)
+                .with(tag::PRICE, "0.02"),
+        );
+        let replaced = c.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(replaced.get(tag::EXEC_TYPE), Some("5"));
+        assert_eq!(replaced.get(tag::ORIG_CL_ORD_ID), Some("o1"));
+        assert_eq!(replaced.get(tag::PRICE), Some("0.02"));
+        assert_eq!(replaced.get_u64(tag::ORDER_QTY), Some(150));
+
+        c.send(
+            Message::new(msg_type::ORDER_CANCEL_REQUEST)
+                .with(tag::ORIG_CL_ORD_ID, "o2")
+                .with(tag::CL_ORD_ID, "o3")
+                .with(tag::SYMBOL, &ticker)
+                .with(tag::SIDE, 1),
+        );
+        let cancelled = c.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(cancelled.get(tag::EXEC_TYPE), Some("4"));
+        assert_eq!(cancelled.get(tag::CL_ORD_ID), Some("o3"));
+        assert_eq!(cancelled.get(tag::ORDER_ID), replaced.get(tag::ORDER_ID));
+
+        // rejects: unknown order, unknown symbol, duplicate ClOrdID
+        c.send(
+            Message::new(msg_type::ORDER_CANCEL_REQUEST)
+                .with(tag::ORIG_CL_ORD_ID, "nope")
+                .with(tag::CL_ORD_ID, "o4"),
+        );
+        let rej = c.expect(msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(rej.get(tag::CXL_REJ_REASON), Some("1"));
+        c.new_order("o5", "NOSUCH", 1, 10, None);
+        let rej = c.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(
+            (rej.get(tag::EXEC_TYPE), rej.get(tag::ORD_REJ_REASON)),
+            (Some("8"), Some("1"))
+        );
+        c.new_order("o1", &ticker, 1, 10, Some("0.01"));
+        let rej = c.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(rej.get(tag::ORD_REJ_REASON), Some("6"));
+
+        // missing tag: session-level Reject
+        c.send(Message::new(msg_type::NEW_ORDER_SINGLE).with(tag::CL_ORD_ID, "o6"));
+        let rej = c.expect(msg_type::REJECT);
+        assert_eq!(rej.get_u64(tag::REF_TAG_ID), Some(tag::SYMBOL as u64));
+
+        // a resend request replays ExecutionReports and gap-fills the rest
+        let first_report = new.get_u64(tag::MSG_SEQ_NUM).unwrap();
+        c.send(
+            Message::new(msg_type::RESEND_REQUEST)
+                .with(tag::BEGIN_SEQ_NO, 1)
+                .with(tag::END_SEQ_NO, first_report),
+        );
+        let fill = c.expect(msg_type::SEQUENCE_RESET);
+        assert_eq!(fill.get_u64(tag::MSG_SEQ_NUM), Some(1));
+        assert_eq!(fill.get(tag::GAP_FILL_FLAG), Some("Y"));
+        assert_eq!(fill.get_u64(tag::NEW_SEQ_NO), Some(first_report));
+        let replay = c.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(replay.get_u64(tag::MSG_SEQ_NUM), Some(first_report));
+        assert_eq!(replay.get(tag::POSS_DUP_FLAG), Some("Y"));
+        assert_eq!(replay.get(tag::CL_ORD_ID), Some("o1"));
+        assert!(replay.get(tag::ORIG_SENDING_TIME).is_some());
+
+        // a sequence gap asks for a resend and holds what came after it
+        // until the client fills the gap
+        let gap = c.seq;
+        c.seq += 1;
+        c.new_order("g2", &ticker, 1, 10, Some("0.01"));
+        let resend = c.expect(msg_type::RESEND_REQUEST);
+        assert_eq!(resend.get_u64(tag::BEGIN_SEQ_NO), Some(gap));
+        c.seq = gap;
+        let mut dup = order("g1", &ticker, 1, 10, Some("0.01"));
+        dup.insert_header(tag::POSS_DUP_FLAG, "Y");
+        c.send(dup);
+        c.seq += 1;
+        for id in ["g1", "g2"] {
+            let r = c.expect(msg_type::EXECUTION_REPORT);
+            assert_eq!(r.get(tag::CL_ORD_ID), Some(id));
+            assert_eq!(r.get(tag::EXEC_TYPE), Some("0"));
+        }
+
+        // a gap filled by a SequenceReset releases what was held
+        let gap = c.seq;
+        c.seq += 2;
+        c.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, "held"));
+        c.expect(msg_type::RESEND_REQUEST);
+        c.seq = gap;
+        c.send(
+            Message::new(msg_type::SEQUENCE_RESET)
+                .with(tag::POSS_DUP_FLAG, "Y")
+                .with(tag::GAP_FILL_FLAG, "Y")
+                .with(tag::NEW_SEQ_NO, gap + 2),
+        );
+        c.seq = gap + 3;
+        let hb = c.expect(msg_type::HEARTBEAT);
+        assert_eq!(hb.get(tag::TEST_REQ_ID), Some("held"));
+
+        // a stale number without PossDupFlag logs us out
+        c.seq -= 2;
+        c.send(Message::new(msg_type::HEARTBEAT));
+        let out = c.expect(msg_type::LOGOUT);
+        assert!(out.get(tag::TEXT).unwrap().contains("too low"));
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn a_late_logon_is_answered_before_its_resend_request() {
+        let (addr, shutdown, handle, _) = start();
+        let mut c = Client::connect(addr);
+        c.seq = 5;
+        c.logon();
+        let resend = c.expect(msg_type::RESEND_REQUEST);
+        assert_eq!(resend.get(tag::TARGET_COMP_ID), Some("CLIENT"));
+        assert_eq!(resend.get_u64(tag::BEGIN_SEQ_NO), Some(1));
+
+        c.seq = 1;
+        let mut fill = Message::new(msg_type::SEQUENCE_RESET)
+            .with(tag::GAP_FILL_FLAG, "Y")
+            .with(tag::NEW_SEQ_NO, 5);
+        fill.insert_header(tag::POSS_DUP_FLAG, "Y");
+        c.send(fill);
+        c.seq = 6;
+        c.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, "after"));
+        let hb = c.expect(msg_type::HEARTBEAT);
+        assert_eq!(hb.get(tag::TEST_REQ_ID), Some("after"));
+
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn oversized_frames_cost_one_session_not_the_acceptor() {
+        let (addr, shutdown, handle, _) = start();
+        let mut bad = Client::connect(addr);
+        bad.stream
+            .write_all(b"8=FIX.4.4\x019=18446744073709551615\x0135=A\x01")
+            .unwrap();
+        let out = bad.expect(msg_type::LOGOUT);
+        assert!(out.get(tag::TEXT).unwrap().contains("garbled"), "{out:?}");
+
+        let mut good = Client::connect(addr);
+        good.logon();
+        shutdown.store(true, Ordering::SeqCst);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn the_inbox_stops_filling_at_its_cap() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut s = Session::new(stream, "SIMEX".into());
+        let writer = thread::spawn(move || {
+            // the reader stops draining, so this may not all get through
+            let _ = peer.write_all(&vec![b'x'; 2 * MAX_INBOX_LEN]);
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while s.inbox.len() < MAX_INBOX_LEN && Instant::now() < deadline {
+            assert!(s.read_available());
+        }
+        assert!(s.inbox.len() >= MAX_INBOX_LEN);
+        let full = s.inbox.len();
+        assert!(s.read_available());
+        assert_eq!(s.inbox.len(), full, "nothing more is read while full");
+        assert!(full < MAX_INBOX_LEN + 4096);
+        drop(s);
+        writer.join().unwrap();
+    }
+
+    /// Poll a few times so what the clients sent has arrived.
+    fn settle(acceptor: &mut FixAcceptor) {
+        for _ in 0..10 {
+            acceptor.poll().unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn a_replace_overtaken_by_fills_is_rejected_not_sent() {
+        let stocks = StockMarket::new();
+        let stock = &stocks.get_all_stocks()[0];
+        let (ticker, px) = (stock.ticker.clone(), format!("{:.2}", stock.initial_price));
+        let market = Market::with_seed(&[], stocks, 7);
+        let mut acceptor = FixAcceptor::bind("127.0.0.1:0", market, FixConfig::default()).unwrap();
+        let addr = acceptor.local_addr().unwrap();
+
+        // the taker's session is polled, and so ticked, first
+        let mut taker = Client::connect(addr);
+        settle(&mut acceptor);
+        let mut maker = Client::connect(addr);
+        settle(&mut acceptor);
+        for c in [&mut taker, &mut maker] {
+            c.send(
+                Message::new(msg_type::LOGON)
+                    .with(tag::ENCRYPT_METHOD, 0)
+                    .with(tag::HEART_BT_INT, 30),
+            );
+            settle(&mut acceptor);
+            c.expect(msg_type::LOGON);
+        }
+        maker.new_order("a1", &ticker, 2, 100, Some(&px));
+        settle(&mut acceptor);
+        acceptor.tick();
+        maker.expect(msg_type::EXECUTION_REPORT);
+
+        let replace = |orig: &str, id: &str, qty: u64| {
+            Message::new(msg_type::ORDER_CANCEL_REPLACE_REQUEST)
+                .with(tag::ORIG_CL_ORD_ID, orig)
+                .with(tag::CL_ORD_ID, id)
+                .with(tag::SYMBOL, &ticker)
+                .with(tag::SIDE, 2)
+                .with(tag::ORDER_QTY, qty)
+                .with(tag::ORD_TYPE, 2)
+                .with(tag::PRICE, &px)
+        };
+
+        // shrink to 60 while 70 trade in the same tick
+        maker.send(replace("a1", "a2", 60));
+        taker.new_order("b1", &ticker, 1, 70, Some(&px));
+        settle(&mut acceptor);
+        acceptor.tick();
+        let fill = maker.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(fill.get_u64(tag::CUM_QTY), Some(70));
+        let rej = maker.expect(msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(rej.get(tag::ORD_STATUS), Some("1"));
+        assert_eq!(rej.get(tag::CXL_REJ_RESPONSE_TO), Some("2"));
+
+        // the rest of the original 100 still rests, unchanged
+        taker.new_order("b2", &ticker, 1, 30, Some(&px));
+        settle(&mut acceptor);
+        acceptor.tick();
+        let fill = maker.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(fill.get_u64(tag::LAST_QTY), Some(30));
+        assert_eq!(fill.get(tag::ORD_STATUS), Some("2"));
+
+        // a replace of an order filled in the meantime is too late
+        maker.new_order("c1", &ticker, 2, 50, Some(&px));
+        settle(&mut acceptor);
+        acceptor.tick();
+        maker.expect(msg_type::EXECUTION_REPORT);
+        maker.send(replace("c1", "c2", 80));
+        taker.new_order("b3", &ticker, 1, 50, Some(&px));
+        settle(&mut acceptor);
+        acceptor.tick();
+        let fill = maker.expect(msg_type::EXECUTION_REPORT);
+        assert_eq!(fill.get(tag::ORD_STATUS), Some("2"));
+        let rej = maker.expect(msg_type::ORDER_CANCEL_REJECT);
+        assert_eq!(rej.get(tag::CXL_REJ_REASON), Some("0"));
+
+        let maker_id = acceptor.session_agents()[1];
+        let stock_id = acceptor
+            .market()
+            .stocks()
+            .get_stock_by_ticker(&ticker)
+            .unwrap()
+            .id;
+        assert_eq!(
+            acceptor.market().account(maker_id).unwrap().qty(stock_id),
+            -150
+        );
+        assert!(
+            acceptor
+                .market()
+                .order_book(stock_id)
+                .unwrap()
+                .best_bid_ask()
+                .1
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn fills_are_reported_and_the_session_is_an_agent() {
+        let (addr, shutdown, handle, ticker) = start();
+        let mut c = Client::connect(addr);
+        c.logon();
+
+        // keep buying at market until something fills
+        let mut filled = 0;
+        for i in 0..50 {
+            let id = format!("m{i}");
+            c.new_order(&id, &ticker, 1, 20, None);
+            let new = c.expect(msg_type::EXECUTION_REPORT);
+            assert_eq!(new.get(tag::EXEC_TYPE), Some("0"));
+            loop {
+                let r = c.expect(msg_type::EXECUTION_REPORT);
+                assert_eq!(r.get(tag::CL_ORD_ID), Some(id.as_str()));
+                match r.get(tag::EXEC_TYPE) {
+                    Some("F") => {
+                        let last = r.get_u64(tag::LAST_QTY).unwrap();
+                        filled += last;
+                        assert!(r.get_u64(tag::CUM_QTY).unwrap() <= 20);
+                        if r.get(tag::ORD_STATUS) == Some("2") {
+                            break;
+                        }
+                    }
+                    Some("4") => break, // unfilled remainder
+                    other => panic!("unexpected ExecType {other:?}"),
+                }
+            }
+            if filled > 0 {
+                break;
+            }
+        }
+        assert!(filled > 0, "a market order should fill against the makers");
+
+        c.send(Message::new(msg_type::LOGOUT));
+        c.expect(msg_type::LOGOUT);
+        shutdown.store(true, Ordering::SeqCst);
+        let market = handle.join().unwrap();
+
+        // the session traded as agent 4, next to the four built-ins
+        let account = market.account(4).expect("the session's ledger");
+        let stock = market.stocks().get_stock_by_ticker(&ticker).unwrap().id;
+        assert_eq!(account.qty(stock), filled as i64);
+    }
+}
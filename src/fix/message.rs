@@ -0,0 +1,346 @@
+// src/fix/message.rs
+//
+// Tag=value FIX codec: framing (BeginString, BodyLength, CheckSum), field
+// access and the UTC timestamps the header needs. Knows nothing about
+// sessions or orders.
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const SOH: u8 = 0x01;
+pub const BEGIN_STRING: &str = "FIX.4.4";
+
+/// Frames claiming a longer BodyLength are refused rather than waited for.
+pub const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// Tags the gateway reads or writes.
+pub mod tag {
+    pub const AVG_PX: u32 = 6;
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const CUM_QTY: u32 = 14;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const EXEC_ID: u32 = 17;
+    pub const LAST_PX: u32 = 31;
+    pub const LAST_QTY: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const LEAVES_QTY: u32 = 151;
+    pub const REF_TAG_ID: u32 = 371;
+    pub const SESSION_REJECT_REASON: u32 = 373;
+    pub const CXL_REJ_RESPONSE_TO: u32 = 434;
+}
+
+/// Message types the gateway handles.
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+    pub const ORDER_CANCEL_REPLACE_REQUEST: &str = "G";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixError {
+    /// Not `8=FIX.4.4|9=<len>|` at the start of a frame.
+    BadHeader,
+    BadChecksum {
+        expected: u8,
+        found: String,
+    },
+    /// A field without `=` or with a non-numeric tag.
+    BadField(String),
+    /// The body does not start with MsgType.
+    MissingMsgType,
+}
+
+impl fmt::Display for FixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixError::BadHeader => write!(f, "malformed BeginString/BodyLength"),
+            FixError::BadChecksum { expected, found } => {
+                write!(f, "checksum {found}, expected {expected:03}")
+            }
+            FixError::BadField(s) => write!(f, "malformed field {s:?}"),
+            FixError::MissingMsgType => write!(f, "MsgType(35) must open the body"),
+        }
+    }
+}
+
+impl std::error::Error for FixError {}
+
+/// One FIX message: its body fields in wire order, MsgType first. The
+/// framing fields (8, 9, 10) are added by `encode` and stripped by `parse`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    fields: Vec<(u32, String)>,
+}
+
+impl Message {
+    pub fn new(msg_type: &str) -> Self {
+        Self {
+            fields: vec![(tag::MSG_TYPE, msg_type.to_string())],
+        }
+    }
+
+    pub fn msg_type(&self) -> &str {
+        &self.fields[0].1
+    }
+
+    /// First value of `tag`.
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_u64(&self, tag: u32) -> Option<u64> {
+        self.get(tag)?.parse().ok()
+    }
+
+    pub fn with(mut self, tag: u32, value: impl ToString) -> Self {
+        self.push(tag, value);
+        self
+    }
+
+    pub fn push(&mut self, tag: u32, value: impl ToString) {
+        self.fields.push((tag, value.to_string()));
+    }
+
+    /// Put header fields right after MsgType, where FIX wants them.
+    pub fn insert_header(&mut self, tag: u32, value: impl ToString) {
+        let at = self
+            .fields
+            .iter()
+            .position(|(t, _)| !is_header(*t))
+            .unwrap_or(self.fields.len());
+        self.fields.insert(at, (tag, value.to_string()));
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.fields.iter().map(|(t, v)| (*t, v.as_str()))
+    }
+
+    /// Full wire form with BeginString, BodyLength and CheckSum.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (t, v) in &self.fields {
+            body.extend_from_slice(format!("{t}={v}").as_bytes());
+            body.push(SOH);
+        }
+        let mut out = format!("8={BEGIN_STRING}\x019={}\x01", body.len()).into_bytes();
+        out.extend_from_slice(&body);
+        let sum = checksum(&out);
+        out.extend_from_slice(format!("10={sum:03}\x01").as_bytes());
+        out
+    }
+
+    /// Parse one message from the front of `buf`. `Ok(None)` while the
+    /// frame is incomplete; otherwise the message and the bytes it used.
+    pub fn parse(buf: &[u8]) -> Result<Option<(Message, usize)>, FixError> {
+        // 8=FIX.4.4|9=NNN|
+        let begin = format!("8={BEGIN_STRING}\x019=");
+        let n = begin.len().min(buf.len());
+        if buf[..n] != begin.as_bytes()[..n] {
+            return Err(FixError::BadHeader);
+        }
+        if buf.len() < begin.len() {
+            return Ok(None);
+        }
+        let Some(len_end) = buf[begin.len()..].iter().position(|&b| b == SOH) else {
+            return if buf.len() - begin.len() > 10 {
+                Err(FixError::BadHeader)
+            } else {
+                Ok(None)
+            };
+        };
+        let body_len: usize = std::str::from_utf8(&buf[begin.len()..begin.len() + len_end])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&n| n <= MAX_BODY_LEN)
+            .ok_or(FixError::BadHeader)?;
+        let body_start = begin.len() + len_end + 1;
+        let trailer_start = body_start
+            .checked_add(body_len)
+            .ok_or(FixError::BadHeader)?;
+        // 10=NNN|
+        let end = trailer_start + 7;
+        if buf.len() < end {
+            return Ok(None);
+        }
+        let trailer = &buf[trailer_start..end];
+        if !trailer.starts_with(b"10=") || trailer[6] != SOH {
+            return Err(FixError::BadHeader);
+        }
+        let found = String::from_utf8_lossy(&trailer[3..6]).into_owned();
+        let expected = checksum(&buf[..trailer_start]);
+        if found.parse::<u8>().ok() != Some(expected) {
+            return Err(FixError::BadChecksum { expected, found });
+        }
+
+        let mut fields = Vec::new();
+        for raw in buf[body_start..trailer_start].split(|&b| b == SOH) {
+            if raw.is_empty() {
+                continue;
+            }
+            let s = String::from_utf8_lossy(raw);
+            let (t, v) = s
+                .split_once('=')
+                .and_then(|(t, v)| Some((t.parse::<u32>().ok()?, v)))
+                .ok_or_else(|| FixError::BadField(s.to_string()))?;
+            fields.push((t, v.to_string()));
+        }
+        if fields.first().map(|(t, _)| *t) != Some(tag::MSG_TYPE) {
+            return Err(FixError::MissingMsgType);
+        }
+        Ok(Some((Message { fields }, end)))
+    }
+}
+
+fn is_header(tag: u32) -> bool {
+    matches!(
+        tag,
+        tag::MSG_TYPE
+            | tag::SENDER_COMP_ID
+            | tag::TARGET_COMP_ID
+            | tag::MSG_SEQ_NUM
+            | tag::SENDING_TIME
+            | tag::POSS_DUP_FLAG
+            | tag::ORIG_SENDING_TIME
+    )
+}
+
+/// Sum of all bytes modulo 256.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |s, &b| s.wrapping_add(b))
+}
+
+/// Current UTC time as `YYYYMMDD-HH:MM:SS.sss`.
+pub fn utc_timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // civil-from-days (H. Hinnant)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}:{:02}:{:02}.{:03}",
+        rem / 3_600,
+        rem / 60 % 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_then_parse_round_trips() {
+        let mut m = Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tag::CL_ORD_ID, "abc")
+            .with(tag::SYMBOL, "AAPL")
+            .with(tag::ORDER_QTY, 100);
+        m.insert_header(tag::MSG_SEQ_NUM, 7);
+        let wire = m.encode();
+        let text = String::from_utf8_lossy(&wire).replace('\x01', "|");
+        assert!(text.starts_with("8=FIX.4.4|9="), "{text}");
+        assert!(text.contains("|35=D|34=7|11=abc|"), "{text}");
+
+        let (parsed, used) = Message::parse(&wire).unwrap().unwrap();
+        assert_eq!(used, wire.len());
+        assert_eq!(parsed, m);
+        assert_eq!(parsed.get_u64(tag::ORDER_QTY), Some(100));
+    }
+
+    #[test]
+    fn partial_frames_wait_and_corruption_is_reported() {
+        let wire = Message::new(msg_type::HEARTBEAT).encode();
+        for cut in [3, 12, wire.len() - 1] {
+            assert_eq!(Message::parse(&wire[..cut]), Ok(None), "cut at {cut}");
+        }
+        let mut two = wire.clone();
+        two.extend_from_slice(&wire);
+        assert_eq!(Message::parse(&two).unwrap().unwrap().1, wire.len());
+
+        let mut bad = wire.clone();
+        let i = bad.len() - 9; // inside the body
+        bad[i] = b'1';
+        assert!(matches!(
+            Message::parse(&bad),
+            Err(FixError::BadChecksum { .. })
+        ));
+        assert_eq!(Message::parse(b"8=FIX.4.2\x01"), Err(FixError::BadHeader));
+    }
+
+    #[test]
+    fn oversized_body_lengths_are_refused() {
+        for len in [usize::MAX, MAX_BODY_LEN + 1] {
+            let frame = format!("8=FIX.4.4\x019={len}\x0135=0\x01");
+            assert_eq!(
+                Message::parse(frame.as_bytes()),
+                Err(FixError::BadHeader),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn timestamps_have_the_fix_shape() {
+        let ts = utc_timestamp();
+        assert_eq!(ts.len(), 21, "{ts}");
+        assert_eq!(&ts[8..9], "-");
+        assert!(ts.starts_with("20"));
+    }
+}
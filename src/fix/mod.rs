@@ -0,0 +1,9 @@
+// src/fix/mod.rs
+//! FIX 4.4 order entry: a tag=value codec and a TCP acceptor that lets
+//! external clients trade against a `Market` as ordinary agents.
+
+pub mod acceptor;
+pub mod message;
+
+pub use acceptor::{FixAcceptor, FixConfig};
+pub use message::{FixError, Message};
@@ -0,0 +1,425 @@
+// src/fundamental.rs
+//! Latent fundamental values, one per stock, owned by a `Market` and stepped
+//! once per tick from a seeded stream (so forks and replays agree). Nobody
+//! trades on them directly: informed agents see a delayed copy through
+//! `MarketView::fundamental` and add their own noise, and the market records
+//! how far each book's mid strays from its value.
+
+use std::collections::{HashMap, VecDeque};
+
+use rand::{SeedableRng, rngs::StdRng};
+use rand_distr::{Distribution, Poisson, StandardNormal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corporate::CorporateAction,
+    simulators::correlated::{CorrelationError, cholesky},
+};
+
+/// Floor for a value, in dollars; a mean-reverting path can otherwise
+/// cross zero.
+const MIN_VALUE: f64 = 0.01;
+
+/// How a value evolves. Rates and volatilities are annualised; a tick lasts
+/// `FundamentalConfig::dt` years.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FundamentalProcess {
+    /// Geometric Brownian motion, as `GBMSimulator`.
+    Gbm { drift: f64, volatility: f64 },
+    /// Pulled back towards `mean` dollars (the starting value when `None`)
+    /// at rate `mean_reversion`; `volatility` is a fraction of the mean.
+    OrnsteinUhlenbeck {
+        mean_reversion: f64,
+        mean: Option<f64>,
+        volatility: f64,
+    },
+    /// GBM plus news: `intensity` jumps a year on average, each moving the
+    /// log-value by a normal with `jump_mean` and `jump_volatility`.
+    Jump {
+        drift: f64,
+        volatility: f64,
+        intensity: f64,
+        jump_mean: f64,
+        jump_volatility: f64,
+    },
+}
+
+impl FundamentalProcess {
+    /// One tick from `value`, given a standard normal `shock` (already
+    /// correlated across stocks) and `start`, the value the stock began at.
+    fn step(&self, value: f64, start: f64, shock: f64, dt: f64, rng: &mut StdRng) -> f64 {
+        let gbm = |drift: f64, vol: f64| {
+            value * ((drift - 0.5 * vol * vol) * dt + vol * dt.sqrt() * shock).exp()
+        };
+        let next = match *self {
+            FundamentalProcess::Gbm { drift, volatility } => gbm(drift, volatility),
+            FundamentalProcess::OrnsteinUhlenbeck {
+                mean_reversion,
+                mean,
+                volatility,
+            } => {
+                let mean = mean.unwrap_or(start);
+                value + mean_reversion * (mean - value) * dt + volatility * mean * dt.sqrt() * shock
+            }
+            FundamentalProcess::Jump {
+                drift,
+                volatility,
+                intensity,
+                jump_mean,
+                jump_volatility,
+            } => {
+                let mut next = gbm(drift, volatility);
+                // no rate, no jumps
+                let jumps = Poisson::new(intensity * dt).map_or(0.0, |p| p.sample(rng));
+                for _ in 0..jumps as u64 {
+                    let z: f64 = StandardNormal.sample(rng);
+                    next *= (jump_mean + jump_volatility * z).exp();
+                }
+                next
+            }
+        };
+        next.max(MIN_VALUE)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FundamentalConfig {
+    /// Every stock's process unless `Fundamentals::set_process` says otherwise.
+    pub process: FundamentalProcess,
+    /// Years per tick.
+    pub dt: f64,
+    /// Past values kept per stock, the longest delay a signal can have.
+    pub history: usize,
+}
+
+impl Default for FundamentalConfig {
+    /// 20 % a year, no drift, one trading minute per tick.
+    fn default() -> Self {
+        Self {
+            process: FundamentalProcess::Gbm {
+                drift: 0.0,
+                volatility: 0.2,
+            },
+            dt: 1.0 / (252.0 * 390.0),
+            history: 100,
+        }
+    }
+}
+
+/// Running statistics of mid minus fundamental value, in dollars.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiscoveryError {
+    /// The latest error; positive when the book trades rich.
+    pub last: f64,
+    pub samples: u64,
+    sum: f64,
+    sum_abs: f64,
+    sum_sq: f64,
+}
+
+impl DiscoveryError {
+    fn record(&mut self, error: f64) {
+        self.last = error;
+        self.samples += 1;
+        self.sum += error;
+        self.sum_abs += error.abs();
+        self.sum_sq += error * error;
+    }
+
+    /// Average signed error: the book's bias.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.samples.max(1) as f64
+    }
+
+    pub fn mean_abs(&self) -> f64 {
+        self.sum_abs / self.samples.max(1) as f64
+    }
+
+    pub fn rmse(&self) -> f64 {
+        (self.sum_sq / self.samples.max(1) as f64).sqrt()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Track {
+    process: FundamentalProcess,
+    start: f64,
+    value: f64,
+    /// Most recent first, the current value excluded.
+    past: VecDeque<f64>,
+    error: DiscoveryError,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fundamentals {
+    tracks: HashMap<u64, Track>,
+    ids: Vec<u64>,
+    default: FundamentalProcess,
+    dt: f64,
+    history: usize,
+    /// Stocks whose shocks are mixed, and the factor they are mixed through.
+    correlated: Option<(Vec<u64>, Vec<Vec<f64>>)>,
+    rng: StdRng,
+}
+
+impl Fundamentals {
+    /// Every stock starts at the given dollar value.
+    pub fn new(
+        starts: impl IntoIterator<Item = (u64, f64)>,
+        cfg: &FundamentalConfig,
+        seed: u64,
+    ) -> Self {
+        let mut f = Self {
+            tracks: HashMap::new(),
+            ids: Vec::new(),
+            default: cfg.process,
+            dt: cfg.dt,
+            history: cfg.history,
+            correlated: None,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        for (id, value) in starts {
+            f.insert(id, value);
+        }
+        f
+    }
+
+    /// Advance every stock one tick.
+    pub fn step(&mut self) {
+        let mut shocks: HashMap<u64, f64> = self
+            .ids
+            .iter()
+            .map(|&id| (id, StandardNormal.sample(&mut self.rng)))
+            .collect();
+        if let Some((ids, l)) = &self.correlated {
+            let z: Vec<f64> = ids.iter().map(|id| shocks[id]).collect();
+            for (i, id) in ids.iter().enumerate() {
+                shocks.insert(*id, (0..=i).map(|k| l[i][k] * z[k]).sum());
+            }
+        }
+        for id in &self.ids {
+            let t = self.tracks.get_mut(id).unwrap();
+            t.past.push_front(t.value);
+            t.past.truncate(self.history);
+            t.value = t
+                .process
+                .step(t.value, t.start, shocks[id], self.dt, &mut self.rng);
+        }
+    }
+
+    /// Current value in dollars.
+    pub fn value(&self, stock_id: u64) -> Option<f64> {
+        Some(self.tracks.get(&stock_id)?.value)
+    }
+
+    /// The value `ticks` ticks ago, or the oldest one kept.
+    pub fn lagged(&self, stock_id: u64, ticks: usize) -> Option<f64> {
+        let t = self.tracks.get(&stock_id)?;
+        Some(match ticks {
+            0 => t.value,
+            n => t
+                .past
+                .get(n - 1)
+                .or(t.past.back())
+                .copied()
+                .unwrap_or(t.value),
+        })
+    }
+
+    pub fn error(&self, stock_id: u64) -> Option<&DiscoveryError> {
+        Some(&self.tracks.get(&stock_id)?.error)
+    }
+
+    /// Record `mid` (dollars) against the current value.
+    pub fn record(&mut self, stock_id: u64, mid: f64) {
+        if let Some(t) = self.tracks.get_mut(&stock_id) {
+            t.error.record(mid - t.value);
+        }
+    }
+
+    /// Give `stock_id` its own process; `false` for an unknown stock.
+    pub fn set_process(&mut self, stock_id: u64, process: FundamentalProcess) -> bool {
+        self.tracks
+            .get_mut(&stock_id)
+            .map(|t| t.process = process)
+            .is_some()
+    }
+
+    /// Override one stock's value, e.g. to script news.
+    pub fn set(&mut self, stock_id: u64, value: f64) {
+        if let Some(t) = self.tracks.get_mut(&stock_id) {
+            t.value = value.max(MIN_VALUE);
+        }
+    }
+
+    /// Draw the shocks of `stock_ids` with `correlation[i][j]` between
+    /// them (see `CorrelatedGbm`); other stocks keep independent shocks.
+    pub fn correlate(
+        &mut self,
+        stock_ids: &[u64],
+        correlation: &[Vec<f64>],
+    ) -> Result<(), CorrelationError> {
+        for (i, id) in stock_ids.iter().enumerate() {
+            if !self.tracks.contains_key(id) {
+                return Err(CorrelationError::UnknownStock(*id));
+            }
+            if stock_ids[..i].contains(id) {
+                return Err(CorrelationError::DuplicateStock(*id));
+            }
+        }
+        if correlation.len() != stock_ids.len() {
+            return Err(CorrelationError::Dimension {
+                expected: stock_ids.len(),
+                found: correlation.len(),
+            });
+        }
+        self.correlated = Some((stock_ids.to_vec(), cholesky(correlation)?));
+        Ok(())
+    }
+
+    /// Start tracking a newly listed stock at `value` dollars.
+    pub fn insert(&mut self, stock_id: u64, value: f64) {
+        let track = Track {
+            process: self.default,
+            start: value,
+            value,
+            past: VecDeque::with_capacity(self.history),
+            error: DiscoveryError::default(),
+        };
+        if self.tracks.insert(stock_id, track).is_none() {
+            self.ids.push(stock_id);
+            self.ids.sort_unstable();
+        }
+    }
+
+    /// Stop tracking `stock_id` (a delisting); it also leaves any
+    /// correlated set, which then draws independently.
+    pub fn remove(&mut self, stock_id: u64) {
+        if self.tracks.remove(&stock_id).is_some() {
+            self.ids.retain(|&id| id != stock_id);
+            if self
+                .correlated
+                .as_ref()
+                .is_some_and(|(ids, _)| ids.contains(&stock_id))
+            {
+                self.correlated = None;
+            }
+        }
+    }
+
+    /// Restate a value and its history in post-action dollars.
+    pub fn apply_corporate_action(&mut self, action: &CorporateAction) {
+        if let Some(t) = self.tracks.get_mut(&action.stock_id()) {
+            t.value = action.price(t.value).max(MIN_VALUE);
+            t.start = action.price(t.start).max(MIN_VALUE);
+            for v in t.past.iter_mut() {
+                *v = action.price(*v).max(MIN_VALUE);
+            }
+        }
+    }
+
+    /// Restart the random stream, keeping current values.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Every stock back to where it started, history and errors cleared.
+    pub fn reset(&mut self, seed: u64) {
+        for t in self.tracks.values_mut() {
+            t.value = t.start;
+            t.past.clear();
+            t.error = DiscoveryError::default();
+        }
+        self.reseed(seed);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(process: FundamentalProcess, ticks: usize) -> Fundamentals {
+        let cfg = FundamentalConfig {
+            process,
+            dt: 1.0 / 252.0,
+            history: 5,
+        };
+        let mut f = Fundamentals::new([(1, 100.0), (2, 50.0)], &cfg, 3);
+        for _ in 0..ticks {
+            f.step();
+        }
+        f
+    }
+
+    #[test]
+    fn processes_step_reproducibly_and_keep_history() {
+        let gbm = FundamentalProcess::Gbm {
+            drift: 0.0,
+            volatility: 0.3,
+        };
+        let (a, b) = (run(gbm, 10), run(gbm, 10));
+        assert_eq!(a.value(1), b.value(1));
+        assert_ne!(a.value(1), Some(100.0));
+        // five past values kept; asking further back gets the oldest
+        assert_eq!(a.lagged(1, 5), a.lagged(1, 50));
+        assert_ne!(a.lagged(1, 1), a.lagged(1, 0));
+
+        // a strong pull keeps the value near its mean
+        let ou = FundamentalProcess::OrnsteinUhlenbeck {
+            mean_reversion: 50.0,
+            mean: None,
+            volatility: 0.1,
+        };
+        let f = run(ou, 2_000);
+        assert!((f.value(2).unwrap() - 50.0).abs() < 5.0);
+
+        // two jumps a tick on average, +1 % in log each, no diffusion: the
+        // log-value counts them
+        let jump = FundamentalProcess::Jump {
+            drift: 0.0,
+            volatility: 0.0,
+            intensity: 2.0 * 252.0,
+            jump_mean: 0.01,
+            jump_volatility: 0.0,
+        };
+        let f = run(jump, 100);
+        let jumps = (f.value(1).unwrap() / 100.0).ln() / 0.01;
+        assert!((jumps - jumps.round()).abs() < 1e-6, "{jumps} jumps");
+        // Poisson with mean 200: well within five standard deviations
+        assert!((jumps - 200.0).abs() < 5.0 * 200f64.sqrt(), "{jumps} jumps");
+    }
+
+    #[test]
+    fn correlated_shocks_and_error_stats() {
+        let mut f = run(
+            FundamentalProcess::Gbm {
+                drift: 0.0,
+                volatility: 0.2,
+            },
+            0,
+        );
+        assert!(
+            f.correlate(&[1, 9], &[vec![1.0, 0.0], vec![0.0, 1.0]])
+                .is_err()
+        );
+        f.correlate(&[1, 2], &[vec![1.0, 1.0], vec![1.0, 1.0]])
+            .unwrap();
+        for _ in 0..20 {
+            f.step();
+        }
+        // perfectly correlated and equally volatile: the same relative path
+        let ratio = f.value(1).unwrap() / f.value(2).unwrap();
+        assert!((ratio - 2.0).abs() < 1e-9);
+
+        f.set(1, 100.0);
+        f.record(1, 101.0);
+        f.record(1, 97.0);
+        let e = f.error(1).unwrap();
+        assert_eq!((e.last, e.samples), (-3.0, 2));
+        assert!((e.mean() + 1.0).abs() < 1e-12 && (e.mean_abs() - 2.0).abs() < 1e-12);
+        assert!((e.rmse() - 5f64.sqrt()).abs() < 1e-12);
+    }
+}
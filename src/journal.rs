@@ -0,0 +1,622 @@
+// src/journal.rs
+//! Append-only event journal for `Market::step`, plus a replayer that
+//! rebuilds order-book state from the journal without running any agents.
+//!
+//! The on-disk format is JSON Lines: one [`JournalEvent`] per line.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corporate::CorporateAction,
+    simulators::order_book::{LevelQuote, OrderBook},
+    types::order::{Order, OrderRequest, Side, Trade},
+};
+
+// -----------------------------------------------------------------------------
+//  Events
+// -----------------------------------------------------------------------------
+/// One line of the journal. `tick` is the step the event happened in;
+/// the first call to `Market::step` is tick 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JournalEvent {
+    /// Start of a session: the books that exist from here on, all empty.
+    /// Written when a journal is attached and whenever the market resets.
+    Open { tick: u64, stock_ids: Vec<u64> },
+    /// An order already resting in a book when the journal was attached,
+    /// emitted in price-time priority right after `Open`; or the unfilled
+    /// rest of an opening-auction order, right after its `Uncross`.
+    Resting { tick: u64, order: Order },
+    /// A new, empty book opened mid-session.
+    Listed { tick: u64, stock_id: u64 },
+    /// An opening auction crossed. Its prints are recorded here rather than
+    /// as `Trade`s because no journalled request produced them.
+    Uncross {
+        tick: u64,
+        stock_id: u64,
+        price: Option<u64>,
+        trades: Vec<Trade>,
+    },
+    /// The book closed; every order still in it was cancelled.
+    Delisted { tick: u64, stock_id: u64 },
+    /// A corporate action restated the book as the tick started.
+    CorporateAction { tick: u64, action: CorporateAction },
+    /// An inbound request and the order id the engine assigned to it
+    /// (`None` for cancels and forced margin-call orders).
+    Request {
+        tick: u64,
+        order_id: Option<u64>,
+        request: OrderRequest,
+    },
+    /// A fill produced by the preceding request.
+    Trade { tick: u64, trade: Trade },
+    /// Outcome of a cancel request.
+    Cancel {
+        tick: u64,
+        agent_id: usize,
+        order_id: u64,
+        success: bool,
+    },
+}
+
+impl JournalEvent {
+    pub fn tick(&self) -> u64 {
+        match self {
+            JournalEvent::Open { tick, .. }
+            | JournalEvent::Resting { tick, .. }
+            | JournalEvent::Listed { tick, .. }
+            | JournalEvent::Uncross { tick, .. }
+            | JournalEvent::Delisted { tick, .. }
+            | JournalEvent::CorporateAction { tick, .. }
+            | JournalEvent::Request { tick, .. }
+            | JournalEvent::Trade { tick, .. }
+            | JournalEvent::Cancel { tick, .. } => *tick,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Writer
+// -----------------------------------------------------------------------------
+/// Append-only JSON Lines sink the market writes into while it steps.
+pub struct Journal {
+    writer: Box<dyn Write + Send>,
+}
+
+impl Journal {
+    /// Journal into any writer (a file, a socket, an in-memory buffer…).
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Box::new(BufWriter::new(writer)),
+        }
+    }
+
+    /// Open `path` in append mode, creating it if needed.
+    pub fn append_to<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+
+    pub fn record(&mut self, event: &JournalEvent) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Replayer
+// -----------------------------------------------------------------------------
+/// A difference between replayed and reference state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayMismatch {
+    /// The replayed book produced a different fill than the one journalled.
+    Trade {
+        index: usize,
+        recorded: Option<String>,
+        replayed: Option<String>,
+    },
+    /// A cancel succeeded on one side and failed on the other.
+    Cancel {
+        order_id: u64,
+        recorded: bool,
+        replayed: bool,
+    },
+    /// A book exists on one side only.
+    MissingBook { stock_id: u64 },
+    /// A price level differs in volume or queue contents.
+    Level {
+        stock_id: u64,
+        side: Side,
+        price: u64,
+        /// `(total_volume, [(order_id, remaining)])`, `None` when absent.
+        live: Option<LevelSummary>,
+        replayed: Option<LevelSummary>,
+    },
+}
+
+pub type LevelSummary = (u64, Vec<(u64, u64)>);
+
+/// Rebuilds `OrderBook` state purely from journalled requests.
+#[derive(Debug, Clone, Default)]
+pub struct Replayer {
+    events: Vec<JournalEvent>,
+}
+
+impl Replayer {
+    pub fn new(events: Vec<JournalEvent>) -> Self {
+        Self { events }
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut events = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let ev = serde_json::from_str(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            events.push(ev);
+        }
+        Ok(Self { events })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn events(&self) -> &[JournalEvent] {
+        &self.events
+    }
+
+    /// Last tick present in the journal.
+    pub fn last_tick(&self) -> u64 {
+        self.events.last().map_or(0, JournalEvent::tick)
+    }
+
+    /// Books as they stood at the end of tick `tick`.
+    pub fn rebuild_at(&self, tick: u64) -> HashMap<u64, OrderBook> {
+        self.run(Some(tick), &mut Vec::new())
+    }
+
+    /// Books as they stood at the end of the journal.
+    pub fn rebuild(&self) -> HashMap<u64, OrderBook> {
+        self.run(None, &mut Vec::new())
+    }
+
+    /// Replay the whole journal, check every fill and cancel outcome against
+    /// what was recorded, then diff the final books against `live`.
+    pub fn verify(&self, live: &HashMap<u64, OrderBook>) -> Result<(), Vec<ReplayMismatch>> {
+        let mut mismatches = Vec::new();
+        let replayed = self.run(None, &mut mismatches);
+        mismatches.extend(diff_books(live, &replayed));
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    fn run(
+        &self,
+        until: Option<u64>,
+        mismatches: &mut Vec<ReplayMismatch>,
+    ) -> HashMap<u64, OrderBook> {
+        let mut books: HashMap<u64, OrderBook> = HashMap::new();
+        let mut recorded = Vec::<Trade>::new();
+        let mut replayed = Vec::<Trade>::new();
+        let mut cancels = HashMap::<u64, bool>::new();
+
+        for ev in &self.events {
+            if until.is_some_and(|t| ev.tick() > t) {
+                break;
+            }
+            match ev {
+                JournalEvent::Open { stock_ids, .. } => {
+                    compare_trades(&recorded, &replayed, mismatches);
+                    recorded.clear();
+                    replayed.clear();
+                    books = stock_ids.iter().map(|&id| (id, OrderBook::new())).collect();
+                }
+                JournalEvent::Resting { order, .. } => {
+                    if let Some(book) = books.get_mut(&order.stock_id) {
+                        let mut o = *order;
+                        book.process_limit_order(&mut o);
+                    }
+                }
+                JournalEvent::Listed { stock_id, .. } => {
+                    books.insert(*stock_id, OrderBook::new());
+                }
+                JournalEvent::Uncross { .. } => {}
+                JournalEvent::Delisted { stock_id, .. } => {
+                    books.remove(stock_id);
+                }
+                JournalEvent::CorporateAction { action, .. } => {
+                    if let Some(book) = books.get_mut(&action.stock_id()) {
+                        *book = book.adjusted(book.kind(), |o| action.adjust_order(o)).0;
+                    }
+                }
+                JournalEvent::Request {
+                    order_id, request, ..
+                } => match *request {
+                    OrderRequest::LimitOrder {
+                        agent_id,
+                        stock_id,
+                        side,
+                        price,
+                        volume,
+                    } => {
+                        let mut o = Order {
+                            id: order_id.unwrap_or_default(),
+                            agent_id,
+                            stock_id,
+                            side,
+                            price,
+                            volume,
+                            filled: 0,
+                        };
+                        if let Some(book) = books.get_mut(&stock_id) {
+                            replayed.extend(book.process_limit_order(&mut o));
+                        }
+                    }
+                    OrderRequest::MarketOrder {
+                        agent_id,
+                        stock_id,
+                        side,
+                        volume,
+                    } => {
+                        if let Some(book) = books.get_mut(&stock_id) {
+                            replayed.extend(book.process_market_order(agent_id, side, volume));
+                        }
+                    }
+                    OrderRequest::CancelOrder { agent_id, order_id } => {
+                        let ok = books
+                            .values_mut()
+                            .any(|book| book.cancel_order(order_id, agent_id));
+                        cancels.insert(order_id, ok);
+                    }
+                    OrderRequest::ModifyOrder {
+                        agent_id,
+                        order_id: old_id,
+                        price,
+                        volume,
+                    } => {
+                        if let Some(book) =
+                            books.values_mut().find(|book| book.order(old_id).is_some())
+                        {
+                            let new_id = order_id.unwrap_or_default();
+                            replayed.extend(
+                                book.modify_order(old_id, agent_id, price, volume, new_id)
+                                    .unwrap_or_default(),
+                            );
+                        }
+                    }
+                    // settled off-book
+                    OrderRequest::CreateUnits { .. } | OrderRequest::RedeemUnits { .. } => {}
+                },
+                JournalEvent::Trade { trade, .. } => recorded.push(*trade),
+                JournalEvent::Cancel {
+                    order_id, success, ..
+                } => {
+                    let ok = cancels.remove(order_id).unwrap_or(false);
+                    if ok != *success {
+                        mismatches.push(ReplayMismatch::Cancel {
+                            order_id: *order_id,
+                            recorded: *success,
+                            replayed: ok,
+                        });
+                    }
+                }
+            }
+        }
+        compare_trades(&recorded, &replayed, mismatches);
+        books
+    }
+}
+
+fn compare_trades(recorded: &[Trade], replayed: &[Trade], out: &mut Vec<ReplayMismatch>) {
+    let n = recorded.len().max(replayed.len());
+    for i in 0..n {
+        let (r, p) = (recorded.get(i), replayed.get(i));
+        let same = match (r, p) {
+            (Some(a), Some(b)) => {
+                a.price == b.price
+                    && a.stock_id == b.stock_id
+                    && a.volume == b.volume
+                    && a.taker_agent_id == b.taker_agent_id
+                    && a.maker_agent_id == b.maker_agent_id
+                    && a.taker_side == b.taker_side
+                    && a.maker_order_id == b.maker_order_id
+            }
+            _ => false,
+        };
+        if !same {
+            out.push(ReplayMismatch::Trade {
+                index: i,
+                recorded: r.map(|t| format!("{t:?}")),
+                replayed: p.map(|t| format!("{t:?}")),
+            });
+        }
+    }
+}
+
+/// Level-by-level comparison of two sets of books.
+pub fn diff_books(
+    live: &HashMap<u64, OrderBook>,
+    replayed: &HashMap<u64, OrderBook>,
+) -> Vec<ReplayMismatch> {
+    let mut out = Vec::new();
+    let mut ids: Vec<u64> = live.keys().chain(replayed.keys()).copied().collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    for id in ids {
+        let (Some(a), Some(b)) = (live.get(&id), replayed.get(&id)) else {
+            out.push(ReplayMismatch::MissingBook { stock_id: id });
+            continue;
+        };
+        let (da, db) = (a.depth(usize::MAX), b.depth(usize::MAX));
+        for (side, la, lb) in [
+            (Side::Buy, &da.bids, &db.bids),
+            (Side::Sell, &da.asks, &db.asks),
+        ] {
+            let mut prices: Vec<u64> = la.iter().chain(lb.iter()).map(|q| q.price).collect();
+            prices.sort_unstable();
+            prices.dedup();
+            for px in prices {
+                let summarise = |book: &OrderBook, quotes: &[LevelQuote]| {
+                    let q = quotes.iter().find(|q| q.price == px)?;
+                    let queue = book
+                        .orders_at(px)
+                        .iter()
+                        .map(|o| (o.id, o.volume.saturating_sub(o.filled)))
+                        .collect::<Vec<_>>();
+                    Some((q.volume, queue))
+                };
+                let sa = summarise(a, la);
+                let sb = summarise(b, lb);
+                if sa != sb {
+                    out.push(ReplayMismatch::Level {
+                        stock_id: id,
+                        side,
+                        price: px,
+                        live: sa,
+                        replayed: sb,
+                    });
+                }
+            }
+        }
+    }
+    out
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AgentType, Listing, Market, Marketable,
+        stocks::definitions::{Stock, StockMarket},
+    };
+    use std::sync::{Arc, Mutex};
+
+    /* in-memory sink we can read back after the market is done with it */
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl SharedBuf {
+        fn replayer(&self) -> Replayer {
+            Replayer::from_reader(&self.0.lock().unwrap()[..]).unwrap()
+        }
+    }
+
+    const PARTICIPANTS: &[AgentType] = &[
+        AgentType::MarketMaker,
+        AgentType::DumbLimit,
+        AgentType::DumbMarket,
+        AgentType::WhaleAgent,
+    ];
+
+    fn journalled_market(seed: u64) -> (Market, SharedBuf) {
+        let buf = SharedBuf::default();
+        let mut m = Market::with_seed(PARTICIPANTS, StockMarket::new(), seed);
+        m.attach_journal(Journal::new(buf.clone()));
+        (m, buf)
+    }
+
+    #[test]
+    fn replay_matches_live_books() {
+        let (mut m, buf) = journalled_market(5);
+        for _ in 0..60 {
+            m.step();
+        }
+        m.flush_journal().unwrap();
+        let replayer = buf.replayer();
+        assert_eq!(replayer.last_tick(), 60);
+        assert_eq!(replayer.verify(m.order_books()), Ok(()));
+    }
+
+    #[test]
+    fn replay_follows_listings_and_delistings() {
+        let (mut m, buf) = journalled_market(23);
+        for _ in 0..20 {
+            m.step();
+        }
+        let ipo = Stock::new("IPO", 777, "Fresh Listing", 5_000_000, 40.0);
+        m.list_stock(ipo, Listing::Auction { ticks: 3 }).unwrap();
+        let gone = m.stocks().get_all_ids()[0];
+        m.delist_stock(gone, None).unwrap();
+        for _ in 0..30 {
+            m.step();
+        }
+        m.flush_journal().unwrap();
+        let replayer = buf.replayer();
+        assert!(
+            replayer
+                .events()
+                .iter()
+                .any(|e| matches!(e, JournalEvent::Uncross { stock_id: 777, .. }))
+        );
+        assert_eq!(replayer.verify(m.order_books()), Ok(()));
+    }
+
+    #[test]
+    fn replay_applies_corporate_actions() {
+        let (mut m, buf) = journalled_market(31);
+        let stock_id = m.stocks().get_all_ids()[0];
+        let actions = [
+            (
+                15,
+                CorporateAction::Split {
+                    stock_id,
+                    new_shares: 3,
+                    old_shares: 2,
+                },
+            ),
+            (
+                25,
+                CorporateAction::CashDividend {
+                    stock_id,
+                    amount: 0.4,
+                },
+            ),
+        ];
+        for (tick, action) in actions {
+            m.schedule_corporate_action(tick, action).unwrap();
+        }
+        for _ in 0..40 {
+            m.step();
+        }
+        m.flush_journal().unwrap();
+        assert_eq!(buf.replayer().verify(m.order_books()), Ok(()));
+    }
+
+    #[test]
+    fn rebuild_as_of_earlier_tick() {
+        let (mut m, buf) = journalled_market(9);
+        for _ in 0..30 {
+            m.step();
+        }
+        let at_30 = m.order_books().clone();
+        for _ in 0..30 {
+            m.step();
+        }
+        m.flush_journal().unwrap();
+        let replayed = buf.replayer().rebuild_at(30);
+        assert!(diff_books(&at_30, &replayed).is_empty());
+        assert!(!diff_books(m.order_books(), &replayed).is_empty());
+    }
+
+    #[test]
+    fn attaching_mid_run_snapshots_resting_orders() {
+        let buf = SharedBuf::default();
+        let mut m = Market::with_seed(PARTICIPANTS, StockMarket::new(), 13);
+        for _ in 0..25 {
+            m.step();
+        }
+        m.attach_journal(Journal::new(buf.clone()));
+        for _ in 0..25 {
+            m.step();
+        }
+        m.flush_journal().unwrap();
+        assert_eq!(buf.replayer().verify(m.order_books()), Ok(()));
+    }
+
+    #[test]
+    fn verify_reports_tampered_state() {
+        let (mut m, buf) = journalled_market(17);
+        for _ in 0..30 {
+            m.step();
+        }
+        m.flush_journal().unwrap();
+        let mut live = m.order_books().clone();
+        let book = live.values_mut().find(|b| b.best_bid().is_some()).unwrap();
+        let mut extra = Order {
+            id: u64::MAX,
+            agent_id: 0,
+            stock_id: 0,
+            side: Side::Buy,
+            price: 1,
+            volume: 1,
+            filled: 0,
+        };
+        book.process_limit_order(&mut extra);
+
+        let err = buf.replayer().verify(&live).unwrap_err();
+        assert!(matches!(
+            err[0],
+            ReplayMismatch::Level {
+                side: Side::Buy,
+                price: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn events_roundtrip_as_json_lines() {
+        let ev = JournalEvent::Request {
+            tick: 3,
+            order_id: Some(42),
+            request: OrderRequest::LimitOrder {
+                agent_id: 1,
+                stock_id: 2,
+                side: Side::Sell,
+                price: 10_100,
+                volume: 7,
+            },
+        };
+        let buf = SharedBuf::default();
+        let mut j = Journal::new(buf.clone());
+        j.record(&ev).unwrap();
+        j.record(&ev).unwrap();
+        j.flush().unwrap();
+        let r = buf.replayer();
+        assert_eq!(r.events().len(), 2);
+        assert_eq!(r.events()[1].tick(), 3);
+    }
+
+    /* sink whose disk is full */
+    struct FullDisk;
+    impl Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("no space left"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_errors_stop_recording_and_surface() {
+        let mut m = Market::with_seed(PARTICIPANTS, StockMarket::new(), 3);
+        m.attach_journal(Journal::new(FullDisk));
+        m.step();
+        assert!(m.journal_error().is_some());
+        let err = m.detach_journal().err().unwrap();
+        assert_eq!(err.to_string(), "no space left");
+        // reported once; nothing is attached any more
+        assert!(m.flush_journal().is_ok());
+        assert!(m.detach_journal().unwrap().is_none());
+    }
+}
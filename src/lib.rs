@@ -2,9 +2,17 @@
 
 // === 1. Declare all the top-level modules ===
 pub mod agents;
+pub mod corporate;
+pub mod fix;
+pub mod fundamental;
+pub mod journal;
+pub mod live;
 pub mod market;
 pub mod pricing;
+pub mod rl;
+pub mod scenario;
 pub mod sentiment;
+pub mod server;
 pub mod shared_types;
 pub mod simulators;
 pub mod stocks;
@@ -13,32 +21,57 @@ pub mod types;
 
 // --- From `agents` ---
 pub use agents::agent_trait::{Agent, MarketView};
-pub use agents::agent_type::AgentType; // <-- EXPORT THE NEW ENUM
+pub use agents::agent_type::{AgentParams, AgentType}; // <-- EXPORT THE NEW ENUM
 pub use agents::dumb_agent::DumbAgent;
 pub use agents::dumb_limit_agent::DumbLimitAgent;
+pub use agents::etf_arb_agent::EtfArbAgent;
+pub use agents::event_agent::{EventAgent, EventDriven, Fill, OpenOrders, Role};
+pub use agents::informed_agent::InformedAgent;
 pub use agents::ipo_agent::IpoAgent;
 pub use agents::market_maker_agent::MarketMakerAgent;
+pub use agents::portfolio::{Portfolio, Position};
 pub use agents::whale_agent::WhaleAgent;
 
 // --- From our `market` engine ---
-pub use market::Market;
+pub use corporate::{CorporateAction, CorporateActionError};
+pub use fix::{FixAcceptor, FixConfig};
+pub use fundamental::{DiscoveryError, FundamentalConfig, FundamentalProcess, Fundamentals};
+pub use journal::{Journal, JournalEvent, Replayer};
+pub use live::{ExecReport, LiveConfig, LiveHandle};
+pub use market::{Delisting, Listing, Market};
+
+// --- From `rl` ---
+pub use rl::{
+    Action, Env, EnvConfig, Feature, FeatureExtractor, FeatureSet, Observation, RewardFn, StepInfo,
+};
+
+// --- From `scenario` ---
+pub use scenario::{RunSummary, Scenario, ScenarioError};
 
 // --- From `simulators` ---
+pub use simulators::auction::CallAuction;
+pub use simulators::correlated::{Asset, CorrelatedGbm, CorrelationError};
+pub use simulators::feed::{BookBuilder, FeedError, FeedEvent, FeedMessage};
 pub use simulators::gbm::GBMSimulator;
+pub use simulators::levels::{BookKind, PriceLevels};
 pub use simulators::market_trait::Marketable;
-pub use simulators::order_book::OrderBook;
+pub use simulators::order_book::{BookDepth, ImpactFill, LevelQuote, MarketImpact, OrderBook};
+pub use simulators::tape::{Print, StockStats, TradeTape};
 
 // --- From `pricing` ---
 pub use pricing::{Greeks, OptionPricer};
 
 // --- From `types` ---
-pub use types::order::{Order, OrderRequest, Side, Trade};
+pub use types::order::{Order, OrderRequest, RejectReason, Side, Trade};
 //pub use types::order::{Order, OrderRequest, Side};
 // --- From `shared_types` ---
 pub use shared_types::OptionType;
 
 // --- From `stocks` ---
-pub use stocks::{Stock, StockMarket, Symbol, default_stock_universe};
+pub use stocks::{
+    Basket, Constituent, Stock, StockMarket, StockMarketBuilder, Symbol, UnitSettlement,
+    UniverseError, default_stock_universe,
+};
 
 // --- From 'sentiment' ---
-pub use sentiment::{SentimentConfig, get as get_sentiment, init as init_sentiment};
+pub use sentiment::{Sentiment, SentimentConfig, get as get_sentiment, init as init_sentiment};
@@ -0,0 +1,445 @@
+// src/live.rs
+//
+// Live (free-running) mode. The `Market` moves onto a matching thread that
+// drains `OrderRequest`s from one channel; every agent runs on its own thread
+// with a private incremental feed and a channel of execution reports. The
+// same `Agent` implementations drive both this and lock-step `step()`, but
+// live runs are not reproducible: thread scheduling decides the order flow.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    Agent, Market, MarketView,
+    corporate::CorporateAction,
+    simulators::feed::{BookBuilder, FeedMessage},
+    stocks::{
+        basket::UnitSettlement,
+        definitions::{Stock, StockMarket},
+    },
+    types::{Order, OrderRequest, RejectReason, Trade},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct LiveConfig {
+    /// Pause between two decisions of the same agent.
+    pub agent_interval: Duration,
+    /// How long the matching thread waits for orders before re-checking
+    /// for shutdown.
+    pub poll_timeout: Duration,
+}
+
+impl Default for LiveConfig {
+    fn default() -> Self {
+        Self {
+            agent_interval: Duration::from_millis(5),
+            poll_timeout: Duration::from_millis(20),
+        }
+    }
+}
+
+/// What the exchange tells one agent about its own orders.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecReport {
+    /// The order as the exchange booked it (id assigned).
+    Ack(Order),
+    /// Signed position change from one trade.
+    Fill {
+        delta: i64,
+        trade: Trade,
+    },
+    /// A cancel took the order off the book.
+    Cancelled(u64),
+    Rejected(OrderRequest, RejectReason),
+    /// A stock joined the universe.
+    Listed(Stock),
+    /// The stock stopped trading; positions settled at `final_price` cents.
+    Delisted {
+        stock_id: u64,
+        final_price: u64,
+    },
+    /// A corporate action took effect; the book was restated.
+    CorporateAction(CorporateAction),
+    /// A creation or redemption went through.
+    UnitsSettled(UnitSettlement),
+}
+
+// -----------------------------------------------------------------------------
+//  Exchange-side stand-in for an agent living on another thread
+// -----------------------------------------------------------------------------
+/// Sits in `Market::agents` while the real agent runs elsewhere and turns the
+/// engine's callbacks into `ExecReport`s.
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteAgent {
+    pub(crate) id: usize,
+    pub(crate) reports: Sender<ExecReport>,
+}
+
+impl Agent for RemoteAgent {
+    fn decide_actions(&mut self, _view: &MarketView) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn buy_stock(&mut self, _stock_id: u64, _volume: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn sell_stock(&mut self, _stock_id: u64, _volume: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn acknowledge_order(&mut self, order: Order) {
+        let _ = self.reports.send(ExecReport::Ack(order));
+    }
+    fn cancel_acknowledged(&mut self, order_id: u64) {
+        let _ = self.reports.send(ExecReport::Cancelled(order_id));
+    }
+    fn order_rejected(&mut self, request: &OrderRequest, reason: RejectReason) {
+        let _ = self
+            .reports
+            .send(ExecReport::Rejected(request.clone(), reason));
+    }
+    fn stock_listed(&mut self, stock: &Stock) {
+        let _ = self.reports.send(ExecReport::Listed(stock.clone()));
+    }
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        let _ = self.reports.send(ExecReport::Delisted {
+            stock_id,
+            final_price,
+        });
+    }
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        let _ = self
+            .reports
+            .send(ExecReport::CorporateAction(action.clone()));
+    }
+    fn units_settled(&mut self, settlement: &UnitSettlement) {
+        let _ = self
+            .reports
+            .send(ExecReport::UnitsSettled(settlement.clone()));
+    }
+    fn margin_call(&mut self) -> Vec<OrderRequest> {
+        vec![] // the agent thread runs its own margin checks
+    }
+    fn update_portfolio(&mut self, delta: i64, trade: &Trade) {
+        let _ = self.reports.send(ExecReport::Fill {
+            delta,
+            trade: *trade,
+        });
+    }
+    fn evaluate_port(&mut self, _view: &MarketView) -> f64 {
+        0.0
+    }
+    fn get_pending_orders(&self) -> Vec<Order> {
+        vec![]
+    }
+    fn cancel_open_order(&mut self, _order_id: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn run(&mut self) {}
+    fn get_id(&self) -> usize {
+        self.id
+    }
+    fn get_inventory(&self) -> i64 {
+        0
+    }
+    fn clone_agent(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Handle
+// -----------------------------------------------------------------------------
+pub struct LiveHandle {
+    shutdown: Arc<AtomicBool>,
+    orders: Sender<OrderRequest>,
+    matcher: JoinHandle<Market>,
+    agents: Vec<JoinHandle<Box<dyn Agent>>>,
+}
+
+impl LiveHandle {
+    /// Extra order entry (e.g. a gateway). Acks and fills for the agent id
+    /// in each request still go to that agent's thread.
+    pub fn order_sender(&self) -> Sender<OrderRequest> {
+        self.orders.clone()
+    }
+
+    /// Stop every thread and hand back the market with its agents (and
+    /// their final state) reinstalled, ready for lock-step `step()` again.
+    pub fn shutdown(self) -> Market {
+        self.shutdown.store(true, Ordering::SeqCst);
+        drop(self.orders);
+        let mut market = self.matcher.join().expect("matching thread panicked");
+
+        // dropping the stand-ins closes every report channel, which lets
+        // agent threads finish draining and return
+        drop(market.swap_agents(HashMap::new()));
+        let agents = self
+            .agents
+            .into_iter()
+            .map(|h| h.join().expect("agent thread panicked"))
+            .map(|a| (a.get_id(), a))
+            .collect();
+        market.swap_agents(agents);
+        market
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Threads
+// -----------------------------------------------------------------------------
+impl Market {
+    /// Switch to live mode. See the module docs; `LiveHandle::shutdown`
+    /// switches back.
+    pub fn run_live(mut self, cfg: LiveConfig) -> LiveHandle {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let (orders_tx, orders_rx) = channel();
+
+        let mut ids: Vec<usize> = self.agent_ids();
+        ids.sort_unstable();
+        let mut stand_ins = HashMap::new();
+        let mut wiring = Vec::new();
+        for id in ids {
+            let (reports_tx, reports_rx) = channel();
+            stand_ins.insert(
+                id,
+                Box::new(RemoteAgent {
+                    id,
+                    reports: reports_tx,
+                }) as Box<dyn Agent>,
+            );
+            wiring.push((id, self.subscribe_feed(), reports_rx));
+        }
+        let mut agents = self.swap_agents(stand_ins);
+
+        let agent_threads = wiring
+            .into_iter()
+            .map(|(id, feed, reports)| {
+                let agent = agents.remove(&id).expect("agent for every id");
+                let stocks = self.stocks().clone();
+                let orders = orders_tx.clone();
+                let shutdown = shutdown.clone();
+                thread::spawn(move || {
+                    agent_loop(agent, stocks, feed, reports, orders, &shutdown, cfg)
+                })
+            })
+            .collect();
+
+        let matcher = {
+            let shutdown = shutdown.clone();
+            thread::spawn(move || matching_loop(self, orders_rx, &shutdown, cfg))
+        };
+
+        LiveHandle {
+            shutdown,
+            orders: orders_tx,
+            matcher,
+            agents: agent_threads,
+        }
+    }
+}
+
+fn matching_loop(
+    mut market: Market,
+    orders: Receiver<OrderRequest>,
+    shutdown: &AtomicBool,
+    cfg: LiveConfig,
+) -> Market {
+    while !shutdown.load(Ordering::SeqCst) {
+        let first = match orders.recv_timeout(cfg.poll_timeout) {
+            Ok(req) => req,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        // everything already queued forms one tick
+        let mut batch = vec![first];
+        batch.extend(orders.try_iter());
+        market.begin_tick();
+        market.execute(batch);
+    }
+    market
+}
+
+fn agent_loop(
+    mut agent: Box<dyn Agent>,
+    mut stocks: StockMarket,
+    feed: Receiver<FeedMessage>,
+    reports: Receiver<ExecReport>,
+    orders: Sender<OrderRequest>,
+    shutdown: &AtomicBool,
+    cfg: LiveConfig,
+) -> Box<dyn Agent> {
+    let mut books = BookBuilder::new();
+    // the thread's copy of the universe follows listings, delistings and
+    // renames, so the agent's view does too
+    let apply = |agent: &mut Box<dyn Agent>, stocks: &mut StockMarket, report| match report {
+        ExecReport::Ack(order) => agent.acknowledge_order(order),
+        ExecReport::Fill { delta, trade } => agent.update_portfolio(delta, &trade),
+        ExecReport::Cancelled(order_id) => agent.cancel_acknowledged(order_id),
+        ExecReport::Rejected(request, reason) => agent.order_rejected(&request, reason),
+        ExecReport::Listed(stock) => {
+            stocks.add_stock(stock.clone());
+            agent.stock_listed(&stock);
+        }
+        ExecReport::Delisted {
+            stock_id,
+            final_price,
+        } => {
+            stocks.remove_stock(stock_id);
+            agent.stock_delisted(stock_id, final_price);
+        }
+        ExecReport::CorporateAction(action) => {
+            stocks.apply_corporate_action(&action);
+            agent.corporate_action(&action);
+        }
+        ExecReport::UnitsSettled(settlement) => agent.units_settled(&settlement),
+    };
+
+    'live: while !shutdown.load(Ordering::SeqCst) {
+        for msg in feed.try_iter() {
+            // one in-process channel per agent: no gaps, so errors are bugs
+            books.apply(&msg).expect("live feed out of sequence");
+        }
+        for report in reports.try_iter() {
+            apply(&mut agent, &mut stocks, report);
+        }
+
+        // only what the feed carries: books, no tape or ledger
+        let view = MarketView::new(books.books(), &stocks).for_agent(agent.get_id());
+        let mut requests = agent.margin_call();
+        requests.extend(agent.decide_actions(&view));
+        for req in requests {
+            if orders.send(req).is_err() {
+                break 'live; // matching thread is gone
+            }
+        }
+        thread::sleep(cfg.agent_interval);
+    }
+
+    // settle everything the exchange reported before it stopped
+    for report in reports.iter() {
+        apply(&mut agent, &mut stocks, report);
+    }
+    agent
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Instant};
+
+    use super::*;
+    use crate::{AgentType, EventAgent, EventDriven, Marketable, OpenOrders};
+
+    #[test]
+    fn live_run_trades_and_hands_back_a_consistent_market() {
+        let participants = [
+            AgentType::MarketMaker,
+            AgentType::DumbLimit,
+            AgentType::DumbMarket,
+            AgentType::DumbMarket,
+        ];
+        let market = Market::with_seed(&participants, StockMarket::new(), 1);
+        let shares = market.total_inventory();
+        let ids = market.stocks().get_all_ids();
+
+        let live = market.run_live(LiveConfig {
+            agent_interval: Duration::from_millis(1),
+            poll_timeout: Duration::from_millis(5),
+        });
+        thread::sleep(Duration::from_millis(300));
+        let mut market = live.shutdown();
+
+        let volume: u64 = ids
+            .iter()
+            .filter_map(|&id| market.cumulative_volume(id))
+            .sum();
+        assert!(volume > 0, "agents should have traded");
+        assert!(market.tick() > 0);
+        // every fill reached both sides: shares are conserved
+        assert_eq!(market.total_inventory(), shares);
+        assert_eq!(market.agent_ids().len(), participants.len());
+
+        // back to lock-step
+        let tick = market.tick();
+        market.step();
+        assert_eq!(market.tick(), tick + 1);
+    }
+
+    /// Keeps the tickers its last view listed.
+    #[derive(Debug, Clone)]
+    struct Watcher {
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl EventAgent for Watcher {
+        fn id(&self) -> usize {
+            0
+        }
+        fn inventory(&self) -> i64 {
+            0
+        }
+        fn on_market_data(&mut self, view: &MarketView, _open: &OpenOrders) -> Vec<OrderRequest> {
+            let mut tickers: Vec<String> = view
+                .stocks
+                .get_all_stocks()
+                .iter()
+                .map(|s| s.ticker.clone())
+                .collect();
+            tickers.sort();
+            *self.seen.lock().unwrap() = tickers;
+            vec![]
+        }
+    }
+
+    #[test]
+    fn agent_threads_follow_listings_delistings_and_renames() {
+        let stocks = StockMarket::new();
+        let ids = stocks.get_all_ids();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let agent: Box<dyn Agent> = Box::new(EventDriven::new(Watcher { seen: seen.clone() }));
+        let (_feed_tx, feed) = channel();
+        let (reports_tx, reports) = channel();
+        let (orders, _orders_rx) = channel();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let flag = shutdown.clone();
+        let cfg = LiveConfig {
+            agent_interval: Duration::from_millis(1),
+            poll_timeout: Duration::from_millis(5),
+        };
+        let handle =
+            thread::spawn(move || agent_loop(agent, stocks, feed, reports, orders, &flag, cfg));
+
+        for report in [
+            ExecReport::Listed(Stock::new("NEWC", 77, "New Co", 1_000, 10.0)),
+            ExecReport::Delisted {
+                stock_id: ids[0],
+                final_price: 100,
+            },
+            ExecReport::CorporateAction(CorporateAction::Rename {
+                stock_id: ids[1],
+                ticker: "RNMD".into(),
+            }),
+        ] {
+            reports_tx.send(report).unwrap();
+        }
+        let want = ["NEWC".to_string(), "RNMD".to_string()];
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *seen.lock().unwrap() != want && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*seen.lock().unwrap(), want);
+
+        shutdown.store(true, Ordering::SeqCst);
+        drop(reports_tx);
+        handle.join().unwrap();
+    }
+}
@@ -3,13 +3,37 @@
 // Multi-ticker engine: one OrderBook per stock-id.
 // All routing keys on `stock_id: u64`; no String clones on the hot path.
 
-use std::{any::Any, collections::HashMap};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    io,
+    sync::mpsc::{Receiver, Sender, channel},
+    thread,
+};
 
 use crate::{
-    Agent, AgentType, DumbAgent, DumbLimitAgent, IpoAgent, MarketMakerAgent, MarketView,
-    Marketable, OrderBook, WhaleAgent,
-    stocks::definitions::StockMarket,
-    types::{Order, OrderRequest, Side, Trade},
+    Agent, AgentParams, AgentType, DumbAgent, DumbLimitAgent, EtfArbAgent, InformedAgent, IpoAgent,
+    MarketMakerAgent, MarketView, Marketable, OrderBook, WhaleAgent,
+    agents::{
+        config::{INFORMED_SIGNAL_DELAY, INFORMED_SIGNAL_NOISE},
+        portfolio::Portfolio,
+    },
+    corporate::{CorporateAction, CorporateActionError},
+    fundamental::{DiscoveryError, FundamentalConfig, Fundamentals},
+    journal::{Journal, JournalEvent},
+    sentiment::{Sentiment, SentimentConfig},
+    simulators::{
+        auction::CallAuction,
+        feed::{FeedEvent, FeedMessage},
+        levels::BookKind,
+        tape::{StockStats, TradeTape},
+    },
+    stocks::{
+        basket::{Basket, UnitSettlement},
+        definitions::{Stock, StockMarket},
+        loader::{UniverseError, check_listing},
+    },
+    types::{Order, OrderRequest, RejectReason, Side, Trade},
 };
 
 // -----------------------------------------------------------------------------
@@ -23,13 +47,116 @@ pub struct Market {
     order_books: HashMap<u64, OrderBook>, // id → book
     last_traded_price: HashMap<u64, f64>, // id → dollars
     cumulative_volume: HashMap<u64, u64>, // id → shares
+    stats: HashMap<u64, StockStats>,      // id → session stats
+    tape: TradeTape,
+    batch: Vec<Trade>,                   // every trade of the last `execute`
+    auctions: HashMap<u64, CallAuction>, // id → opening auction still collecting
+
+    /* corporate actions: pending by tick, and those that took effect this tick */
+    corporate_actions: Vec<(u64, CorporateAction)>,
+    announced: Vec<CorporateAction>,
+
+    /* baskets: indicative NAV (dollars) by basket id, and who may create or redeem */
+    nav: HashMap<u64, f64>,
+    authorised: HashSet<usize>,
+
+    /* the exchange's own ledger: starting inventory plus every fill */
+    accounts: HashMap<usize, Portfolio>,
+
+    /* session rules: no margin-call orders in these stocks or from these agents */
+    halted: HashSet<u64>,
+    suspended: HashSet<usize>,
+
+    /* optional per-market sentiment, stepped every tick */
+    sentiment: Option<Sentiment>,
+
+    /* optional latent value per stock, stepped every tick */
+    fundamentals: Option<Fundamentals>,
 
     /* participants */
     agents: HashMap<usize, Box<dyn Agent>>,
     initial_agent_types: Vec<AgentType>,
+    /// Overrides for built-in agents, by id; kept across `reset`.
+    agent_params: HashMap<usize, AgentParams>,
 
     /* counters */
     order_id_counter: u64,
+    tick: u64,
+
+    /* randomness: every agent stream is derived from this */
+    seed: u64,
+
+    /* optional append-only event log (never carried into forks) */
+    journal: Option<Journal>,
+    /* first write error; recording stopped there */
+    journal_error: Option<io::Error>,
+
+    /* incremental market-data subscribers (never carried into forks) */
+    feed_subscribers: Vec<Sender<FeedMessage>>,
+
+    /* worker threads per phase; 1 = serial */
+    threads: usize,
+}
+
+/// How a newly listed stock starts trading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Listing {
+    /// Continuous matching from the next tick.
+    #[default]
+    Continuous,
+    /// Limit orders collect for `ticks` ticks and cross at one price at the
+    /// end of the last; market orders are rejected until then.
+    Auction { ticks: u64 },
+}
+
+/// What `Market::delist_stock` cleaned up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delisting {
+    pub stock_id: u64,
+    pub ticker: String,
+    /// Dollars; every open position was settled here.
+    pub final_price: f64,
+    /// `(agent_id, order_id)` of every order still open, by order id.
+    pub cancelled: Vec<(usize, u64)>,
+    /// `(agent_id, shares)` closed out on the exchange's ledger, by agent id.
+    pub settled: Vec<(usize, i64)>,
+}
+
+/// What one request did to its book; merged back in request order.
+#[derive(Default)]
+struct Outcome {
+    fills: Vec<Trade>,
+    cancelled: Option<bool>,
+    ack: Option<Order>,
+    rejected: Option<RejectReason>,
+}
+
+/// Seed of the market's sentiment stream, apart from every agent's.
+#[inline]
+fn sentiment_seed(seed: u64) -> u64 {
+    agent_seed(seed, usize::MAX)
+}
+
+/// Seed of the market's fundamental-value stream.
+#[inline]
+fn fundamental_seed(seed: u64) -> u64 {
+    agent_seed(seed, usize::MAX - 1)
+}
+
+/// Derive an independent per-agent seed from the market seed.
+#[inline]
+fn agent_seed(seed: u64, agent_id: usize) -> u64 {
+    mix_seed(seed, agent_id as u64)
+}
+
+/// Derive an independent seed for stream `stream` of `seed` (SplitMix64
+/// finaliser).
+#[inline]
+pub(crate) fn mix_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed ^ stream.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
 }
 
 impl Market {
@@ -37,259 +164,1748 @@ impl Market {
     //  Construction
     // ---------------------------------------------------------------------
     pub fn new(participant_types: &[AgentType], stocks: StockMarket) -> Self {
+        Self::with_seed(participant_types, stocks, rand::random())
+    }
+
+    /// Same as [`Market::new`] but every agent's random stream is derived from
+    /// `seed`, so two markets built with the same seed evolve identically.
+    pub fn with_seed(participant_types: &[AgentType], stocks: StockMarket, seed: u64) -> Self {
         /* build empty books + price/vol maps */
         let mut order_books = HashMap::new();
         let mut last_traded_price = HashMap::new();
         let mut cumulative_volume = HashMap::new();
+        let mut stats = HashMap::new();
 
         for s in stocks.get_all_stocks() {
             order_books.insert(s.id, OrderBook::new());
             last_traded_price.insert(s.id, s.initial_price);
             cumulative_volume.insert(s.id, 0);
+            stats.insert(s.id, StockStats::default());
         }
 
         /* instantiate agents */
         let agents = participant_types
             .iter()
             .enumerate()
-            .map(|(id, t)| (id, Self::spawn_agent(*t, id)))
+            .map(|(id, t)| (id, Self::spawn_agent(*t, id, seed, &AgentParams::default())))
+            .collect();
+        let authorised = participant_types
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| matches!(t, AgentType::EtfArb))
+            .map(|(id, _)| id)
             .collect();
 
-        Self {
+        let mut market = Self {
             stocks,
             order_books,
             last_traded_price,
             cumulative_volume,
+            stats,
+            tape: TradeTape::default(),
+            batch: Vec::new(),
+            auctions: HashMap::new(),
+            corporate_actions: Vec::new(),
+            announced: Vec::new(),
+            nav: HashMap::new(),
+            authorised,
+            accounts: HashMap::new(),
+            halted: HashSet::new(),
+            suspended: HashSet::new(),
+            sentiment: None,
+            fundamentals: None,
             agents,
             initial_agent_types: participant_types.to_vec(),
+            agent_params: HashMap::new(),
             order_id_counter: 0,
+            tick: 0,
+            seed,
+            journal: None,
+            journal_error: None,
+            feed_subscribers: Vec::new(),
+            threads: 1,
+        };
+        for id in 0..participant_types.len() {
+            market.introduce_all(id);
         }
+        market.refresh_nav();
+        market
     }
 
-    fn spawn_agent(t: AgentType, id: usize) -> Box<dyn Agent> {
+    fn spawn_agent(t: AgentType, id: usize, seed: u64, params: &AgentParams) -> Box<dyn Agent> {
+        let mut agent = Self::build_agent(t, id, params);
+        agent.reseed(agent_seed(seed, id));
+        agent
+    }
+
+    /// Tell `agent_id` that `stock` is listed and open its ledger with
+    /// whatever starting inventory the agent took.
+    fn introduce(&mut self, agent_id: usize, stock: &Stock) {
+        let Some(agent) = self.agents.get_mut(&agent_id) else {
+            return;
+        };
+        agent.stock_listed(stock);
+        let qty = agent.portfolio().map_or(0, |p| p.qty(stock.id));
+        if qty != 0 {
+            self.accounts
+                .entry(agent_id)
+                .or_default()
+                .endow(stock.id, qty, stock.initial_price);
+        }
+    }
+
+    /// `introduce` every listed stock to `agent_id`.
+    fn introduce_all(&mut self, agent_id: usize) {
+        let stocks: Vec<Stock> = self.stocks.get_all_stocks().into_iter().cloned().collect();
+        for stock in &stocks {
+            self.introduce(agent_id, stock);
+        }
+    }
+
+    /// A built-in agent with `params` laid over its kind's defaults; fields
+    /// the kind does not take are ignored.
+    fn build_agent(t: AgentType, id: usize, params: &AgentParams) -> Box<dyn Agent> {
+        let p = params;
         match t {
-            AgentType::DumbMarket => Box::new(DumbAgent::new(id)),
-            AgentType::DumbLimit => Box::new(DumbLimitAgent::new(id)),
-            AgentType::MarketMaker => Box::new(MarketMakerAgent::new(id)),
+            AgentType::DumbMarket => {
+                let mut a = DumbAgent::new(id);
+                if let Some(x) = p.action_prob {
+                    a = a.with_action_prob(x);
+                }
+                if let Some([min, max]) = p.volume {
+                    a = a.with_volume(min, max);
+                }
+                if let Some(x) = p.cash {
+                    a = a.with_cash(x);
+                }
+                Box::new(a)
+            }
+            AgentType::DumbLimit => {
+                let mut a = DumbLimitAgent::new(id);
+                if let Some(x) = p.action_prob {
+                    a = a.with_action_prob(x);
+                }
+                if let Some([min, max]) = p.volume {
+                    a = a.with_volume(min, max);
+                }
+                if let Some(x) = p.cash {
+                    a = a.with_cash(x);
+                }
+                if let Some(x) = p.inventory {
+                    a = a.with_inventory(x);
+                }
+                Box::new(a)
+            }
+            AgentType::MarketMaker => {
+                let mut a = MarketMakerAgent::new(id);
+                if let Some([min, max]) = p.volume {
+                    a = a.with_volume(min, max);
+                }
+                if let Some(x) = p.cash {
+                    a = a.with_cash(x);
+                }
+                if let Some(x) = p.inventory {
+                    a = a.with_inventory(x);
+                }
+                Box::new(a)
+            }
             AgentType::IPO => Box::new(IpoAgent::new(id)),
-            AgentType::WhaleAgent => Box::new(WhaleAgent::new(id)),
+            AgentType::WhaleAgent => {
+                let mut a = WhaleAgent::new(id);
+                if let Some(x) = p.action_prob {
+                    a = a.with_action_prob(x);
+                }
+                if let Some(x) = p.cash {
+                    a = a.with_cash(x);
+                }
+                if let Some(x) = p.inventory {
+                    a = a.with_inventory(x);
+                }
+                Box::new(a)
+            }
+            AgentType::EtfArb => {
+                let mut a = EtfArbAgent::new(id);
+                if let Some(x) = p.cash {
+                    a = a.with_cash(x);
+                }
+                if let Some(x) = p.threshold_bps {
+                    a = a.with_threshold_bps(x);
+                }
+                if let Some(x) = p.units {
+                    a = a.with_units(x);
+                }
+                Box::new(a)
+            }
+            AgentType::Informed => {
+                let mut a = InformedAgent::new(id);
+                if let Some(x) = p.action_prob {
+                    a = a.with_action_prob(x);
+                }
+                if let Some([min, max]) = p.volume {
+                    a = a.with_volume(min, max);
+                }
+                if let Some(x) = p.cash {
+                    a = a.with_cash(x);
+                }
+                if p.signal_delay.is_some() || p.signal_noise.is_some() {
+                    a = a.with_signal(
+                        p.signal_delay.unwrap_or(INFORMED_SIGNAL_DELAY),
+                        p.signal_noise.unwrap_or(INFORMED_SIGNAL_NOISE),
+                    );
+                }
+                Box::new(a)
+            }
         }
     }
 
-    #[inline]
-    fn next_order_id(&mut self) -> u64 {
-        self.order_id_counter += 1;
-        self.order_id_counter
+    /// Rebuild built-in agent `agent_id` with `params` over its kind's
+    /// defaults, starting inventory included. Meant for before the first
+    /// tick; `reset` keeps the overrides. `false` if `agent_id` is not one
+    /// of the `AgentType` population.
+    pub fn set_agent_params(&mut self, agent_id: usize, params: AgentParams) -> bool {
+        let Some(&t) = self.initial_agent_types.get(agent_id) else {
+            return false;
+        };
+        let agent = Self::spawn_agent(t, agent_id, self.seed, &params);
+        self.agents.insert(agent_id, agent);
+        self.agent_params.insert(agent_id, params);
+        self.accounts.remove(&agent_id);
+        self.introduce_all(agent_id);
+        true
+    }
+
+    /// Add a participant that is not one of the `AgentType`s, e.g. an
+    /// `EventDriven` strategy. `build` gets the new agent's id; its random
+    /// stream is seeded like everyone else's and it hears `stock_listed` for
+    /// every stock. `reset` only rebuilds the `AgentType` population, so
+    /// added agents do not survive it.
+    pub fn add_agent(&mut self, build: impl FnOnce(usize) -> Box<dyn Agent>) -> usize {
+        let id = self.agents.keys().max().map_or(0, |&m| m + 1);
+        let mut agent = build(id);
+        agent.reseed(agent_seed(self.seed, id));
+        self.agents.insert(id, agent);
+        self.introduce_all(id);
+        id
     }
 
     // ---------------------------------------------------------------------
-    //  Convenience getters
+    //  Forking
     // ---------------------------------------------------------------------
-    pub fn order_book(&self, stock_id: u64) -> Option<&OrderBook> {
-        self.order_books.get(&stock_id)
+    /// Deep copy of the whole engine: books, prices, volumes, agents (with
+    /// their inventory, open orders and RNG state) and the order-id counter.
+    /// Stepping the fork never affects `self`, and with no reseed both
+    /// evolve identically. An attached journal stays with the parent.
+    pub fn fork(&self) -> Self {
+        let mut order_books = self.order_books.clone();
+        for book in order_books.values_mut() {
+            book.disable_feed();
+        }
+        Self {
+            stocks: self.stocks.clone(),
+            order_books,
+            last_traded_price: self.last_traded_price.clone(),
+            cumulative_volume: self.cumulative_volume.clone(),
+            stats: self.stats.clone(),
+            tape: self.tape.clone(),
+            batch: self.batch.clone(),
+            auctions: self.auctions.clone(),
+            corporate_actions: self.corporate_actions.clone(),
+            announced: self.announced.clone(),
+            nav: self.nav.clone(),
+            authorised: self.authorised.clone(),
+            accounts: self.accounts.clone(),
+            halted: self.halted.clone(),
+            suspended: self.suspended.clone(),
+            sentiment: self.sentiment.clone(),
+            fundamentals: self.fundamentals.clone(),
+            agents: self
+                .agents
+                .iter()
+                .map(|(&id, a)| (id, a.clone_agent()))
+                .collect(),
+            initial_agent_types: self.initial_agent_types.clone(),
+            agent_params: self.agent_params.clone(),
+            order_id_counter: self.order_id_counter,
+            tick: self.tick,
+            seed: self.seed,
+            journal: None,
+            journal_error: None,
+            feed_subscribers: Vec::new(),
+            threads: self.threads,
+        }
     }
 
-    pub fn cumulative_volume(&self, stock_id: u64) -> Option<u64> {
-        self.cumulative_volume.get(&stock_id).copied()
+    /// Fork, then restart every agent's random stream from `seed` so
+    /// Monte-Carlo rollouts from the same state diverge.
+    pub fn fork_with_seed(&self, seed: u64) -> Self {
+        let mut fork = self.fork();
+        fork.seed = seed;
+        for (&id, agent) in fork.agents.iter_mut() {
+            agent.reseed(agent_seed(seed, id));
+        }
+        if let Some(s) = fork.sentiment.as_mut() {
+            s.reseed(sentiment_seed(seed));
+        }
+        if let Some(f) = fork.fundamentals.as_mut() {
+            f.reseed(fundamental_seed(seed));
+        }
+        fork
     }
 
-    pub fn total_inventory(&self) -> i64 {
-        self.agents.values().map(|a| a.get_inventory()).sum()
+    /// Run agent decisions and per-stock matching on up to `threads` worker
+    /// threads (1 = serial). Results are merged in request order, so the
+    /// outcome of a step does not depend on this setting.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
     }
+
     #[inline]
-    pub fn order_books(&self) -> &HashMap<u64, OrderBook> {
-        &self.order_books
+    pub fn threads(&self) -> usize {
+        self.threads
     }
 
-    #[inline]
-    pub fn last_price(&self, stock_id: u64) -> f64 {
-        *self.last_traded_price.get(&stock_id).unwrap_or(&150.0)
+    /// Halt or resume `stock_id` for the orders the engine raises itself
+    /// (margin calls). Requests handed to `execute` are the caller's to
+    /// filter. `reset` lifts every halt.
+    pub fn set_halted(&mut self, stock_id: u64, halted: bool) {
+        if halted {
+            self.halted.insert(stock_id);
+        } else {
+            self.halted.remove(&stock_id);
+        }
+    }
+
+    pub fn is_halted(&self, stock_id: u64) -> bool {
+        self.halted.contains(&stock_id)
+    }
+
+    /// Skip (or restore) `agent_id`'s margin calls, e.g. while it is out of
+    /// the session. `reset` restores everyone.
+    pub fn set_suspended(&mut self, agent_id: usize, suspended: bool) {
+        if suspended {
+            self.suspended.insert(agent_id);
+        } else {
+            self.suspended.remove(&agent_id);
+        }
     }
 
+    /// Seed every agent stream is derived from.
     #[inline]
-    pub fn last_price_map_iter(&self) -> impl Iterator<Item = (&u64, &f64)> {
-        self.last_traded_price.iter()
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
-    pub fn ticker(&self, stock_id: u64) -> &str {
-        self.stocks
-            .get_ticker_by_id(stock_id)
-            .map(String::as_str)
-            .unwrap_or("??")
+
+    // ---------------------------------------------------------------------
+    //  Journal
+    // ---------------------------------------------------------------------
+    /// Start recording every request, fill and cancel outcome into `journal`.
+    /// The current books are written first so a mid-run attach still replays.
+    /// Clears the error of any previous journal.
+    pub fn attach_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+        self.journal_error = None;
+        self.journal_open_session(true);
     }
-}
 
-// -----------------------------------------------------------------------------
-//  Marketable
-// -----------------------------------------------------------------------------
-impl Marketable for Market {
-    fn step(&mut self) -> f64 {
-        /* -------- Phase 1: agent decisions -------- */
-        let view = MarketView {
-            order_books: &self.order_books,
-            stocks: &self.stocks,
-        };
+    /// Stop recording and hand the journal back (flushed). Fails with the
+    /// first write error if recording stopped early; that journal is
+    /// truncated and has already been dropped.
+    pub fn detach_journal(&mut self) -> io::Result<Option<Journal>> {
+        self.flush_journal()?;
+        Ok(self.journal.take())
+    }
 
-        let mut requests = Vec::<OrderRequest>::new();
-        let mut ids: Vec<_> = self.agents.keys().copied().collect();
-        ids.sort_unstable();
+    /// Flush the journal. A failed write or flush stops recording, and the
+    /// error is returned once, by whichever of this and `detach_journal`
+    /// runs next.
+    pub fn flush_journal(&mut self) -> io::Result<()> {
+        self.journal_flush();
+        match self.journal_error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
 
-        for id in &ids {
-            if let Some(a) = self.agents.get_mut(id) {
-                requests.extend(a.decide_actions(&view));
-            }
+    /// Why recording stopped, if it did.
+    pub fn journal_error(&self) -> Option<&io::Error> {
+        self.journal_error.as_ref()
+    }
+
+    fn journal_flush(&mut self) {
+        if let Some(j) = self.journal.as_mut()
+            && let Err(e) = j.flush()
+        {
+            self.journal_failed(e);
         }
+    }
 
-        /* -------- Phase 2: execute orders -------- */
-        let mut trades = Vec::<Trade>::new();
+    fn journal_failed(&mut self, e: io::Error) {
+        self.journal = None;
+        self.journal_error.get_or_insert(e);
+    }
 
-        for req in requests {
-            match req {
-                OrderRequest::LimitOrder {
-                    agent_id,
-                    stock_id,
-                    side,
-                    price,
-                    volume,
-                } => {
-                    let mut o = Order {
-                        id: self.next_order_id(),
-                        agent_id,
-                        stock_id,
-                        side,
-                        price,
-                        volume,
-                        filled: 0,
-                    };
-                    self.agents.get_mut(&agent_id).unwrap().acknowledge_order(o);
-                    if let Some(book) = self.order_books.get_mut(&stock_id) {
-                        trades.extend(book.process_limit_order(&mut o));
-                    }
-                }
-                OrderRequest::MarketOrder {
-                    agent_id,
-                    stock_id,
-                    side,
-                    volume,
-                } => {
-                    let px_cents = (self
-                        .last_traded_price
-                        .get(&stock_id)
-                        .copied()
-                        .unwrap_or(150.0)
-                        * 100.0)
-                        .round() as u64;
-                    let o = Order {
-                        id: self.next_order_id(),
-                        agent_id,
-                        stock_id,
-                        side,
-                        price: px_cents,
-                        volume,
-                        filled: 0,
-                    };
-                    self.agents.get_mut(&agent_id).unwrap().acknowledge_order(o);
-                    if let Some(book) = self.order_books.get_mut(&stock_id) {
-                        trades.extend(book.process_market_order(agent_id, side, volume));
-                    }
-                }
-                OrderRequest::CancelOrder { agent_id, order_id } => {
-                    for book in self.order_books.values_mut() {
-                        if book.cancel_order(order_id, agent_id) {
-                            break;
-                        }
-                    }
-                }
-            }
+    fn journal_open_session(&mut self, with_resting: bool) {
+        if self.journal.is_none() {
+            return;
+        }
+        let mut stock_ids: Vec<u64> = self.order_books.keys().copied().collect();
+        stock_ids.sort_unstable();
+        self.log(JournalEvent::Open {
+            tick: self.tick,
+            stock_ids: stock_ids.clone(),
+        });
+        if !with_resting {
+            return;
+        }
+        let mut resting = Vec::new();
+        for id in stock_ids {
+            let book = &self.order_books[&id];
+            resting.extend(book.resting_orders(Side::Buy));
+            resting.extend(book.resting_orders(Side::Sell));
+        }
+        for order in resting {
+            self.log(JournalEvent::Resting {
+                tick: self.tick,
+                order,
+            });
         }
+    }
 
-        /* -------- Phase 3: margin calls -------- */
-        let mut margin = Vec::<OrderRequest>::new();
-        for id in &ids {
-            if let Some(a) = self.agents.get_mut(id) {
-                margin.extend(a.margin_call());
-            }
+    #[inline]
+    fn log(&mut self, event: JournalEvent) {
+        if let Some(j) = self.journal.as_mut()
+            && let Err(e) = j.record(&event)
+        {
+            self.journal_failed(e);
         }
-        for req in margin {
-            if let OrderRequest::MarketOrder {
+    }
+
+    fn journal_request(
+        &mut self,
+        order_id: Option<u64>,
+        request: OrderRequest,
+        fills: &[Trade],
+        cancelled: Option<(usize, u64, bool)>,
+    ) {
+        if self.journal.is_none() {
+            return;
+        }
+        let tick = self.tick;
+        self.log(JournalEvent::Request {
+            tick,
+            order_id,
+            request,
+        });
+        for &trade in fills {
+            self.log(JournalEvent::Trade { tick, trade });
+        }
+        if let Some((agent_id, order_id, success)) = cancelled {
+            self.log(JournalEvent::Cancel {
+                tick,
                 agent_id,
-                stock_id,
-                side,
-                volume,
-            } = req
-            {
-                if let Some(book) = self.order_books.get_mut(&stock_id) {
-                    trades.extend(book.process_market_order(agent_id, side, volume));
-                }
-            }
+                order_id,
+                success,
+            });
         }
+    }
 
-        /* -------- Phase 4: update portfolios -------- */
-        for tr in &trades {
-            if let Some(taker) = self.agents.get_mut(&tr.taker_agent_id) {
-                let delta = if tr.taker_side == Side::Buy {
-                    tr.volume as i64
-                } else {
-                    -(tr.volume as i64)
-                };
-                taker.update_portfolio(delta, tr);
+    // ---------------------------------------------------------------------
+    //  Incremental feed
+    // ---------------------------------------------------------------------
+    /// Receive every book change as a sequenced `FeedMessage`. The first
+    /// message per stock is a `Snapshot` of the current book; after that
+    /// messages arrive at the end of each `step`, stocks in id order.
+    /// Dropping the receiver unsubscribes.
+    pub fn subscribe_feed(&mut self) -> Receiver<FeedMessage> {
+        let (tx, rx) = channel();
+        let mut stock_ids: Vec<u64> = self.order_books.keys().copied().collect();
+        stock_ids.sort_unstable();
+        for id in stock_ids {
+            let book = self.order_books.get_mut(&id).unwrap();
+            book.enable_feed();
+            let mut orders = book.resting_orders(Side::Buy);
+            orders.extend(book.resting_orders(Side::Sell));
+            let _ = tx.send(FeedMessage {
+                stock_id: id,
+                seq: book.feed_seq(),
+                event: FeedEvent::Snapshot { orders },
+            });
+        }
+        self.feed_subscribers.push(tx);
+        rx
+    }
+
+    fn publish_feed(&mut self) {
+        if self.feed_subscribers.is_empty() {
+            return;
+        }
+        let mut stock_ids: Vec<u64> = self.order_books.keys().copied().collect();
+        stock_ids.sort_unstable();
+        let mut messages = Vec::new();
+        for id in stock_ids {
+            messages.extend(self.order_books.get_mut(&id).unwrap().drain_feed());
+        }
+        self.feed_subscribers
+            .retain(|tx| messages.iter().all(|m| tx.send(m.clone()).is_ok()));
+        if self.feed_subscribers.is_empty() {
+            for book in self.order_books.values_mut() {
+                book.disable_feed();
             }
-            if let Some(maker) = self.agents.get_mut(&tr.maker_agent_id) {
-                let delta = if tr.taker_side == Side::Sell {
-                    tr.volume as i64
-                } else {
-                    -(tr.volume as i64)
-                };
-                maker.update_portfolio(delta, tr);
+        }
+    }
+
+    #[inline]
+    fn next_order_id(&mut self) -> u64 {
+        self.order_id_counter += 1;
+        self.order_id_counter
+    }
+
+    // ---------------------------------------------------------------------
+    //  Convenience getters
+    // ---------------------------------------------------------------------
+    pub fn order_book(&self, stock_id: u64) -> Option<&OrderBook> {
+        self.order_books.get(&stock_id)
+    }
+
+    /// Move `stock_id` onto a different level store (e.g. a dense ladder for a
+    /// stock that trades in a known band). Resting orders keep their priority
+    /// and the choice survives `reset` and `fork`. `false` for an unknown stock.
+    pub fn set_book_kind(&mut self, stock_id: u64, kind: BookKind) -> bool {
+        match self.order_books.get_mut(&stock_id) {
+            Some(book) => {
+                *book = book.rebuilt_as(kind);
+                true
             }
+            None => false,
         }
+    }
 
-        /* -------- Phase 5: book-level bookkeeping -------- */
-        if let Some(last) = trades.last() {
-            self.last_traded_price
-                .insert(last.stock_id, last.price as f64 / 100.0);
+    // ---------------------------------------------------------------------
+    //  Listings
+    // ---------------------------------------------------------------------
+    /// Add `stock` to the universe mid-session with an empty book, trading
+    /// from the next tick or after an opening auction. Every agent hears
+    /// `stock_listed` and finds it in `MarketView::stocks` like any other;
+    /// `reset` keeps it.
+    pub fn list_stock(&mut self, stock: Stock, listing: Listing) -> Result<(), UniverseError> {
+        check_listing(&self.stocks, &stock)?;
+        let id = stock.id;
+        let mut book = OrderBook::new();
+        if !self.feed_subscribers.is_empty() {
+            book.enable_feed();
         }
-        for tr in &trades {
-            *self.cumulative_volume.entry(tr.stock_id).or_insert(0) += tr.volume;
+        self.order_books.insert(id, book);
+        self.last_traded_price.insert(id, stock.initial_price);
+        self.cumulative_volume.insert(id, 0);
+        self.stats.insert(id, StockStats::default());
+        if let Some(s) = self.sentiment.as_mut() {
+            s.set(id, 0.0);
+        }
+        if let Some(f) = self.fundamentals.as_mut() {
+            f.insert(id, stock.initial_price);
+        }
+        if let Listing::Auction { ticks } = listing
+            && ticks > 0
+        {
+            let reference = (stock.initial_price * 100.0).round() as u64;
+            self.auctions
+                .insert(id, CallAuction::new(self.tick + ticks, reference));
+        }
+        let mut ids = self.agent_ids();
+        ids.sort_unstable();
+        for agent_id in ids {
+            self.introduce(agent_id, &stock);
         }
+        self.stocks.add_stock(stock);
 
-        /* Return any price (first) for backward compatibility */
-        self.last_traded_price
-            .values()
-            .next()
-            .copied()
-            .unwrap_or(150.0)
+        self.log(JournalEvent::Listed {
+            tick: self.tick,
+            stock_id: id,
+        });
+        self.feed_subscribers.retain(|tx| {
+            tx.send(FeedMessage {
+                stock_id: id,
+                seq: 0,
+                event: FeedEvent::Snapshot { orders: Vec::new() },
+            })
+            .is_ok()
+        });
+        Ok(())
     }
 
-    fn current_price(&self) -> f64 {
-        self.last_traded_price
-            .values()
-            .next()
-            .copied()
-            .unwrap_or(150.0)
-    }
+    /// Take `stock_id` off the exchange. Every order still open in its book
+    /// or opening auction is cancelled (owners get `cancel_acknowledged`),
+    /// every position is settled at `final_price` dollars (the last trade
+    /// when `None`), then all agents get `stock_delisted`. `None` for an
+    /// unknown stock.
+    pub fn delist_stock(&mut self, stock_id: u64, final_price: Option<f64>) -> Option<Delisting> {
+        let book = self.order_books.remove(&stock_id)?;
+        let final_price = final_price.unwrap_or_else(|| self.last_price(stock_id));
+        let cents = (final_price * 100.0).round() as u64;
 
-    fn reset(&mut self) {
-        /* agents */
-        self.agents = self
-            .initial_agent_types
-            .iter()
-            .enumerate()
-            .map(|(id, t)| (id, Self::spawn_agent(*t, id)))
-            .collect();
+        let mut open = book.resting_orders(Side::Buy);
+        open.extend(book.resting_orders(Side::Sell));
+        if let Some(auction) = self.auctions.remove(&stock_id) {
+            open.extend_from_slice(auction.orders());
+        }
+        open.sort_unstable_by_key(|o| o.id);
+        for o in &open {
+            if let Some(agent) = self.agents.get_mut(&o.agent_id) {
+                agent.cancel_acknowledged(o.id);
+            }
+        }
 
-        /* per-symbol state */
-        // fresh books
-        for book in self.order_books.values_mut() {
-            *book = OrderBook::new();
+        let mut settled: Vec<(usize, i64)> = self
+            .accounts
+            .iter_mut()
+            .filter_map(|(&id, account)| {
+                let qty = account.settle(stock_id, cents);
+                (qty != 0).then_some((id, qty))
+            })
+            .collect();
+        settled.sort_unstable();
+        let mut ids = self.agent_ids();
+        ids.sort_unstable();
+        for id in ids {
+            self.agents
+                .get_mut(&id)
+                .unwrap()
+                .stock_delisted(stock_id, cents);
         }
 
-        // restore initial prices **per instrument** (instead of hard-coding 150)
-        for s in self.stocks.get_all_stocks() {
-            self.last_traded_price.insert(s.id, s.initial_price);
-            self.cumulative_volume.insert(s.id, 0);
+        let ticker = self.ticker(stock_id).to_string();
+        self.stocks.remove_stock(stock_id);
+        self.last_traded_price.remove(&stock_id);
+        self.cumulative_volume.remove(&stock_id);
+        self.stats.remove(&stock_id);
+        if let Some(s) = self.sentiment.as_mut() {
+            s.remove(stock_id);
+        }
+        if let Some(f) = self.fundamentals.as_mut() {
+            f.remove(stock_id);
         }
+        self.refresh_nav();
 
-        self.order_id_counter = 0;
+        self.log(JournalEvent::Delisted {
+            tick: self.tick,
+            stock_id,
+        });
+        let seq = book.feed_seq();
+        self.feed_subscribers.retain(|tx| {
+            tx.send(FeedMessage {
+                stock_id,
+                seq,
+                event: FeedEvent::Delisted,
+            })
+            .is_ok()
+        });
+        Some(Delisting {
+            stock_id,
+            ticker,
+            final_price,
+            cancelled: open.iter().map(|o| (o.agent_id, o.id)).collect(),
+            settled,
+        })
+    }
+
+    /// The opening auction `stock_id` is still in, if any.
+    pub fn auction(&self, stock_id: u64) -> Option<&CallAuction> {
+        self.auctions.get(&stock_id)
+    }
+
+    // ---------------------------------------------------------------------
+    //  Baskets
+    // ---------------------------------------------------------------------
+    /// Make the listed stock `basket.stock_id` a basket (see
+    /// [`StockMarket::add_basket`]); its NAV is available right away.
+    pub fn add_basket(&mut self, basket: Basket) -> Result<(), UniverseError> {
+        self.stocks.add_basket(basket)?;
+        self.refresh_nav();
+        Ok(())
+    }
+
+    /// Let `agent_id` create and redeem basket units. Agents of type
+    /// `AgentType::EtfArb` are authorised from the start.
+    pub fn authorise_participant(&mut self, agent_id: usize) {
+        self.authorised.insert(agent_id);
+    }
+
+    pub fn is_authorised(&self, agent_id: usize) -> bool {
+        self.authorised.contains(&agent_id)
+    }
+
+    /// Indicative NAV of the basket `stock_id`, dollars per share, as of the
+    /// end of the last batch.
+    pub fn nav(&self, stock_id: u64) -> Option<f64> {
+        self.nav.get(&stock_id).copied()
+    }
+
+    /// Dollar price a constituent is valued at: the book's mid, else the
+    /// last trade. `None` while it is not trading continuously.
+    fn mark(&self, stock_id: u64) -> Option<f64> {
+        if self.auctions.contains_key(&stock_id) {
+            return None;
+        }
+        self.order_books
+            .get(&stock_id)?
+            .mid_price()
+            .map(|c| c as f64 / 100.0)
+            .or_else(|| self.last_traded_price.get(&stock_id).copied())
+    }
+
+    fn refresh_nav(&mut self) {
+        let nav = self
+            .stocks
+            .baskets()
+            .filter_map(|b| Some((b.stock_id, b.nav(|id| self.mark(id))?)))
+            .collect();
+        self.nav = nav;
+    }
+
+    fn record_discovery_error(&mut self) {
+        if self.fundamentals.is_none() {
+            return;
+        }
+        let marks: Vec<(u64, f64)> = self
+            .order_books
+            .keys()
+            .filter_map(|&id| Some((id, self.mark(id)?)))
+            .collect();
+        let f = self.fundamentals.as_mut().unwrap();
+        for (id, mark) in marks {
+            f.record(id, mark);
+        }
+    }
+
+    /// Swap constituents for basket shares (or back) on the exchange's
+    /// ledger at the current marks, charge the fee and tell the agent.
+    /// `fills` are the batch's trades so far, which count towards what the
+    /// agent holds although the ledger only books them after the merge.
+    fn settle_units(&mut self, req: &OrderRequest, fills: &[Trade]) -> Result<(), RejectReason> {
+        let (agent_id, stock_id, units, sign) = match *req {
+            OrderRequest::CreateUnits {
+                agent_id,
+                stock_id,
+                units,
+            } => (agent_id, stock_id, units, 1),
+            OrderRequest::RedeemUnits {
+                agent_id,
+                stock_id,
+                units,
+            } => (agent_id, stock_id, units, -1),
+            _ => return Ok(()),
+        };
+        let basket = match self.stocks.basket(stock_id) {
+            Some(b) if self.authorised.contains(&agent_id) => b,
+            _ => return Err(RejectReason::NotAuthorised),
+        };
+        if units == 0 {
+            return Ok(());
+        }
+        if self.auctions.contains_key(&stock_id) {
+            return Err(RejectReason::NotTrading);
+        }
+        let largest = basket
+            .constituents
+            .iter()
+            .map(|c| c.shares)
+            .fold(basket.creation_unit, u64::max)
+            .max(1);
+        if units > i64::MAX as u64 / largest {
+            return Err(RejectReason::TooLarge);
+        }
+        let units = units as i64;
+        let mut legs = Vec::with_capacity(basket.constituents.len() + 1);
+        let mut unit_value = 0u64;
+        for c in &basket.constituents {
+            let mark = self.mark(c.stock_id).ok_or(RejectReason::NotTrading)?;
+            let cents = ((mark * 100.0).round() as u64).max(1);
+            unit_value = c
+                .shares
+                .checked_mul(cents)
+                .and_then(|v| unit_value.checked_add(v))
+                .ok_or(RejectReason::TooLarge)?;
+            legs.push((c.stock_id, -sign * units * c.shares as i64, cents));
+        }
+        let share_cents = ((unit_value as f64 / basket.creation_unit as f64).round() as u64).max(1);
+        legs.insert(
+            0,
+            (
+                stock_id,
+                sign * units * basket.creation_unit as i64,
+                share_cents,
+            ),
+        );
+        // in kind: every share delivered must already be held
+        let held = |id| {
+            let booked = self.accounts.get(&agent_id).map_or(0, |a| a.qty(id));
+            let filled: i64 = fills
+                .iter()
+                .filter(|t| t.stock_id == id)
+                .map(|t| {
+                    let v = t.volume as i64;
+                    let taker = if t.taker_side == Side::Buy { v } else { -v };
+                    match agent_id {
+                        a if a == t.taker_agent_id && a == t.maker_agent_id => 0,
+                        a if a == t.taker_agent_id => taker,
+                        a if a == t.maker_agent_id => -taker,
+                        _ => 0,
+                    }
+                })
+                .sum();
+            booked + filled
+        };
+        if legs
+            .iter()
+            .any(|&(id, delta, _)| delta < 0 && held(id) < -delta)
+        {
+            return Err(RejectReason::InsufficientHoldings);
+        }
+        let settlement = UnitSettlement {
+            agent_id,
+            stock_id,
+            units: sign * units,
+            legs,
+            fee: basket.fee * units as f64,
+        };
+
+        self.accounts
+            .entry(agent_id)
+            .or_default()
+            .apply_settlement(&settlement);
+        if let Some(agent) = self.agents.get_mut(&agent_id) {
+            agent.units_settled(&settlement);
+        }
+        Ok(())
+    }
+
+    // ---------------------------------------------------------------------
+    //  Corporate actions
+    // ---------------------------------------------------------------------
+    /// Apply `action` as tick `tick` starts, before agents decide. Actions
+    /// due on the same tick apply in the order they were scheduled. A
+    /// rename whose ticker has been taken by then, or an action on a stock
+    /// delisted by then, is dropped.
+    pub fn schedule_corporate_action(
+        &mut self,
+        tick: u64,
+        action: CorporateAction,
+    ) -> Result<(), CorporateActionError> {
+        if tick <= self.tick {
+            return Err(CorporateActionError::InThePast {
+                tick,
+                now: self.tick,
+            });
+        }
+        action.validate()?;
+        let stock_id = action.stock_id();
+        if self.stocks.get_stock_by_id(stock_id).is_none() {
+            return Err(CorporateActionError::UnknownStock(stock_id));
+        }
+        if let CorporateAction::Rename { ticker, .. } = &action
+            && self
+                .stocks
+                .get_id_by_ticker(ticker)
+                .is_some_and(|id| id != stock_id)
+        {
+            return Err(CorporateActionError::TickerTaken(ticker.clone()));
+        }
+        let at = self.corporate_actions.partition_point(|(t, _)| *t <= tick);
+        self.corporate_actions.insert(at, (tick, action));
+        Ok(())
+    }
+
+    /// Actions still to come, by tick.
+    pub fn scheduled_corporate_actions(&self) -> &[(u64, CorporateAction)] {
+        &self.corporate_actions
+    }
+
+    /// Actions that took effect as the current tick started.
+    pub fn corporate_actions(&self) -> &[CorporateAction] {
+        &self.announced
+    }
+
+    fn apply_corporate_actions(&mut self) {
+        self.announced.clear();
+        let due = self
+            .corporate_actions
+            .partition_point(|(t, _)| *t <= self.tick);
+        for (_, action) in self.corporate_actions.drain(..due).collect::<Vec<_>>() {
+            if self.apply_corporate_action(&action) {
+                self.announced.push(action);
+            }
+        }
+    }
+
+    /// Restate the book, auction, prices, stats and ledger for `action`,
+    /// then tell the agents. `false` if it no longer applies, or if a
+    /// position or count would overflow once restated.
+    fn apply_corporate_action(&mut self, action: &CorporateAction) -> bool {
+        let stock_id = action.stock_id();
+        if self.stocks.get_stock_by_id(stock_id).is_none() {
+            return false;
+        }
+        let mut dropped = Vec::new();
+        if let CorporateAction::Rename { ticker, .. } = action {
+            if self.stocks.get_stock_by_ticker(ticker).is_some() {
+                return false;
+            }
+        } else {
+            let fits = self.accounts.values().all(|a| {
+                a.position(stock_id)
+                    .is_none_or(|p| action.shares(p.qty).is_some())
+            }) && self
+                .cumulative_volume
+                .get(&stock_id)
+                .is_none_or(|&v| action.units(v).is_some());
+            if !fits {
+                return false;
+            }
+        }
+        // the listing first, as it refuses a split that overflows its float
+        // or a basket; `reset` restarts from the restated listing
+        if self.stocks.apply_corporate_action(action).is_none() {
+            return false;
+        }
+        if action.adjusts_prices() {
+            if let Some(book) = self.order_books.get_mut(&stock_id) {
+                // a ladder keeps its width, re-centred on the restated price:
+                // scaling it would allocate a level per cent of the new band
+                let kind = match book.kind() {
+                    BookKind::Ladder {
+                        min_price,
+                        max_price,
+                    } => {
+                        let width = max_price - min_price;
+                        let centre = match self.last_traded_price.get(&stock_id) {
+                            Some(&px) => action.price_cents((px * 100.0).round() as u64),
+                            None => action.price_cents(min_price + width / 2),
+                        };
+                        let min_price = centre.saturating_sub(width / 2).max(1);
+                        BookKind::Ladder {
+                            min_price,
+                            max_price: min_price + width,
+                        }
+                    }
+                    kind => kind,
+                };
+                let (restated, gone) = book.adjusted(kind, |o| action.adjust_order(o));
+                *book = restated;
+                dropped = gone;
+            }
+            if let Some(auction) = self.auctions.get_mut(&stock_id) {
+                dropped.extend(auction.adjust(action));
+            }
+            if let Some(px) = self.last_traded_price.get_mut(&stock_id) {
+                *px = action.price(*px);
+            }
+            if let Some(volume) = self.cumulative_volume.get_mut(&stock_id) {
+                *volume = action.units(*volume).expect("checked above");
+            }
+            if let Some(stats) = self.stats.get_mut(&stock_id) {
+                stats.adjust(action);
+            }
+            for account in self.accounts.values_mut() {
+                account.apply_corporate_action(action);
+            }
+            if let Some(f) = self.fundamentals.as_mut() {
+                f.apply_corporate_action(action);
+            }
+        }
+        self.log(JournalEvent::CorporateAction {
+            tick: self.tick,
+            action: action.clone(),
+        });
+        if action.adjusts_prices()
+            && let Some(book) = self.order_books.get(&stock_id)
+            && !self.feed_subscribers.is_empty()
+        {
+            let mut orders = book.resting_orders(Side::Buy);
+            orders.extend(book.resting_orders(Side::Sell));
+            let seq = book.feed_seq();
+            self.feed_subscribers.retain(|tx| {
+                tx.send(FeedMessage {
+                    stock_id,
+                    seq,
+                    event: FeedEvent::Snapshot {
+                        orders: orders.clone(),
+                    },
+                })
+                .is_ok()
+            });
+        }
+
+        dropped.sort_unstable_by_key(|o| o.id);
+        for o in &dropped {
+            if let Some(agent) = self.agents.get_mut(&o.agent_id) {
+                agent.cancel_acknowledged(o.id);
+            }
+        }
+        let mut ids = self.agent_ids();
+        ids.sort_unstable();
+        for id in ids {
+            self.agents.get_mut(&id).unwrap().corporate_action(action);
+        }
+        true
+    }
+
+    /// Drive `MarketView::sentiment` from a per-market engine seeded from
+    /// the market seed; one `cfg.tick_interval` passes per tick.
+    pub fn enable_sentiment(&mut self, cfg: &SentimentConfig) {
+        let ids = self.stocks.get_all_ids();
+        self.sentiment = Some(Sentiment::new(ids, cfg, sentiment_seed(self.seed)));
+    }
+
+    pub fn sentiment(&self) -> Option<&Sentiment> {
+        self.sentiment.as_ref()
+    }
+
+    pub fn sentiment_mut(&mut self) -> Option<&mut Sentiment> {
+        self.sentiment.as_mut()
+    }
+
+    /// Give every stock a latent value, starting at its last price and
+    /// stepped once per tick from a stream seeded from the market seed.
+    /// Informed agents read it through `MarketView::fundamental`.
+    pub fn enable_fundamentals(&mut self, cfg: &FundamentalConfig) {
+        let mut starts: Vec<(u64, f64)> = self
+            .last_traded_price
+            .iter()
+            .map(|(&id, &px)| (id, px))
+            .collect();
+        starts.sort_unstable_by_key(|&(id, _)| id);
+        self.fundamentals = Some(Fundamentals::new(starts, cfg, fundamental_seed(self.seed)));
+    }
+
+    pub fn fundamentals(&self) -> Option<&Fundamentals> {
+        self.fundamentals.as_ref()
+    }
+
+    pub fn fundamentals_mut(&mut self) -> Option<&mut Fundamentals> {
+        self.fundamentals.as_mut()
+    }
+
+    /// Latent value of `stock_id` in dollars.
+    pub fn fundamental(&self, stock_id: u64) -> Option<f64> {
+        self.fundamentals.as_ref()?.value(stock_id)
+    }
+
+    /// Mid (or last trade) minus fundamental value, sampled after every
+    /// batch.
+    pub fn discovery_error(&self, stock_id: u64) -> Option<&DiscoveryError> {
+        self.fundamentals.as_ref()?.error(stock_id)
+    }
+
+    /// Keep the last `capacity` trades on the tape (clears it).
+    pub fn set_tape_capacity(&mut self, capacity: usize) {
+        self.tape = TradeTape::with_capacity(capacity);
+    }
+
+    #[inline]
+    pub fn tape(&self) -> &TradeTape {
+        &self.tape
+    }
+
+    /// Every trade of the last `execute`, margin calls and uncrosses
+    /// included, in the order they printed. Unlike the tape this never
+    /// drops any.
+    pub fn last_trades(&self) -> &[Trade] {
+        &self.batch
+    }
+
+    pub fn stats(&self, stock_id: u64) -> Option<&StockStats> {
+        self.stats.get(&stock_id)
+    }
+
+    /// `agent_id`'s starting inventory and every fill, as the exchange
+    /// booked them.
+    pub fn account(&self, agent_id: usize) -> Option<&Portfolio> {
+        self.accounts.get(&agent_id)
+    }
+
+    /// What agents see when they decide, without asking them.
+    pub fn view(&self) -> MarketView<'_> {
+        MarketView {
+            order_books: &self.order_books,
+            stocks: &self.stocks,
+            tick: self.tick,
+            tape: Some(&self.tape),
+            stats: Some(&self.stats),
+            sentiment: self.sentiment.as_ref(),
+            fundamentals: self.fundamentals.as_ref(),
+            accounts: Some(&self.accounts),
+            agent_id: None,
+            corporate_actions: &self.announced,
+            nav: Some(&self.nav),
+        }
+    }
+
+    /// Number of ticks begun, whether by `step` or by `decide` (the live and
+    /// FIX drivers never call `step`).
+    #[inline]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn cumulative_volume(&self, stock_id: u64) -> Option<u64> {
+        self.cumulative_volume.get(&stock_id).copied()
+    }
+
+    pub fn total_inventory(&self) -> i64 {
+        self.agents.values().map(|a| a.get_inventory()).sum()
+    }
+
+    /// Ids of the current participants, unordered.
+    pub fn agent_ids(&self) -> Vec<usize> {
+        self.agents.keys().copied().collect()
+    }
+
+    #[inline]
+    pub fn stocks(&self) -> &StockMarket {
+        &self.stocks
+    }
+
+    /// Replace the participant table wholesale, returning the old one.
+    pub(crate) fn swap_agents(
+        &mut self,
+        agents: HashMap<usize, Box<dyn Agent>>,
+    ) -> HashMap<usize, Box<dyn Agent>> {
+        std::mem::replace(&mut self.agents, agents)
+    }
+    #[inline]
+    pub fn order_books(&self) -> &HashMap<u64, OrderBook> {
+        &self.order_books
+    }
+
+    #[inline]
+    pub fn last_price(&self, stock_id: u64) -> f64 {
+        *self.last_traded_price.get(&stock_id).unwrap_or(&150.0)
+    }
+
+    #[inline]
+    pub fn last_price_map_iter(&self) -> impl Iterator<Item = (&u64, &f64)> {
+        self.last_traded_price.iter()
+    }
+    pub fn ticker(&self, stock_id: u64) -> &str {
+        self.stocks
+            .get_ticker_by_id(stock_id)
+            .map(String::as_str)
+            .unwrap_or("??")
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Stepping in phases (what `step` runs back to back)
+// -----------------------------------------------------------------------------
+impl Market {
+    /// First half of `step`: advance the clock and collect every agent's
+    /// requests (agents in id order) without touching the books.
+    pub fn decide(&mut self) -> Vec<OrderRequest> {
+        self.begin_tick();
+
+        /* -------- Phase 1: agent decisions -------- */
+        let view = MarketView {
+            order_books: &self.order_books,
+            stocks: &self.stocks,
+            tick: self.tick,
+            tape: Some(&self.tape),
+            stats: Some(&self.stats),
+            sentiment: self.sentiment.as_ref(),
+            fundamentals: self.fundamentals.as_ref(),
+            accounts: Some(&self.accounts),
+            agent_id: None,
+            corporate_actions: &self.announced,
+            nav: Some(&self.nav),
+        };
+
+        let mut agents: Vec<(usize, &mut Box<dyn Agent>)> =
+            self.agents.iter_mut().map(|(&id, a)| (id, a)).collect();
+        agents.sort_unstable_by_key(|(id, _)| *id);
+
+        if self.threads <= 1 || agents.len() < 2 {
+            return agents
+                .into_iter()
+                .flat_map(|(id, a)| a.decide_actions(&view.for_agent(id)))
+                .collect();
+        }
+
+        // contiguous id-ordered chunks, concatenated back in order
+        let per_thread = agents.len().div_ceil(self.threads);
+        thread::scope(|scope| {
+            let workers: Vec<_> = agents
+                .chunks_mut(per_thread)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter_mut()
+                            .flat_map(|(id, a)| a.decide_actions(&view.for_agent(*id)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().expect("agent thread panicked"))
+                .collect()
+        })
+    }
+
+    /// Advance the clock without asking agents; live mode pairs this with
+    /// `execute` for every batch it drains.
+    pub(crate) fn begin_tick(&mut self) {
+        self.tick += 1;
+        if let Some(s) = self.sentiment.as_mut() {
+            s.step();
+        }
+        if let Some(f) = self.fundamentals.as_mut() {
+            f.step();
+        }
+        self.apply_corporate_actions();
+        if !self.announced.is_empty() {
+            self.refresh_nav();
+        }
+    }
+
+    /// Second half of `step`: match `requests` in order, run margin calls,
+    /// settle fills and publish. Returns the same price `step` does.
+    pub fn execute(&mut self, requests: Vec<OrderRequest>) -> f64 {
+        let mut ids: Vec<_> = self.agents.keys().copied().collect();
+        ids.sort_unstable();
+
+        /* -------- Phase 2: execute orders -------- */
+        let routes = self.route(&requests);
+        let mut outcomes: Vec<Outcome> = Vec::with_capacity(requests.len());
+        outcomes.resize_with(requests.len(), Outcome::default);
+
+        // stocks still in their opening auction collect instead of matching
+        let mut auctioned = vec![false; requests.len()];
+        if !self.auctions.is_empty() {
+            for (i, req) in requests.iter().enumerate() {
+                let (stock_id, order_id) = routes[i];
+                if let Some(auction) = stock_id.and_then(|s| self.auctions.get_mut(&s)) {
+                    outcomes[i] = Self::collect(auction, req, order_id);
+                    auctioned[i] = true;
+                }
+            }
+        }
+
+        if self.threads <= 1 {
+            for (i, req) in requests.iter().enumerate() {
+                let (stock_id, order_id) = routes[i];
+                if !auctioned[i]
+                    && let Some(book) = stock_id.and_then(|s| self.order_books.get_mut(&s))
+                {
+                    outcomes[i] = Self::apply(book, req, order_id);
+                }
+            }
+        } else {
+            // each book sees its own requests in their original order
+            let mut by_stock: HashMap<u64, Vec<usize>> = HashMap::new();
+            for (i, (stock_id, _)) in routes.iter().enumerate() {
+                if let Some(s) = stock_id
+                    && !auctioned[i]
+                {
+                    by_stock.entry(*s).or_default().push(i);
+                }
+            }
+            let mut jobs: Vec<(&mut OrderBook, Vec<usize>)> = self
+                .order_books
+                .iter_mut()
+                .filter_map(|(s, book)| Some((book, by_stock.remove(s)?)))
+                .collect();
+            let per_thread = jobs.len().div_ceil(self.threads).max(1);
+            let (requests, routes) = (&requests, &routes);
+            let done: Vec<(usize, Outcome)> = thread::scope(|scope| {
+                let workers: Vec<_> = jobs
+                    .chunks_mut(per_thread)
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            let mut out = Vec::new();
+                            for (book, idx) in chunk.iter_mut() {
+                                for &i in idx.iter() {
+                                    out.push((i, Self::apply(book, &requests[i], routes[i].1)));
+                                }
+                            }
+                            out
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().expect("matching thread panicked"))
+                    .collect()
+            });
+            for (i, out) in done {
+                outcomes[i] = out;
+            }
+        }
+
+        // deterministic merge: request order, exactly as a serial pass
+        let mut trades = Vec::<Trade>::new();
+        let merged = requests
+            .into_iter()
+            .zip(routes)
+            .zip(outcomes)
+            .zip(auctioned);
+        for (((req, (stock_id, order_id)), out), auctioned) in merged {
+            let routed = stock_id.is_some_and(|s| self.order_books.contains_key(&s));
+            let rejected = match (routed, &req) {
+                (
+                    false,
+                    OrderRequest::LimitOrder { .. }
+                    | OrderRequest::MarketOrder { .. }
+                    | OrderRequest::CreateUnits { .. }
+                    | OrderRequest::RedeemUnits { .. },
+                ) => Some(RejectReason::UnknownStock),
+                (false, _) => Some(RejectReason::UnknownOrder),
+                (true, OrderRequest::CreateUnits { .. } | OrderRequest::RedeemUnits { .. })
+                    if out.rejected.is_none() =>
+                {
+                    self.settle_units(&req, &trades).err()
+                }
+                (true, _) => out.rejected,
+            };
+            if let Some(reason) = rejected {
+                if let Some(agent) = self.agents.get_mut(&Self::requester(&req)) {
+                    agent.order_rejected(&req, reason);
+                }
+            } else if let OrderRequest::CancelOrder { agent_id, order_id } = req
+                && let Some(agent) = self.agents.get_mut(&agent_id)
+            {
+                agent.cancel_acknowledged(order_id);
+            }
+            let ack = match req {
+                _ if rejected.is_some() => None,
+                OrderRequest::LimitOrder { .. } | OrderRequest::MarketOrder { .. } => {
+                    self.entry_ack(&req, order_id)
+                }
+                _ => out.ack,
+            };
+            if let Some(o) = ack
+                && let Some(agent) = self.agents.get_mut(&o.agent_id)
+            {
+                agent.acknowledge_order(o);
+            }
+            let cancelled = match req {
+                OrderRequest::CancelOrder { agent_id, order_id } => {
+                    Some((agent_id, order_id, out.cancelled.unwrap_or(false)))
+                }
+                _ => None,
+            };
+            // auction orders reach the journal through the uncross
+            if !auctioned {
+                self.journal_request(order_id, req, &out.fills, cancelled);
+            }
+            trades.extend(out.fills);
+        }
+        trades.extend(self.uncross_auctions());
+
+        /* -------- Phase 3: margin calls -------- */
+        let mut margin = Vec::<OrderRequest>::new();
+        for id in ids.iter().filter(|id| !self.suspended.contains(id)) {
+            if let Some(a) = self.agents.get_mut(id) {
+                margin.extend(a.margin_call());
+            }
+        }
+        for req in margin {
+            if let OrderRequest::MarketOrder {
+                agent_id,
+                stock_id,
+                side,
+                volume,
+            } = req
+                && !self.halted.contains(&stock_id)
+                && let Some(book) = self.order_books.get_mut(&stock_id)
+            {
+                let fills = book.process_market_order(agent_id, side, volume);
+                self.journal_request(None, req, &fills, None);
+                trades.extend(fills);
+            }
+        }
+
+        /* -------- Phase 4: update portfolios -------- */
+        for tr in &trades {
+            let taker_delta = if tr.taker_side == Side::Buy {
+                tr.volume as i64
+            } else {
+                -(tr.volume as i64)
+            };
+            for (agent_id, delta) in [
+                (tr.taker_agent_id, taker_delta),
+                (tr.maker_agent_id, -taker_delta),
+            ] {
+                self.accounts
+                    .entry(agent_id)
+                    .or_default()
+                    .on_trade(delta, tr);
+                if let Some(agent) = self.agents.get_mut(&agent_id) {
+                    agent.update_portfolio(delta, tr);
+                }
+            }
+        }
+
+        /* -------- Phase 5: book-level bookkeeping -------- */
+        if let Some(last) = trades.last() {
+            self.last_traded_price
+                .insert(last.stock_id, last.price as f64 / 100.0);
+        }
+        for tr in &trades {
+            *self.cumulative_volume.entry(tr.stock_id).or_insert(0) += tr.volume;
+            self.stats
+                .entry(tr.stock_id)
+                .or_default()
+                .record(self.tick, tr);
+            self.tape.record(self.tick, *tr);
+        }
+        self.batch = trades;
+
+        self.refresh_nav();
+        self.record_discovery_error();
+        self.journal_flush();
+        self.publish_feed();
+
+        /* Return any price (first) for backward compatibility */
+        self.last_traded_price
+            .values()
+            .next()
+            .copied()
+            .unwrap_or(150.0)
+    }
+
+    /// Hand out order ids in request order and pick the book each request
+    /// touches. Cancels and modifies follow their order, including orders
+    /// created earlier in the same batch.
+    fn route(&mut self, requests: &[OrderRequest]) -> Vec<(Option<u64>, Option<u64>)> {
+        let mut born: HashMap<u64, u64> = HashMap::new();
+        let mut routes = Vec::with_capacity(requests.len());
+        for req in requests {
+            let route = match *req {
+                OrderRequest::LimitOrder { stock_id, .. }
+                | OrderRequest::MarketOrder { stock_id, .. } => {
+                    let id = self.next_order_id();
+                    born.insert(id, stock_id);
+                    (Some(stock_id), Some(id))
+                }
+                OrderRequest::CancelOrder { order_id, .. } => (self.book_of(order_id, &born), None),
+                // settled in the merge, never matched
+                OrderRequest::CreateUnits { stock_id, .. }
+                | OrderRequest::RedeemUnits { stock_id, .. } => (Some(stock_id), None),
+                OrderRequest::ModifyOrder { order_id, .. } => {
+                    // the fresh id is only used if the order loses priority
+                    let stock_id = self.book_of(order_id, &born);
+                    let new_id = self.next_order_id();
+                    if let Some(s) = stock_id {
+                        born.insert(new_id, s);
+                    }
+                    (stock_id, Some(new_id))
+                }
+            };
+            routes.push(route);
+        }
+        routes
+    }
+
+    fn book_of(&self, order_id: u64, born: &HashMap<u64, u64>) -> Option<u64> {
+        born.get(&order_id)
+            .copied()
+            .or_else(|| {
+                self.order_books
+                    .iter()
+                    .find(|(_, book)| book.order(order_id).is_some())
+                    .map(|(&s, _)| s)
+            })
+            .or_else(|| {
+                self.auctions
+                    .iter()
+                    .find(|(_, a)| a.order(order_id).is_some())
+                    .map(|(&s, _)| s)
+            })
+    }
+
+    /// Run one routed request against an opening auction.
+    fn collect(auction: &mut CallAuction, req: &OrderRequest, order_id: Option<u64>) -> Outcome {
+        match *req {
+            OrderRequest::LimitOrder {
+                agent_id,
+                stock_id,
+                side,
+                price,
+                volume,
+            } => {
+                auction.add(Order {
+                    id: order_id.unwrap_or_default(),
+                    agent_id,
+                    stock_id,
+                    side,
+                    price,
+                    volume,
+                    filled: 0,
+                });
+                Outcome::default()
+            }
+            OrderRequest::MarketOrder { .. }
+            | OrderRequest::CreateUnits { .. }
+            | OrderRequest::RedeemUnits { .. } => Outcome {
+                rejected: Some(RejectReason::NotTrading),
+                ..Outcome::default()
+            },
+            OrderRequest::CancelOrder { agent_id, order_id } => {
+                let done = auction.cancel(order_id, agent_id);
+                Outcome {
+                    cancelled: Some(done),
+                    rejected: (!done).then_some(RejectReason::UnknownOrder),
+                    ..Outcome::default()
+                }
+            }
+            OrderRequest::ModifyOrder {
+                agent_id,
+                order_id,
+                price,
+                volume,
+            } => match auction.modify(order_id, agent_id, price, volume) {
+                Some(o) => Outcome {
+                    ack: Some(o),
+                    ..Outcome::default()
+                },
+                None => Outcome {
+                    rejected: Some(RejectReason::UnknownOrder),
+                    ..Outcome::default()
+                },
+            },
+        }
+    }
+
+    /// Cross every auction whose last batch this was; the unfilled rest of
+    /// each goes into the (empty) book in arrival order.
+    fn uncross_auctions(&mut self) -> Vec<Trade> {
+        let mut due: Vec<u64> = self
+            .auctions
+            .iter()
+            .filter(|(_, a)| a.ends_at() <= self.tick)
+            .map(|(&s, _)| s)
+            .collect();
+        due.sort_unstable();
+
+        let tick = self.tick;
+        let mut trades = Vec::new();
+        for stock_id in due {
+            let (price, prints, rest) = self.auctions.remove(&stock_id).unwrap().uncross();
+            self.log(JournalEvent::Uncross {
+                tick,
+                stock_id,
+                price,
+                trades: prints.clone(),
+            });
+            trades.extend(prints);
+            if let Some(book) = self.order_books.get_mut(&stock_id) {
+                for mut order in rest.iter().copied() {
+                    // nothing left crosses, so this only rests the order
+                    trades.extend(book.process_limit_order(&mut order));
+                }
+            }
+            for order in rest {
+                self.log(JournalEvent::Resting { tick, order });
+            }
+        }
+        trades
+    }
+
+    /// Run one routed request against its book.
+    fn apply(book: &mut OrderBook, req: &OrderRequest, order_id: Option<u64>) -> Outcome {
+        match *req {
+            OrderRequest::LimitOrder {
+                agent_id,
+                stock_id,
+                side,
+                price,
+                volume,
+            } => {
+                let mut o = Order {
+                    id: order_id.unwrap_or_default(),
+                    agent_id,
+                    stock_id,
+                    side,
+                    price,
+                    volume,
+                    filled: 0,
+                };
+                Outcome {
+                    fills: book.process_limit_order(&mut o),
+                    ..Outcome::default()
+                }
+            }
+            OrderRequest::MarketOrder {
+                agent_id,
+                side,
+                volume,
+                ..
+            } => Outcome {
+                fills: book.process_market_order(agent_id, side, volume),
+                ..Outcome::default()
+            },
+            OrderRequest::CancelOrder { agent_id, order_id } => {
+                let done = book.cancel_order(order_id, agent_id);
+                Outcome {
+                    cancelled: Some(done),
+                    rejected: (!done).then_some(RejectReason::UnknownOrder),
+                    ..Outcome::default()
+                }
+            }
+            OrderRequest::ModifyOrder {
+                agent_id,
+                order_id: old_id,
+                price,
+                volume,
+            } => {
+                let new_id = order_id.unwrap_or_default();
+                let Some(fills) = book.modify_order(old_id, agent_id, price, volume, new_id) else {
+                    return Outcome {
+                        rejected: Some(RejectReason::UnknownOrder),
+                        ..Outcome::default()
+                    };
+                };
+                Outcome {
+                    fills,
+                    ack: book.order(new_id).or_else(|| book.order(old_id)),
+                    ..Outcome::default()
+                }
+            }
+            OrderRequest::CreateUnits { .. } | OrderRequest::RedeemUnits { .. } => {
+                Outcome::default()
+            }
+        }
+    }
+
+    fn requester(req: &OrderRequest) -> usize {
+        match *req {
+            OrderRequest::LimitOrder { agent_id, .. }
+            | OrderRequest::MarketOrder { agent_id, .. }
+            | OrderRequest::CancelOrder { agent_id, .. }
+            | OrderRequest::ModifyOrder { agent_id, .. }
+            | OrderRequest::CreateUnits { agent_id, .. }
+            | OrderRequest::RedeemUnits { agent_id, .. } => agent_id,
+        }
+    }
+
+    /// The order an agent is told about when it submits a new order.
+    fn entry_ack(&self, req: &OrderRequest, order_id: Option<u64>) -> Option<Order> {
+        let (agent_id, stock_id, side, price, volume) = match *req {
+            OrderRequest::LimitOrder {
+                agent_id,
+                stock_id,
+                side,
+                price,
+                volume,
+            } => (agent_id, stock_id, side, price, volume),
+            OrderRequest::MarketOrder {
+                agent_id,
+                stock_id,
+                side,
+                volume,
+            } => {
+                let px_cents = (self
+                    .last_traded_price
+                    .get(&stock_id)
+                    .copied()
+                    .unwrap_or(150.0)
+                    * 100.0)
+                    .round() as u64;
+                (agent_id, stock_id, side, px_cents, volume)
+            }
+            _ => return None,
+        };
+        Some(Order {
+            id: order_id?,
+            agent_id,
+            stock_id,
+            side,
+            price,
+            volume,
+            filled: 0,
+        })
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Marketable
+// -----------------------------------------------------------------------------
+impl Marketable for Market {
+    fn step(&mut self) -> f64 {
+        let requests = self.decide();
+        self.execute(requests)
+    }
+
+    fn current_price(&self) -> f64 {
+        self.last_traded_price
+            .values()
+            .next()
+            .copied()
+            .unwrap_or(150.0)
+    }
+
+    fn reset(&mut self) {
+        /* agents */
+        self.agents = self
+            .initial_agent_types
+            .iter()
+            .enumerate()
+            .map(|(id, t)| {
+                let params = self.agent_params.get(&id).cloned().unwrap_or_default();
+                (id, Self::spawn_agent(*t, id, self.seed, &params))
+            })
+            .collect();
+
+        /* per-symbol state */
+        // fresh books
+        self.auctions.clear();
+        for book in self.order_books.values_mut() {
+            *book = OrderBook::with_kind(book.kind(), 0);
+        }
+        // feed consumers start over from an empty snapshot
+        if !self.feed_subscribers.is_empty() {
+            let mut stock_ids: Vec<u64> = self.order_books.keys().copied().collect();
+            stock_ids.sort_unstable();
+            for &id in &stock_ids {
+                self.order_books.get_mut(&id).unwrap().enable_feed();
+            }
+            self.feed_subscribers.retain(|tx| {
+                stock_ids.iter().all(|&stock_id| {
+                    tx.send(FeedMessage {
+                        stock_id,
+                        seq: 0,
+                        event: FeedEvent::Snapshot { orders: Vec::new() },
+                    })
+                    .is_ok()
+                })
+            });
+        }
+
+        // restore initial prices **per instrument** (instead of hard-coding 150)
+        for s in self.stocks.get_all_stocks() {
+            self.last_traded_price.insert(s.id, s.initial_price);
+            self.cumulative_volume.insert(s.id, 0);
+            self.stats.insert(s.id, StockStats::default());
+        }
+        self.tape.clear();
+        self.batch.clear();
+        self.announced.clear();
+        self.accounts.clear();
+        self.halted.clear();
+        self.suspended.clear();
+        for id in 0..self.initial_agent_types.len() {
+            self.introduce_all(id);
+        }
+        self.refresh_nav();
+        if let Some(s) = self.sentiment.as_mut() {
+            s.reset(sentiment_seed(self.seed));
+        }
+        if let Some(f) = self.fundamentals.as_mut() {
+            f.reset(fundamental_seed(self.seed));
+        }
+
+        self.order_id_counter = 0;
+        self.tick = 0;
+        self.journal_open_session(false);
     }
 
     fn get_order_book(&self) -> Option<&OrderBook> {
@@ -303,3 +1919,688 @@ impl Marketable for Market {
         self.step();
     }
 }
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BookBuilder, FundamentalProcess, agents::latency::ETF_ARB_TICKS_UNTIL_ACTIVE,
+        journal::diff_books,
+    };
+
+    const PARTICIPANTS: &[AgentType] = &[
+        AgentType::MarketMaker,
+        AgentType::DumbLimit,
+        AgentType::DumbMarket,
+        AgentType::WhaleAgent,
+    ];
+
+    /* everything an outside observer can see about one instrument:
+    (id, last px, cum vol, best bid, best ask) */
+    type Snapshot = (u64, u64, u64, Option<u64>, Option<u64>);
+
+    fn snapshot(m: &Market) -> Vec<Snapshot> {
+        let mut ids = m.stocks.get_all_ids();
+        ids.sort_unstable();
+        ids.into_iter()
+            .map(|id| {
+                let (bid, ask) = m.order_book(id).unwrap().best_bid_ask();
+                (
+                    id,
+                    (m.last_price(id) * 100.0).round() as u64,
+                    m.cumulative_volume(id).unwrap(),
+                    bid,
+                    ask,
+                )
+            })
+            .collect()
+    }
+
+    fn warmed_up(seed: u64) -> Market {
+        let mut m = Market::with_seed(PARTICIPANTS, StockMarket::new(), seed);
+        for _ in 0..40 {
+            m.step();
+        }
+        m
+    }
+
+    #[test]
+    fn fork_evolves_identically() {
+        let mut parent = warmed_up(7);
+        let mut fork = parent.fork();
+        assert_eq!(snapshot(&parent), snapshot(&fork));
+
+        for _ in 0..40 {
+            parent.step();
+            fork.step();
+            assert_eq!(snapshot(&parent), snapshot(&fork));
+            assert_eq!(parent.total_inventory(), fork.total_inventory());
+        }
+    }
+
+    #[test]
+    fn stepping_fork_leaves_parent_untouched() {
+        let parent = warmed_up(11);
+        let before = snapshot(&parent);
+        let mut fork = parent.fork();
+        for _ in 0..20 {
+            fork.step();
+        }
+        assert_eq!(snapshot(&parent), before);
+    }
+
+    #[test]
+    fn fork_with_seed_diverges() {
+        let parent = warmed_up(3);
+        let mut same = parent.fork();
+        let mut other = parent.fork_with_seed(4);
+        assert_eq!(other.seed(), 4);
+
+        let mut diverged = false;
+        for _ in 0..40 {
+            same.step();
+            other.step();
+            diverged |= snapshot(&same) != snapshot(&other);
+        }
+        assert!(diverged, "override seed should change the rollout");
+    }
+
+    #[test]
+    fn same_seed_is_reproducible() {
+        let a = warmed_up(21);
+        let b = warmed_up(21);
+        assert_eq!(snapshot(&a), snapshot(&b));
+    }
+
+    #[test]
+    fn feed_rebuilds_live_books() {
+        let mut m = warmed_up(5);
+        let rx = m.subscribe_feed();
+        let mut builder = BookBuilder::new();
+        for _ in 0..40 {
+            m.step();
+            for msg in rx.try_iter() {
+                builder.apply(&msg).unwrap();
+            }
+            assert!(diff_books(m.order_books(), builder.books()).is_empty());
+        }
+
+        m.reset();
+        m.step();
+        for msg in rx.try_iter() {
+            builder.apply(&msg).unwrap();
+        }
+        assert!(diff_books(m.order_books(), builder.books()).is_empty());
+    }
+
+    #[test]
+    fn dropped_subscriber_turns_feed_off() {
+        let mut m = warmed_up(9);
+        drop(m.subscribe_feed());
+        m.step();
+        assert!(m.order_books().values().all(|b| !b.feed_enabled()));
+        assert!(m.fork().order_books().values().all(|b| !b.feed_enabled()));
+    }
+
+    #[test]
+    fn ladder_books_trade_like_tree_books() {
+        let mut tree = warmed_up(21);
+        let mut ladder = tree.fork();
+        for id in ladder.stocks.get_all_ids() {
+            let kind = BookKind::Ladder {
+                min_price: 100,
+                max_price: 300_000,
+            };
+            assert!(ladder.set_book_kind(id, kind));
+        }
+        for _ in 0..40 {
+            tree.step();
+            ladder.step();
+            assert_eq!(snapshot(&tree), snapshot(&ladder));
+        }
+        ladder.reset();
+        assert!(
+            ladder
+                .order_books()
+                .values()
+                .all(|b| matches!(b.kind(), BookKind::Ladder { .. }))
+        );
+    }
+
+    #[test]
+    fn parallel_step_matches_serial() {
+        let mut stocks = StockMarket::new();
+        for id in 100..106 {
+            stocks.add_stock(Stock::new(format!("T{id}"), id, "test", 1_000_000, 20.0));
+        }
+        let mut serial = Market::with_seed(PARTICIPANTS, stocks, 13);
+        for _ in 0..20 {
+            serial.step();
+        }
+        let mut parallel = serial.fork();
+        parallel.set_threads(4);
+        let (rx_s, rx_p) = (serial.subscribe_feed(), parallel.subscribe_feed());
+
+        for _ in 0..60 {
+            serial.step();
+            parallel.step();
+            assert_eq!(snapshot(&serial), snapshot(&parallel));
+            assert_eq!(serial.total_inventory(), parallel.total_inventory());
+            let a: Vec<_> = rx_s.try_iter().collect();
+            let b: Vec<_> = rx_p.try_iter().collect();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn view_exposes_tape_stats_and_own_account() {
+        let mut m = warmed_up(11);
+        m.enable_sentiment(&SentimentConfig {
+            tick_interval: std::time::Duration::from_millis(10),
+            spike_prob: 0.3,
+            half_life: std::time::Duration::from_millis(50),
+        });
+        let mut twin = m.fork();
+        for _ in 0..40 {
+            m.step();
+            twin.step();
+        }
+
+        let view = m.view().for_agent(0); // the market maker
+        assert_eq!(view.tick, m.tick());
+        let tape = view.tape.unwrap();
+        assert!(!tape.is_empty());
+        assert!(tape.iter().all(|p| p.tick <= m.tick()));
+        for id in m.stocks().get_all_ids() {
+            let stats = view.stats(id).unwrap();
+            assert_eq!(Some(stats.volume), m.cumulative_volume(id));
+            assert!((-1.0..=1.0).contains(&view.sentiment(id)));
+            assert_eq!(view.sentiment(id), twin.view().sentiment(id));
+        }
+
+        // the exchange's ledger agrees with the agent's own books
+        let account = view.account().unwrap();
+        let own = m.agents[&0].portfolio().unwrap();
+        for (stock_id, pos) in account.positions() {
+            assert_eq!(pos.qty, own.qty(stock_id));
+        }
+        assert!((account.cash() - own.cash() + 100_000_000_000.0).abs() < 1.0);
+
+        let open = view.open_orders();
+        assert!(!open.is_empty());
+        for o in &open {
+            assert_eq!(o.agent_id, 0);
+            assert_eq!(m.order_books[&o.stock_id].order(o.id), Some(*o));
+        }
+        assert!(m.view().open_orders().is_empty(), "no agent, no orders");
+    }
+
+    fn limit(agent_id: usize, side: Side, price: u64, volume: u64) -> OrderRequest {
+        OrderRequest::LimitOrder {
+            agent_id,
+            stock_id: 999,
+            side,
+            price,
+            volume,
+        }
+    }
+
+    #[test]
+    fn listing_opens_with_a_single_price_auction() {
+        let mut m = Market::with_seed(&[], StockMarket::new(), 1);
+        m.step();
+        let rx = m.subscribe_feed();
+        let mut builder = BookBuilder::new();
+
+        let newco = Stock::new("NEWCO", 999, "New Co", 1_000_000, 25.0);
+        m.list_stock(newco.clone(), Listing::Auction { ticks: 2 })
+            .unwrap();
+        assert!(matches!(
+            m.list_stock(newco, Listing::Continuous),
+            Err(UniverseError::DuplicateId { id: 999, .. })
+        ));
+        assert_eq!(m.ticker(999), "NEWCO");
+
+        // first batch: limits collect, the market order bounces
+        m.decide();
+        m.execute(vec![
+            limit(100, Side::Buy, 2_510, 300),
+            limit(101, Side::Sell, 2_490, 200),
+            OrderRequest::MarketOrder {
+                agent_id: 100,
+                stock_id: 999,
+                side: Side::Buy,
+                volume: 50,
+            },
+        ]);
+        assert_eq!(m.auction(999).unwrap().orders().len(), 2);
+        assert_eq!(m.order_book(999).unwrap().best_bid_ask(), (None, None));
+        assert!(m.account(100).is_none());
+
+        // last batch: 200 clear at the reference, the rest opens the book
+        m.decide();
+        m.execute(vec![limit(101, Side::Sell, 2_520, 100)]);
+        assert!(m.auction(999).is_none());
+        let prints: Vec<&Trade> = m.tape().since(m.tick()).map(|p| &p.trade).collect();
+        assert!(prints.iter().all(|t| t.stock_id == 999 && t.price == 2_500));
+        assert_eq!(prints.iter().map(|t| t.volume).sum::<u64>(), 200);
+        assert_eq!(m.cumulative_volume(999), Some(200));
+        assert_eq!(m.account(100).unwrap().qty(999), 200);
+        assert_eq!(m.account(101).unwrap().qty(999), -200);
+        assert_eq!(
+            m.order_book(999).unwrap().best_bid_ask(),
+            (Some(2_510), Some(2_520))
+        );
+
+        for msg in rx.try_iter() {
+            builder.apply(&msg).unwrap();
+        }
+        assert!(diff_books(m.order_books(), builder.books()).is_empty());
+    }
+
+    #[test]
+    fn starting_inventory_is_priced_and_on_the_ledger() {
+        use crate::agents::config::MM_INITIAL_INVENTORY;
+
+        let mut m = Market::with_seed(&[AgentType::MarketMaker], StockMarket::new(), 2);
+        let newco = Stock::new("NEWC", 4_242, "NewCo", 1_000, 12.5);
+        m.list_stock(newco, Listing::Continuous).unwrap();
+
+        let stocks: Vec<(u64, f64)> = m
+            .stocks()
+            .get_all_stocks()
+            .iter()
+            .map(|s| (s.id, s.initial_price))
+            .collect();
+        let own = m.agents[&0].portfolio().unwrap().clone();
+        let ledger = m.account(0).unwrap();
+        let mut worth = own.cash();
+        for &(id, price) in &stocks {
+            assert_eq!(own.qty(id), MM_INITIAL_INVENTORY);
+            assert_eq!(ledger.qty(id), MM_INITIAL_INVENTORY);
+            worth += MM_INITIAL_INVENTORY as f64 * price;
+        }
+        assert_eq!(
+            m.total_inventory(),
+            MM_INITIAL_INVENTORY * stocks.len() as i64
+        );
+        // empty books: everything is marked at its initial price
+        assert!((own.evaluate(&m.view()) - worth).abs() < 1e-3 * worth.abs());
+
+        m.reset();
+        assert_eq!(m.account(0).unwrap().qty(4_242), MM_INITIAL_INVENTORY);
+    }
+
+    #[test]
+    fn delisting_cancels_orders_and_settles_positions() {
+        let mut m = warmed_up(17);
+        let rx = m.subscribe_feed();
+        let mut builder = BookBuilder::new();
+        let stock_id = m.stocks().get_all_ids()[0];
+        let book = m.order_book(stock_id).unwrap();
+        let resting = book.resting_orders(Side::Buy).len() + book.resting_orders(Side::Sell).len();
+        let held: i64 = m.accounts.values().map(|a| a.qty(stock_id).abs()).sum();
+        assert!(resting > 0 && held > 0);
+
+        let d = m.delist_stock(stock_id, Some(10.0)).unwrap();
+        assert_eq!(d.cancelled.len(), resting);
+        assert_eq!(d.settled.iter().map(|(_, q)| q.abs()).sum::<i64>(), held);
+        assert!(m.delist_stock(stock_id, None).is_none());
+
+        assert!(m.order_book(stock_id).is_none());
+        assert!(m.stocks().get_stock_by_id(stock_id).is_none());
+        assert!(m.accounts.values().all(|a| a.qty(stock_id) == 0));
+        for agent in m.agents.values() {
+            assert_eq!(agent.portfolio().unwrap().qty(stock_id), 0);
+            assert!(
+                agent
+                    .get_pending_orders()
+                    .iter()
+                    .all(|o| o.stock_id != stock_id)
+            );
+        }
+
+        for _ in 0..20 {
+            m.step();
+        }
+        assert!(
+            m.tape()
+                .since(m.tick() - 19)
+                .all(|p| p.trade.stock_id != stock_id)
+        );
+        for msg in rx.try_iter() {
+            builder.apply(&msg).unwrap();
+        }
+        assert!(diff_books(m.order_books(), builder.books()).is_empty());
+    }
+
+    #[test]
+    fn corporate_actions_restate_books_ledgers_and_agents() {
+        let mut m = warmed_up(29);
+        let rx = m.subscribe_feed();
+        let mut builder = BookBuilder::new();
+        let stock_id = m.stocks().get_all_ids()[0];
+        let other = m.stocks().get_all_ids()[1];
+        let next = m.tick() + 1;
+
+        let split = CorporateAction::Split {
+            stock_id,
+            new_shares: 2,
+            old_shares: 1,
+        };
+        let taken = CorporateAction::Rename {
+            stock_id,
+            ticker: m.ticker(other).to_string(),
+        };
+        assert!(matches!(
+            m.schedule_corporate_action(m.tick(), split.clone()),
+            Err(CorporateActionError::InThePast { .. })
+        ));
+        assert!(matches!(
+            m.schedule_corporate_action(next, taken),
+            Err(CorporateActionError::TickerTaken(_))
+        ));
+        let dividend = CorporateAction::CashDividend {
+            stock_id,
+            amount: 0.25,
+        };
+        let rename = CorporateAction::Rename {
+            stock_id,
+            ticker: "SPLT".into(),
+        };
+        m.schedule_corporate_action(next + 1, dividend.clone())
+            .unwrap();
+        m.schedule_corporate_action(next, split.clone()).unwrap();
+        m.schedule_corporate_action(next, rename.clone()).unwrap();
+        assert_eq!(m.scheduled_corporate_actions().len(), 3);
+
+        // the split: half the price, twice the shares, before anyone decides
+        let (bid, ask) = m.order_book(stock_id).unwrap().best_bid_ask();
+        let qty: Vec<(usize, i64)> = m
+            .accounts
+            .iter()
+            .map(|(&a, p)| (a, p.qty(stock_id)))
+            .collect();
+        let px = m.last_price(stock_id);
+        let requests = m.decide();
+        assert_eq!(m.view().corporate_actions, [split, rename]);
+        assert_eq!(m.ticker(stock_id), "SPLT");
+        assert_eq!(
+            m.order_book(stock_id).unwrap().best_bid_ask(),
+            (bid.map(|b| b / 2), ask.map(|a| a.div_ceil(2)))
+        );
+        assert_eq!(m.last_price(stock_id), px / 2.0);
+        for (agent_id, before) in qty {
+            assert_eq!(m.accounts[&agent_id].qty(stock_id), before * 2);
+        }
+        m.execute(requests);
+
+        // the dividend: cash per share held, prices a quarter lower
+        let cash: Vec<(usize, f64, i64)> = m
+            .accounts
+            .iter()
+            .map(|(&a, p)| (a, p.cash(), p.qty(stock_id)))
+            .collect();
+        let px = m.last_price(stock_id);
+        let requests = m.decide();
+        assert_eq!(m.corporate_actions(), [dividend]);
+        assert!((m.last_price(stock_id) - (px - 0.25)).abs() < 1e-9);
+        for (agent_id, before, held) in cash {
+            let paid = m.accounts[&agent_id].cash() - before;
+            assert!((paid - held as f64 * 0.25).abs() < 1e-6);
+        }
+        m.execute(requests);
+        assert!(m.scheduled_corporate_actions().is_empty());
+
+        // every agent kept its own books in step with the exchange
+        let own = m.agents[&0].portfolio().unwrap();
+        assert_eq!(own.qty(stock_id), m.accounts[&0].qty(stock_id));
+        for msg in rx.try_iter() {
+            builder.apply(&msg).unwrap();
+        }
+        assert!(diff_books(m.order_books(), builder.books()).is_empty());
+    }
+
+    fn quote(stock_id: u64, side: Side, price: u64, volume: u64) -> OrderRequest {
+        OrderRequest::LimitOrder {
+            agent_id: 9,
+            stock_id,
+            side,
+            price,
+            volume,
+        }
+    }
+
+    #[test]
+    fn baskets_create_redeem_and_get_arbed() {
+        let mut m = Market::with_seed(&[AgentType::EtfArb], StockMarket::new(), 5);
+        let etf = Stock::new("PAIR", 50, "Pair ETF", 1_000_000, 80.0);
+        m.list_stock(etf, Listing::Continuous).unwrap();
+        assert!(m.add_basket(Basket::new(50, 10)).is_err());
+        let pair = Basket::new(50, 10)
+            .with_constituent(1, 2)
+            .with_constituent(2, 1)
+            .with_fee(1.0);
+        m.add_basket(pair).unwrap();
+        assert!(m.is_authorised(0) && !m.is_authorised(9));
+
+        m.execute(vec![
+            quote(1, Side::Buy, 19_900, 100),
+            quote(1, Side::Sell, 20_100, 100),
+            quote(2, Side::Buy, 39_900, 100),
+            quote(2, Side::Sell, 40_100, 100),
+        ]);
+        // (2 × $200 + $400) / 10
+        assert_eq!(m.nav(50), Some(80.0));
+        assert_eq!(m.view().nav(50), Some(80.0));
+
+        // the participant swaps stocks for basket shares and back; others
+        // and non-baskets are turned away
+        let create = |agent_id, stock_id, units| OrderRequest::CreateUnits {
+            agent_id,
+            stock_id,
+            units,
+        };
+        let trade = |stock_id, side, volume| OrderRequest::MarketOrder {
+            agent_id: 0,
+            stock_id,
+            side,
+            volume,
+        };
+        m.execute(vec![
+            trade(1, Side::Buy, 6),
+            trade(2, Side::Buy, 3),
+            create(0, 50, 3),
+            create(9, 50, 1),
+            create(0, 1, 1),
+        ]);
+        let ap = &m.accounts[&0];
+        assert_eq!((ap.qty(50), ap.qty(1), ap.qty(2)), (30, 0, 0));
+        // 6 × $201 + 3 × $401 for the stocks, $3 in fees
+        assert!((ap.cash() + 2_412.0).abs() < 1e-9);
+        assert_eq!(m.accounts[&9].qty(50), 0);
+        assert_eq!(m.agents[&0].portfolio().unwrap().qty(50), 30);
+        m.execute(vec![OrderRequest::RedeemUnits {
+            agent_id: 0,
+            stock_id: 50,
+            units: 3,
+        }]);
+        let ap = &m.accounts[&0];
+        assert_eq!((ap.qty(50), ap.qty(1), ap.qty(2)), (0, 6, 3));
+        assert!((ap.fees_paid() - 6.0).abs() < 1e-9);
+
+        // creation is in kind: no stocks on the ledger, no basket shares,
+        // and no unit count the ledger cannot hold
+        assert_eq!(
+            m.settle_units(&create(0, 50, 4), &[]),
+            Err(RejectReason::InsufficientHoldings)
+        );
+        assert_eq!(
+            m.settle_units(&create(0, 50, u64::MAX), &[]),
+            Err(RejectReason::TooLarge)
+        );
+        let redeem = OrderRequest::RedeemUnits {
+            agent_id: 0,
+            stock_id: 50,
+            units: 1,
+        };
+        assert_eq!(
+            m.settle_units(&redeem, &[]),
+            Err(RejectReason::InsufficientHoldings)
+        );
+        m.execute(vec![trade(1, Side::Sell, 6), trade(2, Side::Sell, 3)]);
+        let ap = &m.accounts[&0];
+        assert_eq!((ap.qty(50), ap.qty(1), ap.qty(2)), (0, 0, 0));
+
+        // rich at a $92 mid: buy the stocks, create, sell the basket
+        m.execute(vec![
+            quote(50, Side::Buy, 9_000, 10),
+            quote(50, Side::Sell, 9_400, 10),
+        ]);
+        for _ in 0..=ETF_ARB_TICKS_UNTIL_ACTIVE {
+            m.step();
+        }
+        let ap = &m.accounts[&0];
+        assert_eq!((ap.qty(50), ap.qty(1), ap.qty(2)), (0, 0, 0));
+        // -$6 in fees and -$18 of spread so far, then $900 - $402 - $401 - $1
+        assert!((ap.cash() - 72.0).abs() < 1e-9);
+        assert_eq!(m.accounts[&9].qty(50), 10);
+        assert_eq!(
+            m.order_book(50).unwrap().best_bid_ask(),
+            (None, Some(9_400))
+        );
+    }
+
+    #[test]
+    fn reset_after_a_split_starts_from_the_restated_listing() {
+        let mut m = Market::with_seed(PARTICIPANTS, StockMarket::new(), 31);
+        m.enable_fundamentals(&FundamentalConfig::default());
+        let stock_id = m.stocks().get_all_ids()[0];
+        let before = m.stocks().get_stock_by_id(stock_id).unwrap().clone();
+        let split = CorporateAction::Split {
+            stock_id,
+            new_shares: 2,
+            old_shares: 1,
+        };
+        m.schedule_corporate_action(m.tick() + 1, split).unwrap();
+        for _ in 0..5 {
+            m.step();
+        }
+        m.reset();
+
+        let half = before.initial_price / 2.0;
+        let stock = m.stocks().get_stock_by_id(stock_id).unwrap();
+        assert_eq!(stock.initial_price, half);
+        assert_eq!(stock.total_float, 2 * before.total_float);
+        assert_eq!(m.last_price(stock_id), half);
+        assert_eq!(m.fundamental(stock_id), Some(half));
+        let mut endowed = 0;
+        for account in m.accounts.values() {
+            if let Some(p) = account.position(stock_id) {
+                assert_eq!(p.avg_price, half);
+                endowed += 1;
+            }
+        }
+        assert!(endowed > 0, "some agent starts with inventory");
+    }
+
+    #[test]
+    fn a_reverse_split_keeps_the_ladder_band_width() {
+        let mut m = warmed_up(23);
+        let stock_id = m.stocks().get_all_ids()[0];
+        let band = BookKind::Ladder {
+            min_price: 100,
+            max_price: 100_000,
+        };
+        assert!(m.set_book_kind(stock_id, band));
+        let split = CorporateAction::Split {
+            stock_id,
+            new_shares: 1,
+            old_shares: 1_000,
+        };
+        m.schedule_corporate_action(m.tick() + 1, split).unwrap();
+        m.step();
+
+        let BookKind::Ladder {
+            min_price,
+            max_price,
+        } = m.order_book(stock_id).unwrap().kind()
+        else {
+            panic!("still a ladder");
+        };
+        assert_eq!(max_price - min_price, 99_900, "no wider than before");
+        let px = (m.last_price(stock_id) * 100.0).round() as u64;
+        assert!((min_price..=max_price).contains(&px), "centred on {px}");
+    }
+
+    #[test]
+    fn a_split_that_would_overflow_is_refused_whole() {
+        let big = Stock::new("BIG", 1, "Big Inc", u64::MAX / 2, 10.0);
+        let mut m = Market::with_seed(&[], StockMarket::from_stocks(vec![big]).unwrap(), 3);
+        let split = CorporateAction::Split {
+            stock_id: 1,
+            new_shares: 1_000,
+            old_shares: 1,
+        };
+        m.schedule_corporate_action(1, split).unwrap();
+        m.step();
+        assert!(m.corporate_actions().is_empty());
+        let stock = m.stocks().get_stock_by_id(1).unwrap();
+        assert_eq!(
+            (stock.total_float, stock.initial_price),
+            (u64::MAX / 2, 10.0)
+        );
+    }
+
+    #[test]
+    fn fundamentals_step_fork_split_and_score_the_mid() {
+        let mut m = warmed_up(43);
+        let stock_id = m.stocks().get_all_ids()[0];
+        assert!(m.fundamental(stock_id).is_none());
+        m.enable_fundamentals(&FundamentalConfig::default());
+        assert_eq!(m.fundamental(stock_id), Some(m.last_price(stock_id)));
+
+        let mut fork = m.fork();
+        let mut other = m.fork_with_seed(44);
+        let mut diverged = false;
+        for n in 1..=20 {
+            let before = m.fundamental(stock_id).unwrap();
+            m.step();
+            fork.step();
+            other.step();
+            let value = m.fundamental(stock_id).unwrap();
+            assert_ne!(value, before);
+            assert_eq!(m.view().fundamental(stock_id, 0), Some(value));
+            assert_eq!(m.view().fundamental(stock_id, 1), Some(before));
+            assert_eq!(fork.fundamental(stock_id), Some(value));
+            diverged |= other.fundamental(stock_id) != Some(value);
+
+            let err = m.discovery_error(stock_id).unwrap();
+            assert_eq!(err.samples, n);
+            assert!((err.last - (m.mark(stock_id).unwrap() - value)).abs() < 1e-9);
+        }
+        assert!(diverged, "override seed should change the value path");
+
+        // a 2-for-1 split halves the value and its history
+        let value = m.fundamental(stock_id).unwrap();
+        let split = CorporateAction::Split {
+            stock_id,
+            new_shares: 2,
+            old_shares: 1,
+        };
+        m.schedule_corporate_action(m.tick() + 1, split).unwrap();
+        m.fundamentals_mut().unwrap().set_process(
+            stock_id,
+            FundamentalProcess::Gbm {
+                drift: 0.0,
+                volatility: 0.0,
+            },
+        );
+        m.step();
+        assert!((m.fundamental(stock_id).unwrap() - value / 2.0).abs() < 1e-9);
+        assert!((m.view().fundamental(stock_id, 1).unwrap() - value / 2.0).abs() < 1e-9);
+    }
+}
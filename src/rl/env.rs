@@ -0,0 +1,820 @@
+// src/rl/env.rs
+//
+// Gym-style environment around `Market`. One extra participant, the
+// controlled agent, trades whatever the caller passes to `step`; the
+// configured population keeps running around it as the background market.
+// Episodes are reproducible: `reset(seed)` rebuilds the market from `seed`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    features::{FeatureExtractor, FeatureSet},
+    reward::{PnlReward, RewardContext, RewardFn},
+};
+use crate::{
+    Agent, AgentType, Fill, Market, MarketView, Marketable, Portfolio, Role, SentimentConfig,
+    corporate::CorporateAction,
+    stocks::{basket::UnitSettlement, definitions::StockMarket},
+    types::order::{Order, OrderRequest, Side, Trade},
+};
+
+#[derive(Clone)]
+pub struct EnvConfig {
+    /// The background population.
+    pub participants: Vec<AgentType>,
+    pub stocks: StockMarket,
+    /// Steps per episode.
+    pub episode_length: u64,
+    /// Ticks the background market runs before the controlled agent's
+    /// first step, so books are populated.
+    pub warmup_ticks: u64,
+    /// The controlled agent's cash at reset, dollars.
+    pub starting_cash: f64,
+    /// End the episode once gross exposure exceeds this multiple of equity.
+    pub max_leverage: Option<f64>,
+    pub reward: Box<dyn RewardFn>,
+    /// What observations contain.
+    pub features: FeatureSet,
+    /// Give the market its own seeded sentiment process (never the global
+    /// engine, so environments stay independent).
+    pub sentiment: Option<SentimentConfig>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            participants: vec![
+                AgentType::MarketMaker,
+                AgentType::DumbLimit,
+                AgentType::DumbLimit,
+                AgentType::DumbMarket,
+                AgentType::DumbMarket,
+            ],
+            stocks: StockMarket::new(),
+            episode_length: 1_000,
+            warmup_ticks: 25,
+            starting_cash: 1_000_000.0,
+            max_leverage: Some(10.0),
+            reward: Box::new(PnlReward::default()),
+            features: FeatureSet::default(),
+            sentiment: None,
+        }
+    }
+}
+
+/// What the controlled agent does in one step. Prices are cents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Hold,
+    Market {
+        stock_id: u64,
+        side: Side,
+        volume: u64,
+    },
+    Limit {
+        stock_id: u64,
+        side: Side,
+        price: u64,
+        volume: u64,
+    },
+    Cancel {
+        order_id: u64,
+    },
+    /// Amend a resting order, see `OrderRequest::ModifyOrder`.
+    Modify {
+        order_id: u64,
+        price: u64,
+        volume: u64,
+    },
+    /// Cancel every resting order of the controlled agent.
+    CancelAll,
+    /// Create or redeem basket units, see `OrderRequest::CreateUnits`;
+    /// the agent must be an authorised participant.
+    Create {
+        stock_id: u64,
+        units: u64,
+    },
+    Redeem {
+        stock_id: u64,
+        units: u64,
+    },
+    /// Several actions in the same tick, in order.
+    Batch(Vec<Action>),
+}
+
+/// The state after a step as laid out by the config's `FeatureSet`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub tick: u64,
+    pub features: Vec<f64>,
+}
+
+/// Why an episode ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Termination {
+    /// `episode_length` steps were taken.
+    EpisodeEnd,
+    /// Equity fell to zero or below.
+    Bankrupt,
+    /// Gross exposure exceeded `max_leverage` times equity.
+    MarginBreach,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepInfo {
+    pub tick: u64,
+    pub equity: f64,
+    /// The controlled agent's executions during the step.
+    pub fills: Vec<Fill>,
+    /// Set on the last step of the episode.
+    pub termination: Option<Termination>,
+}
+
+pub struct Env {
+    cfg: EnvConfig,
+    market: Market,
+    agent_id: usize,
+    extractor: FeatureExtractor,
+    last: Observation,
+    reward: Box<dyn RewardFn>,
+    steps: u64,
+    done: Option<Termination>,
+    /// The controlled agent's executions since `step` last drained them.
+    fills: Arc<Mutex<Vec<Fill>>>,
+}
+
+impl Env {
+    /// Build and reset with seed 0; call `reset` for any other episode.
+    pub fn new(cfg: EnvConfig) -> Self {
+        let mut env = Self::unstarted(cfg);
+        env.reset(0);
+        env
+    }
+
+    /// Build without playing any episode: the market is empty until the
+    /// first `reset`, which saves a warm-up when the caller picks the seed.
+    pub(crate) fn unstarted(cfg: EnvConfig) -> Self {
+        let extractor = FeatureExtractor::new(cfg.features.clone(), cfg.stocks.get_all_ids())
+            .with_cash_offset(cfg.starting_cash);
+        Self {
+            market: Market::with_seed(&[], cfg.stocks.clone(), 0), // replaced by reset
+            agent_id: 0,
+            extractor,
+            last: Observation {
+                tick: 0,
+                features: vec![],
+            },
+            reward: cfg.reward.clone(),
+            steps: 0,
+            done: None,
+            fills: Arc::default(),
+            cfg,
+        }
+    }
+
+    /// Start a fresh episode: rebuild the market from `seed`, add the
+    /// controlled agent, run the warm-up and return the first observation.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.market = Market::with_seed(&self.cfg.participants, self.cfg.stocks.clone(), seed);
+        if let Some(cfg) = &self.cfg.sentiment {
+            self.market.enable_sentiment(cfg);
+        }
+        self.fills = Arc::default();
+        let fills = Arc::clone(&self.fills);
+        self.agent_id = self
+            .market
+            .add_agent(|id| Box::new(ControlledAgent::new(id, fills)));
+        self.extractor.reset();
+        for _ in 0..self.cfg.warmup_ticks {
+            self.market.step();
+            self.extractor
+                .update(&self.market.view().for_agent(self.agent_id));
+        }
+        self.steps = 0;
+        self.done = None;
+
+        self.reward = self.cfg.reward.clone();
+        let ctx = Self::context(
+            &self.market,
+            self.agent_id,
+            self.cfg.starting_cash,
+            &[],
+            false,
+        );
+        self.reward.reset(&ctx);
+        self.observe()
+    }
+
+    /// Submit `action` for the controlled agent and advance one tick. The
+    /// agent's requests reach the books after the background agents' ones.
+    /// Stepping a finished episode changes nothing and returns reward 0.
+    pub fn step(&mut self, action: Action) -> (Observation, f64, bool, StepInfo) {
+        if let Some(t) = self.done {
+            let info = StepInfo {
+                tick: self.market.tick(),
+                equity: self.equity(),
+                fills: vec![],
+                termination: Some(t),
+            };
+            return (self.last.clone(), 0.0, true, info);
+        }
+
+        let mut requests = self.market.decide();
+        requests.extend(self.requests(action));
+        self.market.execute(requests);
+        self.steps += 1;
+
+        let fills = std::mem::take(&mut *self.fills.lock().unwrap());
+        let equity = self.equity();
+        let termination = self.termination(equity);
+        self.done = termination;
+
+        let done = termination.is_some();
+        let ctx = Self::context(
+            &self.market,
+            self.agent_id,
+            self.cfg.starting_cash,
+            &fills,
+            done,
+        );
+        let reward = self.reward.reward(&ctx);
+        let info = StepInfo {
+            tick: self.market.tick(),
+            equity,
+            fills,
+            termination,
+        };
+        (self.observe(), reward, done, info)
+    }
+
+    // ---------------------------------------------------------------------
+    //  Accessors
+    // ---------------------------------------------------------------------
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    /// Id of the controlled agent in `market()`.
+    pub fn agent_id(&self) -> usize {
+        self.agent_id
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.cfg
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.is_some()
+    }
+
+    /// Steps taken in the current episode.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The controlled agent's resting orders.
+    pub fn open_orders(&self) -> Vec<Order> {
+        self.view().open_orders()
+    }
+
+    /// Starting cash plus the controlled agent's account marked to market.
+    pub fn equity(&self) -> f64 {
+        self.cfg.starting_cash + self.account().evaluate(&self.view())
+    }
+
+    /// Length of every observation's feature vector.
+    pub fn observation_dim(&self) -> usize {
+        self.extractor.dim()
+    }
+
+    /// E.g. to freeze the normalisation statistics for evaluation.
+    pub fn extractor_mut(&mut self) -> &mut FeatureExtractor {
+        &mut self.extractor
+    }
+
+    /// The observation returned by the latest `reset` or `step`.
+    pub fn observation(&self) -> &Observation {
+        &self.last
+    }
+
+    // ---------------------------------------------------------------------
+    //  Internals
+    // ---------------------------------------------------------------------
+    /// Extract the features of the current tick (once per tick: history
+    /// features count calls).
+    fn observe(&mut self) -> Observation {
+        let view = self.market.view().for_agent(self.agent_id);
+        self.last = Observation {
+            tick: self.market.tick(),
+            features: self.extractor.extract(&view),
+        };
+        self.last.clone()
+    }
+
+    fn view(&self) -> MarketView<'_> {
+        self.market.view().for_agent(self.agent_id)
+    }
+
+    fn account(&self) -> &Portfolio {
+        ledger(&self.market, self.agent_id)
+    }
+
+    /// Free of `self` so the reward (another field) can be borrowed mutably
+    /// next to it.
+    fn context<'a>(
+        market: &'a Market,
+        agent_id: usize,
+        starting_cash: f64,
+        fills: &'a [Fill],
+        done: bool,
+    ) -> RewardContext<'a> {
+        let view = market.view().for_agent(agent_id);
+        let account = ledger(market, agent_id);
+        RewardContext {
+            view,
+            account,
+            equity: starting_cash + account.evaluate(&view),
+            fills,
+            done,
+        }
+    }
+
+    fn termination(&self, equity: f64) -> Option<Termination> {
+        if equity <= 0.0 {
+            return Some(Termination::Bankrupt);
+        }
+        if let Some(max) = self.cfg.max_leverage
+            && self.account().gross_exposure(&self.view()) > max * equity
+        {
+            return Some(Termination::MarginBreach);
+        }
+        (self.steps >= self.cfg.episode_length).then_some(Termination::EpisodeEnd)
+    }
+
+    fn requests(&self, action: Action) -> Vec<OrderRequest> {
+        let agent_id = self.agent_id;
+        match action {
+            Action::Hold => vec![],
+            Action::Market {
+                stock_id,
+                side,
+                volume,
+            } => vec![OrderRequest::MarketOrder {
+                agent_id,
+                stock_id,
+                side,
+                volume,
+            }],
+            Action::Limit {
+                stock_id,
+                side,
+                price,
+                volume,
+            } => vec![OrderRequest::LimitOrder {
+                agent_id,
+                stock_id,
+                side,
+                price,
+                volume,
+            }],
+            Action::Cancel { order_id } => vec![OrderRequest::CancelOrder { agent_id, order_id }],
+            Action::Modify {
+                order_id,
+                price,
+                volume,
+            } => vec![OrderRequest::ModifyOrder {
+                agent_id,
+                order_id,
+                price,
+                volume,
+            }],
+            Action::CancelAll => self
+                .open_orders()
+                .into_iter()
+                .map(|o| OrderRequest::CancelOrder {
+                    agent_id,
+                    order_id: o.id,
+                })
+                .collect(),
+            Action::Create { stock_id, units } => vec![OrderRequest::CreateUnits {
+                agent_id,
+                stock_id,
+                units,
+            }],
+            Action::Redeem { stock_id, units } => vec![OrderRequest::RedeemUnits {
+                agent_id,
+                stock_id,
+                units,
+            }],
+            Action::Batch(actions) => actions.into_iter().flat_map(|a| self.requests(a)).collect(),
+        }
+    }
+}
+
+/// The exchange's ledger for `agent_id`; empty until its first fill.
+fn ledger(market: &Market, agent_id: usize) -> &Portfolio {
+    static EMPTY: LazyLock<Portfolio> = LazyLock::new(Portfolio::default);
+    market.account(agent_id).unwrap_or(&EMPTY)
+}
+
+// -----------------------------------------------------------------------------
+//  The controlled slot inside the market
+// -----------------------------------------------------------------------------
+/// Never decides anything itself; `Env` injects its requests. It keeps its
+/// own book of fills so `Market::total_inventory` stays conserved, and hands
+/// each one to `Env` through `fills`, however many prints the tape keeps.
+#[derive(Debug)]
+struct ControlledAgent {
+    id: usize,
+    portfolio: Portfolio,
+    acked: HashMap<u64, Order>,
+    fills: Arc<Mutex<Vec<Fill>>>,
+}
+
+impl ControlledAgent {
+    fn new(id: usize, fills: Arc<Mutex<Vec<Fill>>>) -> Self {
+        Self {
+            id,
+            portfolio: Portfolio::default(),
+            acked: HashMap::new(),
+            fills,
+        }
+    }
+}
+
+impl Agent for ControlledAgent {
+    fn decide_actions(&mut self, view: &MarketView) -> Vec<OrderRequest> {
+        // acks don't say whether anything rested (a market order, a limit
+        // that crossed in full, the old id of a re-entered modify); the
+        // books do
+        self.acked.retain(|&id, o| {
+            match view.order_books.get(&o.stock_id).and_then(|b| b.order(id)) {
+                Some(live) => {
+                    *o = live;
+                    true
+                }
+                None => false,
+            }
+        });
+        vec![]
+    }
+    fn buy_stock(&mut self, _stock_id: u64, _volume: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn sell_stock(&mut self, _stock_id: u64, _volume: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn acknowledge_order(&mut self, order: Order) {
+        if order.filled < order.volume {
+            self.acked.insert(order.id, order);
+        }
+    }
+    fn cancel_acknowledged(&mut self, order_id: u64) {
+        self.acked.remove(&order_id);
+    }
+    fn margin_call(&mut self) -> Vec<OrderRequest> {
+        vec![] // the env terminates the episode instead
+    }
+    fn update_portfolio(&mut self, delta: i64, trade: &Trade) {
+        self.portfolio.on_trade(delta, trade);
+        if trade.maker_agent_id == self.id
+            && let Some(o) = self.acked.get_mut(&trade.maker_order_id)
+        {
+            o.filled += trade.volume;
+            if o.filled >= o.volume {
+                self.acked.remove(&trade.maker_order_id);
+            }
+        }
+        let side = if delta > 0 { Side::Buy } else { Side::Sell };
+        // a self-trade arrives twice: the taker's side first
+        let role = if trade.taker_agent_id == self.id && trade.taker_side == side {
+            Role::Taker
+        } else {
+            Role::Maker
+        };
+        self.fills.lock().unwrap().push(Fill {
+            trade: *trade,
+            role,
+            side,
+        });
+    }
+    fn stock_delisted(&mut self, stock_id: u64, final_price: u64) {
+        self.portfolio.settle(stock_id, final_price);
+        self.acked.retain(|_, o| o.stock_id != stock_id);
+    }
+    fn corporate_action(&mut self, action: &CorporateAction) {
+        self.portfolio.apply_corporate_action(action);
+        self.acked
+            .retain(|_, o| o.stock_id != action.stock_id() || action.adjust_order(o));
+    }
+    fn units_settled(&mut self, settlement: &UnitSettlement) {
+        self.portfolio.apply_settlement(settlement);
+    }
+    fn evaluate_port(&mut self, view: &MarketView) -> f64 {
+        self.portfolio.evaluate(view)
+    }
+    fn portfolio(&self) -> Option<&Portfolio> {
+        Some(&self.portfolio)
+    }
+    fn get_pending_orders(&self) -> Vec<Order> {
+        self.acked.values().copied().collect()
+    }
+    fn cancel_open_order(&mut self, _order_id: u64) -> Vec<OrderRequest> {
+        vec![]
+    }
+    fn run(&mut self) {}
+    fn get_id(&self) -> usize {
+        self.id
+    }
+    fn get_inventory(&self) -> i64 {
+        self.portfolio.shares()
+    }
+    fn clone_agent(&self) -> Box<dyn Agent> {
+        // a forked market's fills are its own
+        let fills = self.fills.lock().unwrap().clone();
+        Box::new(Self {
+            portfolio: self.portfolio.clone(),
+            acked: self.acked.clone(),
+            fills: Arc::new(Mutex::new(fills)),
+            ..Self::new(self.id, Arc::default())
+        })
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rl::{features::Feature, reward::RiskAdjustedPnl},
+        simulators::order_book::OrderBook,
+    };
+
+    fn config(episode_length: u64) -> EnvConfig {
+        EnvConfig {
+            episode_length,
+            ..EnvConfig::default()
+        }
+    }
+
+    fn first_stock(env: &Env) -> u64 {
+        env.market()
+            .stocks()
+            .get_all_ids()
+            .into_iter()
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_episode() {
+        let mut a = Env::new(config(50));
+        let mut b = Env::new(config(50));
+        assert_eq!(a.reset(7), b.reset(7));
+        let stock_id = first_stock(&a);
+        for i in 0..50 {
+            let action = if i % 10 == 0 {
+                Action::Market {
+                    stock_id,
+                    side: Side::Buy,
+                    volume: 5,
+                }
+            } else {
+                Action::Hold
+            };
+            let (oa, ra, da, ia) = a.step(action.clone());
+            let (ob, rb, db, ib) = b.step(action);
+            assert_eq!((oa, ra, da), (ob, rb, db));
+            assert_eq!(ia, ib);
+        }
+        assert_ne!(a.reset(8), b.reset(7), "another seed, another episode");
+    }
+
+    #[test]
+    fn observations_follow_the_feature_set() {
+        let mut env = Env::new(EnvConfig {
+            features: FeatureSet::empty()
+                .with(Feature::Spread)
+                .with(Feature::Inventory)
+                .with_cash(),
+            ..config(10)
+        });
+        let stocks = env.market().stocks().get_all_ids().len();
+        assert_eq!(env.observation_dim(), 2 * stocks + 1);
+        let obs = env.reset(1);
+        assert_eq!(obs.features.len(), env.observation_dim());
+        assert_eq!(obs.features[2 * stocks], 1_000_000.0, "starting cash");
+        let (obs, ..) = env.step(Action::Hold);
+        assert_eq!(&obs, env.observation());
+    }
+
+    #[test]
+    fn episode_ends_after_its_length() {
+        let mut env = Env::new(config(5));
+        let start = env.reset(1).tick;
+        for i in 1..=5 {
+            let (obs, _, done, _) = env.step(Action::Hold);
+            assert_eq!(obs.tick, start + i);
+            assert_eq!(done, i == 5);
+        }
+        let (_, reward, done, info) = env.step(Action::Hold);
+        assert!(done);
+        assert_eq!(reward, 0.0);
+        assert_eq!(info.termination, Some(Termination::EpisodeEnd));
+        assert_eq!(env.steps(), 5);
+    }
+
+    #[test]
+    fn pnl_rewards_add_up_to_the_equity_change() {
+        let mut env = Env::new(config(40));
+        env.reset(3);
+        let stock_id = first_stock(&env);
+        let start = env.equity();
+        let mut total = 0.0;
+        let mut traded = 0;
+        for i in 0..40 {
+            let side = if i % 2 == 0 { Side::Buy } else { Side::Sell };
+            let (_, r, _, info) = env.step(Action::Market {
+                stock_id,
+                side,
+                volume: 10,
+            });
+            total += r;
+            traded += info.fills.len();
+        }
+        assert!(traded > 0, "the controlled agent should get filled");
+        assert!((total - (env.equity() - start)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fills_do_not_depend_on_the_tape() {
+        let mut env = Env::new(config(10));
+        env.reset(6);
+        env.market.set_tape_capacity(1);
+        let stock_id = first_stock(&env);
+        let (_, _, _, info) = env.step(Action::Market {
+            stock_id,
+            side: Side::Buy,
+            volume: 5_000,
+        });
+        assert!(
+            info.fills.len() > 1,
+            "the order should sweep several prints"
+        );
+        let bought: i64 = info.fills.iter().map(Fill::signed_volume).sum();
+        assert_eq!(bought, env.account().qty(stock_id));
+        assert!(info.fills.iter().all(|f| f.role == Role::Taker));
+    }
+
+    #[test]
+    fn bankruptcy_and_margin_breach_end_the_episode() {
+        let mut env = Env::new(EnvConfig {
+            starting_cash: 0.0,
+            max_leverage: None,
+            ..config(100)
+        });
+        env.reset(2);
+        let (_, _, done, info) = env.step(Action::Hold);
+        assert!(done, "no cash, no equity");
+        assert_eq!(info.termination, Some(Termination::Bankrupt));
+
+        let mut env = Env::new(EnvConfig {
+            starting_cash: 1_000.0,
+            max_leverage: Some(1.0),
+            ..config(100)
+        });
+        env.reset(2);
+        let stock_id = first_stock(&env);
+        let termination = loop {
+            // a share is worth far more than $10 in the default universe
+            let (_, _, _, info) = env.step(Action::Market {
+                stock_id,
+                side: Side::Buy,
+                volume: 100,
+            });
+            if let Some(t) = info.termination {
+                break t;
+            }
+        };
+        assert_eq!(termination, Termination::MarginBreach);
+    }
+
+    #[test]
+    fn limit_orders_rest_and_cancel_all_clears_them() {
+        let mut env = Env::new(config(10));
+        env.reset(4);
+        let stock_id = first_stock(&env);
+        env.step(Action::Batch(vec![
+            Action::Limit {
+                stock_id,
+                side: Side::Buy,
+                price: 1,
+                volume: 10,
+            },
+            Action::Limit {
+                stock_id,
+                side: Side::Buy,
+                price: 2,
+                volume: 10,
+            },
+        ]));
+        assert_eq!(env.open_orders().len(), 2);
+        env.step(Action::CancelAll);
+        assert!(env.open_orders().is_empty());
+        // the controlled agent's fills are conserved like everyone else's
+        let market = env.market();
+        assert_eq!(
+            market.total_inventory(),
+            Market::with_seed(&env.cfg.participants, env.cfg.stocks.clone(), 4).total_inventory()
+        );
+    }
+
+    #[test]
+    fn the_controlled_agent_only_lists_what_still_rests() {
+        let order = |id, volume| Order {
+            id,
+            agent_id: 0,
+            stock_id: 1,
+            side: Side::Sell,
+            price: 10_000,
+            volume,
+            filled: 0,
+        };
+        let mut agent = ControlledAgent::new(0, Arc::default());
+        for o in [order(1, 10), order(2, 10), order(3, 5), order(4, 5)] {
+            agent.acknowledge_order(o);
+        }
+        agent.acknowledge_order(Order {
+            filled: 5,
+            ..order(5, 5)
+        });
+        let fill = |maker_order_id, volume| Trade {
+            price: 10_000,
+            stock_id: 1,
+            volume,
+            taker_agent_id: 9,
+            maker_agent_id: 0,
+            taker_side: Side::Buy,
+            maker_order_id,
+        };
+        agent.update_portfolio(-4, &fill(1, 4));
+        agent.update_portfolio(-10, &fill(2, 10));
+        let mut ids: Vec<u64> = agent.get_pending_orders().iter().map(|o| o.id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, [1, 3, 4], "filled in full or on arrival");
+
+        // 3 was a market order and 4 re-entered under 6: only 1 and 6 rest
+        let mut book = OrderBook::new();
+        let mut left = Order {
+            filled: 4,
+            ..order(1, 10)
+        };
+        book.process_limit_order(&mut left);
+        book.process_limit_order(&mut order(6, 5));
+        agent.acknowledge_order(order(6, 5));
+        let (books, stocks) = (HashMap::from([(1, book)]), StockMarket::new());
+        agent.decide_actions(&MarketView::new(&books, &stocks));
+        let mut pending = agent.get_pending_orders();
+        pending.sort_unstable_by_key(|o| o.id);
+        assert_eq!(
+            pending
+                .iter()
+                .map(|o| (o.id, o.volume - o.filled))
+                .collect::<Vec<_>>(),
+            [(1, 6), (6, 5)]
+        );
+    }
+
+    #[test]
+    fn inventory_penalty_lowers_the_reward() {
+        let run = |reward: Box<dyn RewardFn>| {
+            let mut env = Env::new(EnvConfig {
+                reward,
+                ..config(100)
+            });
+            env.reset(5);
+            let stock_id = first_stock(&env);
+            let mut total = 0.0;
+            while env.account().qty(stock_id) == 0 {
+                let (_, r, _, _) = env.step(Action::Market {
+                    stock_id,
+                    side: Side::Buy,
+                    volume: 50,
+                });
+                total += r;
+            }
+            let (_, r, _, _) = env.step(Action::Hold);
+            total + r
+        };
+        let plain = run(Box::<PnlReward>::default());
+        let penalised = run(Box::new(RiskAdjustedPnl::new(0.01)));
+        assert!(penalised < plain, "holding inventory is penalised");
+    }
+}
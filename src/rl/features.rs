@@ -0,0 +1,512 @@
+// src/rl/features.rs
+//
+// Fixed-size numeric features from books and market state, for RL policies
+// and learned strategies. A `FeatureSet` lists what to compute; a
+// `FeatureExtractor` keeps the little history some features need (mid
+// returns, previous best quotes) and optionally standardises its output with
+// statistics gathered online.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{MarketView, OrderBook};
+
+/// One feature (or block of features) computed for every stock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Best `n` levels per side, bids then asks, each as `[distance from
+    /// mid in bps, resting shares]`; missing levels are zeros. `4n` values.
+    Levels(usize),
+    /// Spread over mid, bps.
+    Spread,
+    /// Resting-volume imbalance over the best `n` levels, in `[-1, 1]`.
+    Imbalance(usize),
+    /// Order-flow imbalance at the touch since the previous observation
+    /// (Cont, Kukanov & Stoikov), shares.
+    OrderFlowImbalance,
+    /// Microprice minus mid, bps.
+    Microprice,
+    /// The last `n` one-observation log returns of the mid, newest first.
+    Returns(usize),
+    /// Standard deviation of the last `n` log returns.
+    RealizedVol(usize),
+    /// Prints and shares traded over the last `n` ticks. 2 values.
+    TradeIntensity(usize),
+    /// The viewing agent's position, shares.
+    Inventory,
+}
+
+impl Feature {
+    fn width(self) -> usize {
+        match self {
+            Feature::Levels(n) => 4 * n,
+            Feature::Returns(n) => n,
+            Feature::TradeIntensity(_) => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns of history this feature looks back over.
+    fn lookback(self) -> usize {
+        match self {
+            Feature::Returns(n) | Feature::RealizedVol(n) => n,
+            _ => 0,
+        }
+    }
+}
+
+/// What to extract: `per_stock` features for every stock in id order, then
+/// the viewing agent's cash if `cash` is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureSet {
+    pub per_stock: Vec<Feature>,
+    pub cash: bool,
+    /// Standardise every value with running mean and variance.
+    pub normalize: bool,
+    /// After normalising, clamp to `[-clip, clip]`.
+    pub clip: Option<f64>,
+}
+
+impl Default for FeatureSet {
+    fn default() -> Self {
+        Self {
+            per_stock: vec![
+                Feature::Levels(5),
+                Feature::Spread,
+                Feature::Imbalance(5),
+                Feature::OrderFlowImbalance,
+                Feature::Microprice,
+                Feature::Returns(5),
+                Feature::RealizedVol(20),
+                Feature::TradeIntensity(10),
+                Feature::Inventory,
+            ],
+            cash: true,
+            normalize: false,
+            clip: None,
+        }
+    }
+}
+
+impl FeatureSet {
+    /// No features at all; add them with `with`.
+    pub fn empty() -> Self {
+        Self {
+            per_stock: Vec::new(),
+            cash: false,
+            normalize: false,
+            clip: None,
+        }
+    }
+
+    pub fn with(mut self, feature: Feature) -> Self {
+        self.per_stock.push(feature);
+        self
+    }
+
+    pub fn with_cash(mut self) -> Self {
+        self.cash = true;
+        self
+    }
+
+    pub fn normalized(mut self, clip: Option<f64>) -> Self {
+        self.normalize = true;
+        self.clip = clip;
+        self
+    }
+
+    /// Values per stock.
+    pub fn stock_width(&self) -> usize {
+        self.per_stock.iter().map(|f| f.width()).sum()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Online normalisation
+// -----------------------------------------------------------------------------
+/// Per-dimension running mean and variance (Welford).
+#[derive(Debug, Clone, Default)]
+pub struct RunningStats {
+    count: u64,
+    mean: Vec<f64>,
+    m2: Vec<f64>,
+}
+
+impl RunningStats {
+    pub fn new(dim: usize) -> Self {
+        Self {
+            count: 0,
+            mean: vec![0.0; dim],
+            m2: vec![0.0; dim],
+        }
+    }
+
+    pub fn update(&mut self, x: &[f64]) {
+        self.count += 1;
+        let n = self.count as f64;
+        for ((m, m2), &v) in self.mean.iter_mut().zip(&mut self.m2).zip(x) {
+            let d = v - *m;
+            *m += d / n;
+            *m2 += d * (v - *m);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> &[f64] {
+        &self.mean
+    }
+
+    /// Population standard deviation of dimension `i`.
+    pub fn std(&self, i: usize) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.m2[i] / self.count as f64).sqrt()
+    }
+
+    /// `(x - mean) / std` in place; constant dimensions are only centred.
+    pub fn standardize(&self, x: &mut [f64]) {
+        for (i, v) in x.iter_mut().enumerate() {
+            let sd = self.std(i);
+            *v -= self.mean[i];
+            if sd > 1e-12 {
+                *v /= sd;
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Extractor
+// -----------------------------------------------------------------------------
+/// Per-stock memory between observations.
+#[derive(Debug, Clone, Default)]
+struct History {
+    mid: Option<f64>,
+    /// Newest first.
+    returns: VecDeque<f64>,
+    /// Best bid and ask as `(price, shares)` at the previous observation.
+    touch: Option<((u64, u64), (u64, u64))>,
+    ofi: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeatureExtractor {
+    set: FeatureSet,
+    stock_ids: Vec<u64>,
+    history: HashMap<u64, History>,
+    lookback: usize,
+    /// Added to the account's cash, which the exchange books as a net flow.
+    cash_offset: f64,
+    stats: Option<RunningStats>,
+    /// Stop updating the normalisation statistics (evaluation runs).
+    frozen: bool,
+}
+
+impl FeatureExtractor {
+    pub fn new(set: FeatureSet, mut stock_ids: Vec<u64>) -> Self {
+        stock_ids.sort_unstable();
+        let lookback = set
+            .per_stock
+            .iter()
+            .map(|f| f.lookback())
+            .max()
+            .unwrap_or(0);
+        let dim = stock_ids.len() * set.stock_width() + usize::from(set.cash);
+        let stats = set.normalize.then(|| RunningStats::new(dim));
+        Self {
+            set,
+            stock_ids,
+            history: HashMap::new(),
+            lookback,
+            cash_offset: 0.0,
+            stats,
+            frozen: false,
+        }
+    }
+
+    /// Starting cash of the viewing agent, added to its ledger cash.
+    pub fn with_cash_offset(mut self, cash: f64) -> Self {
+        self.cash_offset = cash;
+        self
+    }
+
+    /// Length of every vector `extract` returns.
+    pub fn dim(&self) -> usize {
+        self.stock_ids.len() * self.set.stock_width() + usize::from(self.set.cash)
+    }
+
+    pub fn feature_set(&self) -> &FeatureSet {
+        &self.set
+    }
+
+    /// Forget returns and quotes (a new episode); normalisation statistics
+    /// are kept.
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn normalization(&self) -> Option<&RunningStats> {
+        self.stats.as_ref()
+    }
+
+    /// Keep normalising with the statistics gathered so far, without
+    /// updating them.
+    pub fn freeze(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Record mids and quotes without producing a vector, e.g. during a
+    /// warm-up. History features assume one call (this or `extract`) per tick.
+    pub fn update(&mut self, view: &MarketView) {
+        for &id in &self.stock_ids {
+            let h = self.history.entry(id).or_default();
+            let Some(book) = view.book(id) else {
+                continue;
+            };
+            if let Some(mid) = book.mid_price().map(|m| m as f64) {
+                if let Some(prev) = h.mid
+                    && prev > 0.0
+                    && mid > 0.0
+                {
+                    h.returns.push_front((mid / prev).ln());
+                    h.returns.truncate(self.lookback);
+                }
+                h.mid = Some(mid);
+            }
+            let touch = touch(book);
+            h.ofi = match (h.touch, touch) {
+                (Some(prev), Some(now)) => order_flow_imbalance(prev, now),
+                _ => 0.0,
+            };
+            h.touch = touch;
+        }
+    }
+
+    /// Update the history with `view` and return the feature vector.
+    pub fn extract(&mut self, view: &MarketView) -> Vec<f64> {
+        self.update(view);
+        let mut out = Vec::with_capacity(self.dim());
+        for &id in &self.stock_ids {
+            for &f in &self.set.per_stock {
+                self.push(&mut out, f, id, view);
+            }
+        }
+        if self.set.cash {
+            out.push(self.cash_offset + view.account().map_or(0.0, |a| a.cash()));
+        }
+
+        if let Some(stats) = self.stats.as_mut() {
+            if !self.frozen {
+                stats.update(&out);
+            }
+            stats.standardize(&mut out);
+            if let Some(c) = self.set.clip {
+                out.iter_mut().for_each(|v| *v = v.clamp(-c, c));
+            }
+        }
+        out
+    }
+
+    fn push(&self, out: &mut Vec<f64>, f: Feature, stock_id: u64, view: &MarketView) {
+        let book = view.book(stock_id);
+        let h = self.history.get(&stock_id);
+        // a reference price even when one side is empty
+        let mid = book
+            .and_then(|b| b.mid_price())
+            .or_else(|| view.last_price(stock_id))
+            .map(|m| m as f64)
+            .filter(|&m| m > 0.0);
+        let bps = |px: f64| mid.map_or(0.0, |m| (px - m) / m * 10_000.0);
+
+        match f {
+            Feature::Levels(n) => {
+                let depth = book.map(|b| b.depth(n)).unwrap_or_default();
+                for side in [&depth.bids, &depth.asks] {
+                    for i in 0..n {
+                        match side.get(i) {
+                            Some(q) => {
+                                out.push(bps(q.price as f64));
+                                out.push(q.volume as f64);
+                            }
+                            None => out.extend([0.0, 0.0]),
+                        }
+                    }
+                }
+            }
+            Feature::Spread => {
+                let spread = book.and_then(|b| b.spread()).map(|s| s as f64);
+                out.push(match (spread, mid) {
+                    (Some(s), Some(m)) => s / m * 10_000.0,
+                    _ => 0.0,
+                });
+            }
+            Feature::Imbalance(n) => {
+                out.push(book.and_then(|b| b.imbalance(n)).unwrap_or(0.0));
+            }
+            Feature::OrderFlowImbalance => out.push(h.map_or(0.0, |h| h.ofi)),
+            Feature::Microprice => {
+                out.push(book.and_then(|b| b.microprice()).map_or(0.0, bps));
+            }
+            Feature::Returns(n) => {
+                for i in 0..n {
+                    out.push(h.and_then(|h| h.returns.get(i).copied()).unwrap_or(0.0));
+                }
+            }
+            Feature::RealizedVol(n) => {
+                let r: Vec<f64> = h.map_or(vec![], |h| h.returns.iter().take(n).copied().collect());
+                out.push(std_dev(&r));
+            }
+            Feature::TradeIntensity(n) => {
+                let from = view.tick.saturating_sub((n as u64).saturating_sub(1));
+                let (prints, volume) = view.tape.map_or((0, 0), |t| {
+                    t.since(from)
+                        .filter(|p| p.trade.stock_id == stock_id)
+                        .fold((0u64, 0u64), |(c, v), p| (c + 1, v + p.trade.volume))
+                });
+                out.push(prints as f64);
+                out.push(volume as f64);
+            }
+            Feature::Inventory => {
+                out.push(view.account().map_or(0.0, |a| a.qty(stock_id) as f64));
+            }
+        }
+    }
+}
+
+/// Best bid and ask with their resting shares.
+fn touch(book: &OrderBook) -> Option<((u64, u64), (u64, u64))> {
+    let depth = book.depth(1);
+    let (b, a) = (depth.bids.first()?, depth.asks.first()?);
+    Some(((b.price, b.volume), (a.price, a.volume)))
+}
+
+/// Net buying pressure at the touch between two snapshots: bid queue growth
+/// (or an improving bid) minus ask queue growth (or an improving ask).
+fn order_flow_imbalance(prev: ((u64, u64), (u64, u64)), now: ((u64, u64), (u64, u64))) -> f64 {
+    let (((pb, qb0), (pa, qa0)), ((b, qb), (a, qa))) = (prev, now);
+    let (qb0, qa0, qb, qa) = (qb0 as f64, qa0 as f64, qb as f64, qa as f64);
+    let bid = if b > pb {
+        qb
+    } else if b == pb {
+        qb - qb0
+    } else {
+        -qb0
+    };
+    let ask = if a < pa {
+        qa
+    } else if a == pa {
+        qa - qa0
+    } else {
+        -qa0
+    };
+    bid - ask
+}
+
+fn std_dev(x: &[f64]) -> f64 {
+    if x.len() < 2 {
+        return 0.0;
+    }
+    let n = x.len() as f64;
+    let mean = x.iter().sum::<f64>() / n;
+    (x.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt()
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Order, Side, StockMarket};
+
+    fn place(book: &mut OrderBook, id: u64, side: Side, price: u64, volume: u64) {
+        book.process_limit_order(&mut Order {
+            id,
+            agent_id: 9,
+            stock_id: 1,
+            side,
+            price,
+            volume,
+            filled: 0,
+        });
+    }
+
+    fn books(bid: u64, bid_qty: u64, ask: u64, ask_qty: u64) -> HashMap<u64, OrderBook> {
+        let mut book = OrderBook::new();
+        place(&mut book, 1, Side::Buy, bid, bid_qty);
+        place(&mut book, 2, Side::Sell, ask, ask_qty);
+        HashMap::from([(1, book)])
+    }
+
+    #[test]
+    fn book_features_are_relative_to_mid() {
+        let stocks = StockMarket::new();
+        let b = books(9_900, 300, 10_100, 100);
+        let view = MarketView::new(&b, &stocks);
+        let set = FeatureSet::empty()
+            .with(Feature::Levels(2))
+            .with(Feature::Spread)
+            .with(Feature::Imbalance(1))
+            .with(Feature::Microprice);
+        let mut fx = FeatureExtractor::new(set, vec![1]);
+        let v = fx.extract(&view);
+        assert_eq!(v.len(), fx.dim());
+        assert_eq!(v.len(), 8 + 3);
+        // bids: -100 bps x 300, then an empty level; asks: +100 bps x 100
+        assert_eq!(&v[..8], &[-100.0, 300.0, 0.0, 0.0, 100.0, 100.0, 0.0, 0.0]);
+        assert!((v[8] - 200.0).abs() < 1e-9, "spread");
+        assert!((v[9] - 0.5).abs() < 1e-9, "imbalance");
+        // microprice leans to the thin ask: 9_900*0.25 + 10_100*0.75 = 10_050
+        assert!((v[10] - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn returns_vol_and_order_flow_come_from_history() {
+        let stocks = StockMarket::new();
+        let set = FeatureSet::empty()
+            .with(Feature::Returns(2))
+            .with(Feature::RealizedVol(2))
+            .with(Feature::OrderFlowImbalance);
+        let mut fx = FeatureExtractor::new(set, vec![1]);
+
+        let first = fx.extract(&MarketView::new(&books(9_900, 100, 10_100, 100), &stocks));
+        assert_eq!(first, vec![0.0; 4]);
+        // bid queue grows by 50 at the same price: OFI +50; mid unchanged
+        let second = fx.extract(&MarketView::new(&books(9_900, 150, 10_100, 100), &stocks));
+        assert_eq!(second, vec![0.0, 0.0, 0.0, 50.0]);
+        // both quotes up a dollar: the new bid counts fully, the old ask leaves
+        let third = fx.extract(&MarketView::new(&books(10_000, 80, 10_200, 60), &stocks));
+        let r = (10_100.0f64 / 10_000.0).ln();
+        assert!((third[0] - r).abs() < 1e-12);
+        assert_eq!(third[1], 0.0);
+        assert!((third[2] - r / 2.0).abs() < 1e-12, "std of [r, 0]");
+        assert_eq!(third[3], 80.0 + 100.0);
+
+        fx.reset();
+        let fresh = fx.extract(&MarketView::new(&books(10_000, 80, 10_200, 60), &stocks));
+        assert_eq!(fresh, vec![0.0; 4]);
+    }
+
+    #[test]
+    fn online_normalisation_standardises_and_clips() {
+        let stocks = StockMarket::new();
+        let set = FeatureSet::empty()
+            .with(Feature::Spread)
+            .normalized(Some(1.0));
+        let mut fx = FeatureExtractor::new(set, vec![1]);
+        for ask in [10_100, 10_300, 10_100, 10_300] {
+            fx.extract(&MarketView::new(&books(9_900, 1, ask, 1), &stocks));
+        }
+        let stats = fx.normalization().unwrap();
+        assert_eq!(stats.count(), 4);
+        assert!(stats.std(0) > 0.0);
+
+        fx.freeze(true);
+        let wide = fx.extract(&MarketView::new(&books(9_000, 1, 11_000, 1), &stocks));
+        assert_eq!(wide, vec![1.0], "clipped");
+        assert_eq!(fx.normalization().unwrap().count(), 4, "frozen");
+    }
+}
@@ -0,0 +1,15 @@
+// src/rl/mod.rs
+//
+// Reinforcement-learning interface: a Gym-style environment with one
+// caller-controlled agent inside a running `Market`, the features it
+// observes, the rewards it can be trained on, and a batch of independent
+// environments for training throughput.
+pub mod env;
+pub mod features;
+pub mod reward;
+pub mod vec_env;
+
+pub use env::{Action, Env, EnvConfig, Observation, StepInfo, Termination};
+pub use features::{Feature, FeatureExtractor, FeatureSet, RunningStats};
+pub use reward::{ImplementationShortfall, PnlReward, RewardContext, RewardFn, RiskAdjustedPnl};
+pub use vec_env::{VecEnv, VecStep};
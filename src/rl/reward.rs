@@ -0,0 +1,266 @@
+// src/rl/reward.rs
+//
+// Reward functions for `Env`. Each one sees the controlled agent's account
+// after every step and turns it into a scalar; `reset` runs once at the
+// start of an episode so stateful rewards can take their reference point.
+
+use crate::{Fill, MarketView, Portfolio, types::order::Side};
+
+/// Everything a reward may look at after one step.
+pub struct RewardContext<'a> {
+    /// The market after the step, seen by the controlled agent.
+    pub view: MarketView<'a>,
+    /// The controlled agent's account as the exchange booked it.
+    pub account: &'a Portfolio,
+    /// Starting cash plus the account marked to market, dollars.
+    pub equity: f64,
+    /// The controlled agent's executions during this step.
+    pub fills: &'a [Fill],
+    /// Whether this is the last step of the episode.
+    pub done: bool,
+}
+
+pub trait RewardFn: Send {
+    /// Start of an episode; `ctx.fills` is empty.
+    fn reset(&mut self, _ctx: &RewardContext) {}
+
+    fn reward(&mut self, ctx: &RewardContext) -> f64;
+
+    fn clone_box(&self) -> Box<dyn RewardFn>;
+}
+
+impl Clone for Box<dyn RewardFn> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Mid, else last trade, in dollars: a price even when one side of the
+/// book is empty.
+fn reference_price(ctx: &RewardContext, stock_id: u64) -> Option<f64> {
+    ctx.view
+        .get_mid_price(stock_id)
+        .or_else(|| ctx.view.last_price(stock_id))
+        .map(|c| c as f64 / 100.0)
+}
+
+// -----------------------------------------------------------------------------
+//  PnL
+// -----------------------------------------------------------------------------
+/// Change in mark-to-market equity since the previous step.
+#[derive(Debug, Clone, Default)]
+pub struct PnlReward {
+    last_equity: f64,
+}
+
+impl RewardFn for PnlReward {
+    fn reset(&mut self, ctx: &RewardContext) {
+        self.last_equity = ctx.equity;
+    }
+
+    fn reward(&mut self, ctx: &RewardContext) -> f64 {
+        let r = ctx.equity - self.last_equity;
+        self.last_equity = ctx.equity;
+        r
+    }
+
+    fn clone_box(&self) -> Box<dyn RewardFn> {
+        Box::new(self.clone())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Risk-adjusted PnL
+// -----------------------------------------------------------------------------
+/// Step PnL minus a running charge on the inventory carried: `penalty`
+/// dollars per dollar of gross exposure per step.
+#[derive(Debug, Clone)]
+pub struct RiskAdjustedPnl {
+    pub penalty: f64,
+    pnl: PnlReward,
+}
+
+impl RiskAdjustedPnl {
+    pub fn new(penalty: f64) -> Self {
+        Self {
+            penalty,
+            pnl: PnlReward::default(),
+        }
+    }
+}
+
+impl RewardFn for RiskAdjustedPnl {
+    fn reset(&mut self, ctx: &RewardContext) {
+        self.pnl.reset(ctx);
+    }
+
+    fn reward(&mut self, ctx: &RewardContext) -> f64 {
+        self.pnl.reward(ctx) - self.penalty * ctx.account.gross_exposure(&ctx.view)
+    }
+
+    fn clone_box(&self) -> Box<dyn RewardFn> {
+        Box::new(self.clone())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Implementation shortfall
+// -----------------------------------------------------------------------------
+/// Execution task: get `target` shares of `stock_id` done on `side`. Every
+/// fill is charged its distance from the arrival mid (the mid at reset),
+/// and whatever is still undone at the end of the episode is charged at
+/// the final mid, so rewards sum to minus the classic shortfall in dollars.
+#[derive(Debug, Clone)]
+pub struct ImplementationShortfall {
+    pub stock_id: u64,
+    pub side: Side,
+    pub target: u64,
+    arrival: f64,
+    executed: u64,
+}
+
+impl ImplementationShortfall {
+    pub fn new(stock_id: u64, side: Side, target: u64) -> Self {
+        Self {
+            stock_id,
+            side,
+            target,
+            arrival: 0.0,
+            executed: 0,
+        }
+    }
+
+    /// Shares done so far this episode.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Cost of trading `qty` at `price` dollars against the arrival mid.
+    fn cost(&self, price: f64, qty: u64) -> f64 {
+        let slip = price - self.arrival;
+        qty as f64
+            * match self.side {
+                Side::Buy => slip,
+                Side::Sell => -slip,
+            }
+    }
+}
+
+impl RewardFn for ImplementationShortfall {
+    fn reset(&mut self, ctx: &RewardContext) {
+        self.arrival = reference_price(ctx, self.stock_id).unwrap_or_default();
+        self.executed = 0;
+    }
+
+    fn reward(&mut self, ctx: &RewardContext) -> f64 {
+        let mut cost = 0.0;
+        for f in ctx.fills {
+            if f.trade.stock_id != self.stock_id || f.side != self.side {
+                continue;
+            }
+            // fills past the target are not part of the task
+            let qty = f.trade.volume.min(self.target - self.executed);
+            self.executed += qty;
+            cost += self.cost(f.trade.price as f64 / 100.0, qty);
+        }
+        if ctx.done {
+            let left = self.target - self.executed;
+            let mark = reference_price(ctx, self.stock_id).unwrap_or(self.arrival);
+            cost += self.cost(mark, left);
+        }
+        -cost
+    }
+
+    fn clone_box(&self) -> Box<dyn RewardFn> {
+        Box::new(self.clone())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Order, OrderBook, Role, StockMarket, Trade};
+
+    fn book(bid: u64, ask: u64) -> HashMap<u64, OrderBook> {
+        let mut book = OrderBook::new();
+        for (id, side, price) in [(1, Side::Buy, bid), (2, Side::Sell, ask)] {
+            book.process_limit_order(&mut Order {
+                id,
+                agent_id: 9,
+                stock_id: 1,
+                side,
+                price,
+                volume: 1_000,
+                filled: 0,
+            });
+        }
+        HashMap::from([(1, book)])
+    }
+
+    fn buy(price: u64, volume: u64) -> Fill {
+        Fill {
+            trade: Trade {
+                price,
+                stock_id: 1,
+                volume,
+                taker_agent_id: 0,
+                maker_agent_id: 9,
+                taker_side: Side::Buy,
+                maker_order_id: 2,
+            },
+            role: Role::Taker,
+            side: Side::Buy,
+        }
+    }
+
+    #[test]
+    fn shortfall_charges_slippage_and_the_undone_remainder() {
+        let stocks = StockMarket::new();
+        let account = Portfolio::default();
+        let ctx = |books, fills, done| RewardContext {
+            view: MarketView::new(books, &stocks),
+            account: &account,
+            equity: 0.0,
+            fills,
+            done,
+        };
+        let mut is = ImplementationShortfall::new(1, Side::Buy, 100);
+        let arrival = book(9_900, 10_100); // $100 mid
+        is.reset(&ctx(&arrival, &[], false));
+
+        // 60 @ $101: $60 of slippage
+        let fills = [buy(10_100, 60)];
+        let r = is.reward(&ctx(&arrival, &fills, false));
+        assert!((r + 60.0).abs() < 1e-9);
+        assert_eq!(is.executed(), 60);
+
+        // 40 left, the mid has run to $105: $200 of opportunity cost
+        let later = book(10_400, 10_600);
+        let r = is.reward(&ctx(&later, &[], true));
+        assert!((r + 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn shortfall_ignores_fills_beyond_the_target() {
+        let stocks = StockMarket::new();
+        let account = Portfolio::default();
+        let books = book(9_900, 10_100);
+        let ctx = |fills, done| RewardContext {
+            view: MarketView::new(&books, &stocks),
+            account: &account,
+            equity: 0.0,
+            fills,
+            done,
+        };
+        let mut is = ImplementationShortfall::new(1, Side::Buy, 10);
+        is.reset(&ctx(&[], false));
+        let fills = [buy(10_200, 30)];
+        let r = is.reward(&ctx(&fills, true));
+        assert!((r + 20.0).abs() < 1e-9, "only 10 shares count");
+    }
+}
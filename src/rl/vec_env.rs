@@ -0,0 +1,294 @@
+// src/rl/vec_env.rs
+//
+// N independent environments stepped together for training throughput.
+// Each owns its own `Market` (books, agents, seeded RNGs, sentiment): nothing
+// is shared between them, so they can step on worker threads and the result
+// never depends on how many threads were used.
+
+use std::thread;
+
+use super::env::{Action, Env, EnvConfig, Observation, StepInfo};
+use crate::market::mix_seed;
+
+/// Buffers of one batched step, row `i` belonging to environment `i`.
+pub struct VecStep<'a> {
+    /// `num_envs × obs_dim`, row-major. Rows of finished episodes already
+    /// hold the first observation of the next one.
+    pub observations: &'a [f64],
+    pub rewards: &'a [f64],
+    pub dones: &'a [bool],
+    pub infos: &'a [StepInfo],
+    /// The last observation of every episode that ended this step.
+    pub final_observations: &'a [Option<Observation>],
+}
+
+pub struct VecEnv {
+    envs: Vec<Env>,
+    seed: u64,
+    /// Episodes started per environment, for their seeds.
+    episodes: Vec<u64>,
+    threads: usize,
+    dim: usize,
+    observations: Vec<f64>,
+    rewards: Vec<f64>,
+    dones: Vec<bool>,
+    infos: Vec<StepInfo>,
+    final_observations: Vec<Option<Observation>>,
+}
+
+/// One environment's step, plus the first observation of the next episode
+/// if this one ended.
+type Stepped = (Observation, f64, bool, StepInfo, Option<Observation>);
+
+/// Step `env`, resetting it with `next_seed` if the episode ended.
+fn step_env(env: &mut Env, action: Action, next_seed: u64) -> Stepped {
+    let (obs, reward, done, info) = env.step(action);
+    let next = done.then(|| env.reset(next_seed));
+    (obs, reward, done, info, next)
+}
+
+/// Seed of episode `episode` of environment `env`.
+fn episode_seed(seed: u64, env: usize, episode: u64) -> u64 {
+    mix_seed(
+        seed ^ episode.wrapping_mul(0xD1B5_4A32_D192_ED03),
+        env as u64,
+    )
+}
+
+impl VecEnv {
+    /// `num_envs` copies of `cfg`; every episode of every copy gets its own
+    /// seed derived from `seed`. Call `reset` before the first `step`; until
+    /// then the environments hold empty markets.
+    pub fn new(cfg: EnvConfig, num_envs: usize, seed: u64) -> Self {
+        let envs: Vec<Env> = (0..num_envs).map(|_| Env::unstarted(cfg.clone())).collect();
+        let dim = envs.first().map_or(0, |e| e.observation_dim());
+        Self {
+            envs,
+            seed,
+            episodes: vec![0; num_envs],
+            threads: 1,
+            dim,
+            observations: vec![0.0; num_envs * dim],
+            rewards: vec![0.0; num_envs],
+            dones: vec![false; num_envs],
+            infos: Vec::with_capacity(num_envs),
+            final_observations: vec![None; num_envs],
+        }
+    }
+
+    /// Step environments on up to `threads` worker threads (1 = serial).
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    #[inline]
+    pub fn num_envs(&self) -> usize {
+        self.envs.len()
+    }
+
+    /// Features per observation row.
+    #[inline]
+    pub fn obs_dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+
+    /// Start a new episode everywhere; returns the stacked observations.
+    pub fn reset(&mut self) -> &[f64] {
+        for i in 0..self.envs.len() {
+            let obs = self.next_episode(i);
+            self.write_row(i, &obs);
+        }
+        &self.observations
+    }
+
+    /// Apply `actions[i]` to environment `i` and step them all. Finished
+    /// episodes are reset at once with a fresh seed.
+    pub fn step(&mut self, actions: Vec<Action>) -> VecStep<'_> {
+        assert_eq!(actions.len(), self.envs.len(), "one action per environment");
+
+        // each worker resets its own finished episodes, so seed them up front
+        let jobs: Vec<(Action, u64)> = actions
+            .into_iter()
+            .enumerate()
+            .map(|(i, a)| (a, episode_seed(self.seed, i, self.episodes[i])))
+            .collect();
+        let results: Vec<Stepped> = if self.threads <= 1 || self.envs.len() < 2 {
+            self.envs
+                .iter_mut()
+                .zip(jobs)
+                .map(|(env, (a, seed))| step_env(env, a, seed))
+                .collect()
+        } else {
+            let per_thread = self.envs.len().div_ceil(self.threads);
+            let mut jobs = jobs.into_iter();
+            let chunks: Vec<_> = self
+                .envs
+                .chunks_mut(per_thread)
+                .map(|chunk| {
+                    let n = chunk.len();
+                    (chunk, jobs.by_ref().take(n).collect::<Vec<_>>())
+                })
+                .collect();
+            thread::scope(|scope| {
+                let workers: Vec<_> = chunks
+                    .into_iter()
+                    .map(|(chunk, jobs)| {
+                        scope.spawn(move || {
+                            chunk
+                                .iter_mut()
+                                .zip(jobs)
+                                .map(|(env, (a, seed))| step_env(env, a, seed))
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().expect("environment thread panicked"))
+                    .collect()
+            })
+        };
+
+        self.infos.clear();
+        for (i, (obs, reward, done, info, next)) in results.into_iter().enumerate() {
+            self.rewards[i] = reward;
+            self.dones[i] = done;
+            self.infos.push(info);
+            match next {
+                Some(next) => {
+                    self.episodes[i] += 1;
+                    self.write_row(i, &next);
+                    self.final_observations[i] = Some(obs);
+                }
+                None => {
+                    self.write_row(i, &obs);
+                    self.final_observations[i] = None;
+                }
+            }
+        }
+
+        VecStep {
+            observations: &self.observations,
+            rewards: &self.rewards,
+            dones: &self.dones,
+            infos: &self.infos,
+            final_observations: &self.final_observations,
+        }
+    }
+
+    fn next_episode(&mut self, i: usize) -> Observation {
+        let seed = episode_seed(self.seed, i, self.episodes[i]);
+        self.episodes[i] += 1;
+        self.envs[i].reset(seed)
+    }
+
+    fn write_row(&mut self, i: usize, obs: &Observation) {
+        self.observations[i * self.dim..(i + 1) * self.dim].copy_from_slice(&obs.features);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        SentimentConfig, Side,
+        rl::features::{Feature, FeatureSet},
+    };
+
+    fn config() -> EnvConfig {
+        EnvConfig {
+            episode_length: 4,
+            warmup_ticks: 20,
+            features: FeatureSet::empty()
+                .with(Feature::Spread)
+                .with(Feature::Returns(2))
+                .with(Feature::Inventory),
+            ..EnvConfig::default()
+        }
+    }
+
+    fn buy_all(v: &VecEnv) -> Vec<Action> {
+        (0..v.num_envs())
+            .map(|i| Action::Market {
+                stock_id: v.envs()[i].market().stocks().get_all_ids()[0],
+                side: Side::Buy,
+                volume: 5,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn environments_are_independent_and_stacked() {
+        let mut v = VecEnv::new(config(), 3, 11);
+        // nothing is warmed up before the seeded reset
+        assert!(v.envs().iter().all(|e| e.market().tick() == 0));
+        let dim = v.obs_dim();
+        let obs = v.reset().to_vec();
+        assert!(v.envs().iter().all(|e| e.market().tick() == 20));
+        assert_eq!(obs.len(), 3 * dim);
+        assert_ne!(obs[..dim], obs[dim..2 * dim], "distinct seeds");
+
+        let seeds: Vec<u64> = v.envs().iter().map(|e| e.market().seed()).collect();
+        assert_eq!(seeds.len(), 3);
+        assert!(seeds[0] != seeds[1] && seeds[1] != seeds[2]);
+
+        let step = v.step(buy_all(&v));
+        assert_eq!(step.rewards.len(), 3);
+        assert_eq!(step.infos.len(), 3);
+    }
+
+    #[test]
+    fn finished_episodes_reset_with_a_new_seed() {
+        let mut v = VecEnv::new(config(), 2, 5);
+        v.reset();
+        let first = v.envs()[0].market().seed();
+        for i in 1..=4 {
+            let step = v.step(vec![Action::Hold, Action::Hold]);
+            assert_eq!(step.dones, &[i == 4, i == 4]);
+            if i == 4 {
+                assert!(step.final_observations.iter().all(Option::is_some));
+            }
+        }
+        assert_ne!(v.envs()[0].market().seed(), first);
+        assert_eq!(v.envs()[0].steps(), 0, "fresh episode");
+        let step = v.step(vec![Action::Hold, Action::Hold]);
+        assert_eq!(step.dones, &[false, false]);
+        assert!(step.final_observations.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn threads_do_not_change_the_outcome() {
+        // with per-market sentiment too: still nothing shared
+        let cfg = EnvConfig {
+            sentiment: Some(SentimentConfig {
+                tick_interval: Duration::from_millis(1),
+                spike_prob: 0.2,
+                half_life: Duration::from_millis(5),
+            }),
+            ..config()
+        };
+        let run = |threads| {
+            let mut v = VecEnv::new(cfg.clone(), 4, 9);
+            v.set_threads(threads);
+            v.reset();
+            let mut trace = Vec::new();
+            for _ in 0..6 {
+                let actions = buy_all(&v);
+                let step = v.step(actions);
+                trace.extend_from_slice(step.observations);
+                trace.extend_from_slice(step.rewards);
+            }
+            trace
+        };
+        assert_eq!(run(1), run(3));
+    }
+}
@@ -0,0 +1,1084 @@
+// src/scenario.rs
+//! Scenario files: everything one headless run needs (universe, agent
+//! populations, seed, length, session rules, outputs) in a TOML or JSON
+//! document, and the runner that plays it at full speed and writes the
+//! results to a directory.
+//!
+//! ```toml
+//! name  = "two-stock baseline"
+//! seed  = 42
+//! ticks = 5000
+//!
+//! [[agents]]
+//! kind  = "MarketMaker"
+//!
+//! [[agents]]
+//! kind  = "DumbLimit"
+//! count = 4
+//! params = { action_prob = 0.2, volume = [100, 1000] }
+//!
+//! [[agents]]
+//! kind = "WhaleAgent"
+//! active_from  = 1000
+//! active_until = 2000
+//!
+//! [session]
+//! warmup_ticks = 50
+//! circuit_breaker = { max_move = 0.10, halt_ticks = 20 }
+//!
+//! [outputs]
+//! dir = "out/baseline"
+//! bar_ticks = 100
+//! snapshots = { every = 500, levels = 5 }
+//! ```
+//! Stocks come from an inline `universe` list or a `universe_file` (CSV or
+//! JSON, see `stocks::loader`, found relative to the scenario file); with
+//! neither, `default_stock_universe()`.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AgentParams, AgentType, BookKind, Market, SentimentConfig, Side, Stock, StockMarket,
+    types::order::OrderRequest,
+};
+
+// -----------------------------------------------------------------------------
+//  File format
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default = "default_name")]
+    pub name: String,
+    #[serde(default)]
+    pub seed: u64,
+    /// Recorded ticks, after the warm-up.
+    pub ticks: u64,
+    #[serde(default)]
+    pub universe: Vec<Stock>,
+    #[serde(default)]
+    pub universe_file: Option<PathBuf>,
+    pub agents: Vec<Population>,
+    #[serde(default)]
+    pub session: SessionRules,
+    #[serde(default)]
+    pub outputs: Outputs,
+}
+
+fn default_name() -> String {
+    "scenario".into()
+}
+
+/// `count` agents of one kind, trading in their active window. `params`
+/// overrides the kind's defaults from `agents::config` for all of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Population {
+    pub kind: AgentType,
+    #[serde(default = "one")]
+    pub count: usize,
+    /// First tick (counted from the start of the run) the agents may trade.
+    #[serde(default)]
+    pub active_from: u64,
+    /// From this tick on their orders are cancelled and new ones dropped.
+    #[serde(default)]
+    pub active_until: Option<u64>,
+    #[serde(default)]
+    pub params: AgentParams,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct SessionRules {
+    /// Ticks played before recording starts (books fill up, agents wake).
+    pub warmup_ticks: u64,
+    /// Worker threads per market phase; results do not depend on it.
+    pub threads: usize,
+    /// Prints kept on the tape agents see; the runner records every trade
+    /// whatever its size.
+    pub tape_capacity: Option<usize>,
+    /// Level store for every book.
+    pub book_kind: Option<BookKind>,
+    pub sentiment: Option<SentimentRules>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+}
+
+impl Default for SessionRules {
+    fn default() -> Self {
+        Self {
+            warmup_ticks: 0,
+            threads: 1,
+            tape_capacity: None,
+            book_kind: None,
+            sentiment: None,
+            circuit_breaker: None,
+        }
+    }
+}
+
+/// Per-market sentiment, in ticks rather than wall-clock time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SentimentRules {
+    pub spike_prob: f64,
+    pub half_life_ticks: f64,
+}
+
+/// Halts new orders in a stock for `halt_ticks` once its price moves more
+/// than `max_move` (a fraction) from the reference. The reference starts at
+/// the initial price and resets to the last trade when a halt begins.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CircuitBreaker {
+    pub max_move: f64,
+    pub halt_ticks: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Outputs {
+    pub dir: PathBuf,
+    /// `trades.csv`: every print.
+    pub trades: bool,
+    /// `bars.csv`: OHLCV bars of this many ticks; `None` for no bars.
+    pub bar_ticks: Option<u64>,
+    /// `book_snapshots.jsonl`: book depth every so often.
+    pub snapshots: Option<SnapshotRules>,
+    /// `pnl.csv`: every agent's ledger at the end.
+    pub pnl: bool,
+}
+
+impl Default for Outputs {
+    fn default() -> Self {
+        Self {
+            dir: "out".into(),
+            trades: true,
+            bar_ticks: Some(100),
+            snapshots: None,
+            pnl: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SnapshotRules {
+    pub every: u64,
+    #[serde(default = "five")]
+    pub levels: usize,
+}
+
+fn five() -> usize {
+    5
+}
+
+// -----------------------------------------------------------------------------
+//  Errors
+// -----------------------------------------------------------------------------
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    /// The file is not valid TOML/JSON or does not fit the schema.
+    Parse(String),
+    /// Well-formed but unusable, e.g. zero ticks or a duplicate ticker.
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "i/o error: {e}"),
+            ScenarioError::Parse(e) => write!(f, "cannot parse scenario: {e}"),
+            ScenarioError::Invalid(e) => write!(f, "invalid scenario: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<io::Error> for ScenarioError {
+    fn from(e: io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> ScenarioError {
+    ScenarioError::Invalid(msg.into())
+}
+
+/// `false` for NaN as well.
+fn positive(x: f64) -> bool {
+    x > 0.0
+}
+
+// -----------------------------------------------------------------------------
+//  Loading
+// -----------------------------------------------------------------------------
+impl Scenario {
+    /// Reads a `.toml` or `.json` file (by extension; anything else is
+    /// tried as JSON, then TOML) and validates it. A relative
+    /// `universe_file` is taken to sit next to the scenario file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut s = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Self::from_json(&text).or_else(|_| Self::from_toml(&text)),
+        }?;
+        if let Some(file) = s.universe_file.as_mut()
+            && file.is_relative()
+            && let Some(dir) = path.parent()
+        {
+            *file = dir.join(&*file);
+        }
+        Ok(s)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ScenarioError> {
+        let s: Self = toml::from_str(text).map_err(|e| ScenarioError::Parse(e.to_string()))?;
+        s.validate()?;
+        Ok(s)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ScenarioError> {
+        let s: Self =
+            serde_json::from_str(text).map_err(|e| ScenarioError::Parse(e.to_string()))?;
+        s.validate()?;
+        Ok(s)
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if self.ticks == 0 {
+            return Err(invalid("ticks must be at least 1"));
+        }
+        if self.agents.iter().map(|p| p.count).sum::<usize>() == 0 {
+            return Err(invalid("no agents"));
+        }
+        for p in &self.agents {
+            validate_params(p)?;
+            if let Some(until) = p.active_until
+                && until <= p.active_from
+            {
+                return Err(invalid(format!(
+                    "{:?}: active_until {until} is not after active_from {}",
+                    p.kind, p.active_from
+                )));
+            }
+        }
+        if !self.universe.is_empty() && self.universe_file.is_some() {
+            return Err(invalid("give either universe or universe_file, not both"));
+        }
+        if !self.universe.is_empty() {
+            StockMarket::from_stocks(self.universe.clone())
+                .map_err(|e| invalid(format!("universe: {e}")))?;
+        }
+        if self.session.threads == 0 {
+            return Err(invalid("session.threads must be at least 1"));
+        }
+        if self.session.tape_capacity == Some(0) {
+            return Err(invalid("session.tape_capacity must be at least 1"));
+        }
+        if let Some(s) = &self.session.sentiment
+            && (!(0.0..=1.0).contains(&s.spike_prob) || !positive(s.half_life_ticks))
+        {
+            return Err(invalid(
+                "sentiment needs spike_prob in [0, 1] and half_life_ticks > 0",
+            ));
+        }
+        if let Some(cb) = &self.session.circuit_breaker
+            && !positive(cb.max_move)
+        {
+            return Err(invalid("circuit_breaker.max_move must be positive"));
+        }
+        if self.outputs.bar_ticks == Some(0) {
+            return Err(invalid("outputs.bar_ticks must be at least 1"));
+        }
+        if let Some(snap) = &self.outputs.snapshots
+            && (snap.every == 0 || snap.levels == 0)
+        {
+            return Err(invalid(
+                "outputs.snapshots needs every >= 1 and levels >= 1",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Agent kinds in id order: population by population.
+    pub fn participants(&self) -> Vec<AgentType> {
+        self.agents
+            .iter()
+            .flat_map(|p| std::iter::repeat_n(p.kind, p.count))
+            .collect()
+    }
+
+    pub fn stock_market(&self) -> Result<StockMarket, ScenarioError> {
+        if let Some(path) = &self.universe_file {
+            return StockMarket::from_path(path)
+                .map_err(|e| invalid(format!("{}: {e}", path.display())));
+        }
+        if self.universe.is_empty() {
+            return Ok(StockMarket::new());
+        }
+        StockMarket::from_stocks(self.universe.clone()).map_err(|e| invalid(e.to_string()))
+    }
+
+    /// The market at tick 0 with every population's parameters and every
+    /// session rule that lives in `Market`.
+    pub fn build_market(&self) -> Result<Market, ScenarioError> {
+        let mut market = Market::with_seed(&self.participants(), self.stock_market()?, self.seed);
+        let mut id = 0;
+        for p in &self.agents {
+            for _ in 0..p.count {
+                if p.params != AgentParams::default() {
+                    market.set_agent_params(id, p.params.clone());
+                }
+                id += 1;
+            }
+        }
+        market.set_threads(self.session.threads);
+        if let Some(cap) = self.session.tape_capacity {
+            market.set_tape_capacity(cap);
+        }
+        if let Some(kind) = self.session.book_kind {
+            for id in market.stocks().get_all_ids() {
+                market.set_book_kind(id, kind);
+            }
+        }
+        if let Some(s) = &self.session.sentiment {
+            market.enable_sentiment(&SentimentConfig {
+                tick_interval: Duration::from_secs(1),
+                spike_prob: s.spike_prob,
+                half_life: Duration::from_secs_f64(s.half_life_ticks),
+            });
+        }
+        Ok(market)
+    }
+
+    /// Play the scenario and write its outputs; see [`Runner`].
+    pub fn run(&self) -> Result<RunSummary, ScenarioError> {
+        Runner::new(self)?.run()
+    }
+}
+
+/// Every override must suit the population's kind and be in range.
+fn validate_params(p: &Population) -> Result<(), ScenarioError> {
+    let bad = |msg: &str| Err(invalid(format!("{:?} params: {msg}", p.kind)));
+    let unused = p.kind.unsupported(&p.params);
+    if !unused.is_empty() {
+        return bad(&format!("{} not taken by this kind", unused.join(", ")));
+    }
+    let q = &p.params;
+    if q.action_prob.is_some_and(|x| !(0.0..=1.0).contains(&x)) {
+        return bad("action_prob must be in [0, 1]");
+    }
+    if q.volume.is_some_and(|[min, max]| min == 0 || max < min) {
+        return bad("volume needs 1 <= min <= max");
+    }
+    if q.cash.is_some_and(|x| !(x.is_finite() && x >= 0.0)) {
+        return bad("cash must be a non-negative amount");
+    }
+    if q.inventory.is_some_and(|x| x < 0) {
+        return bad("inventory must not be negative");
+    }
+    if q.signal_noise.is_some_and(|x| !(x.is_finite() && x >= 0.0)) {
+        return bad("signal_noise must not be negative");
+    }
+    if q.threshold_bps
+        .is_some_and(|x| !(x.is_finite() && x >= 0.0))
+    {
+        return bad("threshold_bps must not be negative");
+    }
+    if q.units == Some(0) {
+        return bad("units must be at least 1");
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+//  Runner
+// -----------------------------------------------------------------------------
+/// What a finished run reports; also written to `summary.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunSummary {
+    pub name: String,
+    pub seed: u64,
+    pub ticks: u64,
+    pub trades: u64,
+    pub volume: u64,
+    pub halts: u64,
+    /// Ticker → last price in dollars.
+    pub final_prices: Vec<(String, f64)>,
+}
+
+/// One stock's bar under construction.
+#[derive(Debug, Clone, Copy)]
+struct Bar {
+    open: u64,
+    high: u64,
+    low: u64,
+    close: u64,
+    volume: u64,
+    trades: u64,
+    notional: u128,
+}
+
+pub struct Runner<'s> {
+    scenario: &'s Scenario,
+    market: Market,
+    /// Active window per agent id.
+    windows: Vec<(u64, Option<u64>)>,
+    /// Circuit breaker: reference price (cents) and halt end per stock.
+    reference: HashMap<u64, u64>,
+    halted_until: HashMap<u64, u64>,
+    halts: u64,
+    bars: HashMap<u64, Bar>,
+    bar_start: u64,
+    trades: u64,
+    volume: u64,
+    trades_out: Option<BufWriter<fs::File>>,
+    bars_out: Option<BufWriter<fs::File>>,
+    snapshots_out: Option<BufWriter<fs::File>>,
+}
+
+impl<'s> Runner<'s> {
+    /// Builds the market and creates the output directory and files.
+    pub fn new(scenario: &'s Scenario) -> Result<Self, ScenarioError> {
+        scenario.validate()?;
+        let market = scenario.build_market()?;
+        let out = &scenario.outputs;
+        fs::create_dir_all(&out.dir)?;
+        let create = |name: &str, header: Option<&str>| -> io::Result<BufWriter<fs::File>> {
+            let mut w = BufWriter::new(fs::File::create(out.dir.join(name))?);
+            if let Some(h) = header {
+                writeln!(w, "{h}")?;
+            }
+            Ok(w)
+        };
+        let trades_out = out
+            .trades
+            .then(|| {
+                create(
+                    "trades.csv",
+                    Some("tick,ticker,price,volume,taker_side,taker_agent,maker_agent,maker_order_id"),
+                )
+            })
+            .transpose()?;
+        let bars_out = out
+            .bar_ticks
+            .map(|_| {
+                create(
+                    "bars.csv",
+                    Some("start_tick,ticker,open,high,low,close,volume,trades,vwap"),
+                )
+            })
+            .transpose()?;
+        let snapshots_out = out
+            .snapshots
+            .map(|_| create("book_snapshots.jsonl", None))
+            .transpose()?;
+
+        let windows = scenario
+            .agents
+            .iter()
+            .flat_map(|p| std::iter::repeat_n((p.active_from, p.active_until), p.count))
+            .collect();
+        let reference = market
+            .stocks()
+            .get_all_stocks()
+            .iter()
+            .map(|s| (s.id, (s.initial_price * 100.0).round() as u64))
+            .collect();
+
+        Ok(Self {
+            scenario,
+            market,
+            windows,
+            reference,
+            halted_until: HashMap::new(),
+            halts: 0,
+            bars: HashMap::new(),
+            bar_start: scenario.session.warmup_ticks + 1,
+            trades: 0,
+            volume: 0,
+            trades_out,
+            bars_out,
+            snapshots_out,
+        })
+    }
+
+    pub fn market(&self) -> &Market {
+        &self.market
+    }
+
+    /// Warm-up plus `ticks` recorded ticks, then the end-of-run files.
+    pub fn run(mut self) -> Result<RunSummary, ScenarioError> {
+        let total = self.scenario.session.warmup_ticks + self.scenario.ticks;
+        for _ in 0..total {
+            self.step()?;
+        }
+        self.finish()
+    }
+
+    /// One tick: decide, apply the session rules to the requests and to
+    /// the margin calls `execute` raises, execute, record.
+    pub fn step(&mut self) -> Result<(), ScenarioError> {
+        let mut requests = self.market.decide();
+        let tick = self.market.tick();
+
+        requests.retain(|r| self.allowed(r, tick));
+        for id in 0..self.windows.len() {
+            let active = self.active(id, tick);
+            self.market.set_suspended(id, !active);
+        }
+        for (&stock_id, &end) in &self.halted_until {
+            self.market.set_halted(stock_id, tick < end);
+        }
+        for (id, &(_, until)) in self.windows.iter().enumerate() {
+            if until == Some(tick) {
+                let view = self.market.view().for_agent(id);
+                requests.extend(view.open_orders().into_iter().map(|o| {
+                    OrderRequest::CancelOrder {
+                        agent_id: id,
+                        order_id: o.id,
+                    }
+                }));
+            }
+        }
+        self.market.execute(requests);
+
+        self.check_breaker(tick);
+        if tick > self.scenario.session.warmup_ticks {
+            self.record(tick)?;
+        }
+        Ok(())
+    }
+
+    fn allowed(&self, req: &OrderRequest, tick: u64) -> bool {
+        let (agent_id, stock_id) = match *req {
+            OrderRequest::LimitOrder {
+                agent_id, stock_id, ..
+            }
+            | OrderRequest::MarketOrder {
+                agent_id, stock_id, ..
+            }
+            | OrderRequest::CreateUnits {
+                agent_id, stock_id, ..
+            }
+            | OrderRequest::RedeemUnits {
+                agent_id, stock_id, ..
+            } => (agent_id, Some(stock_id)),
+            OrderRequest::ModifyOrder {
+                agent_id, order_id, ..
+            } => (
+                agent_id,
+                self.market
+                    .order_books()
+                    .iter()
+                    .find_map(|(&s, b)| b.order(order_id).map(|_| s)),
+            ),
+            // pulling liquidity is always allowed
+            OrderRequest::CancelOrder { .. } => return true,
+        };
+        let halted = stock_id
+            .and_then(|s| self.halted_until.get(&s))
+            .is_some_and(|&end| tick < end);
+        self.active(agent_id, tick) && !halted
+    }
+
+    /// Inside its population's window; agents outside the scenario always are.
+    fn active(&self, agent_id: usize, tick: u64) -> bool {
+        self.windows
+            .get(agent_id)
+            .is_none_or(|&(from, until)| tick >= from && until.is_none_or(|u| tick < u))
+    }
+
+    fn check_breaker(&mut self, tick: u64) {
+        let Some(cb) = self.scenario.session.circuit_breaker else {
+            return;
+        };
+        let mut last = HashMap::new();
+        for t in self.market.last_trades() {
+            last.insert(t.stock_id, t.price);
+        }
+        for (stock_id, price) in last {
+            let reference = self.reference.entry(stock_id).or_insert(price);
+            let moved = (price as f64 - *reference as f64).abs() / *reference as f64;
+            if moved > cb.max_move {
+                self.halted_until.insert(stock_id, tick + 1 + cb.halt_ticks);
+                *reference = price;
+                self.halts += 1;
+            }
+        }
+    }
+
+    fn record(&mut self, tick: u64) -> io::Result<()> {
+        let trades = self.market.last_trades().to_vec();
+        for t in trades {
+            self.trades += 1;
+            self.volume += t.volume;
+            if let Some(w) = self.trades_out.as_mut() {
+                writeln!(
+                    w,
+                    "{},{},{},{},{},{},{},{}",
+                    tick,
+                    self.market.ticker(t.stock_id),
+                    dollars(t.price),
+                    t.volume,
+                    if t.taker_side == Side::Buy {
+                        "buy"
+                    } else {
+                        "sell"
+                    },
+                    t.taker_agent_id,
+                    t.maker_agent_id,
+                    t.maker_order_id
+                )?;
+            }
+            self.bars
+                .entry(t.stock_id)
+                .and_modify(|b| {
+                    b.high = b.high.max(t.price);
+                    b.low = b.low.min(t.price);
+                    b.close = t.price;
+                    b.volume += t.volume;
+                    b.trades += 1;
+                    b.notional += t.price as u128 * t.volume as u128;
+                })
+                .or_insert(Bar {
+                    open: t.price,
+                    high: t.price,
+                    low: t.price,
+                    close: t.price,
+                    volume: t.volume,
+                    trades: 1,
+                    notional: t.price as u128 * t.volume as u128,
+                });
+        }
+
+        if let Some(len) = self.scenario.outputs.bar_ticks
+            && tick + 1 - self.bar_start >= len
+        {
+            self.flush_bars()?;
+            self.bar_start = tick + 1;
+        }
+
+        if let (Some(snap), Some(w)) =
+            (self.scenario.outputs.snapshots, self.snapshots_out.as_mut())
+            && (tick - self.scenario.session.warmup_ticks).is_multiple_of(snap.every)
+        {
+            let mut ids = self.market.stocks().get_all_ids();
+            ids.sort_unstable();
+            for id in ids {
+                let Some(book) = self.market.order_book(id) else {
+                    continue;
+                };
+                let line = serde_json::json!({
+                    "tick": tick,
+                    "ticker": self.market.ticker(id),
+                    "depth": book.depth(snap.levels),
+                });
+                writeln!(w, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the bars in progress (stocks that traded) and start over.
+    fn flush_bars(&mut self) -> io::Result<()> {
+        let Some(w) = self.bars_out.as_mut() else {
+            return Ok(());
+        };
+        let mut ids: Vec<u64> = self.bars.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let b = self.bars[&id];
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{:.4}",
+                self.bar_start,
+                self.market.ticker(id),
+                dollars(b.open),
+                dollars(b.high),
+                dollars(b.low),
+                dollars(b.close),
+                b.volume,
+                b.trades,
+                b.notional as f64 / b.volume as f64 / 100.0
+            )?;
+        }
+        self.bars.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<RunSummary, ScenarioError> {
+        self.flush_bars()?;
+        let dir = &self.scenario.outputs.dir;
+
+        if self.scenario.outputs.pnl {
+            let mut w = BufWriter::new(fs::File::create(dir.join("pnl.csv"))?);
+            writeln!(
+                w,
+                "agent,kind,shares,cash,realized_pnl,unrealized_pnl,fees,equity"
+            )?;
+            let view = self.market.view();
+            let kinds = self.scenario.participants();
+            let mut ids = self.market.agent_ids();
+            ids.sort_unstable();
+            for id in ids {
+                let kind = kinds.get(id).map_or("Custom".into(), |k| format!("{k:?}"));
+                let (shares, cash, realized, unrealized, fees, equity) =
+                    match self.market.account(id) {
+                        Some(a) => (
+                            a.shares(),
+                            a.cash(),
+                            a.realized_pnl(),
+                            a.unrealized_pnl(&view),
+                            a.fees_paid(),
+                            a.evaluate(&view),
+                        ),
+                        None => (0, 0.0, 0.0, 0.0, 0.0, 0.0),
+                    };
+                writeln!(
+                    w,
+                    "{id},{kind},{shares},{cash:.2},{realized:.2},{unrealized:.2},{fees:.2},{equity:.2}"
+                )?;
+            }
+            w.flush()?;
+        }
+        for w in [
+            self.trades_out.as_mut(),
+            self.bars_out.as_mut(),
+            self.snapshots_out.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            w.flush()?;
+        }
+
+        let mut stocks = self.market.stocks().get_all_stocks();
+        stocks.sort_unstable_by_key(|s| s.id);
+        let summary = RunSummary {
+            name: self.scenario.name.clone(),
+            seed: self.scenario.seed,
+            ticks: self.scenario.ticks,
+            trades: self.trades,
+            volume: self.volume,
+            halts: self.halts,
+            final_prices: stocks
+                .iter()
+                .map(|s| (s.ticker.clone(), self.market.last_price(s.id)))
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&summary).map_err(io::Error::other)?;
+        fs::write(dir.join("summary.json"), json)?;
+        Ok(summary)
+    }
+}
+
+fn dollars(cents: u64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DumbLimitAgent, Marketable};
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scenario-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    const TOML: &str = r#"
+        name  = "small"
+        seed  = 3
+        ticks = 150
+
+        [[agents]]
+        kind = "MarketMaker"
+
+        [[agents]]
+        kind  = "DumbLimit"
+        count = 2
+
+        [[agents]]
+        kind = "DumbMarket"
+        count = 2
+        active_until = 120
+
+        [session]
+        warmup_ticks = 20
+
+        [outputs]
+        bar_ticks = 50
+        snapshots = { every = 50, levels = 3 }
+    "#;
+
+    #[test]
+    fn toml_and_json_describe_the_same_scenario() {
+        let from_toml = Scenario::from_toml(TOML).unwrap();
+        let json = serde_json::to_string(&from_toml).unwrap();
+        let from_json = Scenario::from_json(&json).unwrap();
+
+        assert_eq!(from_toml.participants().len(), 5);
+        assert_eq!(from_json.participants().len(), 5);
+        assert_eq!(from_json.agents[2].active_until, Some(120));
+        assert_eq!(from_json.outputs.snapshots.unwrap().levels, 3);
+        assert_eq!(from_json.session.threads, 1);
+        assert_eq!(from_json.stock_market().unwrap().stocks.len(), 2);
+    }
+
+    #[test]
+    fn universe_files_resolve_next_to_the_scenario() {
+        let dir = scratch("universe");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("stocks.csv"),
+            "ticker,id,company_name,total_float,initial_price\nAAA,7,Aaa Inc,1000,12.5\n",
+        )
+        .unwrap();
+        let toml =
+            "ticks = 10\nuniverse_file = \"stocks.csv\"\n[[agents]]\nkind = \"MarketMaker\"\n";
+        fs::write(dir.join("scenario.toml"), toml).unwrap();
+
+        let s = Scenario::from_path(dir.join("scenario.toml")).unwrap();
+        assert_eq!(s.universe_file, Some(dir.join("stocks.csv")));
+        assert_eq!(s.stock_market().unwrap().get_all_ids(), [7]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn bad_scenarios_are_refused() {
+        let parse = Scenario::from_toml("ticks = 10\nagents = []\nbogus = 1");
+        assert!(matches!(parse, Err(ScenarioError::Parse(_))));
+
+        let empty = Scenario::from_json(r#"{"ticks": 10, "agents": []}"#);
+        assert!(matches!(empty, Err(ScenarioError::Invalid(_))));
+
+        let dup = Scenario::from_json(
+            r#"{"ticks": 10, "agents": [{"kind": "MarketMaker"}], "universe": [
+                {"ticker": "A", "id": 1, "company_name": "A", "total_float": 1, "initial_price": 10.0},
+                {"ticker": "A", "id": 2, "company_name": "B", "total_float": 1, "initial_price": 10.0}]}"#,
+        );
+        match dup {
+            Err(ScenarioError::Invalid(msg)) => assert!(msg.contains("ticker A listed twice")),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn population_params_reach_their_agents() {
+        let s = Scenario::from_toml(
+            r#"
+            ticks = 10
+
+            [[agents]]
+            kind = "MarketMaker"
+
+            [[agents]]
+            kind   = "DumbLimit"
+            count  = 2
+            params = { action_prob = 1.0, volume = [7, 7], cash = 1000.0, inventory = 5 }
+
+            [[agents]]
+            kind   = "Informed"
+            params = { signal_delay = 0, signal_noise = 0.0 }
+        "#,
+        )
+        .unwrap();
+        let mut market = s.build_market().unwrap();
+        let stocks = market.stocks().get_all_ids();
+        for id in [1, 2] {
+            for &stock in &stocks {
+                assert_eq!(market.account(id).unwrap().qty(stock), 5);
+            }
+        }
+        assert_ne!(market.account(0).unwrap().qty(stocks[0]), 5);
+
+        let mut limits = 0;
+        for _ in 0..20 {
+            let requests = market.decide();
+            for r in &requests {
+                if let OrderRequest::LimitOrder {
+                    agent_id: 1 | 2,
+                    volume,
+                    ..
+                } = *r
+                {
+                    assert_eq!(volume, 7);
+                    limits += 1;
+                }
+            }
+            market.execute(requests);
+        }
+        assert!(limits > 0, "the limit traders act every tick once awake");
+
+        // reset rebuilds them with the same overrides
+        market.reset();
+        assert_eq!(market.account(2).unwrap().qty(stocks[0]), 5);
+
+        let wrong = TOML.replace(
+            "kind = \"DumbMarket\"",
+            "kind = \"DumbMarket\"\n        params = { inventory = 3 }",
+        );
+        match Scenario::from_toml(&wrong) {
+            Err(ScenarioError::Invalid(msg)) => assert!(msg.contains("inventory"), "{msg}"),
+            other => panic!("{other:?}"),
+        }
+        let out_of_range = TOML.replace(
+            "kind = \"DumbMarket\"",
+            "kind = \"DumbMarket\"\n        params = { action_prob = 1.5 }",
+        );
+        assert!(matches!(
+            Scenario::from_toml(&out_of_range),
+            Err(ScenarioError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn halted_stocks_and_retired_agents_take_no_margin_calls() {
+        let mut s = Scenario::from_toml(TOML).unwrap();
+        s.outputs = Outputs {
+            dir: scratch("halt"),
+            trades: false,
+            bar_ticks: None,
+            snapshots: None,
+            pnl: false,
+        };
+        let mut runner = Runner::new(&s).unwrap();
+        let ids = runner.market().stocks().get_all_ids();
+        let (halted, open) = (ids[0], ids[1]);
+        // let the makers fill both books first
+        for _ in 0..20 {
+            runner.step().unwrap();
+        }
+        // past the margin threshold, so it buys back every stock each tick
+        let short = |id| -> Box<dyn crate::Agent> {
+            Box::new(DumbLimitAgent::new(id).with_inventory(-30_000))
+        };
+
+        // an agent whose window has closed keeps its position
+        let tick = runner.market().tick();
+        runner.windows.push((0, Some(tick + 1)));
+        let retired = runner.market.add_agent(short);
+        assert_eq!(retired, runner.windows.len() - 1);
+        for _ in 0..5 {
+            runner.step().unwrap();
+        }
+        for &stock in &ids {
+            assert!(
+                runner
+                    .market()
+                    .order_book(stock)
+                    .unwrap()
+                    .best_ask()
+                    .is_some()
+            );
+            assert_eq!(
+                runner.market().account(retired).unwrap().qty(stock),
+                -30_000
+            );
+        }
+
+        // a halted stock takes no margin call; the open one does
+        runner.windows.push((0, None));
+        let active = runner.market.add_agent(short);
+        runner.halted_until.insert(halted, u64::MAX);
+        let mut covered = 0;
+        for _ in 0..5 {
+            runner.step().unwrap();
+            for t in runner.market().last_trades() {
+                assert_ne!(t.stock_id, halted, "{t:?}");
+                covered += u64::from(t.taker_agent_id == active && t.stock_id == open);
+            }
+        }
+        assert!(covered > 0, "the open stock still takes the margin call");
+        assert_eq!(
+            runner.market().account(active).unwrap().qty(halted),
+            -30_000
+        );
+        let _ = fs::remove_dir_all(&s.outputs.dir);
+    }
+
+    #[test]
+    fn a_run_writes_every_output_and_is_reproducible() {
+        let mut s = Scenario::from_toml(TOML).unwrap();
+        let mut outputs = Vec::new();
+        for run in ["a", "b"] {
+            s.outputs.dir = scratch(run);
+            // the second run keeps a single print on the tape
+            s.session.tape_capacity = (run == "b").then_some(1);
+            let summary = s.run().unwrap();
+            assert_eq!(summary.ticks, 150);
+            assert!(summary.trades > 0);
+            outputs.push((summary, s.outputs.dir.clone()));
+        }
+
+        let (summary, dir) = &outputs[0];
+        let read = |d: &PathBuf, f: &str| fs::read_to_string(d.join(f)).unwrap();
+        let trades = read(dir, "trades.csv");
+        assert_eq!(trades.lines().count() as u64, summary.trades + 1);
+        // warm-up ticks are played but not recorded
+        let first_tick: u64 = trades
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(first_tick > 20);
+        assert!(read(dir, "bars.csv").lines().count() > 1);
+        // 150 recorded ticks, a snapshot every 50, two stocks
+        assert_eq!(read(dir, "book_snapshots.jsonl").lines().count(), 6);
+        // one row per agent
+        assert_eq!(read(dir, "pnl.csv").lines().count(), 6);
+
+        // same seed, same tape, however little of it the market keeps
+        assert_eq!(outputs[0].0, outputs[1].0);
+        assert_eq!(trades, read(&outputs[1].1, "trades.csv"));
+        for (_, d) in &outputs {
+            let _ = fs::remove_dir_all(d);
+        }
+    }
+
+    #[test]
+    fn retired_agents_stop_trading() {
+        let mut s = Scenario::from_toml(TOML).unwrap();
+        s.outputs.dir = scratch("retired");
+        s.outputs.pnl = false;
+        s.run().unwrap();
+        let trades = fs::read_to_string(s.outputs.dir.join("trades.csv")).unwrap();
+        // agents 3 and 4 (the DumbMarket population) retire at tick 120
+        for line in trades.lines().skip(1) {
+            let cols: Vec<&str> = line.split(',').collect();
+            let tick: u64 = cols[0].parse().unwrap();
+            if tick >= 120 {
+                assert!(
+                    !["3", "4"].contains(&cols[5]) && !["3", "4"].contains(&cols[6]),
+                    "{line}"
+                );
+            }
+        }
+        let _ = fs::remove_dir_all(&s.outputs.dir);
+    }
+}
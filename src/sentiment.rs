@@ -2,12 +2,13 @@
 
 use once_cell::sync::OnceCell;
 use parking_lot::RwLock;
-use rand::Rng;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use std::{collections::HashMap, thread, time::Duration};
 
 /// What each stock’s sentiment looks like right now.
 static SENTIMENT: OnceCell<RwLock<HashMap<u64, f64>>> = OnceCell::new();
 
+#[derive(Debug, Clone)]
 pub struct SentimentConfig {
     pub tick_interval: Duration,
     pub spike_prob: f64,
@@ -48,23 +49,94 @@ pub fn init(stock_ids: Vec<u64>, cfg: SentimentConfig) {
                     *v *= decay;
                 }
                 // ensure we stay within [-1, 1]
-                if *v > 1.0 {
-                    *v = 1.0
-                } else if *v < -1.0 {
-                    *v = -1.0
-                }
+                *v = v.clamp(-1.0, 1.0);
             }
         }
     });
 }
 
 /// Agents call this to get the latest sentiment (≈ instantaneous).
+/// Inside a `Market`, prefer `MarketView::sentiment`, which is per market
+/// and reproducible.
 pub fn get(stock_id: u64) -> f64 {
     let table = SENTIMENT
         .get()
         .expect("sentiment_engine::init() must be called first");
     *table.read().get(&stock_id).unwrap_or(&0.0)
 }
+// ──────────────────────────────────────────────────────────────────────────────
+//  Per-market engine
+// ──────────────────────────────────────────────────────────────────────────────
+/// The same spike-and-decay process as the global engine, but owned by one
+/// `Market`, stepped once per tick (`tick_interval` is the length of a tick)
+/// and driven by a seeded stream, so forks and replays agree.
+#[derive(Debug, Clone)]
+pub struct Sentiment {
+    values: HashMap<u64, f64>,
+    ids: Vec<u64>,
+    spike_prob: f64,
+    decay: f64,
+    rng: StdRng,
+}
+
+impl Sentiment {
+    pub fn new(stock_ids: impl IntoIterator<Item = u64>, cfg: &SentimentConfig, seed: u64) -> Self {
+        let mut ids: Vec<u64> = stock_ids.into_iter().collect();
+        ids.sort_unstable();
+        Self {
+            values: ids.iter().map(|&id| (id, 0.0)).collect(),
+            ids,
+            spike_prob: cfg.spike_prob,
+            decay: 2f64.powf(-cfg.tick_interval.as_secs_f64() / cfg.half_life.as_secs_f64()),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Advance one tick.
+    pub fn step(&mut self) {
+        for id in &self.ids {
+            let v = self.values.get_mut(id).unwrap();
+            if self.rng.gen_bool(self.spike_prob) {
+                *v = self.rng.gen_range(-1.0..=1.0);
+            } else {
+                *v *= self.decay;
+            }
+        }
+    }
+
+    /// Current value in [-1, 1]; 0 for an unknown stock.
+    pub fn get(&self, stock_id: u64) -> f64 {
+        self.values.get(&stock_id).copied().unwrap_or(0.0)
+    }
+
+    /// Restart the random stream, keeping current values.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Back to neutral everywhere with a fresh stream.
+    pub fn reset(&mut self, seed: u64) {
+        self.values.values_mut().for_each(|v| *v = 0.0);
+        self.reseed(seed);
+    }
+
+    /// Override one stock's value, e.g. to script a news shock.
+    pub fn set(&mut self, stock_id: u64, value: f64) {
+        if !self.values.contains_key(&stock_id) {
+            self.ids.push(stock_id);
+            self.ids.sort_unstable();
+        }
+        self.values.insert(stock_id, value.clamp(-1.0, 1.0));
+    }
+
+    /// Stop tracking `stock_id` (a delisting).
+    pub fn remove(&mut self, stock_id: u64) {
+        if self.values.remove(&stock_id).is_some() {
+            self.ids.retain(|&id| id != stock_id);
+        }
+    }
+}
+
 // ──────────────────────────────────────────────────────────────────────────────
 //  Unit tests for sentiment engine
 // ──────────────────────────────────────────────────────────────────────────────
@@ -117,7 +189,7 @@ mod tests {
 
     #[test]
     fn unknown_ticker_returns_zero() {
-        let _ = init(vec![100], cfg());
+        init(vec![100], cfg());
         // 9999 was never inserted
         assert_eq!(get(9999), 0.0);
     }
@@ -141,7 +213,7 @@ mod tests {
         sleep(Duration::from_millis(35));
         for &id in &ids {
             let v = get(id);
-            assert!(v >= -1.0 && v <= 1.0, "spike out of [-1,1]: {}", v);
+            assert!((-1.0..=1.0).contains(&v), "spike out of [-1,1]: {}", v);
         }
     }
 
@@ -165,6 +237,28 @@ mod tests {
         // run for a few hundred ms
         sleep(Duration::from_millis(500));
         let v = get(7);
-        assert!(v >= -1.0 && v <= 1.0, "long‐run value out of [-1,1]: {}", v);
+        assert!(
+            (-1.0..=1.0).contains(&v),
+            "long‐run value out of [-1,1]: {}",
+            v
+        );
+    }
+
+    #[test]
+    fn per_market_engine_is_seeded_and_decays() {
+        let mut a = Sentiment::new([1, 2], &make_cfg(0.2, 10, 100), 9);
+        let mut b = a.clone();
+        for _ in 0..50 {
+            a.step();
+            b.step();
+        }
+        assert_eq!(a.get(1), b.get(1));
+        assert_eq!(a.get(2), b.get(2));
+
+        let mut quiet = Sentiment::new([1], &make_cfg(0.0, 100, 100), 0);
+        quiet.set(1, 3.0);
+        assert_eq!(quiet.get(1), 1.0, "clamped");
+        quiet.step();
+        assert_eq!(quiet.get(1), 0.5);
     }
 }
@@ -0,0 +1,471 @@
+// src/server.rs
+//! Control server for external policies (e.g. a Python training loop).
+//!
+//! A [`Server`] hosts one [`Env`]: the caller's orders go through the
+//! environment's controlled agent while the configured population keeps
+//! trading around it. Clients connect over TCP or a Unix socket and talk in
+//! frames:
+//!
+//! ```text
+//! +----------------------+---------------------------+
+//! | length: u32, big-end | body: `length` bytes JSON |
+//! +----------------------+---------------------------+
+//! ```
+//!
+//! Every [`Request`] gets exactly one [`Response`]. After `SubscribeTrades`,
+//! each `Step` reply is preceded by a `Trades` frame with that step's prints.
+//! Orders from `Submit`/`Cancel` are queued and reach the books with the
+//! next `Step`. Clients are served one at a time, in accept order.
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    rl::{Action, Env, EnvConfig, Observation, StepInfo},
+    simulators::{order_book::BookDepth, tape::Print},
+    types::order::{Order, OrderRequest},
+};
+
+/// Frames above this size are refused rather than allocated.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+// -----------------------------------------------------------------------------
+//  Protocol
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Start a new episode; seed 0 when omitted.
+    Reset {
+        seed: Option<u64>,
+    },
+    /// Send the queued orders and advance one tick.
+    Step,
+    /// Queue an order. Its `agent_id` is replaced by the controlled agent's.
+    Submit {
+        order: OrderRequest,
+    },
+    /// Queue a cancel of one of the controlled agent's orders.
+    Cancel {
+        order_id: u64,
+    },
+    /// Aggregated depth of one book, best `levels` per side (default 10).
+    QueryBook {
+        stock_id: u64,
+        levels: Option<usize>,
+    },
+    /// The controlled agent's resting orders.
+    OpenOrders,
+    SubscribeTrades,
+    UnsubscribeTrades,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Reset {
+        agent_id: usize,
+        observation: Observation,
+    },
+    Step {
+        observation: Observation,
+        reward: f64,
+        done: bool,
+        info: StepInfo,
+    },
+    /// Orders waiting for the next step.
+    Queued {
+        pending: usize,
+    },
+    Book {
+        stock_id: u64,
+        tick: u64,
+        depth: BookDepth,
+    },
+    OpenOrders {
+        orders: Vec<Order>,
+    },
+    Subscribed {
+        trades: bool,
+    },
+    /// Pushed to subscribers before each `Step` reply.
+    Trades {
+        prints: Vec<Print>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Write one frame.
+pub fn write_frame<W: Write, T: Serialize>(w: &mut W, msg: &T) -> io::Result<()> {
+    let body = serde_json::to_vec(msg)?;
+    let len = u32::try_from(body.len())
+        .ok()
+        .filter(|&n| n as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "frame too large"))?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(&body)?;
+    w.flush()
+}
+
+/// Read one frame's body; `None` when the peer closed cleanly between frames.
+pub fn read_frame_bytes<R: Read>(r: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, "frame too large"));
+    }
+    let mut body = vec![0u8; len];
+    r.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Read and decode one frame; `None` on a clean close.
+pub fn read_frame<R: Read, T: DeserializeOwned>(r: &mut R) -> io::Result<Option<T>> {
+    match read_frame_bytes(r)? {
+        Some(body) => Ok(Some(serde_json::from_slice(&body)?)),
+        None => Ok(None),
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Server
+// -----------------------------------------------------------------------------
+pub struct Server {
+    env: Env,
+    pending: Vec<Action>,
+}
+
+impl Server {
+    pub fn new(cfg: EnvConfig) -> Self {
+        Self {
+            env: Env::new(cfg),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Serve one client until it disconnects. Undecodable frames get an
+    /// `Error` reply and the session goes on; I/O errors end it.
+    pub fn serve<S: Read + Write>(&mut self, mut stream: S) -> io::Result<()> {
+        let mut subscribed = false;
+        while let Some(body) = read_frame_bytes(&mut stream)? {
+            let replies = match serde_json::from_slice::<Request>(&body) {
+                Ok(req) => self.handle(req, &mut subscribed),
+                Err(e) => vec![Response::Error {
+                    message: format!("bad request: {e}"),
+                }],
+            };
+            for reply in &replies {
+                write_frame(&mut stream, reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer one request; the last response is the reply, anything before
+    /// it is pushed data.
+    pub fn handle(&mut self, req: Request, subscribed: &mut bool) -> Vec<Response> {
+        match req {
+            Request::Reset { seed } => {
+                self.pending.clear();
+                let observation = self.env.reset(seed.unwrap_or(0));
+                vec![Response::Reset {
+                    agent_id: self.env.agent_id(),
+                    observation,
+                }]
+            }
+            Request::Step => {
+                let actions = std::mem::take(&mut self.pending);
+                let finished = self.env.is_done();
+                let (observation, reward, done, info) = self.env.step(Action::Batch(actions));
+                let mut out = Vec::with_capacity(2);
+                if *subscribed {
+                    // the tape is capped, the step's batch is not
+                    let trades = if finished {
+                        &[][..]
+                    } else {
+                        self.env.market().last_trades()
+                    };
+                    let prints = trades
+                        .iter()
+                        .map(|&trade| Print {
+                            tick: info.tick,
+                            trade,
+                        })
+                        .collect();
+                    out.push(Response::Trades { prints });
+                }
+                out.push(Response::Step {
+                    observation,
+                    reward,
+                    done,
+                    info,
+                });
+                out
+            }
+            Request::Submit { order } => {
+                self.pending.push(action_for(order));
+                vec![self.queued()]
+            }
+            Request::Cancel { order_id } => {
+                self.pending.push(Action::Cancel { order_id });
+                vec![self.queued()]
+            }
+            Request::QueryBook { stock_id, levels } => {
+                let reply = match self.env.market().order_book(stock_id) {
+                    Some(book) => Response::Book {
+                        stock_id,
+                        tick: self.env.market().tick(),
+                        depth: book.depth(levels.unwrap_or(10)),
+                    },
+                    None => Response::Error {
+                        message: format!("unknown stock {stock_id}"),
+                    },
+                };
+                vec![reply]
+            }
+            Request::OpenOrders => vec![Response::OpenOrders {
+                orders: self.env.open_orders(),
+            }],
+            Request::SubscribeTrades | Request::UnsubscribeTrades => {
+                *subscribed = req == Request::SubscribeTrades;
+                vec![Response::Subscribed {
+                    trades: *subscribed,
+                }]
+            }
+        }
+    }
+
+    fn queued(&self) -> Response {
+        Response::Queued {
+            pending: self.pending.len(),
+        }
+    }
+}
+
+/// The controlled agent's action for a client's request.
+fn action_for(order: OrderRequest) -> Action {
+    match order {
+        OrderRequest::LimitOrder {
+            stock_id,
+            side,
+            price,
+            volume,
+            ..
+        } => Action::Limit {
+            stock_id,
+            side,
+            price,
+            volume,
+        },
+        OrderRequest::MarketOrder {
+            stock_id,
+            side,
+            volume,
+            ..
+        } => Action::Market {
+            stock_id,
+            side,
+            volume,
+        },
+        OrderRequest::CancelOrder { order_id, .. } => Action::Cancel { order_id },
+        OrderRequest::ModifyOrder {
+            order_id,
+            price,
+            volume,
+            ..
+        } => Action::Modify {
+            order_id,
+            price,
+            volume,
+        },
+        OrderRequest::CreateUnits {
+            stock_id, units, ..
+        } => Action::Create { stock_id, units },
+        OrderRequest::RedeemUnits {
+            stock_id, units, ..
+        } => Action::Redeem { stock_id, units },
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::*;
+    use crate::types::order::Side;
+
+    fn config() -> EnvConfig {
+        EnvConfig {
+            episode_length: 50,
+            warmup_ticks: 20,
+            ..EnvConfig::default()
+        }
+    }
+
+    fn call<S: Read + Write>(s: &mut S, req: &Request) -> Response {
+        write_frame(s, req).unwrap();
+        read_frame(s).unwrap().unwrap()
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buf = Vec::new();
+        let req = Request::QueryBook {
+            stock_id: 1,
+            levels: Some(3),
+        };
+        write_frame(&mut buf, &req).unwrap();
+        assert_eq!(
+            u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize,
+            buf.len() - 4
+        );
+        let mut r = &buf[..];
+        assert_eq!(read_frame::<_, Request>(&mut r).unwrap(), Some(req));
+        assert_eq!(
+            read_frame::<_, Request>(&mut r).unwrap(),
+            None,
+            "clean close"
+        );
+
+        let wire = br#"{"type":"reset","seed":3}"#;
+        assert_eq!(
+            serde_json::from_slice::<Request>(wire).unwrap(),
+            Request::Reset { seed: Some(3) }
+        );
+    }
+
+    #[test]
+    fn tcp_session_trades_and_streams_prints() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut server = Server::new(config());
+            server.serve(stream).unwrap();
+            server.env().market().tick()
+        });
+
+        let mut c = TcpStream::connect(addr).unwrap();
+        let Response::Reset { agent_id, .. } = call(&mut c, &Request::Reset { seed: Some(4) })
+        else {
+            panic!("expected a reset reply");
+        };
+        let stock_id = 1;
+        let Response::Book { depth, .. } = call(
+            &mut c,
+            &Request::QueryBook {
+                stock_id,
+                levels: Some(5),
+            },
+        ) else {
+            panic!("expected a book");
+        };
+        assert!(!depth.bids.is_empty() || !depth.asks.is_empty());
+        assert!(matches!(
+            call(&mut c, &Request::SubscribeTrades),
+            Response::Subscribed { trades: true }
+        ));
+
+        // a resting bid far below the market, then a marketable buy
+        let resting = OrderRequest::LimitOrder {
+            agent_id: 999, // overwritten by the server
+            stock_id,
+            side: Side::Buy,
+            price: 1,
+            volume: 7,
+        };
+        assert_eq!(
+            call(&mut c, &Request::Submit { order: resting }),
+            Response::Queued { pending: 1 }
+        );
+        write_frame(&mut c, &Request::Step).unwrap();
+        let Some(Response::Trades { prints }) = read_frame(&mut c).unwrap() else {
+            panic!("subscribers get prints first");
+        };
+        let Some(Response::Step { info, .. }) = read_frame::<_, Response>(&mut c).unwrap() else {
+            panic!("then the step reply");
+        };
+        assert!(info.termination.is_none());
+        assert!(prints.iter().all(|p| p.tick == info.tick));
+
+        let Response::OpenOrders { orders } = call(&mut c, &Request::OpenOrders) else {
+            panic!("expected open orders");
+        };
+        assert_eq!(orders.len(), 1);
+        assert_eq!((orders[0].agent_id, orders[0].price), (agent_id, 1));
+
+        call(
+            &mut c,
+            &Request::Cancel {
+                order_id: orders[0].id,
+            },
+        );
+        call(&mut c, &Request::UnsubscribeTrades);
+        assert!(matches!(
+            call(&mut c, &Request::Step),
+            Response::Step { .. }
+        ));
+        let Response::OpenOrders { orders } = call(&mut c, &Request::OpenOrders) else {
+            panic!("expected open orders");
+        };
+        assert!(orders.is_empty(), "cancelled");
+
+        // garbage gets an error and the session survives
+        c.write_all(&3u32.to_be_bytes()).unwrap();
+        c.write_all(b"???").unwrap();
+        assert!(matches!(
+            read_frame::<_, Response>(&mut c).unwrap(),
+            Some(Response::Error { .. })
+        ));
+        assert!(matches!(
+            call(
+                &mut c,
+                &Request::QueryBook {
+                    stock_id: 12_345,
+                    levels: None
+                }
+            ),
+            Response::Error { .. }
+        ));
+
+        drop(c);
+        assert!(server.join().unwrap() > 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_session() {
+        use std::os::unix::net::UnixStream;
+
+        let (mut client, stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || Server::new(config()).serve(stream));
+        assert!(matches!(
+            call(&mut client, &Request::Reset { seed: None }),
+            Response::Reset { .. }
+        ));
+        assert!(matches!(
+            call(&mut client, &Request::Step),
+            Response::Step { done: false, .. }
+        ));
+        drop(client);
+        server.join().unwrap().unwrap();
+    }
+}
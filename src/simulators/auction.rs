@@ -0,0 +1,257 @@
+// src/simulators/auction.rs
+//
+// Single-price call auction, used to open a newly listed stock. Limit
+// orders collect without matching; at the end everything that crosses
+// trades at the one price that executes the most shares, and the unfilled
+// remainder goes on to the continuous book.
+
+use crate::{
+    corporate::CorporateAction,
+    types::order::{Order, Side, Trade},
+};
+
+#[derive(Debug, Clone)]
+pub struct CallAuction {
+    /// Tick whose batch is the last one collected.
+    ends_at: u64,
+    /// Cents; breaks ties between equally good clearing prices.
+    reference: u64,
+    /// Arrival order.
+    orders: Vec<Order>,
+}
+
+impl CallAuction {
+    pub fn new(ends_at: u64, reference: u64) -> Self {
+        Self {
+            ends_at,
+            reference,
+            orders: Vec::new(),
+        }
+    }
+
+    pub fn ends_at(&self) -> u64 {
+        self.ends_at
+    }
+
+    pub fn orders(&self) -> &[Order] {
+        &self.orders
+    }
+
+    pub fn order(&self, order_id: u64) -> Option<Order> {
+        self.orders.iter().find(|o| o.id == order_id).copied()
+    }
+
+    pub(crate) fn add(&mut self, order: Order) {
+        self.orders.push(order);
+    }
+
+    pub(crate) fn cancel(&mut self, order_id: u64, agent_id: usize) -> bool {
+        let before = self.orders.len();
+        self.orders
+            .retain(|o| !(o.id == order_id && o.agent_id == agent_id));
+        self.orders.len() != before
+    }
+
+    /// Reprice or resize in place; time priority does not matter until the
+    /// uncross, which only ranks by price and order id.
+    pub(crate) fn modify(
+        &mut self,
+        order_id: u64,
+        agent_id: usize,
+        price: u64,
+        volume: u64,
+    ) -> Option<Order> {
+        let o = self
+            .orders
+            .iter_mut()
+            .find(|o| o.id == order_id && o.agent_id == agent_id)?;
+        o.price = price;
+        o.volume = volume;
+        o.filled = 0;
+        Some(*o)
+    }
+
+    /// Restate the collected orders and the reference for a corporate
+    /// action. Returns the orders it left nothing of, now removed.
+    pub(crate) fn adjust(&mut self, action: &CorporateAction) -> Vec<Order> {
+        self.reference = action.price_cents(self.reference);
+        let mut dropped = Vec::new();
+        self.orders.retain_mut(|o| {
+            let before = *o;
+            let keep = action.adjust_order(o);
+            if !keep {
+                dropped.push(before);
+            }
+            keep
+        });
+        dropped
+    }
+
+    /// Clearing price and shares if the auction ended now: the price that
+    /// executes the most, then the smallest imbalance, then the one nearest
+    /// the reference. `None` if nothing crosses.
+    pub fn indicative(&self) -> Option<(u64, u64)> {
+        // any order price can clear, and so can the reference itself
+        let mut prices: Vec<u64> = self.orders.iter().map(|o| o.price).collect();
+        prices.push(self.reference);
+        prices.sort_unstable();
+        prices.dedup();
+
+        let side_volume = |side, ok: &dyn Fn(u64) -> bool| -> u64 {
+            self.orders
+                .iter()
+                .filter(|o| o.side == side && ok(o.price))
+                .map(|o| o.volume - o.filled)
+                .sum()
+        };
+        prices
+            .into_iter()
+            .map(|p| {
+                let bid = side_volume(Side::Buy, &|px| px >= p);
+                let ask = side_volume(Side::Sell, &|px| px <= p);
+                (p, bid.min(ask), bid.abs_diff(ask))
+            })
+            .filter(|&(_, exec, _)| exec > 0)
+            .min_by_key(|&(p, exec, imbalance)| {
+                (
+                    std::cmp::Reverse(exec),
+                    imbalance,
+                    p.abs_diff(self.reference),
+                    p,
+                )
+            })
+            .map(|(p, exec, _)| (p, exec))
+    }
+
+    /// Cross the book at the indicative price. Returns the prints and every
+    /// order that still has shares left (partially filled ones carry their
+    /// `filled`), in arrival order. Of each matched pair the later order is
+    /// the taker.
+    pub(crate) fn uncross(mut self) -> (Option<u64>, Vec<Trade>, Vec<Order>) {
+        let Some((price, _)) = self.indicative() else {
+            return (None, Vec::new(), self.orders);
+        };
+        let rank = |side: Side| {
+            let mut idx: Vec<usize> = (0..self.orders.len())
+                .filter(|&i| {
+                    let o = &self.orders[i];
+                    o.side == side
+                        && match side {
+                            Side::Buy => o.price >= price,
+                            Side::Sell => o.price <= price,
+                        }
+                })
+                .collect();
+            idx.sort_by_key(|&i| {
+                let o = &self.orders[i];
+                let px = match side {
+                    Side::Buy => u64::MAX - o.price,
+                    Side::Sell => o.price,
+                };
+                (px, o.id)
+            });
+            idx
+        };
+        let (buys, sells) = (rank(Side::Buy), rank(Side::Sell));
+
+        let mut trades = Vec::new();
+        let (mut b, mut s) = (0, 0);
+        while b < buys.len() && s < sells.len() {
+            let (bi, si) = (buys[b], sells[s]);
+            let (buy, sell) = (self.orders[bi], self.orders[si]);
+            let volume = (buy.volume - buy.filled).min(sell.volume - sell.filled);
+            let (taker, maker) = if buy.id > sell.id {
+                (buy, sell)
+            } else {
+                (sell, buy)
+            };
+            trades.push(Trade {
+                price,
+                stock_id: buy.stock_id,
+                volume,
+                taker_agent_id: taker.agent_id,
+                maker_agent_id: maker.agent_id,
+                taker_side: taker.side,
+                maker_order_id: maker.id,
+            });
+            self.orders[bi].filled += volume;
+            self.orders[si].filled += volume;
+            if self.orders[bi].filled >= self.orders[bi].volume {
+                b += 1;
+            }
+            if self.orders[si].filled >= self.orders[si].volume {
+                s += 1;
+            }
+        }
+        let rest = self
+            .orders
+            .into_iter()
+            .filter(|o| o.filled < o.volume)
+            .collect();
+        (Some(price), trades, rest)
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: u64, side: Side, price: u64, volume: u64) -> Order {
+        Order {
+            id,
+            agent_id: id as usize,
+            stock_id: 9,
+            side,
+            price,
+            volume,
+            filled: 0,
+        }
+    }
+
+    #[test]
+    fn clears_at_the_volume_maximising_price() {
+        let mut a = CallAuction::new(5, 1_000);
+        a.add(order(1, Side::Buy, 1_010, 300));
+        a.add(order(2, Side::Buy, 1_000, 200));
+        a.add(order(3, Side::Sell, 990, 250));
+        a.add(order(4, Side::Sell, 1_005, 300));
+        a.add(order(5, Side::Buy, 950, 100));
+
+        // at 10.05: bids 300, asks 550 -> 300; at 10.00: 500 vs 250 -> 250
+        assert_eq!(a.indicative(), Some((1_005, 300)));
+
+        let (price, trades, rest) = a.uncross();
+        assert_eq!(price, Some(1_005));
+        assert!(trades.iter().all(|t| t.price == 1_005));
+        assert_eq!(trades.iter().map(|t| t.volume).sum::<u64>(), 300);
+        // the cheapest seller goes first; order 1 arrived before both sells
+        assert_eq!(
+            (trades[0].maker_order_id, trades[0].taker_side),
+            (1, Side::Sell)
+        );
+
+        let left: Vec<(u64, u64)> = rest.iter().map(|o| (o.id, o.volume - o.filled)).collect();
+        assert_eq!(left, [(2, 200), (4, 250), (5, 100)]);
+    }
+
+    #[test]
+    fn ties_go_to_the_reference_and_nothing_crossing_is_a_no_op() {
+        let mut a = CallAuction::new(1, 1_000);
+        a.add(order(1, Side::Buy, 1_020, 100));
+        a.add(order(2, Side::Sell, 980, 100));
+        // every price in [9.80, 10.20] clears 100 with no imbalance
+        assert_eq!(a.indicative(), Some((1_000, 100)));
+
+        let mut quiet = CallAuction::new(1, 1_000);
+        quiet.add(order(1, Side::Buy, 990, 100));
+        quiet.add(order(2, Side::Sell, 1_010, 100));
+        assert!(quiet.cancel(2, 2));
+        assert!(!quiet.cancel(2, 2));
+        quiet.add(order(3, Side::Sell, 1_010, 100));
+        let (price, trades, rest) = quiet.uncross();
+        assert_eq!((price, trades.len(), rest.len()), (None, 0, 2));
+    }
+}
@@ -0,0 +1,377 @@
+// src/simulators/correlated.rs
+//
+// Several GBM prices stepped together. Each step draws one independent
+// normal per asset and mixes them through the Cholesky factor of the
+// correlation matrix, so log-returns co-move as the matrix says while each
+// asset keeps its own drift and volatility (annualised, as in
+// `GBMSimulator`).
+
+use std::{any::Any, collections::HashMap, fmt};
+
+use rand::{SeedableRng, rngs::StdRng};
+use rand_distr::{Distribution, StandardNormal};
+
+use super::market_trait::Marketable;
+use crate::{OrderBook, stocks::definitions::StockMarket};
+
+/// Slack allowed on symmetry, the unit diagonal and zero pivots.
+const EPS: f64 = 1e-9;
+
+/// Per-asset parameters, annualised.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Asset {
+    pub stock_id: u64,
+    pub drift: f64,
+    pub volatility: f64,
+}
+
+impl Asset {
+    pub fn new(stock_id: u64, drift: f64, volatility: f64) -> Self {
+        Self {
+            stock_id,
+            drift,
+            volatility,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorrelationError {
+    UnknownStock(u64),
+    DuplicateStock(u64),
+    /// The matrix is not `expected` × `expected`, one row per asset.
+    Dimension {
+        expected: usize,
+        found: usize,
+    },
+    /// An entry outside [-1, 1], a diagonal other than 1, or a mismatch
+    /// between `(row, col)` and `(col, row)`.
+    InvalidEntry {
+        row: usize,
+        col: usize,
+        msg: &'static str,
+    },
+    /// The matrix has a negative eigenvalue, so no set of assets could have
+    /// these correlations.
+    NotPositiveSemiDefinite,
+    /// A non-finite drift or a negative or non-finite volatility.
+    InvalidAsset {
+        stock_id: u64,
+        msg: &'static str,
+    },
+}
+
+impl fmt::Display for CorrelationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorrelationError::UnknownStock(id) => write!(f, "no stock with id {id}"),
+            CorrelationError::DuplicateStock(id) => write!(f, "stock id {id} appears twice"),
+            CorrelationError::Dimension { expected, found } => {
+                write!(
+                    f,
+                    "expected a {expected}×{expected} matrix, found {found} rows or columns"
+                )
+            }
+            CorrelationError::InvalidEntry { row, col, msg } => {
+                write!(f, "correlation ({row}, {col}): {msg}")
+            }
+            CorrelationError::NotPositiveSemiDefinite => {
+                write!(f, "correlation matrix is not positive semi-definite")
+            }
+            CorrelationError::InvalidAsset { stock_id, msg } => {
+                write!(f, "stock id {stock_id}: {msg}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CorrelationError {}
+
+/// Lower-triangular `L` with `L Lᵀ = correlation`. Semi-definite matrices
+/// (perfectly correlated assets) are accepted: a zero pivot leaves its
+/// column empty as long as nothing below needs it.
+pub fn cholesky(correlation: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, CorrelationError> {
+    let n = correlation.len();
+    if let Some(row) = correlation.iter().find(|r| r.len() != n) {
+        return Err(CorrelationError::Dimension {
+            expected: n,
+            found: row.len(),
+        });
+    }
+    for (i, row) in correlation.iter().enumerate() {
+        for (j, &rho) in row.iter().enumerate() {
+            let msg = if !rho.is_finite() || rho.abs() > 1.0 + EPS {
+                "must be within [-1, 1]"
+            } else if i == j && (rho - 1.0).abs() > EPS {
+                "diagonal must be 1"
+            } else if (rho - correlation[j][i]).abs() > EPS {
+                "matrix must be symmetric"
+            } else {
+                continue;
+            };
+            return Err(CorrelationError::InvalidEntry {
+                row: i,
+                col: j,
+                msg,
+            });
+        }
+    }
+
+    let mut l = vec![vec![0.0; n]; n];
+    for j in 0..n {
+        let pivot = correlation[j][j] - (0..j).map(|k| l[j][k] * l[j][k]).sum::<f64>();
+        if pivot < -EPS {
+            return Err(CorrelationError::NotPositiveSemiDefinite);
+        }
+        l[j][j] = pivot.max(0.0).sqrt();
+        for i in j + 1..n {
+            let rest = correlation[i][j] - (0..j).map(|k| l[i][k] * l[j][k]).sum::<f64>();
+            if l[j][j] > EPS {
+                l[i][j] = rest / l[j][j];
+            } else if rest.abs() > EPS {
+                return Err(CorrelationError::NotPositiveSemiDefinite);
+            }
+        }
+    }
+    Ok(l)
+}
+
+#[derive(Debug, Clone)]
+pub struct CorrelatedGbm {
+    assets: Vec<Asset>,
+    index: HashMap<u64, usize>,
+    initial: Vec<f64>,
+    prices: Vec<f64>,
+    cholesky: Vec<Vec<f64>>,
+    /// Years per step.
+    dt: f64,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl CorrelatedGbm {
+    /// One price per asset, starting at the stock's `initial_price`, with
+    /// `correlation[i][j]` between the log-returns of `assets[i]` and
+    /// `assets[j]`. Steps are one trading day (1/252 of a year) apart and
+    /// draw from seed 0 unless `with_seed` picks another.
+    pub fn new(
+        stocks: &StockMarket,
+        assets: &[Asset],
+        correlation: &[Vec<f64>],
+    ) -> Result<Self, CorrelationError> {
+        if correlation.len() != assets.len() {
+            return Err(CorrelationError::Dimension {
+                expected: assets.len(),
+                found: correlation.len(),
+            });
+        }
+        let mut index = HashMap::with_capacity(assets.len());
+        let mut initial = Vec::with_capacity(assets.len());
+        for (i, a) in assets.iter().enumerate() {
+            let stock = stocks
+                .get_stock_by_id(a.stock_id)
+                .ok_or(CorrelationError::UnknownStock(a.stock_id))?;
+            if index.insert(a.stock_id, i).is_some() {
+                return Err(CorrelationError::DuplicateStock(a.stock_id));
+            }
+            let msg = if !a.drift.is_finite() {
+                "drift must be finite"
+            } else if !(a.volatility.is_finite() && a.volatility >= 0.0) {
+                "volatility must be a non-negative number"
+            } else {
+                initial.push(stock.initial_price);
+                continue;
+            };
+            return Err(CorrelationError::InvalidAsset {
+                stock_id: a.stock_id,
+                msg,
+            });
+        }
+        let seed = 0;
+        Ok(Self {
+            assets: assets.to_vec(),
+            index,
+            prices: initial.clone(),
+            initial,
+            cholesky: cholesky(correlation)?,
+            dt: 1.0 / 252.0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    /// Draw from `seed` instead of 0; `reset` rewinds to it.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.reseed(seed);
+        self
+    }
+
+    /// Step length in years.
+    pub fn with_dt(mut self, years: f64) -> Self {
+        self.dt = years;
+        self
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Advance every asset by one step.
+    pub fn step_all(&mut self) {
+        let z: Vec<f64> = (0..self.assets.len())
+            .map(|_| StandardNormal.sample(&mut self.rng))
+            .collect();
+        let sqrt_dt = self.dt.sqrt();
+        for (i, a) in self.assets.iter().enumerate() {
+            let shock: f64 = (0..=i).map(|k| self.cholesky[i][k] * z[k]).sum();
+            let sigma = a.volatility;
+            self.prices[i] *=
+                ((a.drift - 0.5 * sigma * sigma) * self.dt + sigma * sqrt_dt * shock).exp();
+        }
+    }
+
+    /// Current price of `stock_id` in dollars.
+    pub fn price(&self, stock_id: u64) -> Option<f64> {
+        Some(self.prices[*self.index.get(&stock_id)?])
+    }
+
+    /// `(stock_id, dollars)` in asset order.
+    pub fn prices(&self) -> impl Iterator<Item = (u64, f64)> + '_ {
+        self.assets
+            .iter()
+            .zip(&self.prices)
+            .map(|(a, &p)| (a.stock_id, p))
+    }
+
+    pub fn assets(&self) -> &[Asset] {
+        &self.assets
+    }
+
+    /// The lower-triangular factor the shocks are mixed through.
+    pub fn cholesky(&self) -> &[Vec<f64>] {
+        &self.cholesky
+    }
+}
+
+// As a `Marketable` the first asset stands in for "the" price, the same
+// way `Market` reports one price for backward compatibility.
+impl Marketable for CorrelatedGbm {
+    fn step(&mut self) -> f64 {
+        self.step_all();
+        self.current_price()
+    }
+    fn current_price(&self) -> f64 {
+        self.prices.first().copied().unwrap_or_default()
+    }
+    fn reset(&mut self) {
+        self.prices.clone_from(&self.initial);
+        self.reseed(self.seed);
+    }
+    fn get_order_book(&self) -> Option<&OrderBook> {
+        None
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn run(&mut self) {
+        loop {
+            self.step_all();
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cholesky_factors_valid_matrices_and_rejects_the_rest() {
+        let l = cholesky(&[vec![1.0, 0.6], vec![0.6, 1.0]]).unwrap();
+        assert!((l[1][0] - 0.6).abs() < 1e-12 && (l[1][1] - 0.8).abs() < 1e-12);
+        // perfectly correlated: semi-definite, still fine
+        let l = cholesky(&[vec![1.0, 1.0], vec![1.0, 1.0]]).unwrap();
+        assert_eq!(l, vec![vec![1.0, 0.0], vec![1.0, 0.0]]);
+
+        let bad = [
+            vec![vec![1.0, 0.5], vec![0.4, 1.0]],
+            vec![vec![0.9, 0.0], vec![0.0, 1.0]],
+            vec![vec![1.0, 1.5], vec![1.5, 1.0]],
+            vec![vec![1.0, 0.0]],
+        ];
+        for m in &bad {
+            assert!(cholesky(m).is_err(), "{m:?}");
+        }
+        let impossible = [
+            vec![1.0, 0.9, -0.9],
+            vec![0.9, 1.0, 0.9],
+            vec![-0.9, 0.9, 1.0],
+        ];
+        assert_eq!(
+            cholesky(&impossible),
+            Err(CorrelationError::NotPositiveSemiDefinite)
+        );
+
+        let stocks = StockMarket::new();
+        let identity = [vec![1.0, 0.0], vec![0.0, 1.0]];
+        let unknown = [Asset::new(1, 0.0, 0.2), Asset::new(7, 0.0, 0.2)];
+        assert_eq!(
+            CorrelatedGbm::new(&stocks, &unknown, &identity).err(),
+            Some(CorrelationError::UnknownStock(7))
+        );
+        let twice = [Asset::new(1, 0.0, 0.2), Asset::new(1, 0.0, 0.2)];
+        assert_eq!(
+            CorrelatedGbm::new(&stocks, &twice, &identity).err(),
+            Some(CorrelationError::DuplicateStock(1))
+        );
+    }
+
+    #[test]
+    fn log_returns_follow_the_matrix() {
+        let stocks = StockMarket::new();
+        let assets = [Asset::new(1, 0.05, 0.3), Asset::new(2, 0.0, 0.2)];
+        let rho = 0.7;
+        let mut gbm = CorrelatedGbm::new(&stocks, &assets, &[vec![1.0, rho], vec![rho, 1.0]])
+            .unwrap()
+            .with_seed(11);
+        assert_eq!(gbm.price(2), Some(422.12));
+
+        let n = 20_000;
+        let mut returns = Vec::with_capacity(n);
+        for _ in 0..n {
+            let before: Vec<f64> = gbm.prices().map(|(_, p)| p).collect();
+            gbm.step_all();
+            let after: Vec<f64> = gbm.prices().map(|(_, p)| p).collect();
+            returns.push(((after[0] / before[0]).ln(), (after[1] / before[1]).ln()));
+        }
+        let avg = |f: &dyn Fn(&(f64, f64)) -> f64| returns.iter().map(f).sum::<f64>() / n as f64;
+        let (ma, mb) = (avg(&|r| r.0), avg(&|r| r.1));
+        let sa = avg(&|r| (r.0 - ma).powi(2)).sqrt();
+        let sb = avg(&|r| (r.1 - mb).powi(2)).sqrt();
+        let corr = avg(&|r| (r.0 - ma) * (r.1 - mb)) / (sa * sb);
+        assert!((corr - rho).abs() < 0.03, "correlation {corr}");
+        // daily vol is annual / √252
+        assert!((sa * 252f64.sqrt() - 0.3).abs() < 0.01, "vol {sa}");
+
+        // the same seed replays the same path
+        let last = gbm.current_price();
+        gbm.reset();
+        assert_eq!(gbm.price(1), Some(195.37));
+        for _ in 0..n {
+            gbm.step();
+        }
+        assert_eq!(gbm.current_price(), last);
+
+        // unseeded runs are reproducible too
+        let correlation = [vec![1.0, rho], vec![rho, 1.0]];
+        let path = || {
+            let mut gbm = CorrelatedGbm::new(&stocks, &assets, &correlation).unwrap();
+            gbm.step_all();
+            gbm.prices().collect::<Vec<_>>()
+        };
+        assert_eq!(path(), path());
+    }
+}
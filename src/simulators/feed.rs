@@ -0,0 +1,411 @@
+// src/simulators/feed.rs
+//! Incremental (ITCH-style) market-data feed.
+//!
+//! Every book change is published as one [`FeedMessage`] carrying a
+//! per-book sequence number, so consumers can maintain their own copy of
+//! the book instead of diffing full snapshots. [`BookBuilder`] does exactly
+//! that and doubles as a correctness check for the feed.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::order_book::OrderBook;
+use crate::types::order::{Order, Side, Trade};
+
+// -----------------------------------------------------------------------------
+//  Messages
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FeedEvent {
+    /// Full state of the book; consumers drop what they have and start over.
+    /// Sent to each new subscriber and after a market reset.
+    Snapshot { orders: Vec<Order> },
+    /// A new order now rests in the book with `volume` unfilled shares.
+    AddOrder {
+        order_id: u64,
+        agent_id: usize,
+        side: Side,
+        price: u64,
+        volume: u64,
+    },
+    /// A resting order was hit for `volume` shares at `price`.
+    OrderExecuted {
+        order_id: u64,
+        volume: u64,
+        price: u64,
+    },
+    /// A resting order was reduced by `volume` shares but keeps its priority.
+    OrderCancelled { order_id: u64, volume: u64 },
+    /// A resting order left the book without trading further.
+    OrderDeleted { order_id: u64 },
+    /// A resting order was replaced; the new one joins the back of the queue
+    /// with `volume` unfilled shares at `price`.
+    OrderReplaced {
+        old_order_id: u64,
+        new_order_id: u64,
+        price: u64,
+        volume: u64,
+    },
+    /// Print for the tape; always follows the matching `OrderExecuted`.
+    Trade(Trade),
+    /// The stock no longer trades; consumers drop its book.
+    Delisted,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedMessage {
+    pub stock_id: u64,
+    /// Per-book, starts at 1 and increases by exactly 1 per message.
+    /// A `Snapshot` carries the sequence number of the last message it covers.
+    pub seq: u64,
+    pub event: FeedEvent,
+}
+
+// -----------------------------------------------------------------------------
+//  Per-book emitter
+// -----------------------------------------------------------------------------
+/// Outbox owned by every `OrderBook`. Does nothing until enabled, so books
+/// nobody listens to pay a single branch per change.
+#[derive(Debug, Clone, Default)]
+pub struct Feed {
+    enabled: bool,
+    seq: u64,
+    pending: Vec<FeedMessage>,
+}
+
+impl Feed {
+    #[inline]
+    pub(crate) fn emit(&mut self, stock_id: u64, event: FeedEvent) {
+        if self.enabled {
+            self.seq += 1;
+            self.pending.push(FeedMessage {
+                stock_id,
+                seq: self.seq,
+                event,
+            });
+        }
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pending.clear();
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub(crate) fn drain(&mut self) -> std::vec::Drain<'_, FeedMessage> {
+        self.pending.drain(..)
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Book builder
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedError {
+    /// A message arrived out of order for its book.
+    SequenceGap {
+        stock_id: u64,
+        expected: u64,
+        got: u64,
+    },
+    /// An incremental message arrived before the book's first snapshot.
+    NoSnapshot { stock_id: u64 },
+    /// A message referenced an order the builder does not know.
+    UnknownOrder { stock_id: u64, order_id: u64 },
+}
+
+/// Rebuilds `OrderBook`s purely from feed messages.
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    books: HashMap<u64, OrderBook>,
+    next_seq: HashMap<u64, u64>,
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn books(&self) -> &HashMap<u64, OrderBook> {
+        &self.books
+    }
+
+    pub fn book(&self, stock_id: u64) -> Option<&OrderBook> {
+        self.books.get(&stock_id)
+    }
+
+    pub fn apply(&mut self, msg: &FeedMessage) -> Result<(), FeedError> {
+        let id = msg.stock_id;
+        if let FeedEvent::Snapshot { orders } = &msg.event {
+            let mut book = OrderBook::new();
+            for o in orders {
+                book.process_limit_order(&mut o.clone());
+            }
+            self.books.insert(id, book);
+            self.next_seq.insert(id, msg.seq + 1);
+            return Ok(());
+        }
+        if msg.event == FeedEvent::Delisted {
+            self.books.remove(&id);
+            self.next_seq.remove(&id);
+            return Ok(());
+        }
+
+        let expected = *self
+            .next_seq
+            .get(&id)
+            .ok_or(FeedError::NoSnapshot { stock_id: id })?;
+        if msg.seq != expected {
+            return Err(FeedError::SequenceGap {
+                stock_id: id,
+                expected,
+                got: msg.seq,
+            });
+        }
+        self.next_seq.insert(id, expected + 1);
+        let book = self.books.get_mut(&id).expect("snapshot seen");
+        let unknown = |order_id| FeedError::UnknownOrder {
+            stock_id: id,
+            order_id,
+        };
+
+        match msg.event {
+            FeedEvent::Snapshot { .. } | FeedEvent::Delisted => unreachable!(),
+            FeedEvent::AddOrder {
+                order_id,
+                agent_id,
+                side,
+                price,
+                volume,
+            } => {
+                book.process_limit_order(&mut Order {
+                    id: order_id,
+                    agent_id,
+                    stock_id: id,
+                    side,
+                    price,
+                    volume,
+                    filled: 0,
+                });
+            }
+            FeedEvent::OrderExecuted {
+                order_id, volume, ..
+            } => {
+                if !book.execute_resting(order_id, volume) {
+                    return Err(unknown(order_id));
+                }
+            }
+            FeedEvent::OrderCancelled { order_id, volume } => {
+                if !book.reduce_resting(order_id, volume) {
+                    return Err(unknown(order_id));
+                }
+            }
+            FeedEvent::OrderDeleted { order_id } => {
+                book.remove_resting(order_id).ok_or(unknown(order_id))?;
+            }
+            FeedEvent::OrderReplaced {
+                old_order_id,
+                new_order_id,
+                price,
+                volume,
+            } => {
+                let old = book
+                    .remove_resting(old_order_id)
+                    .ok_or(unknown(old_order_id))?;
+                book.process_limit_order(&mut Order {
+                    id: new_order_id,
+                    price,
+                    volume,
+                    filled: 0,
+                    ..old
+                });
+            }
+            FeedEvent::Trade(_) => {}
+        }
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: u64, agent_id: usize, side: Side, price: u64, volume: u64) -> Order {
+        Order {
+            id,
+            agent_id,
+            stock_id: 7,
+            side,
+            price,
+            volume,
+            filled: 0,
+        }
+    }
+
+    /* drive a live book, mirror it through the feed, compare */
+    fn mirror(book: &mut OrderBook, builder: &mut BookBuilder) {
+        for msg in book.drain_feed().collect::<Vec<_>>() {
+            builder.apply(&msg).unwrap();
+        }
+    }
+
+    fn live_with_feed() -> (OrderBook, BookBuilder) {
+        let mut book = OrderBook::new();
+        book.enable_feed();
+        let mut builder = BookBuilder::new();
+        builder
+            .apply(&FeedMessage {
+                stock_id: 7,
+                seq: 0,
+                event: FeedEvent::Snapshot { orders: vec![] },
+            })
+            .unwrap();
+        (book, builder)
+    }
+
+    fn same_depth(a: &OrderBook, b: &OrderBook) -> bool {
+        a.depth(usize::MAX) == b.depth(usize::MAX)
+            && a.resting_orders(Side::Buy)
+                .iter()
+                .map(|o| (o.id, o.volume - o.filled))
+                .eq(b
+                    .resting_orders(Side::Buy)
+                    .iter()
+                    .map(|o| (o.id, o.volume - o.filled)))
+            && a.resting_orders(Side::Sell)
+                .iter()
+                .map(|o| (o.id, o.volume - o.filled))
+                .eq(b
+                    .resting_orders(Side::Sell)
+                    .iter()
+                    .map(|o| (o.id, o.volume - o.filled)))
+    }
+
+    #[test]
+    fn add_execute_trade_sequence() {
+        let (mut book, mut builder) = live_with_feed();
+        book.process_limit_order(&mut order(1, 1, Side::Sell, 101, 50));
+        book.process_market_order(2, Side::Buy, 20);
+        let msgs: Vec<_> = book.drain_feed().collect();
+        assert_eq!(
+            msgs.iter().map(|m| m.seq).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(matches!(
+            msgs[0].event,
+            FeedEvent::AddOrder {
+                order_id: 1,
+                volume: 50,
+                ..
+            }
+        ));
+        assert!(matches!(
+            msgs[1].event,
+            FeedEvent::OrderExecuted {
+                order_id: 1,
+                volume: 20,
+                price: 101
+            }
+        ));
+        assert!(matches!(msgs[2].event, FeedEvent::Trade(t) if t.volume == 20));
+        for m in &msgs {
+            builder.apply(m).unwrap();
+        }
+        assert!(same_depth(&book, builder.book(7).unwrap()));
+    }
+
+    #[test]
+    fn modify_in_place_and_replace_are_mirrored() {
+        let (mut book, mut builder) = live_with_feed();
+        book.process_limit_order(&mut order(1, 1, Side::Buy, 99, 100));
+        book.process_limit_order(&mut order(2, 2, Side::Buy, 99, 100));
+        book.process_limit_order(&mut order(3, 3, Side::Sell, 102, 30));
+        mirror(&mut book, &mut builder);
+
+        // shrink at same price: keeps priority, partial cancel
+        book.modify_order(1, 1, 99, 60, 1).unwrap();
+        let msgs: Vec<_> = book.drain_feed().collect();
+        assert!(matches!(
+            msgs[0].event,
+            FeedEvent::OrderCancelled {
+                order_id: 1,
+                volume: 40
+            }
+        ));
+        for m in &msgs {
+            builder.apply(m).unwrap();
+        }
+        assert_eq!(book.orders_at(99)[0].id, 1);
+
+        // reprice through the ask: executes, then the remainder replaces
+        let trades = book.modify_order(2, 2, 102, 50, 10).unwrap();
+        assert_eq!(trades.len(), 1);
+        let msgs: Vec<_> = book.drain_feed().collect();
+        assert!(matches!(
+            msgs.last().unwrap().event,
+            FeedEvent::OrderReplaced {
+                old_order_id: 2,
+                new_order_id: 10,
+                price: 102,
+                volume: 20
+            }
+        ));
+        for m in &msgs {
+            builder.apply(m).unwrap();
+        }
+        book.cancel_order(1, 1);
+        mirror(&mut book, &mut builder);
+        assert!(same_depth(&book, builder.book(7).unwrap()));
+    }
+
+    #[test]
+    fn modify_rejects_wrong_owner() {
+        let mut book = OrderBook::new();
+        book.process_limit_order(&mut order(1, 1, Side::Buy, 99, 100));
+        assert!(book.modify_order(1, 2, 99, 10, 1).is_none());
+        assert!(book.modify_order(5, 1, 99, 10, 5).is_none());
+        assert_eq!(book.orders_at(99)[0].volume, 100);
+    }
+
+    #[test]
+    fn builder_detects_gaps() {
+        let (mut book, mut builder) = live_with_feed();
+        book.process_limit_order(&mut order(1, 1, Side::Sell, 101, 50));
+        book.process_limit_order(&mut order(2, 1, Side::Sell, 102, 50));
+        let msgs: Vec<_> = book.drain_feed().collect();
+        assert_eq!(
+            builder.apply(&msgs[1]),
+            Err(FeedError::SequenceGap {
+                stock_id: 7,
+                expected: 1,
+                got: 2
+            })
+        );
+        let mut fresh = BookBuilder::new();
+        assert_eq!(
+            fresh.apply(&msgs[0]),
+            Err(FeedError::NoSnapshot { stock_id: 7 })
+        );
+    }
+
+    #[test]
+    fn disabled_feed_emits_nothing() {
+        let mut book = OrderBook::new();
+        book.process_limit_order(&mut order(1, 1, Side::Sell, 101, 50));
+        assert_eq!(book.drain_feed().count(), 0);
+        assert_eq!(book.feed_seq(), 0);
+    }
+}
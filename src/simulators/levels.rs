@@ -0,0 +1,188 @@
+// src/simulators/levels.rs
+//
+// Storage for one side of an `OrderBook`: price → `PriceLevel`. The matching
+// engine only talks to the `PriceLevels` trait, so the sparse BTreeMap store
+// and the dense `PriceLadder` are interchangeable per book.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::price_ladder::PriceLadder;
+use crate::types::Side;
+
+/// Arena index; `NIL` terminates a list.
+pub(crate) type Handle = u32;
+pub(crate) const NIL: Handle = Handle::MAX;
+
+/// FIFO queue of the orders resting at one price.
+#[derive(Debug, Clone, Copy)]
+pub struct PriceLevel {
+    pub total_volume: u64,
+    pub(crate) head: Handle,
+    pub(crate) tail: Handle,
+    pub(crate) len: usize,
+}
+
+impl Default for PriceLevel {
+    fn default() -> Self {
+        Self {
+            total_volume: 0,
+            head: NIL,
+            tail: NIL,
+            len: 0,
+        }
+    }
+}
+
+impl PriceLevel {
+    pub fn order_count(&self) -> usize {
+        self.len
+    }
+}
+
+/// Boxed best-first walk over the occupied levels of one side.
+pub type LevelIter<'a> = Box<dyn Iterator<Item = (u64, &'a PriceLevel)> + 'a>;
+
+/// One side of a book. "Best" means highest for bids, lowest for asks.
+pub trait PriceLevels {
+    fn best(&self) -> Option<u64>;
+    fn level(&self, price: u64) -> Option<&PriceLevel>;
+    fn level_mut(&mut self, price: u64) -> Option<&mut PriceLevel>;
+    /// The level at `price`, created empty if missing.
+    fn level_or_insert(&mut self, price: u64) -> &mut PriceLevel;
+    fn remove_level(&mut self, price: u64);
+    fn iter_best_first(&self) -> LevelIter<'_>;
+
+    fn is_empty(&self) -> bool {
+        self.best().is_none()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Sparse store
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct TreeLevels {
+    side: Side,
+    map: BTreeMap<u64, PriceLevel>,
+}
+
+impl TreeLevels {
+    pub fn new(side: Side) -> Self {
+        Self {
+            side,
+            map: BTreeMap::new(),
+        }
+    }
+}
+
+impl PriceLevels for TreeLevels {
+    fn best(&self) -> Option<u64> {
+        match self.side {
+            Side::Buy => self.map.keys().next_back().copied(),
+            Side::Sell => self.map.keys().next().copied(),
+        }
+    }
+
+    fn level(&self, price: u64) -> Option<&PriceLevel> {
+        self.map.get(&price)
+    }
+
+    fn level_mut(&mut self, price: u64) -> Option<&mut PriceLevel> {
+        self.map.get_mut(&price)
+    }
+
+    fn level_or_insert(&mut self, price: u64) -> &mut PriceLevel {
+        self.map.entry(price).or_default()
+    }
+
+    fn remove_level(&mut self, price: u64) {
+        self.map.remove(&price);
+    }
+
+    fn iter_best_first(&self) -> LevelIter<'_> {
+        let it = self.map.iter().map(|(&p, l)| (p, l));
+        match self.side {
+            Side::Buy => Box::new(it.rev()),
+            Side::Sell => Box::new(it),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Per-book choice
+// -----------------------------------------------------------------------------
+/// Which level store a book uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BookKind {
+    /// `BTreeMap` levels: any price, O(log n) level lookup.
+    #[default]
+    Tree,
+    /// Dense array of one-cent levels covering `[min_price, max_price]`
+    /// (cents). Prices outside the band still work, just on the slow path.
+    Ladder { min_price: u64, max_price: u64 },
+}
+
+/// Enum dispatch over the stores so a `Market` can mix kinds per stock
+/// without boxing on the matching path.
+#[derive(Debug, Clone)]
+pub enum Levels {
+    Tree(TreeLevels),
+    Ladder(PriceLadder),
+}
+
+impl Levels {
+    pub fn new(kind: BookKind, side: Side) -> Self {
+        match kind {
+            BookKind::Tree => Levels::Tree(TreeLevels::new(side)),
+            BookKind::Ladder {
+                min_price,
+                max_price,
+            } => Levels::Ladder(PriceLadder::new(side, min_price, max_price)),
+        }
+    }
+}
+
+macro_rules! dispatch {
+    ($self:ident, $l:ident => $e:expr) => {
+        match $self {
+            Levels::Tree($l) => $e,
+            Levels::Ladder($l) => $e,
+        }
+    };
+}
+
+impl PriceLevels for Levels {
+    #[inline]
+    fn best(&self) -> Option<u64> {
+        dispatch!(self, l => l.best())
+    }
+    #[inline]
+    fn level(&self, price: u64) -> Option<&PriceLevel> {
+        dispatch!(self, l => l.level(price))
+    }
+    #[inline]
+    fn level_mut(&mut self, price: u64) -> Option<&mut PriceLevel> {
+        dispatch!(self, l => l.level_mut(price))
+    }
+    #[inline]
+    fn level_or_insert(&mut self, price: u64) -> &mut PriceLevel {
+        dispatch!(self, l => l.level_or_insert(price))
+    }
+    #[inline]
+    fn remove_level(&mut self, price: u64) {
+        dispatch!(self, l => l.remove_level(price))
+    }
+    fn iter_best_first(&self) -> LevelIter<'_> {
+        dispatch!(self, l => l.iter_best_first())
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        dispatch!(self, l => l.is_empty())
+    }
+}
@@ -1,4 +1,10 @@
 // src/simulators/mod.rs
+pub mod auction;
+pub mod correlated;
+pub mod feed;
 pub mod gbm;
+pub mod levels;
 pub mod market_trait;
 pub mod order_book;
+pub mod price_ladder;
+pub mod tape;
@@ -1,21 +1,94 @@
 // src/simulators/order_book.rs
-use std::collections::{BTreeMap, HashMap, VecDeque};
+//
+// Price levels are intrusive doubly-linked lists threaded through one order
+// arena. `order_id_map` stores arena handles, so lookup, cancel and modify
+// are O(1), and matching walks/unlinks slots without allocating.
+use std::collections::{BTreeSet, HashMap};
 
+use serde::{Deserialize, Serialize};
+
+use super::feed::{Feed, FeedEvent, FeedMessage};
+pub use super::levels::PriceLevel;
+use super::levels::{BookKind, Handle, LevelIter, Levels, NIL, PriceLevels};
 use crate::types::{Order, Side, Trade};
 
 // -----------------------------------------------------------------------------
 //  Core data structures
 // -----------------------------------------------------------------------------
-#[derive(Debug, Default, Clone)]
-pub struct PriceLevel {
-    pub total_volume: u64,
-    pub orders: VecDeque<Order>,
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    order: Order,
+    prev: Handle,
+    next: Handle, // doubles as the free-list link
 }
-#[derive(Debug, Default, Clone)]
+
+#[derive(Debug, Clone)]
 pub struct OrderBook {
-    pub bids: BTreeMap<u64, PriceLevel>,
-    pub asks: BTreeMap<u64, PriceLevel>,
-    order_id_map: HashMap<u64, (Side, u64)>,
+    kind: BookKind,
+    bids: Levels,
+    asks: Levels,
+    arena: Vec<Slot>,
+    free: Handle,
+    order_id_map: HashMap<u64, Handle>,
+    /// Resting order ids per owner, for per-agent queries.
+    by_agent: HashMap<usize, BTreeSet<u64>>,
+    feed: Feed,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One aggregated (level-2) price level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelQuote {
+    pub price: u64,
+    /// Unfilled shares resting at this price.
+    pub volume: u64,
+    pub order_count: usize,
+}
+
+/// One resting order a hypothetical market order would hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImpactFill {
+    pub price: u64,
+    pub volume: u64,
+    pub maker_order_id: u64,
+    pub maker_agent_id: usize,
+}
+
+/// Pre-trade estimate of a market order, see [`OrderBook::simulate_market_order`].
+/// Prices are in cents.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarketImpact {
+    /// Fills in the order `process_market_order` would produce them.
+    pub fills: Vec<ImpactFill>,
+    pub filled: u64,
+    /// Shares the opposite side could not absorb.
+    pub unfilled: u64,
+    /// Sum of price × volume over all fills.
+    pub notional: u64,
+    pub avg_price: Option<f64>,
+    pub worst_price: Option<u64>,
+    /// Average price against the pre-trade mid, in cents; positive means the
+    /// order pays away (buys above / sells below mid). `None` without a mid.
+    pub slippage: Option<f64>,
+}
+
+impl MarketImpact {
+    /// Slippage as basis points of mid.
+    pub fn slippage_bps(&self, mid: u64) -> Option<f64> {
+        Some(self.slippage? / mid as f64 * 10_000.0)
+    }
+}
+
+/// Top-of-book-first level-2 snapshot: bids descending, asks ascending.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookDepth {
+    pub bids: Vec<LevelQuote>,
+    pub asks: Vec<LevelQuote>,
 }
 
 impl OrderBook {
@@ -23,206 +96,615 @@ impl OrderBook {
     //  Construction
     // ---------------------------------------------------------------------
     pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Preallocate room for `orders` resting orders.
+    pub fn with_capacity(orders: usize) -> Self {
+        Self::with_kind(BookKind::Tree, orders)
+    }
+
+    /// Dense ladder book covering `[min_price, max_price]` cents.
+    pub fn ladder(min_price: u64, max_price: u64) -> Self {
+        Self::with_kind(
+            BookKind::Ladder {
+                min_price,
+                max_price,
+            },
+            0,
+        )
+    }
+
+    pub fn with_kind(kind: BookKind, orders: usize) -> Self {
         Self {
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
-            order_id_map: HashMap::new(),
+            kind,
+            bids: Levels::new(kind, Side::Buy),
+            asks: Levels::new(kind, Side::Sell),
+            arena: Vec::with_capacity(orders),
+            free: NIL,
+            order_id_map: HashMap::with_capacity(orders),
+            by_agent: HashMap::new(),
+            feed: Feed::default(),
         }
     }
 
+    pub fn kind(&self) -> BookKind {
+        self.kind
+    }
+
+    /// Same resting orders (and queue priority) on a different level store.
+    /// Feed state carries over, so subscribers see no gap.
+    pub fn rebuilt_as(&self, kind: BookKind) -> OrderBook {
+        let mut book = Self::with_kind(kind, self.order_count());
+        for side in [Side::Buy, Side::Sell] {
+            for order in self.resting_orders(side) {
+                book.add_limit_order(order);
+            }
+        }
+        book.feed = self.feed.clone();
+        book
+    }
+
+    /// Same orders and queue priority on a `kind` store, each passed
+    /// through `adjust` first (a corporate action rescaling prices and
+    /// volumes). Orders `adjust` rejects are left out and returned, in
+    /// priority order. Feed state carries over; the caller republishes the
+    /// book as a snapshot.
+    pub fn adjusted(
+        &self,
+        kind: BookKind,
+        mut adjust: impl FnMut(&mut Order) -> bool,
+    ) -> (OrderBook, Vec<Order>) {
+        let mut book = Self::with_kind(kind, self.order_count());
+        let mut dropped = Vec::new();
+        for side in [Side::Buy, Side::Sell] {
+            for mut order in self.resting_orders(side) {
+                let before = order;
+                if adjust(&mut order) {
+                    book.add_limit_order(order);
+                } else {
+                    dropped.push(before);
+                }
+            }
+        }
+        book.feed = self.feed.clone();
+        (book, dropped)
+    }
+
+    // ---------------------------------------------------------------------
+    //  Arena
+    // ---------------------------------------------------------------------
+    fn alloc(&mut self, order: Order) -> Handle {
+        let slot = Slot {
+            order,
+            prev: NIL,
+            next: NIL,
+        };
+        if self.free != NIL {
+            let h = self.free;
+            self.free = self.arena[h as usize].next;
+            self.arena[h as usize] = slot;
+            h
+        } else {
+            assert!(self.arena.len() < NIL as usize, "order arena exhausted");
+            self.arena.push(slot);
+            (self.arena.len() - 1) as Handle
+        }
+    }
+
+    fn release(arena: &mut [Slot], free: &mut Handle, h: Handle) {
+        arena[h as usize].next = *free;
+        *free = h;
+    }
+
+    fn push_back(arena: &mut [Slot], level: &mut PriceLevel, h: Handle) {
+        arena[h as usize].prev = level.tail;
+        arena[h as usize].next = NIL;
+        if level.tail == NIL {
+            level.head = h;
+        } else {
+            arena[level.tail as usize].next = h;
+        }
+        level.tail = h;
+        level.len += 1;
+    }
+
+    fn unlink(arena: &mut [Slot], level: &mut PriceLevel, h: Handle) {
+        let Slot { prev, next, .. } = arena[h as usize];
+        if prev == NIL {
+            level.head = next;
+        } else {
+            arena[prev as usize].next = next;
+        }
+        if next == NIL {
+            level.tail = prev;
+        } else {
+            arena[next as usize].prev = prev;
+        }
+        level.len -= 1;
+    }
+
+    /// Orders of one level, front of the queue first.
+    fn queue<'a>(&'a self, level: &PriceLevel) -> impl Iterator<Item = &'a Order> + 'a {
+        let arena = &self.arena;
+        std::iter::successors((level.head != NIL).then_some(level.head), move |&h| {
+            let next = arena[h as usize].next;
+            (next != NIL).then_some(next)
+        })
+        .map(move |h| &arena[h as usize].order)
+    }
+
     // ---------------------------------------------------------------------
     //  Internal helpers
     // ---------------------------------------------------------------------
     fn add_limit_order(&mut self, order: Order) {
+        let h = self.alloc(order);
         let book_side = match order.side {
             Side::Buy => &mut self.bids,
             Side::Sell => &mut self.asks,
         };
-        let level = book_side.entry(order.price).or_default();
-        let remaining_volume = order.volume.saturating_sub(order.filled);
-        level.total_volume += remaining_volume;
-        level.orders.push_back(order);
-        self.order_id_map
-            .insert(order.id, (order.side, order.price));
+        let level = book_side.level_or_insert(order.price);
+        level.total_volume += order.volume.saturating_sub(order.filled);
+        Self::push_back(&mut self.arena, level, h);
+        self.order_id_map.insert(order.id, h);
+        self.by_agent
+            .entry(order.agent_id)
+            .or_default()
+            .insert(order.id);
     }
 
-    // ---------------------------------------------------------------------
-    //  Public API
-    // ---------------------------------------------------------------------
-    pub fn process_market_order(
+    fn forget_owner(by_agent: &mut HashMap<usize, BTreeSet<u64>>, order: &Order) {
+        if let Some(ids) = by_agent.get_mut(&order.agent_id) {
+            ids.remove(&order.id);
+            if ids.is_empty() {
+                by_agent.remove(&order.agent_id);
+            }
+        }
+    }
+
+    /// Price levels of one side, best price first.
+    fn levels(&self, side: Side) -> LevelIter<'_> {
+        match side {
+            Side::Buy => self.bids.iter_best_first(),
+            Side::Sell => self.asks.iter_best_first(),
+        }
+    }
+
+    /// Fill up to `qty` shares against the side opposite `taker_side`, best
+    /// price first, never crossing `limit`. Returns the trades and shares filled.
+    fn sweep(
         &mut self,
+        taker_side: Side,
         taker_agent_id: usize,
-        side: Side,
-        mut volume_to_fill: u64,
-    ) -> Vec<Trade> {
+        limit: Option<u64>,
+        qty: u64,
+    ) -> (Vec<Trade>, u64) {
         let mut trades = Vec::new();
-        let mut filled_order_ids = Vec::new();
-        let mut empty_levels = Vec::new();
-
-        let book_to_match = match side {
+        let mut remaining = qty;
+        let book_to_match = match taker_side {
             Side::Buy => &mut self.asks,
             Side::Sell => &mut self.bids,
         };
 
-        let price_levels: Vec<u64> = match side {
-            Side::Buy => book_to_match.keys().cloned().collect(),
-            Side::Sell => book_to_match.keys().rev().cloned().collect(),
-        };
-
-        for price in price_levels {
-            if volume_to_fill == 0 {
+        while remaining > 0 {
+            let Some(price) = book_to_match.best() else {
+                break;
+            };
+            let price_is_good = match (taker_side, limit) {
+                (_, None) => true,
+                (Side::Buy, Some(l)) => price <= l,
+                (Side::Sell, Some(l)) => price >= l,
+            };
+            if !price_is_good {
                 break;
             }
-            if let Some(level) = book_to_match.get_mut(&price) {
-                while let Some(maker_order) = level.orders.front_mut() {
-                    if volume_to_fill == 0 {
-                        break;
-                    }
-                    let remaining_volume = maker_order.volume.saturating_sub(maker_order.filled);
-                    if remaining_volume == 0 {
-                        filled_order_ids.push(level.orders.pop_front().unwrap().id);
-                        continue;
-                    }
-                    let trade_volume = volume_to_fill.min(remaining_volume);
 
+            let level = book_to_match.level_mut(price).expect("best level exists");
+            while remaining > 0 && level.head != NIL {
+                let h = level.head;
+                let maker = &mut self.arena[h as usize].order;
+                let trade_volume = remaining.min(maker.volume.saturating_sub(maker.filled));
+                if trade_volume > 0 {
                     // ---------- build Trade ----------
-                    let (_buy_id, _sell_id) = match side {
-                        Side::Buy => (0, maker_order.id),
-                        Side::Sell => (maker_order.id, 0),
-                    };
-                    trades.push(Trade {
-                        stock_id: maker_order.stock_id.clone(),
+                    let trade = Trade {
+                        stock_id: maker.stock_id,
                         price,
                         volume: trade_volume,
-                        taker_agent_id: taker_agent_id,
-                        maker_agent_id: maker_order.agent_id,
-                        taker_side: side,
-                        maker_order_id: maker_order.id,
-                    });
+                        taker_agent_id,
+                        maker_agent_id: maker.agent_id,
+                        taker_side,
+                        maker_order_id: maker.id,
+                    };
+                    trades.push(trade);
+                    self.feed.emit(
+                        trade.stock_id,
+                        FeedEvent::OrderExecuted {
+                            order_id: maker.id,
+                            volume: trade_volume,
+                            price,
+                        },
+                    );
+                    self.feed.emit(trade.stock_id, FeedEvent::Trade(trade));
                     // ----------------------------------
 
-                    maker_order.filled += trade_volume;
+                    maker.filled += trade_volume;
                     level.total_volume -= trade_volume;
-                    volume_to_fill -= trade_volume;
-                    if maker_order.filled >= maker_order.volume {
-                        filled_order_ids.push(level.orders.pop_front().unwrap().id);
-                    }
+                    remaining -= trade_volume;
                 }
-                if level.orders.is_empty() {
-                    empty_levels.push(price);
+                if maker.filled >= maker.volume {
+                    self.order_id_map.remove(&maker.id);
+                    Self::forget_owner(&mut self.by_agent, maker);
+                    Self::unlink(&mut self.arena, level, h);
+                    Self::release(&mut self.arena, &mut self.free, h);
                 }
             }
+            if level.len == 0 {
+                book_to_match.remove_level(price);
+            }
         }
-        for price in empty_levels {
-            book_to_match.remove(&price);
+        (trades, qty - remaining)
+    }
+
+    // ---------------------------------------------------------------------
+    //  Market-data queries
+    // ---------------------------------------------------------------------
+    pub fn best_bid(&self) -> Option<u64> {
+        self.bids.best()
+    }
+
+    pub fn best_ask(&self) -> Option<u64> {
+        self.asks.best()
+    }
+
+    pub fn best_bid_ask(&self) -> (Option<u64>, Option<u64>) {
+        (self.best_bid(), self.best_ask())
+    }
+
+    /// Integer mid in cents; `None` unless both sides are quoted.
+    pub fn mid_price(&self) -> Option<u64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2)
+    }
+
+    /// Best ask minus best bid in cents (zero when locked or crossed).
+    pub fn spread(&self) -> Option<u64> {
+        Some(self.best_ask()?.saturating_sub(self.best_bid()?))
+    }
+
+    /// Size-weighted mid: leans towards the side with less resting volume.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid_px, bid_lvl) = self.levels(Side::Buy).next()?;
+        let (ask_px, ask_lvl) = self.levels(Side::Sell).next()?;
+        let (bv, av) = (bid_lvl.total_volume as f64, ask_lvl.total_volume as f64);
+        if bv + av == 0.0 {
+            return self.mid_price().map(|m| m as f64);
         }
-        for id in filled_order_ids {
-            self.order_id_map.remove(&id);
+        Some((bid_px as f64 * av + ask_px as f64 * bv) / (bv + av))
+    }
+
+    /// Aggregated level-2 view of the best `levels` prices per side.
+    pub fn depth(&self, levels: usize) -> BookDepth {
+        let quotes = |side| {
+            self.levels(side)
+                .take(levels)
+                .map(|(price, lvl)| LevelQuote {
+                    price,
+                    volume: lvl.total_volume,
+                    order_count: lvl.len,
+                })
+                .collect()
+        };
+        BookDepth {
+            bids: quotes(Side::Buy),
+            asks: quotes(Side::Sell),
         }
-        trades
     }
 
-    pub fn process_limit_order(&mut self, order: &mut Order) -> Vec<Trade> {
-        let mut trades = Vec::new();
-        let mut filled_order_ids = Vec::new();
-        let mut empty_levels = Vec::new();
+    /// Level-3 view: every order resting at `price`, in time priority.
+    pub fn orders_at(&self, price: u64) -> Vec<Order> {
+        self.bids
+            .level(price)
+            .or_else(|| self.asks.level(price))
+            .map(|lvl| self.queue(lvl).copied().collect())
+            .unwrap_or_default()
+    }
 
-        let book_to_match = match order.side {
-            Side::Buy => &mut self.asks,
-            Side::Sell => &mut self.bids,
-        };
+    /// Every order resting on `side`, in price-time priority.
+    pub fn resting_orders(&self, side: Side) -> Vec<Order> {
+        self.levels(side)
+            .flat_map(|(_, lvl)| self.queue(lvl).copied())
+            .collect()
+    }
 
-        let price_levels: Vec<u64> = match order.side {
-            Side::Buy => book_to_match.keys().cloned().collect(),
-            Side::Sell => book_to_match.keys().rev().cloned().collect(),
-        };
+    /// A resting order by id.
+    pub fn order(&self, order_id: u64) -> Option<Order> {
+        let &h = self.order_id_map.get(&order_id)?;
+        Some(self.arena[h as usize].order)
+    }
 
-        for price in price_levels {
-            let order_remaining = order.volume.saturating_sub(order.filled);
-            if order_remaining == 0 {
-                break;
-            }
-            let price_is_good = match order.side {
-                Side::Buy => price <= order.price,
-                Side::Sell => price >= order.price,
-            };
-            if !price_is_good {
-                break;
+    /// `agent_id`'s resting orders, oldest id first.
+    pub fn orders_of(&self, agent_id: usize) -> impl Iterator<Item = Order> + '_ {
+        self.by_agent
+            .get(&agent_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|&id| self.order(id))
+    }
+
+    /// Number of orders resting in the book.
+    pub fn order_count(&self) -> usize {
+        self.order_id_map.len()
+    }
+
+    /// Worst price an aggressing order on `side` would reach to fill `qty`
+    /// (a buy walks the asks, a sell walks the bids). `None` if the opposite
+    /// side cannot absorb `qty`.
+    pub fn volume_to_price(&self, side: Side, qty: u64) -> Option<u64> {
+        let mut remaining = qty;
+        for (price, lvl) in self.levels(side.opposite()) {
+            if remaining <= lvl.total_volume {
+                return Some(price);
             }
-            if let Some(level) = book_to_match.get_mut(&price) {
-                while let Some(maker_order) = level.orders.front_mut() {
-                    let order_remaining = order.volume.saturating_sub(order.filled);
-                    if order_remaining == 0 {
-                        break;
-                    }
-                    let maker_remaining = maker_order.volume.saturating_sub(maker_order.filled);
-                    let trade_volume = order_remaining.min(maker_remaining);
-                    if trade_volume > 0 {
-                        // ---------- build Trade ----------
-                        let (_buy_id, _sell_id) = match order.side {
-                            Side::Buy => (order.id, maker_order.id),
-                            Side::Sell => (maker_order.id, order.id),
-                        };
-                        trades.push(Trade {
-                            stock_id: maker_order.stock_id.clone(),
-                            price,
-                            volume: trade_volume,
-                            taker_agent_id: order.agent_id,
-                            maker_agent_id: maker_order.agent_id,
-                            taker_side: order.side,
-                            maker_order_id: maker_order.id,
-                        });
-                        // ----------------------------------
-
-                        maker_order.filled += trade_volume;
-                        order.filled += trade_volume;
-                        level.total_volume -= trade_volume;
-                    }
-                    if maker_order.filled >= maker_order.volume {
-                        filled_order_ids.push(level.orders.pop_front().unwrap().id);
-                    }
+            remaining -= lvl.total_volume;
+        }
+        None
+    }
+
+    /// Dry run of `process_market_order(_, side, qty)`: walks the same
+    /// levels and queue positions without touching the book.
+    pub fn simulate_market_order(&self, side: Side, qty: u64) -> MarketImpact {
+        let mut out = MarketImpact::default();
+        let mut remaining = qty;
+        'levels: for (price, lvl) in self.levels(side.opposite()) {
+            for maker in self.queue(lvl) {
+                if remaining == 0 {
+                    break 'levels;
                 }
-                if level.orders.is_empty() {
-                    empty_levels.push(price);
+                let volume = remaining.min(maker.volume.saturating_sub(maker.filled));
+                if volume == 0 {
+                    continue;
                 }
+                out.fills.push(ImpactFill {
+                    price,
+                    volume,
+                    maker_order_id: maker.id,
+                    maker_agent_id: maker.agent_id,
+                });
+                out.notional += price * volume;
+                out.worst_price = Some(price);
+                remaining -= volume;
             }
         }
-        for price in empty_levels {
-            book_to_match.remove(&price);
+        out.filled = qty - remaining;
+        out.unfilled = remaining;
+        if out.filled > 0 {
+            let avg = out.notional as f64 / out.filled as f64;
+            out.avg_price = Some(avg);
+            out.slippage = self.mid_price().map(|mid| match side {
+                Side::Buy => avg - mid as f64,
+                Side::Sell => mid as f64 - avg,
+            });
         }
-        for id in filled_order_ids {
-            self.order_id_map.remove(&id);
+        out
+    }
+
+    /// Order-book imbalance over the best `levels` prices, in `[-1, 1]`:
+    /// positive when bids outweigh asks.
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let sum = |side| -> u64 {
+            self.levels(side)
+                .take(levels)
+                .map(|(_, l)| l.total_volume)
+                .sum()
+        };
+        let (b, a) = (sum(Side::Buy) as f64, sum(Side::Sell) as f64);
+        if b + a == 0.0 {
+            return None;
         }
+        Some((b - a) / (b + a))
+    }
+
+    // ---------------------------------------------------------------------
+    //  Public API
+    // ---------------------------------------------------------------------
+    pub fn process_market_order(
+        &mut self,
+        taker_agent_id: usize,
+        side: Side,
+        volume_to_fill: u64,
+    ) -> Vec<Trade> {
+        self.sweep(side, taker_agent_id, None, volume_to_fill).0
+    }
+
+    pub fn process_limit_order(&mut self, order: &mut Order) -> Vec<Trade> {
+        let trades = self.match_limit_order(order);
         if order.filled < order.volume {
             self.add_limit_order(*order);
+            self.feed.emit(
+                order.stock_id,
+                FeedEvent::AddOrder {
+                    order_id: order.id,
+                    agent_id: order.agent_id,
+                    side: order.side,
+                    price: order.price,
+                    volume: order.volume - order.filled,
+                },
+            );
+        }
+        trades
+    }
+
+    /// Amend a resting order to `new_volume` unfilled shares at `new_price`.
+    ///
+    /// Shrinking at the same price happens in place and keeps queue priority.
+    /// Anything else pulls the order and re-enters it as `new_order_id`, which
+    /// may trade immediately; the remainder (if any) rests at the back of the
+    /// queue. A `new_volume` of zero cancels. Returns `None` if the order is
+    /// not in the book or belongs to another agent.
+    pub fn modify_order(
+        &mut self,
+        order_id: u64,
+        agent_id: usize,
+        new_price: u64,
+        new_volume: u64,
+        new_order_id: u64,
+    ) -> Option<Vec<Trade>> {
+        let old = self.order(order_id).filter(|o| o.agent_id == agent_id)?;
+        let remaining = old.volume - old.filled;
+        if new_volume == 0 {
+            self.cancel_order(order_id, agent_id);
+            return Some(Vec::new());
+        }
+        if new_price == old.price && new_volume <= remaining {
+            let cut = remaining - new_volume;
+            if cut > 0 {
+                self.reduce_resting(order_id, cut);
+                self.feed.emit(
+                    old.stock_id,
+                    FeedEvent::OrderCancelled {
+                        order_id,
+                        volume: cut,
+                    },
+                );
+            }
+            return Some(Vec::new());
+        }
+
+        self.remove_resting(order_id);
+        let mut order = Order {
+            id: new_order_id,
+            price: new_price,
+            volume: new_volume,
+            filled: 0,
+            ..old
+        };
+        let trades = self.match_limit_order(&mut order);
+        if order.filled < order.volume {
+            self.add_limit_order(order);
+            self.feed.emit(
+                old.stock_id,
+                FeedEvent::OrderReplaced {
+                    old_order_id: order_id,
+                    new_order_id,
+                    price: new_price,
+                    volume: order.volume - order.filled,
+                },
+            );
+        } else {
+            // the old order's Delete trails the executions it caused; it was
+            // pulled before matching, so it can never be one of the makers
+            self.feed
+                .emit(old.stock_id, FeedEvent::OrderDeleted { order_id });
         }
+        Some(trades)
+    }
+
+    /// Match `order` against the opposite side without resting the remainder.
+    fn match_limit_order(&mut self, order: &mut Order) -> Vec<Trade> {
+        let remaining = order.volume.saturating_sub(order.filled);
+        let (trades, filled) = self.sweep(order.side, order.agent_id, Some(order.price), remaining);
+        order.filled += filled;
         trades
     }
 
     pub fn cancel_order(&mut self, order_id: u64, agent_id: usize) -> bool {
-        if let Some(&(side, price)) = self.order_id_map.get(&order_id) {
-            let book_side = match side {
-                Side::Buy => &mut self.bids,
-                Side::Sell => &mut self.asks,
-            };
-            if let Some(level) = book_side.get_mut(&price) {
-                if let Some(index) = level.orders.iter().position(|o| o.id == order_id) {
-                    if level.orders[index].agent_id == agent_id {
-                        let cancelled_order = level.orders.remove(index).unwrap();
-                        let remaining_volume = cancelled_order
-                            .volume
-                            .saturating_sub(cancelled_order.filled);
-                        level.total_volume = level.total_volume.saturating_sub(remaining_volume);
-                        if level.orders.is_empty() {
-                            book_side.remove(&price);
-                        }
-                        self.order_id_map.remove(&order_id);
-                        return true;
-                    }
-                }
+        match self.order(order_id) {
+            Some(order) if order.agent_id == agent_id => {
+                self.remove_resting(order_id);
+                self.feed
+                    .emit(order.stock_id, FeedEvent::OrderDeleted { order_id });
+                true
             }
+            _ => false,
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    //  Incremental feed
+    // ---------------------------------------------------------------------
+    /// Start recording every change as a `FeedMessage`. Sequence numbers
+    /// continue from where they were if the feed was enabled before.
+    pub fn enable_feed(&mut self) {
+        self.feed.set_enabled(true);
+    }
+
+    /// Stop recording and discard anything not yet drained.
+    pub fn disable_feed(&mut self) {
+        self.feed.set_enabled(false);
+    }
+
+    pub fn feed_enabled(&self) -> bool {
+        self.feed.is_enabled()
+    }
+
+    /// Sequence number of the last message emitted (0 if none yet).
+    pub fn feed_seq(&self) -> u64 {
+        self.feed.seq()
+    }
+
+    /// Messages emitted since the last drain, oldest first.
+    pub fn drain_feed(&mut self) -> std::vec::Drain<'_, FeedMessage> {
+        self.feed.drain()
+    }
+
+    // ---------------------------------------------------------------------
+    //  Raw edits used to replay a feed (no matching, no feed output)
+    // ---------------------------------------------------------------------
+    /// Fill `volume` shares of a resting order, removing it once complete.
+    pub(crate) fn execute_resting(&mut self, order_id: u64, volume: u64) -> bool {
+        let Some((level, order)) = self.locate(order_id) else {
+            return false;
+        };
+        level.total_volume = level.total_volume.saturating_sub(volume);
+        order.filled += volume;
+        if order.filled >= order.volume {
+            self.remove_resting(order_id);
+        }
+        true
+    }
+
+    /// Take `volume` unfilled shares off a resting order in place.
+    pub(crate) fn reduce_resting(&mut self, order_id: u64, volume: u64) -> bool {
+        let Some((level, order)) = self.locate(order_id) else {
+            return false;
+        };
+        let cut = volume.min(order.volume - order.filled);
+        order.volume -= cut;
+        level.total_volume -= cut;
+        if order.filled >= order.volume {
+            self.remove_resting(order_id);
+        }
+        true
+    }
+
+    /// Pull a resting order regardless of owner.
+    pub(crate) fn remove_resting(&mut self, order_id: u64) -> Option<Order> {
+        let h = self.order_id_map.remove(&order_id)?;
+        let order = self.arena[h as usize].order;
+        Self::forget_owner(&mut self.by_agent, &order);
+        let book_side = match order.side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let level = book_side.level_mut(order.price)?;
+        Self::unlink(&mut self.arena, level, h);
+        Self::release(&mut self.arena, &mut self.free, h);
+        level.total_volume = level
+            .total_volume
+            .saturating_sub(order.volume.saturating_sub(order.filled));
+        if level.len == 0 {
+            book_side.remove_level(order.price);
         }
-        false
+        Some(order)
+    }
+
+    fn locate(&mut self, order_id: u64) -> Option<(&mut PriceLevel, &mut Order)> {
+        let &h = self.order_id_map.get(&order_id)?;
+        let order = &mut self.arena[h as usize].order;
+        let level = match order.side {
+            Side::Buy => self.bids.level_mut(order.price),
+            Side::Sell => self.asks.level_mut(order.price),
+        }?;
+        Some((level, order))
     }
 }
 
@@ -245,7 +727,7 @@ mod tests {
         Order {
             id,
             agent_id,
-            stock_id: stock_id,
+            stock_id,
             side,
             price,
             volume,
@@ -259,7 +741,7 @@ mod tests {
         let order = new_order(1, 1, 1, Side::Buy, 100, 50);
         book.add_limit_order(order);
         assert!(book.order_id_map.contains_key(&1));
-        let level = book.bids.get(&100).unwrap();
+        let level = book.bids.level(100).unwrap();
         assert_eq!(level.total_volume, 50);
     }
 
@@ -269,9 +751,9 @@ mod tests {
         book.add_limit_order(new_order(1, 1, 1, Side::Sell, 100, 50));
         let trades = book.process_market_order(2, Side::Buy, 30);
         assert_eq!(trades.len(), 1);
-        let ask_level = book.asks.get(&100).unwrap();
+        let ask_level = book.asks.level(100).unwrap();
         assert_eq!(ask_level.total_volume, 20);
-        assert_eq!(ask_level.orders[0].filled, 30);
+        assert_eq!(book.order(1).unwrap().filled, 30);
     }
 
     #[test]
@@ -279,8 +761,8 @@ mod tests {
         let mut book = OrderBook::new();
         book.add_limit_order(new_order(1, 1, 1, Side::Sell, 100, 50));
         book.process_market_order(2, Side::Buy, 50);
-        assert!(book.asks.get(&100).is_none());
-        assert!(book.order_id_map.get(&1).is_none());
+        assert!(book.asks.level(100).is_none());
+        assert!(!book.order_id_map.contains_key(&1));
     }
 
     #[test]
@@ -289,7 +771,7 @@ mod tests {
         book.add_limit_order(new_order(1, 1, 1, Side::Sell, 100, 50));
         let mut aggressive_buy = new_order(2, 2, 1, Side::Buy, 101, 30);
         book.process_limit_order(&mut aggressive_buy);
-        assert_eq!(book.asks.get(&100).unwrap().total_volume, 20);
+        assert_eq!(book.asks.level(100).unwrap().total_volume, 20);
         assert!(book.bids.is_empty());
     }
 
@@ -299,8 +781,8 @@ mod tests {
         book.add_limit_order(new_order(1, 1, 1, Side::Sell, 100, 30));
         let mut aggressive_buy = new_order(2, 2, 1, Side::Buy, 101, 50);
         book.process_limit_order(&mut aggressive_buy);
-        assert!(book.asks.get(&100).is_none());
-        let bid_level = book.bids.get(&101).unwrap();
+        assert!(book.asks.level(100).is_none());
+        let bid_level = book.bids.level(101).unwrap();
         assert_eq!(bid_level.total_volume, 20);
     }
 
@@ -311,7 +793,7 @@ mod tests {
         let success = book.cancel_order(1, 1);
         assert!(success);
         assert!(book.bids.is_empty());
-        assert!(book.order_id_map.get(&1).is_none());
+        assert!(!book.order_id_map.contains_key(&1));
     }
 
     #[test]
@@ -320,7 +802,7 @@ mod tests {
         book.add_limit_order(new_order(1, 1, 1, Side::Buy, 100, 50));
         let success = book.cancel_order(1, 2);
         assert!(!success);
-        assert_eq!(book.bids.get(&100).unwrap().total_volume, 50);
+        assert_eq!(book.bids.level(100).unwrap().total_volume, 50);
     }
 
     #[test]
@@ -348,13 +830,13 @@ mod tests {
 
         book.process_market_order(2, Side::Buy, 40);
 
-        let level = book.asks.get(&100).unwrap();
+        let level = book.asks.level(100).unwrap();
         assert_eq!(level.total_volume, 60);
-        assert_eq!(level.orders[0].filled, 40);
+        assert_eq!(book.order(1).unwrap().filled, 40);
 
         let success = book.cancel_order(1, 1);
         assert!(success);
-        assert!(book.asks.get(&100).is_none());
+        assert!(book.asks.level(100).is_none());
     }
     #[test]
     fn test_exact_cross_limit_executes_immediately() {
@@ -369,8 +851,222 @@ mod tests {
 
         // Should trade fully and remove that ask level
         assert_eq!(trades.len(), 1, "one trade produced");
-        assert!(book.asks.get(&100).is_none(), "ask level consumed");
+        assert!(book.asks.level(100).is_none(), "ask level consumed");
         assert_eq!(aggressive_buy.filled, 40, "incoming order fully filled");
         assert!(book.bids.is_empty(), "no residual resting on bid side");
     }
+
+    /* ---------------- market-data queries ---------------- */
+    fn two_sided_book() -> OrderBook {
+        let mut book = OrderBook::new();
+        book.add_limit_order(new_order(1, 1, 1, Side::Buy, 99, 30));
+        book.add_limit_order(new_order(2, 2, 1, Side::Buy, 99, 20));
+        book.add_limit_order(new_order(3, 1, 1, Side::Buy, 98, 100));
+        book.add_limit_order(new_order(4, 3, 1, Side::Sell, 101, 10));
+        book.add_limit_order(new_order(5, 3, 1, Side::Sell, 103, 40));
+        book
+    }
+
+    #[test]
+    fn test_top_of_book_queries() {
+        let book = two_sided_book();
+        assert_eq!(book.best_bid_ask(), (Some(99), Some(101)));
+        assert_eq!(book.spread(), Some(2));
+        assert_eq!(book.mid_price(), Some(100));
+        // 50 bid vs 10 ask: microprice leans towards the thin ask
+        let micro = book.microprice().unwrap();
+        assert!((micro - (99.0 * 10.0 + 101.0 * 50.0) / 60.0).abs() < 1e-9);
+        assert!(OrderBook::new().spread().is_none());
+    }
+
+    #[test]
+    fn test_depth_aggregates_levels_best_first() {
+        let depth = two_sided_book().depth(1);
+        assert_eq!(depth.bids.len(), 1);
+        assert_eq!(
+            depth.bids[0],
+            LevelQuote {
+                price: 99,
+                volume: 50,
+                order_count: 2
+            }
+        );
+        let full = two_sided_book().depth(usize::MAX);
+        assert_eq!(
+            full.asks.iter().map(|q| q.price).collect::<Vec<_>>(),
+            vec![101, 103]
+        );
+        assert_eq!(full.bids[1].price, 98);
+    }
+
+    #[test]
+    fn test_orders_at_keeps_time_priority() {
+        let book = two_sided_book();
+        let ids: Vec<u64> = book.orders_at(99).iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(book.orders_at(100).is_empty());
+        assert_eq!(book.order(5).unwrap().price, 103);
+        assert_eq!(book.order_count(), 5);
+    }
+
+    #[test]
+    fn test_simulate_market_order_matches_execution() {
+        let mut book = two_sided_book();
+        let before = book.depth(usize::MAX);
+        let impact = book.simulate_market_order(Side::Buy, 250);
+        assert_eq!(book.depth(usize::MAX), before, "dry run must not mutate");
+
+        let trades = book.process_market_order(9, Side::Buy, 250);
+        assert_eq!(impact.fills.len(), trades.len());
+        for (f, t) in impact.fills.iter().zip(&trades) {
+            assert_eq!(
+                (f.price, f.volume, f.maker_order_id),
+                (t.price, t.volume, t.maker_order_id)
+            );
+        }
+        assert_eq!(impact.filled, trades.iter().map(|t| t.volume).sum::<u64>());
+        assert_eq!(impact.worst_price, trades.last().map(|t| t.price));
+    }
+
+    #[test]
+    fn test_simulate_market_order_reports_remainder_and_slippage() {
+        let book = two_sided_book();
+        let ask_volume: u64 = book.depth(usize::MAX).asks.iter().map(|l| l.volume).sum();
+        let impact = book.simulate_market_order(Side::Buy, ask_volume + 10);
+        assert_eq!(impact.unfilled, 10);
+        assert_eq!(impact.filled, ask_volume);
+        assert!(impact.slippage.unwrap() > 0.0);
+
+        let sell = book.simulate_market_order(Side::Sell, 1);
+        let (bid, _) = book.best_bid_ask();
+        assert_eq!(sell.worst_price, bid);
+        assert!(
+            sell.slippage.unwrap() > 0.0,
+            "crossing the spread costs half of it"
+        );
+
+        let empty = OrderBook::new().simulate_market_order(Side::Buy, 5);
+        assert_eq!(
+            (empty.unfilled, empty.avg_price, empty.slippage),
+            (5, None, None)
+        );
+    }
+
+    #[test]
+    fn test_cancel_from_middle_keeps_queue_and_reuses_slots() {
+        let mut book = OrderBook::with_capacity(4);
+        for id in 1..=4 {
+            book.add_limit_order(new_order(id, 1, 1, Side::Buy, 100, 10));
+        }
+        assert!(book.cancel_order(2, 1));
+        assert!(book.cancel_order(4, 1));
+        let ids: Vec<u64> = book.orders_at(100).iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(book.depth(1).bids[0].order_count, 2);
+
+        book.add_limit_order(new_order(5, 1, 1, Side::Buy, 100, 10));
+        book.add_limit_order(new_order(6, 1, 1, Side::Buy, 100, 10));
+        assert_eq!(book.arena.len(), 4, "freed slots are recycled");
+        let ids: Vec<u64> = book.orders_at(100).iter().map(|o| o.id).collect();
+        assert_eq!(ids, vec![1, 3, 5, 6]);
+
+        let trades = book.process_market_order(2, Side::Sell, 40);
+        let makers: Vec<u64> = trades.iter().map(|t| t.maker_order_id).collect();
+        assert_eq!(makers, vec![1, 3, 5, 6]);
+        assert_eq!(book.order_count(), 0);
+        assert!(book.bids.is_empty());
+    }
+
+    #[test]
+    fn test_ladder_book_matches_tree_book() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+        let mut rng = StdRng::seed_from_u64(17);
+        let mut tree = OrderBook::new();
+        // narrow band so both the array and the spill maps get exercised
+        let mut ladder = OrderBook::ladder(9_950, 10_050);
+        let mut next_id = 0;
+        for _ in 0..5_000 {
+            match rng.gen_range(0..10) {
+                0..=5 => {
+                    next_id += 1;
+                    let side = if rng.gen_bool(0.5) {
+                        Side::Buy
+                    } else {
+                        Side::Sell
+                    };
+                    let mut o = new_order(
+                        next_id,
+                        rng.gen_range(0..5),
+                        1,
+                        side,
+                        rng.gen_range(9_900..10_100),
+                        rng.gen_range(1..100),
+                    );
+                    let mut o2 = o;
+                    assert_eq!(
+                        tree.process_limit_order(&mut o),
+                        ladder.process_limit_order(&mut o2)
+                    );
+                }
+                6..=7 => {
+                    let side = if rng.gen_bool(0.5) {
+                        Side::Buy
+                    } else {
+                        Side::Sell
+                    };
+                    let v = rng.gen_range(1..300);
+                    assert_eq!(
+                        tree.process_market_order(9, side, v),
+                        ladder.process_market_order(9, side, v)
+                    );
+                }
+                8 => {
+                    let id = rng.gen_range(1..=next_id.max(1));
+                    let agent = rng.gen_range(0..5);
+                    assert_eq!(tree.cancel_order(id, agent), ladder.cancel_order(id, agent));
+                }
+                _ => {
+                    let id = rng.gen_range(1..=next_id.max(1));
+                    let agent = rng.gen_range(0..5);
+                    let (px, v) = (rng.gen_range(9_900..10_100), rng.gen_range(0..100));
+                    next_id += 1;
+                    assert_eq!(
+                        tree.modify_order(id, agent, px, v, next_id),
+                        ladder.modify_order(id, agent, px, v, next_id)
+                    );
+                }
+            }
+            assert_eq!(tree.depth(usize::MAX), ladder.depth(usize::MAX));
+        }
+        assert_eq!(
+            tree.resting_orders(Side::Buy),
+            ladder.resting_orders(Side::Buy)
+        );
+        assert_eq!(
+            tree.resting_orders(Side::Sell),
+            ladder.resting_orders(Side::Sell)
+        );
+
+        let converted = tree.rebuilt_as(BookKind::Ladder {
+            min_price: 10_000,
+            max_price: 10_010,
+        });
+        assert_eq!(converted.depth(usize::MAX), tree.depth(usize::MAX));
+        assert_eq!(
+            converted.resting_orders(Side::Sell),
+            tree.resting_orders(Side::Sell)
+        );
+    }
+
+    #[test]
+    fn test_volume_to_price_and_imbalance() {
+        let book = two_sided_book();
+        assert_eq!(book.volume_to_price(Side::Buy, 10), Some(101));
+        assert_eq!(book.volume_to_price(Side::Buy, 11), Some(103));
+        assert_eq!(book.volume_to_price(Side::Buy, 51), None);
+        assert_eq!(book.volume_to_price(Side::Sell, 60), Some(98));
+        // top level: 50 bid vs 10 ask
+        assert!((book.imbalance(1).unwrap() - 40.0 / 60.0).abs() < 1e-9);
+        assert!(OrderBook::new().imbalance(5).is_none());
+    }
 }
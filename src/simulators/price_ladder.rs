@@ -0,0 +1,218 @@
+// src/simulators/price_ladder.rs
+//
+// Dense one-side level store: a contiguous array of `PriceLevel`s, one per
+// cent between `min_price` and `max_price`, with a two-level occupancy bitset
+// so the best price is a couple of `trailing_zeros`/`leading_zeros` away.
+// Prices outside the band spill into small BTreeMaps at either end.
+
+use std::collections::BTreeMap;
+
+use super::levels::{LevelIter, PriceLevel, PriceLevels};
+use crate::types::Side;
+
+#[derive(Debug, Clone)]
+pub struct PriceLadder {
+    side: Side,
+    base: u64,
+    levels: Vec<PriceLevel>,
+    words: Vec<u64>,   // bit i set ⇔ levels[i] occupied
+    summary: Vec<u64>, // bit w set ⇔ words[w] != 0
+    below: BTreeMap<u64, PriceLevel>,
+    above: BTreeMap<u64, PriceLevel>,
+}
+
+impl PriceLadder {
+    pub fn new(side: Side, min_price: u64, max_price: u64) -> Self {
+        assert!(min_price <= max_price, "empty price band");
+        let ticks = (max_price - min_price + 1) as usize;
+        let n_words = ticks.div_ceil(64);
+        Self {
+            side,
+            base: min_price,
+            levels: vec![PriceLevel::default(); ticks],
+            words: vec![0; n_words],
+            summary: vec![0; n_words.div_ceil(64)],
+            below: BTreeMap::new(),
+            above: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    fn slot(&self, price: u64) -> Option<usize> {
+        let i = price.checked_sub(self.base)? as usize;
+        (i < self.levels.len()).then_some(i)
+    }
+
+    #[inline]
+    fn occupied(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn spill(&mut self, price: u64) -> &mut BTreeMap<u64, PriceLevel> {
+        if price < self.base {
+            &mut self.below
+        } else {
+            &mut self.above
+        }
+    }
+
+    fn lowest_slot(&self) -> Option<usize> {
+        let s = self.summary.iter().position(|&w| w != 0)?;
+        let w = s * 64 + self.summary[s].trailing_zeros() as usize;
+        Some(w * 64 + self.words[w].trailing_zeros() as usize)
+    }
+
+    fn highest_slot(&self) -> Option<usize> {
+        let s = self.summary.iter().rposition(|&w| w != 0)?;
+        let w = s * 64 + 63 - self.summary[s].leading_zeros() as usize;
+        Some(w * 64 + 63 - self.words[w].leading_zeros() as usize)
+    }
+
+    /// Occupied ladder levels in ascending price order.
+    fn ascending(&self) -> impl DoubleEndedIterator<Item = (u64, &PriceLevel)> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| **w != 0)
+            .flat_map(|(wi, &w)| {
+                (0..64)
+                    .filter(move |b| w & (1 << b) != 0)
+                    .map(move |b| wi * 64 + b)
+            })
+            .map(|i| (self.base + i as u64, &self.levels[i]))
+    }
+}
+
+impl PriceLevels for PriceLadder {
+    fn best(&self) -> Option<u64> {
+        let on_ladder = |i: usize| self.base + i as u64;
+        match self.side {
+            Side::Buy => self
+                .above
+                .keys()
+                .next_back()
+                .copied()
+                .or_else(|| self.highest_slot().map(on_ladder))
+                .or_else(|| self.below.keys().next_back().copied()),
+            Side::Sell => self
+                .below
+                .keys()
+                .next()
+                .copied()
+                .or_else(|| self.lowest_slot().map(on_ladder))
+                .or_else(|| self.above.keys().next().copied()),
+        }
+    }
+
+    fn level(&self, price: u64) -> Option<&PriceLevel> {
+        match self.slot(price) {
+            Some(i) => self.occupied(i).then(|| &self.levels[i]),
+            None if price < self.base => self.below.get(&price),
+            None => self.above.get(&price),
+        }
+    }
+
+    fn level_mut(&mut self, price: u64) -> Option<&mut PriceLevel> {
+        match self.slot(price) {
+            Some(i) => self.occupied(i).then(|| &mut self.levels[i]),
+            None => self.spill(price).get_mut(&price),
+        }
+    }
+
+    fn level_or_insert(&mut self, price: u64) -> &mut PriceLevel {
+        let Some(i) = self.slot(price) else {
+            return self.spill(price).entry(price).or_default();
+        };
+        if !self.occupied(i) {
+            self.words[i / 64] |= 1 << (i % 64);
+            self.summary[i / 4096] |= 1 << ((i / 64) % 64);
+            self.levels[i] = PriceLevel::default();
+        }
+        &mut self.levels[i]
+    }
+
+    fn remove_level(&mut self, price: u64) {
+        let Some(i) = self.slot(price) else {
+            self.spill(price).remove(&price);
+            return;
+        };
+        self.words[i / 64] &= !(1 << (i % 64));
+        if self.words[i / 64] == 0 {
+            self.summary[i / 4096] &= !(1 << ((i / 64) % 64));
+        }
+        self.levels[i] = PriceLevel::default();
+    }
+
+    fn iter_best_first(&self) -> LevelIter<'_> {
+        match self.side {
+            Side::Buy => Box::new(
+                spilled(&self.above)
+                    .rev()
+                    .chain(self.ascending().rev())
+                    .chain(spilled(&self.below).rev()),
+            ),
+            Side::Sell => Box::new(
+                spilled(&self.below)
+                    .chain(self.ascending())
+                    .chain(spilled(&self.above)),
+            ),
+        }
+    }
+}
+
+fn spilled(m: &BTreeMap<u64, PriceLevel>) -> impl DoubleEndedIterator<Item = (u64, &PriceLevel)> {
+    m.iter().map(|(&p, l)| (p, l))
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(l: &PriceLadder) -> Vec<u64> {
+        l.iter_best_first().map(|(p, _)| p).collect()
+    }
+
+    #[test]
+    fn best_tracks_inserts_and_removals_across_words() {
+        let mut asks = PriceLadder::new(Side::Sell, 100, 10_000);
+        for p in [9_000, 150, 5_000, 4_196] {
+            asks.level_or_insert(p).total_volume = 1;
+        }
+        assert_eq!(asks.best(), Some(150));
+        asks.remove_level(150);
+        assert_eq!(asks.best(), Some(4_196));
+        assert_eq!(prices(&asks), vec![4_196, 5_000, 9_000]);
+
+        let mut bids = PriceLadder::new(Side::Buy, 100, 10_000);
+        for p in [100, 10_000, 777] {
+            bids.level_or_insert(p);
+        }
+        assert_eq!(bids.best(), Some(10_000));
+        bids.remove_level(10_000);
+        assert_eq!(bids.best(), Some(777));
+        bids.remove_level(777);
+        bids.remove_level(100);
+        assert!(bids.is_empty());
+    }
+
+    #[test]
+    fn out_of_band_prices_spill_in_order() {
+        let mut asks = PriceLadder::new(Side::Sell, 100, 200);
+        for p in [50, 150, 250, 99, 201] {
+            asks.level_or_insert(p);
+        }
+        assert_eq!(prices(&asks), vec![50, 99, 150, 201, 250]);
+        assert!(asks.level(150).is_some() && asks.level(151).is_none());
+
+        let mut bids = PriceLadder::new(Side::Buy, 100, 200);
+        for p in [50, 150, 250] {
+            bids.level_or_insert(p);
+        }
+        assert_eq!(prices(&bids), vec![250, 150, 50]);
+        bids.remove_level(250);
+        assert_eq!(bids.best(), Some(150));
+    }
+}
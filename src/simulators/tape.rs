@@ -0,0 +1,183 @@
+// src/simulators/tape.rs
+//
+// What has printed on the exchange: a bounded, tick-stamped trade tape and
+// running per-stock statistics. `Market` keeps both and lends them to agents
+// through `MarketView`.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{corporate::CorporateAction, types::Trade};
+
+/// Trades kept on the tape unless configured otherwise.
+pub const DEFAULT_TAPE_CAPACITY: usize = 10_000;
+
+/// One print, stamped with the tick it happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Print {
+    pub tick: u64,
+    pub trade: Trade,
+}
+
+/// The most recent trades across all stocks, oldest first.
+#[derive(Debug, Clone)]
+pub struct TradeTape {
+    prints: VecDeque<Print>,
+    capacity: usize,
+}
+
+impl Default for TradeTape {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_TAPE_CAPACITY)
+    }
+}
+
+impl TradeTape {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            prints: VecDeque::with_capacity(capacity.min(DEFAULT_TAPE_CAPACITY)),
+            capacity,
+        }
+    }
+
+    pub(crate) fn record(&mut self, tick: u64, trade: Trade) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.prints.len() == self.capacity {
+            self.prints.pop_front();
+        }
+        self.prints.push_back(Print { tick, trade });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.prints.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.prints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prints.is_empty()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Print> {
+        self.prints.iter()
+    }
+
+    /// Prints for one stock, newest first.
+    pub fn recent(&self, stock_id: u64) -> impl Iterator<Item = &Print> {
+        self.prints
+            .iter()
+            .rev()
+            .filter(move |p| p.trade.stock_id == stock_id)
+    }
+
+    /// Prints from `tick` onwards, oldest first.
+    pub fn since(&self, tick: u64) -> impl Iterator<Item = &Print> {
+        let start = self.prints.partition_point(|p| p.tick < tick);
+        self.prints.range(start..)
+    }
+}
+
+/// Running statistics of one stock since the session started. Prices are
+/// cents; `None` until the first trade.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StockStats {
+    pub last_price: Option<u64>,
+    pub open: Option<u64>,
+    pub high: Option<u64>,
+    pub low: Option<u64>,
+    /// Shares traded.
+    pub volume: u64,
+    pub trade_count: u64,
+    /// Cents × shares, for the VWAP.
+    pub notional: u128,
+    /// Tick of the latest trade.
+    pub last_trade_tick: Option<u64>,
+}
+
+impl StockStats {
+    pub(crate) fn record(&mut self, tick: u64, trade: &Trade) {
+        let px = trade.price;
+        self.open.get_or_insert(px);
+        self.high = Some(self.high.map_or(px, |h| h.max(px)));
+        self.low = Some(self.low.map_or(px, |l| l.min(px)));
+        self.last_price = Some(px);
+        self.volume += trade.volume;
+        self.trade_count += 1;
+        self.notional += px as u128 * trade.volume as u128;
+        self.last_trade_tick = Some(tick);
+    }
+
+    /// Restate the session in post-action prices and shares, as if it had
+    /// traded that way all along. Trade count and timing stay as they were.
+    pub(crate) fn adjust(&mut self, action: &CorporateAction) {
+        for px in [
+            &mut self.last_price,
+            &mut self.open,
+            &mut self.high,
+            &mut self.low,
+        ] {
+            *px = px.map(|c| action.price_cents(c));
+        }
+        if self.volume > 0 {
+            let vwap = self.notional as f64 / self.volume as f64 / 100.0;
+            self.volume = action.units(self.volume).unwrap_or(u64::MAX);
+            self.notional = (action.price(vwap) * 100.0 * self.volume as f64).round() as u128;
+        }
+    }
+
+    /// Volume-weighted average price in cents.
+    pub fn vwap(&self) -> Option<f64> {
+        (self.volume > 0).then(|| self.notional as f64 / self.volume as f64)
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Side;
+
+    fn trade(stock_id: u64, price: u64, volume: u64) -> Trade {
+        Trade {
+            price,
+            stock_id,
+            volume,
+            taker_agent_id: 1,
+            maker_agent_id: 2,
+            taker_side: Side::Buy,
+            maker_order_id: 0,
+        }
+    }
+
+    #[test]
+    fn tape_is_bounded_and_queryable() {
+        let mut tape = TradeTape::with_capacity(3);
+        for (tick, stock) in [(1, 1), (2, 2), (2, 1), (3, 1)] {
+            tape.record(tick, trade(stock, 100 + tick, 1));
+        }
+        assert_eq!(tape.len(), 3, "oldest print evicted");
+        let prices: Vec<u64> = tape.recent(1).map(|p| p.trade.price).collect();
+        assert_eq!(prices, vec![103, 102]);
+        assert_eq!(tape.since(3).count(), 1);
+        assert_eq!(tape.since(0).count(), 3);
+    }
+
+    #[test]
+    fn stats_track_ohlc_and_vwap() {
+        let mut s = StockStats::default();
+        for (px, vol) in [(100, 10), (120, 10), (90, 20)] {
+            s.record(1, &trade(1, px, vol));
+        }
+        assert_eq!((s.open, s.high, s.low), (Some(100), Some(120), Some(90)));
+        assert_eq!(s.last_price, Some(90));
+        assert_eq!((s.volume, s.trade_count), (40, 3));
+        assert_eq!(s.vwap(), Some(100.0));
+    }
+}
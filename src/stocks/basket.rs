@@ -0,0 +1,209 @@
+// src/stocks/basket.rs
+//! Basket instruments (ETFs, index trackers). A basket is a listed stock of
+//! its own whose creation unit is a fixed bundle of other stocks, so its
+//! fair value follows from theirs and authorised participants can swap one
+//! for the other with the exchange.
+
+use serde::{Deserialize, Serialize};
+
+use super::{definitions::StockMarket, loader::UniverseError};
+use crate::corporate::CorporateAction;
+
+/// Shares of one stock in a creation unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constituent {
+    pub stock_id: u64,
+    pub shares: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Basket {
+    /// The basket's own listing.
+    pub stock_id: u64,
+    /// Basket shares issued per creation unit.
+    pub creation_unit: u64,
+    pub constituents: Vec<Constituent>,
+    /// Dollars charged per creation unit created or redeemed.
+    #[serde(default)]
+    pub fee: f64,
+}
+
+impl Basket {
+    pub fn new(stock_id: u64, creation_unit: u64) -> Self {
+        Self {
+            stock_id,
+            creation_unit,
+            constituents: Vec::new(),
+            fee: 0.0,
+        }
+    }
+
+    pub fn with_constituent(mut self, stock_id: u64, shares: u64) -> Self {
+        self.constituents.push(Constituent { stock_id, shares });
+        self
+    }
+
+    pub fn with_fee(mut self, dollars: f64) -> Self {
+        self.fee = dollars;
+        self
+    }
+
+    /// Value of one basket share with each constituent at `price(stock_id)`
+    /// dollars; `None` if any price is missing.
+    pub fn nav(&self, price: impl Fn(u64) -> Option<f64>) -> Option<f64> {
+        let mut unit = 0.0;
+        for c in &self.constituents {
+            unit += c.shares as f64 * price(c.stock_id)?;
+        }
+        Some(unit / self.creation_unit as f64)
+    }
+
+    /// Keep the unit worth the same after a split of the basket or one of
+    /// its constituents; counts round down but never below one share.
+    /// `None`, changing nothing, if a count would overflow.
+    pub(crate) fn adjust(&mut self, action: &CorporateAction) -> Option<()> {
+        if !matches!(action, CorporateAction::Split { .. }) {
+            return Some(());
+        }
+        let scale = |n: u64| action.units(n).map(|n| n.max(1));
+        let mut restated = self.clone();
+        if action.stock_id() == self.stock_id {
+            restated.creation_unit = scale(self.creation_unit)?;
+        }
+        for c in &mut restated.constituents {
+            if c.stock_id == action.stock_id() {
+                c.shares = scale(c.shares)?;
+            }
+        }
+        *self = restated;
+        Some(())
+    }
+}
+
+/// One creation or redemption as the exchange booked it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitSettlement {
+    pub agent_id: usize,
+    /// The basket.
+    pub stock_id: u64,
+    /// Creation units: positive for a creation, negative for a redemption.
+    pub units: i64,
+    /// `(stock_id, signed shares, cents)` for the basket and every
+    /// constituent. Constituents move at their marks and the basket at the
+    /// matching NAV, so only the fee changes the participant's net worth.
+    pub legs: Vec<(u64, i64, u64)>,
+    /// Dollars.
+    pub fee: f64,
+}
+
+// -----------------------------------------------------------------------------
+//  Baskets in the universe
+// -----------------------------------------------------------------------------
+impl StockMarket {
+    /// Declare `basket.stock_id`, which must already be listed, a basket of
+    /// other listed stocks. Replaces an earlier definition for the same id.
+    pub fn add_basket(&mut self, basket: Basket) -> Result<(), UniverseError> {
+        let ticker = self
+            .get_ticker_by_id(basket.stock_id)
+            .cloned()
+            .ok_or_else(|| UniverseError::Parse {
+                line: None,
+                msg: format!("basket stock id {} is not listed", basket.stock_id),
+            })?;
+        let bad = |field, msg: String| UniverseError::InvalidField {
+            ticker: ticker.clone(),
+            field,
+            msg,
+        };
+        if basket.creation_unit == 0 {
+            return Err(bad("creation_unit", "must be at least one share".into()));
+        }
+        if basket.constituents.is_empty() {
+            return Err(bad("constituents", "must not be empty".into()));
+        }
+        if !(basket.fee.is_finite() && basket.fee >= 0.0) {
+            return Err(bad("fee", "must be a non-negative number".into()));
+        }
+        if let Some(outer) = self
+            .baskets()
+            .find(|b| b.constituents.iter().any(|c| c.stock_id == basket.stock_id))
+        {
+            let msg = format!("already held by basket {}", outer.stock_id);
+            return Err(bad("stock_id", msg));
+        }
+        for (i, c) in basket.constituents.iter().enumerate() {
+            let msg = if c.stock_id == basket.stock_id {
+                "cannot hold the basket itself".to_string()
+            } else if self.get_stock_by_id(c.stock_id).is_none() {
+                format!("stock id {} is not listed", c.stock_id)
+            } else if self.baskets.contains_key(&c.stock_id) {
+                format!("stock id {} is itself a basket", c.stock_id)
+            } else if c.shares == 0 {
+                format!("stock id {} has zero shares", c.stock_id)
+            } else if basket.constituents[..i]
+                .iter()
+                .any(|d| d.stock_id == c.stock_id)
+            {
+                format!("stock id {} appears twice", c.stock_id)
+            } else {
+                continue;
+            };
+            return Err(bad("constituents", msg));
+        }
+        self.baskets.insert(basket.stock_id, basket);
+        Ok(())
+    }
+
+    pub fn basket(&self, stock_id: u64) -> Option<&Basket> {
+        self.baskets.get(&stock_id)
+    }
+
+    /// Every basket, by stock id.
+    pub fn baskets(&self) -> impl Iterator<Item = &Basket> {
+        self.baskets.values()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stocks::definitions::Stock;
+
+    #[test]
+    fn baskets_validate_and_price_from_constituents() {
+        let mut sm = StockMarket::new();
+        sm.add_stock(Stock::new("PAIR", 50, "Pair ETF", 1_000_000, 100.0));
+
+        let pair = Basket::new(50, 10)
+            .with_constituent(1, 2)
+            .with_constituent(2, 1);
+        assert!(sm.add_basket(pair.clone()).is_ok());
+        // 2 × 200 + 1 × 400 per 10 shares
+        let px = |id| Some(if id == 1 { 200.0 } else { 400.0 });
+        assert_eq!(sm.basket(50).unwrap().nav(px), Some(80.0));
+        assert_eq!(pair.nav(|id| (id == 1).then_some(1.0)), None);
+
+        let broken = [
+            Basket::new(99, 1).with_constituent(1, 1),
+            Basket::new(50, 0).with_constituent(1, 1),
+            Basket::new(50, 1),
+            Basket::new(50, 1).with_constituent(50, 1),
+            Basket::new(50, 1).with_constituent(7, 1),
+            Basket::new(50, 1)
+                .with_constituent(1, 1)
+                .with_constituent(1, 2),
+            Basket::new(1, 1).with_constituent(50, 1),
+            Basket::new(1, 1).with_constituent(2, 1),
+        ];
+        for b in broken {
+            assert!(sm.add_basket(b.clone()).is_err(), "{b:?}");
+        }
+
+        // dropping a constituent dissolves the basket
+        sm.remove_stock(2);
+        assert!(sm.basket(50).is_none());
+    }
+}
@@ -1,15 +1,18 @@
 // src/stocks/definitions.rs
 //! Core stock metadata used by the simulator.
 //
-//! For now we hard-code two symbols.  Extend `default_stock_universe()`
-//! with more entries whenever you add tickers.
+//! `default_stock_universe()` hard-codes two symbols; larger universes are
+//! loaded from CSV or JSON, see `stocks::loader`.
 
 pub type Symbol = String;
 
 use serde::{Deserialize, Serialize};
 
+use super::basket::Basket;
+use crate::corporate::CorporateAction;
+
 /// Immutable facts about a listed company. Adding a uniuqe stock ticker index to avoid the bs with String Copy
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Stock {
     /// NASDAQ / NYSE ticker (e.g. "AAPL").
     pub ticker: Symbol,
@@ -21,6 +24,21 @@ pub struct Stock {
     pub total_float: u64,
     /// Opening mid-price at time-zero of the simulation.
     pub initial_price: f64,
+    /// Industry classification, e.g. "Technology".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sector: Option<String>,
+    /// Minimum price increment in cents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick_size: Option<u64>,
+    /// Shares per round lot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lot_size: Option<u64>,
+    /// Annualised volatility the name is calibrated to (0.25 = 25 %).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_vol: Option<f64>,
+    /// Annual dividend yield (0.02 = 2 %).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dividend_yield: Option<f64>,
 }
 
 /// Convenience factory so call-sites stay concise.
@@ -39,12 +57,42 @@ impl Stock {
             company_name: company_name.into(),
             total_float,
             initial_price,
+            sector: None,
+            tick_size: None,
+            lot_size: None,
+            reference_vol: None,
+            dividend_yield: None,
         }
     }
+
+    pub fn with_sector(mut self, sector: impl Into<String>) -> Self {
+        self.sector = Some(sector.into());
+        self
+    }
+
+    pub fn with_tick_size(mut self, cents: u64) -> Self {
+        self.tick_size = Some(cents);
+        self
+    }
+
+    pub fn with_lot_size(mut self, shares: u64) -> Self {
+        self.lot_size = Some(shares);
+        self
+    }
+
+    pub fn with_reference_vol(mut self, vol: f64) -> Self {
+        self.reference_vol = Some(vol);
+        self
+    }
+
+    pub fn with_dividend_yield(mut self, yield_: f64) -> Self {
+        self.dividend_yield = Some(yield_);
+        self
+    }
 }
 /// Maybe later we can have a Stock Mareket struct that holds a collection of stocks and their metadata.
 /// We can then have a facility to add stocks to the market, remove them, and query for them.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StockMarket {
     /// Collection of stocks available in the market.
     pub stocks: Vec<Stock>,
@@ -52,6 +100,8 @@ pub struct StockMarket {
     pub id_to_stock: std::collections::HashMap<u64, Stock>,
     /// A mapping from stock ticekr to Stock for fast lookups
     pub ticker_to_stock: std::collections::HashMap<Symbol, Stock>,
+    /// Listed stocks that are baskets of other listed stocks, by id.
+    pub baskets: std::collections::BTreeMap<u64, Basket>,
 }
 /// The universe of stocks available when the market boots.
 ///
@@ -83,13 +133,26 @@ pub fn stock_ticker_to_stock_map(stocks: &[Stock]) -> std::collections::HashMap<
         .map(|s| (s.ticker.clone(), s.clone()))
         .collect()
 }
+impl Default for StockMarket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StockMarket {
     /// Creates a new stock market with the default universe.
     pub fn new() -> Self {
+        Self::from_stocks_unchecked(default_stock_universe())
+    }
+
+    /// Wraps `stocks` as they are; see [`StockMarket::from_stocks`] for the
+    /// validating version.
+    pub(crate) fn from_stocks_unchecked(stocks: Vec<Stock>) -> Self {
         Self {
-            stocks: default_stock_universe(),
-            id_to_stock: stock_id_to_stock_map(&default_stock_universe()),
-            ticker_to_stock: stock_ticker_to_stock_map(&default_stock_universe()),
+            id_to_stock: stock_id_to_stock_map(&stocks),
+            ticker_to_stock: stock_ticker_to_stock_map(&stocks),
+            stocks,
+            baskets: Default::default(),
         }
     }
 
@@ -114,17 +177,52 @@ impl StockMarket {
             self.id_to_stock.remove(&id);
             // Remove the stock from the ticker map.
             self.ticker_to_stock.remove(&removed_stock.ticker);
+            // A basket goes with its listing or with any of its constituents.
+            self.baskets.retain(|&b, basket| {
+                b != id && basket.constituents.iter().all(|c| c.stock_id != id)
+            });
             Some(())
         } else {
             None
         }
     }
+    /// Restate the listing for `action`: the new ticker for a rename; the
+    /// initial price, float and basket share counts for a split or
+    /// dividend. `None`, changing nothing, if the stock is not listed or a
+    /// restated count would overflow.
+    pub fn apply_corporate_action(&mut self, action: &CorporateAction) -> Option<()> {
+        let stock = self.get_stock_by_id(action.stock_id())?.clone();
+        let restated = match action {
+            CorporateAction::Rename { ticker, .. } => Stock {
+                ticker: ticker.clone(),
+                ..stock
+            },
+            _ => {
+                let total_float = action.units(stock.total_float)?;
+                let mut baskets = self.baskets.clone();
+                for basket in baskets.values_mut() {
+                    basket.adjust(action)?;
+                }
+                self.baskets = baskets;
+                Stock {
+                    initial_price: action.price(stock.initial_price),
+                    total_float,
+                    ..stock
+                }
+            }
+        };
+        self.update_stock(restated.id, restated)
+    }
+
     /// Update stock information by ID.
     pub fn update_stock(&mut self, id: u64, new_stock: Stock) -> Option<()> {
         // tHREE updates in vector and the two maps
         if let Some(pos) = self.stocks.iter().position(|s| s.id == id) {
-            // Update the stock in the vector.
-            self.stocks[pos] = new_stock.clone();
+            // Update the stock in the vector, dropping the old ticker if it changed.
+            let old = std::mem::replace(&mut self.stocks[pos], new_stock.clone());
+            if old.ticker != new_stock.ticker {
+                self.ticker_to_stock.remove(&old.ticker);
+            }
             // Update the ID map.
             self.id_to_stock.insert(id, new_stock.clone());
             // Update the ticker map.
@@ -222,6 +320,13 @@ mod tests {
         assert_eq!(s_by_id.total_float, 9_999_999);
         let s_by_tkr = sm.get_stock_by_ticker(&edited.ticker).unwrap();
         assert_eq!(s_by_tkr.total_float, 9_999_999);
+
+        // a renamed stock is no longer reachable under its old ticker
+        let old = edited.ticker.clone();
+        edited.ticker = "AAPX".into();
+        assert!(sm.update_stock(edited.id, edited.clone()).is_some());
+        assert!(sm.get_stock_by_ticker(&old).is_none());
+        assert_eq!(sm.get_id_by_ticker(&edited.ticker), Some(edited.id));
     }
 
     #[test]
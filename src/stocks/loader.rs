@@ -0,0 +1,480 @@
+// src/stocks/loader.rs
+//! Building a `StockMarket` from a file or piece by piece, with the
+//! universe checked on the way in: unique ids and tickers, sane prices and
+//! optional fields.
+//!
+//! CSV files need a header row. `ticker, id, company_name, total_float,
+//! initial_price` are required; `sector, tick_size, lot_size,
+//! reference_vol, dividend_yield` may be added in any order and left
+//! blank. Fields with commas go in double quotes; `#` lines are comments.
+//!
+//! JSON files hold an array of `Stock`s, or `{ "stocks": [...] }`.
+
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use serde::Deserialize;
+
+use super::definitions::{Stock, StockMarket, default_stock_universe};
+
+// -----------------------------------------------------------------------------
+//  Errors
+// -----------------------------------------------------------------------------
+#[derive(Debug)]
+pub enum UniverseError {
+    Io(io::Error),
+    /// Malformed input. `line` is 1-based when known.
+    Parse {
+        line: Option<usize>,
+        msg: String,
+    },
+    DuplicateId {
+        id: u64,
+        tickers: (String, String),
+    },
+    DuplicateTicker(String),
+    /// A field outside its domain, e.g. a zero lot size.
+    InvalidField {
+        ticker: String,
+        field: &'static str,
+        msg: String,
+    },
+    Empty,
+}
+
+impl fmt::Display for UniverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniverseError::Io(e) => write!(f, "i/o error: {e}"),
+            UniverseError::Parse { line: Some(l), msg } => write!(f, "line {l}: {msg}"),
+            UniverseError::Parse { line: None, msg } => write!(f, "{msg}"),
+            UniverseError::DuplicateId { id, tickers } => {
+                write!(
+                    f,
+                    "stock id {id} used by both {} and {}",
+                    tickers.0, tickers.1
+                )
+            }
+            UniverseError::DuplicateTicker(t) => write!(f, "ticker {t} listed twice"),
+            UniverseError::InvalidField { ticker, field, msg } => {
+                write!(f, "{ticker}: {field} {msg}")
+            }
+            UniverseError::Empty => write!(f, "the universe has no stocks"),
+        }
+    }
+}
+
+impl std::error::Error for UniverseError {}
+
+impl From<io::Error> for UniverseError {
+    fn from(e: io::Error) -> Self {
+        UniverseError::Io(e)
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Validation
+// -----------------------------------------------------------------------------
+fn check(stocks: &[Stock]) -> Result<(), UniverseError> {
+    if stocks.is_empty() {
+        return Err(UniverseError::Empty);
+    }
+    let mut ids: HashMap<u64, &str> = HashMap::with_capacity(stocks.len());
+    let mut tickers = HashMap::with_capacity(stocks.len());
+    for s in stocks {
+        let bad = |field, msg: &str| UniverseError::InvalidField {
+            ticker: s.ticker.clone(),
+            field,
+            msg: msg.into(),
+        };
+        if s.ticker.trim().is_empty() {
+            return Err(UniverseError::Parse {
+                line: None,
+                msg: format!("stock id {} has an empty ticker", s.id),
+            });
+        }
+        if let Some(first) = ids.insert(s.id, &s.ticker) {
+            return Err(UniverseError::DuplicateId {
+                id: s.id,
+                tickers: (first.to_string(), s.ticker.clone()),
+            });
+        }
+        if tickers.insert(s.ticker.as_str(), ()).is_some() {
+            return Err(UniverseError::DuplicateTicker(s.ticker.clone()));
+        }
+        if !(s.initial_price.is_finite() && s.initial_price > 0.0) {
+            return Err(bad("initial_price", "must be a positive number"));
+        }
+        if s.tick_size == Some(0) {
+            return Err(bad("tick_size", "must be at least one cent"));
+        }
+        if s.lot_size == Some(0) {
+            return Err(bad("lot_size", "must be at least one share"));
+        }
+        if s.reference_vol
+            .is_some_and(|v| !(v.is_finite() && v >= 0.0))
+        {
+            return Err(bad("reference_vol", "must be a non-negative number"));
+        }
+        if s.dividend_yield
+            .is_some_and(|y| !(y.is_finite() && y >= 0.0))
+        {
+            return Err(bad("dividend_yield", "must be a non-negative number"));
+        }
+    }
+    Ok(())
+}
+
+/// `stock` on its own, then against what `universe` already lists.
+pub(crate) fn check_listing(universe: &StockMarket, stock: &Stock) -> Result<(), UniverseError> {
+    check(std::slice::from_ref(stock))?;
+    if let Some(existing) = universe.get_stock_by_id(stock.id) {
+        return Err(UniverseError::DuplicateId {
+            id: stock.id,
+            tickers: (existing.ticker.clone(), stock.ticker.clone()),
+        });
+    }
+    if universe.get_stock_by_ticker(&stock.ticker).is_some() {
+        return Err(UniverseError::DuplicateTicker(stock.ticker.clone()));
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+//  Builder
+// -----------------------------------------------------------------------------
+/// Collects stocks and validates them once, in `build`.
+#[derive(Debug, Clone, Default)]
+pub struct StockMarketBuilder {
+    stocks: Vec<Stock>,
+}
+
+impl StockMarketBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stock(mut self, stock: Stock) -> Self {
+        self.stocks.push(stock);
+        self
+    }
+
+    pub fn stocks(mut self, stocks: impl IntoIterator<Item = Stock>) -> Self {
+        self.stocks.extend(stocks);
+        self
+    }
+
+    /// Adds AAPL and MSFT from `default_stock_universe()`.
+    pub fn default_universe(self) -> Self {
+        self.stocks(default_stock_universe())
+    }
+
+    pub fn build(self) -> Result<StockMarket, UniverseError> {
+        StockMarket::from_stocks(self.stocks)
+    }
+}
+
+// -----------------------------------------------------------------------------
+//  Loaders
+// -----------------------------------------------------------------------------
+#[derive(Deserialize)]
+struct Wrapped {
+    stocks: Vec<Stock>,
+}
+
+const REQUIRED: [&str; 5] = [
+    "ticker",
+    "id",
+    "company_name",
+    "total_float",
+    "initial_price",
+];
+const OPTIONAL: [&str; 5] = [
+    "sector",
+    "tick_size",
+    "lot_size",
+    "reference_vol",
+    "dividend_yield",
+];
+
+impl StockMarket {
+    pub fn builder() -> StockMarketBuilder {
+        StockMarketBuilder::new()
+    }
+
+    /// Validated universe, in the given order.
+    pub fn from_stocks(stocks: Vec<Stock>) -> Result<Self, UniverseError> {
+        check(&stocks)?;
+        Ok(Self::from_stocks_unchecked(stocks))
+    }
+
+    /// Picks the loader by extension: `.csv` or `.json`.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, UniverseError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("csv") => Self::from_csv(path),
+            Some(e) if e.eq_ignore_ascii_case("json") => Self::from_json(path),
+            _ => Err(UniverseError::Parse {
+                line: None,
+                msg: format!("{}: expected a .csv or .json file", path.display()),
+            }),
+        }
+    }
+
+    pub fn from_json(path: impl AsRef<Path>) -> Result<Self, UniverseError> {
+        Self::from_json_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Self, UniverseError> {
+        let parsed = if text.trim_start().starts_with('{') {
+            serde_json::from_str::<Wrapped>(text).map(|w| w.stocks)
+        } else {
+            serde_json::from_str::<Vec<Stock>>(text)
+        };
+        let stocks = parsed.map_err(|e| UniverseError::Parse {
+            line: Some(e.line()),
+            msg: e.to_string(),
+        })?;
+        Self::from_stocks(stocks)
+    }
+
+    pub fn from_csv(path: impl AsRef<Path>) -> Result<Self, UniverseError> {
+        Self::from_csv_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn from_csv_str(text: &str) -> Result<Self, UniverseError> {
+        let mut rows = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'));
+
+        let Some((header_line, header)) = rows.next() else {
+            return Err(UniverseError::Empty);
+        };
+        let columns: Vec<String> = split_csv(header, header_line)?
+            .into_iter()
+            .map(|c| c.trim().to_ascii_lowercase())
+            .collect();
+        let parse_err = |line, msg: String| UniverseError::Parse {
+            line: Some(line),
+            msg,
+        };
+        for c in &columns {
+            if !REQUIRED.contains(&c.as_str()) && !OPTIONAL.contains(&c.as_str()) {
+                return Err(parse_err(header_line, format!("unknown column {c:?}")));
+            }
+        }
+        let col = |name: &str| columns.iter().position(|c| c == name);
+        for name in REQUIRED {
+            if col(name).is_none() {
+                return Err(parse_err(header_line, format!("missing column {name:?}")));
+            }
+        }
+
+        let mut stocks = Vec::new();
+        for (line, row) in rows {
+            let fields = split_csv(row, line)?;
+            if fields.len() != columns.len() {
+                return Err(parse_err(
+                    line,
+                    format!("{} fields, header has {}", fields.len(), columns.len()),
+                ));
+            }
+            let get = |name: &str| {
+                col(name)
+                    .map(|i| fields[i].trim())
+                    .filter(|v| !v.is_empty())
+            };
+            let required =
+                |name: &str| get(name).ok_or_else(|| parse_err(line, format!("{name} is empty")));
+            let mut stock = Stock::new(
+                required("ticker")?,
+                num(Some(required("id")?), "id", line)?.unwrap_or_default(),
+                required("company_name")?,
+                num(Some(required("total_float")?), "total_float", line)?.unwrap_or_default(),
+                num(Some(required("initial_price")?), "initial_price", line)?.unwrap_or_default(),
+            );
+            stock.sector = get("sector").map(str::to_string);
+            stock.tick_size = num(get("tick_size"), "tick_size", line)?;
+            stock.lot_size = num(get("lot_size"), "lot_size", line)?;
+            stock.reference_vol = num(get("reference_vol"), "reference_vol", line)?;
+            stock.dividend_yield = num(get("dividend_yield"), "dividend_yield", line)?;
+            stocks.push(stock);
+        }
+        Self::from_stocks(stocks)
+    }
+}
+
+/// Parses an optional number; `_` separators are allowed.
+fn num<T: std::str::FromStr>(
+    v: Option<&str>,
+    name: &str,
+    line: usize,
+) -> Result<Option<T>, UniverseError> {
+    v.map(|v| {
+        v.replace('_', "")
+            .parse()
+            .map_err(|_| UniverseError::Parse {
+                line: Some(line),
+                msg: format!("{name}: cannot parse {v:?}"),
+            })
+    })
+    .transpose()
+}
+
+/// Splits one CSV record; `"..."` quotes a field and `""` is a literal quote.
+fn split_csv(row: &str, line: usize) -> Result<Vec<String>, UniverseError> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(UniverseError::Parse {
+            line: Some(line),
+            msg: "unterminated quote".into(),
+        });
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+// -----------------------------------------------------------------------------
+//  Unit tests
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "\
+# ticker universe
+ticker,id,company_name,total_float,initial_price,sector,lot_size,tick_size
+AAPL,1,\"Apple, Inc.\",15_982_000_000,195.37,Technology,100,1
+MSFT,2,Microsoft Corporation,7448000000,422.12,,,
+\"BRK \"\"B\"\"\",3,Berkshire Hathaway,1300000000,410.5,Financials,1,
+";
+
+    #[test]
+    fn csv_with_quotes_and_optional_columns() {
+        let sm = StockMarket::from_csv_str(CSV).unwrap();
+        assert_eq!(sm.get_all_tickers(), ["AAPL", "MSFT", "BRK \"B\""]);
+
+        let aapl = sm.get_stock_by_id(1).unwrap();
+        assert_eq!(aapl.company_name, "Apple, Inc.");
+        assert_eq!(aapl.total_float, 15_982_000_000);
+        assert_eq!(aapl.sector.as_deref(), Some("Technology"));
+        assert_eq!((aapl.lot_size, aapl.tick_size), (Some(100), Some(1)));
+        assert_eq!(aapl.reference_vol, None);
+
+        let msft = sm.get_stock_by_id(2).unwrap();
+        assert_eq!((msft.sector.as_ref(), msft.lot_size), (None, None));
+    }
+
+    #[test]
+    fn json_round_trips_and_accepts_a_wrapper() {
+        let stocks = vec![
+            Stock::new("AAA", 1, "A", 1_000, 10.0).with_reference_vol(0.3),
+            Stock::new("BBB", 2, "B", 2_000, 20.0).with_dividend_yield(0.02),
+        ];
+        let list = serde_json::to_string(&stocks).unwrap();
+        let sm = StockMarket::from_json_str(&list).unwrap();
+        assert_eq!(sm.get_stock_by_id(1).unwrap().reference_vol, Some(0.3));
+        assert_eq!(sm.get_stock_by_id(2).unwrap().dividend_yield, Some(0.02));
+        // unset optionals stay out of the file
+        assert!(!list.contains("sector"));
+
+        let wrapped = format!("{{\"stocks\": {list}}}");
+        assert_eq!(
+            StockMarket::from_json_str(&wrapped).unwrap().stocks.len(),
+            2
+        );
+
+        let missing = StockMarket::from_json_str("[\n{\"ticker\": \"A\", \"id\": 1}\n]");
+        let msg = missing.unwrap_err().to_string();
+        assert!(
+            msg.starts_with("line 2:") && msg.contains("company_name"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn bad_universes_say_what_is_wrong() {
+        let err = |text: &str| StockMarket::from_csv_str(text).unwrap_err().to_string();
+        let header = "ticker,id,company_name,total_float,initial_price\n";
+
+        assert!(
+            err(&format!("{header}A,1,a,1,1\nB,1,b,1,1"))
+                .contains("stock id 1 used by both A and B")
+        );
+        assert!(err(&format!("{header}A,1,a,1,1\nA,2,b,1,1")).contains("ticker A listed twice"));
+        assert!(err(&format!("{header}A,1,a,1,abc")).starts_with("line 2: initial_price"));
+        assert!(err(&format!("{header}A,1,a,1")).contains("4 fields, header has 5"));
+        assert!(err(&format!("{header}A,1,a,1,-3")).contains("initial_price must be"));
+        assert!(
+            err("ticker,id,company_name,total_float\n")
+                .contains("missing column \"initial_price\"")
+        );
+        assert!(
+            err("ticker,id,company_name,total_float,initial_price,colour\n")
+                .contains("unknown column")
+        );
+        assert!(err(&format!("{header}\"A,1,a,1,1")).contains("unterminated quote"));
+
+        let lots = StockMarket::builder()
+            .stock(Stock::new("A", 1, "a", 1, 1.0).with_lot_size(0))
+            .build();
+        assert!(matches!(
+            lots,
+            Err(UniverseError::InvalidField {
+                field: "lot_size",
+                ..
+            })
+        ));
+        assert!(matches!(
+            StockMarket::builder().build(),
+            Err(UniverseError::Empty)
+        ));
+    }
+
+    #[test]
+    fn five_hundred_names_load_from_a_file() {
+        let mut csv =
+            String::from("ticker,id,company_name,total_float,initial_price,sector,reference_vol\n");
+        let sectors = ["Technology", "Energy", "Health Care", "Financials"];
+        for i in 1..=500u64 {
+            csv.push_str(&format!(
+                "S{i:03},{i},\"Synthetic {i}, Corp.\",{},{:.2},{},0.{:02}\n",
+                1_000_000 * i,
+                10.0 + i as f64 * 0.5,
+                sectors[i as usize % sectors.len()],
+                15 + i % 30
+            ));
+        }
+        let path = std::env::temp_dir().join(format!("universe-500-{}.csv", std::process::id()));
+        fs::write(&path, csv).unwrap();
+        let sm = StockMarket::from_path(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(sm.stocks.len(), 500);
+        assert_eq!(sm.id_to_stock.len(), 500);
+        assert_eq!(sm.ticker_to_stock.len(), 500);
+        let last = sm.get_stock_by_ticker(&"S500".to_string()).unwrap();
+        assert_eq!(last.id, 500);
+        assert_eq!(last.company_name, "Synthetic 500, Corp.");
+        assert_eq!(last.sector.as_deref(), Some("Technology"));
+    }
+}
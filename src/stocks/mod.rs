@@ -1,7 +1,11 @@
 // src/stocks/mod.rs
 // -----------------
+pub mod basket;
 pub mod definitions;
+pub mod loader;
 
 // Re-export the most useful items so callers don’t have to dive
 // another level down the path.
+pub use basket::{Basket, Constituent, UnitSettlement};
 pub use definitions::{Stock, StockMarket, Symbol, default_stock_universe};
+pub use loader::{StockMarketBuilder, UniverseError};
@@ -1,4 +1,4 @@
 // src/types/mod.rs
 
 pub mod order;
-pub use order::{Order, OrderRequest, Side, Trade};
+pub use order::{Order, OrderRequest, RejectReason, Side, Trade};
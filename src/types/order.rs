@@ -19,7 +19,7 @@ impl Side {
 }
 
 /// Order currently resting in an order book.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Order {
     pub id: u64,
     pub agent_id: usize,
@@ -31,7 +31,7 @@ pub struct Order {
 }
 
 /// Message from an agent to the market engine.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderRequest {
     /// Limit order at a specific price.
     LimitOrder {
@@ -53,11 +53,53 @@ pub enum OrderRequest {
         agent_id: usize, // to verify ownership
         order_id: u64,
     },
+    /// Amend a resting order to `volume` unfilled shares at `price`.
+    /// Shrinking at the same price keeps the order id and queue priority;
+    /// anything else re-enters it under a fresh id.
+    ModifyOrder {
+        agent_id: usize,
+        order_id: u64,
+        price: u64,
+        volume: u64,
+    },
+    /// Deliver the constituents of `units` creation units of the basket
+    /// `stock_id` and receive basket shares. Authorised participants only.
+    CreateUnits {
+        agent_id: usize,
+        stock_id: u64,
+        units: u64,
+    },
+    /// Hand back basket shares for `units` creation units and receive the
+    /// constituents. Authorised participants only.
+    RedeemUnits {
+        agent_id: usize,
+        stock_id: u64,
+        units: u64,
+    },
     // TODO: Add the ability to short the market, it I think allow naked shorts. A new Market Order type
 }
 
+/// Why the market refused a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// The request names a stock with no order book.
+    UnknownStock,
+    /// Cancel or modify of an order that is not resting (or not the agent's).
+    UnknownOrder,
+    /// A market order for a stock still in its opening auction.
+    NotTrading,
+    /// Creation or redemption by an agent that is not an authorised
+    /// participant, or for a stock that is not a basket.
+    NotAuthorised,
+    /// A creation or redemption whose share counts do not fit the ledger.
+    TooLarge,
+    /// A creation or redemption that delivers shares the participant does
+    /// not hold on the exchange's ledger.
+    InsufficientHoldings,
+}
+
 /// Execution report emitted when two orders match.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Trade {
     pub price: u64,
     pub stock_id: u64,
@@ -71,6 +113,7 @@ pub struct Trade {
 //  Unit tests for order-flow types
 // -----------------------------------------------------------------------------
 #[cfg(test)]
+#[allow(clippy::inconsistent_digit_grouping)] // dollars_cents literals
 mod tests {
     use super::*;
 